@@ -0,0 +1,173 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use chrono::Utc;
+use clap::ArgMatches;
+use huelight_core::adaptive::AdaptiveController;
+use huelight_core::circadian::{CircadianCurve, kelvin_to_mirek};
+use huelight_core::config::Config;
+use huelight_core::domain::brightness::BrightnessValue;
+use huelight_core::duration::parse_duration;
+use huelight_core::error::{ConfigError, CoreError, HueBridgeError};
+use huelight_core::hue_api::HueApi;
+use huelight_core::logger::{ILogger, Logger};
+use huelight_core::scheduler::resolve_timezone;
+
+use crate::error::CLIError;
+
+pub fn adaptive_command() -> clap::Command {
+    clap::Command::new("adaptive")
+        .about("Adaptive lighting: color temperature and brightness that follow the sun")
+        .subcommand(
+            clap::Command::new("run")
+                .about("Keep a group's lights on the circadian curve until interrupted with Ctrl-C")
+                .arg(
+                    clap::Arg::new("group")
+                        .long("group")
+                        .short('g')
+                        .required(true)
+                        .help("ID or name of the group to adjust. 0 is all lights."),
+                )
+                .arg(
+                    clap::Arg::new("interval")
+                        .long("interval")
+                        .default_value("60s")
+                        .help("How often to re-check and adjust the lights, e.g. 30s or 2m"),
+                )
+                .arg(
+                    clap::Arg::new("transition")
+                        .long("transition")
+                        .default_value("2s")
+                        .help("Transition time for each adjustment"),
+                )
+                .arg(
+                    clap::Arg::new("warmest")
+                        .long("warmest")
+                        .default_value("2200")
+                        .help("Warmest color temperature in kelvin, used around sunrise, sunset and at night"),
+                )
+                .arg(
+                    clap::Arg::new("coolest")
+                        .long("coolest")
+                        .default_value("5500")
+                        .help("Coolest color temperature in kelvin, used at solar noon"),
+                )
+                .arg(
+                    clap::Arg::new("min_brightness")
+                        .long("min-brightness")
+                        .default_value("30%")
+                        .value_parser(BrightnessValue::from_str)
+                        .help("Brightness at solar midnight, e.g. 30% or 0.3"),
+                )
+                .arg(
+                    clap::Arg::new("once")
+                        .long("once")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Adjust the lights once and exit"),
+                ),
+        )
+}
+
+pub async fn run_adaptive(
+    cmd: &ArgMatches,
+    api: Arc<dyn HueApi + Send + Sync>,
+    config: &Config,
+    logger: &Logger,
+) -> Result<(), CLIError> {
+    let Some(("run", run_cmd)) = cmd.subcommand() else {
+        return Err(CLIError::InvalidCommandError);
+    };
+    // Every argument below is either required or has a default value.
+    let arg = |name: &str| run_cmd.get_one::<String>(name).unwrap();
+
+    let location = config
+        .location()
+        .ok_or(CoreError::Config(ConfigError::LocationNotConfigured))?;
+    let timezone = resolve_timezone(config.timezone.as_deref()).map_err(CoreError::Parse)?;
+    let interval = parse_duration(arg("interval")).map_err(CoreError::Parse)?;
+    let transition = parse_duration(arg("transition")).map_err(CoreError::Parse)?;
+    let min_brightness = run_cmd
+        .get_one::<BrightnessValue>("min_brightness")
+        .unwrap(); // has a default value
+    let min_brightness = min_brightness
+        .absolute()
+        .ok_or_else(|| CLIError::RelativeBrightness(min_brightness.to_string()))?
+        .percent();
+    let curve = CircadianCurve {
+        warmest_mirek: kelvin_to_mirek(arg("warmest").parse()?),
+        coolest_mirek: kelvin_to_mirek(arg("coolest").parse()?),
+        min_brightness,
+        ..Default::default()
+    };
+
+    let (ip, user) = (config.bridge_ip.as_str(), config.username.as_str());
+    let group = arg("group");
+    let light_ids = if group == "0" {
+        api.async_get_all_lights(ip, user)
+            .await?
+            .0
+            .keys()
+            .copied()
+            .collect()
+    } else {
+        let groups = api.async_get_all_groups(ip, user).await?;
+        let (_, found) = groups
+            .find(group)
+            .ok_or(CoreError::Bridge(HueBridgeError::GroupNotFound))?;
+        found.light_ids()
+    };
+
+    let mut controller =
+        AdaptiveController::new(curve, location, timezone).with_transition(transition);
+    let target = controller.target_at(Utc::now());
+    logger.log(&format!(
+        "Adaptive lighting for group '{}' ({} lights). Current target: {}K at {:.0}% brightness.",
+        group,
+        light_ids.len(),
+        1_000_000 / target.mirek.max(1) as u32,
+        target.brightness
+    ));
+
+    let once = run_cmd.get_flag("once");
+    loop {
+        // A bridge that is briefly unreachable shouldn't end adaptive lighting; try again
+        // next time.
+        match controller
+            .step(api.as_ref(), ip, user, &light_ids, Utc::now())
+            .await
+        {
+            Ok(report) => {
+                if !report.adjusted.is_empty() {
+                    logger.log(&format!("Adjusted lights: {:?}", report.adjusted));
+                }
+                if !report.overridden.is_empty() {
+                    logger.log(&format!(
+                        "Manually overridden, leaving alone: {:?}",
+                        report.overridden
+                    ));
+                }
+                for (id, err) in &report.failed {
+                    logger.error(&format!("Could not adjust light {}: {}", id, err));
+                }
+                if once && !report.failed.is_empty() {
+                    return Err(CLIError::LightsFailed {
+                        failed: report.failed.len(),
+                        total: report.adjusted.len() + report.failed.len(),
+                    });
+                }
+            }
+            Err(err) if once => return Err(err.into()),
+            Err(err) => logger.error(&format!("Could not read the lights: {}", err)),
+        }
+        if once {
+            return Ok(());
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = tokio::signal::ctrl_c() => {
+                logger.log("Adaptive lighting stopped.");
+                return Ok(());
+            }
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use chrono::Utc;
+use clap::ArgMatches;
+use huelight_core::animation::{Animation, Animator, PRESETS, TimelineFile};
+use huelight_core::config::{Config, FileHandler, TokioFileHandler};
+use huelight_core::domain::light::Lights;
+use huelight_core::duration::parse_duration;
+use huelight_core::error::{AnimationError, CoreError, HueBridgeError};
+use huelight_core::fanout::DEFAULT_CONCURRENCY;
+use huelight_core::hue_api::HueApi;
+use huelight_core::logger::{ILogger, Logger};
+use huelight_core::snapshot::Snapshot;
+
+use crate::error::CLIError;
+
+/// How quickly lights go back to where they were after Ctrl-C.
+const RESTORE_TRANSITION: Duration = Duration::from_millis(400);
+
+pub fn animate_command() -> clap::Command {
+    clap::Command::new("animate")
+        .about("Play an animation across lights; Ctrl-C stops it and restores the lights")
+        .arg(clap::Arg::new("animation").required(true).help(format!(
+            "Preset ({}) or the path to a TOML keyframe file",
+            PRESETS.join(", ")
+        )))
+        .arg(
+            clap::Arg::new("lights")
+                .long("lights")
+                .short('l')
+                .required(true)
+                .num_args(1..)
+                .help("IDs or names of the lights to animate, in chase order"),
+        )
+        .arg(
+            clap::Arg::new("duration")
+                .long("duration")
+                .short('d')
+                .default_value("30s")
+                .help("How long to play, e.g. 30s or 5m"),
+        )
+        .arg(
+            clap::Arg::new("seed")
+                .long("seed")
+                .value_parser(clap::value_parser!(u64))
+                .help("Seed for the candle flicker, to repeat a previous run"),
+        )
+}
+
+pub async fn run_animate(
+    cmd: &ArgMatches,
+    api: Arc<dyn HueApi + Send + Sync>,
+    config: &Config,
+    logger: &Logger,
+) -> Result<(), CLIError> {
+    let (ip, user) = (config.bridge_ip.as_str(), config.username.as_str());
+    let name = cmd.get_one::<String>("animation").unwrap(); // required by cli
+    let duration = parse_duration(cmd.get_one::<String>("duration").unwrap()) // has a default value
+        .map_err(CoreError::Parse)?;
+    let seed = cmd.get_one::<u64>("seed").copied().unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64)
+    });
+
+    let animation = match Animation::preset(name, seed) {
+        Some(animation) => animation,
+        None if Path::new(name).is_file() => {
+            let text = TokioFileHandler.read_file(name).await?;
+            let registry = config.color_registry().map_err(CoreError::Parse)?;
+            Animation::Timeline(TimelineFile::from_toml(&text)?.parse(&registry)?)
+        }
+        None => {
+            return Err(CoreError::Animation(AnimationError::UnknownPreset(name.clone())).into());
+        }
+    };
+
+    let all_lights = api.async_get_all_lights(ip, user).await?;
+    let lights = cmd
+        .get_many::<String>("lights")
+        .unwrap() // required by cli
+        .map(|light| {
+            all_lights
+                .find(light)
+                .map(|(id, light)| (id, light.clone()))
+                .ok_or(CoreError::Bridge(HueBridgeError::LightNotFound))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    // Only the animated lights are put back, in case others changed in the meantime.
+    let animated = Lights(lights.iter().cloned().collect());
+    let before = Snapshot::capture("animate", &animated, Utc::now());
+
+    let animator = Animator::new(animation);
+    logger.log(&format!(
+        "Playing {} on {} lights for {}s, one frame every {}ms. Ctrl-C to stop.",
+        name,
+        lights.len(),
+        duration.as_secs(),
+        animator.frame_interval(lights.len()).as_millis()
+    ));
+    if name == "candle" {
+        logger.log(&format!("Flicker seed: {seed}"));
+    }
+
+    tokio::select! {
+        report = animator.play(api.as_ref(), ip, user, &lights, duration) => {
+            let report = report?;
+            for (id, err) in &report.failed {
+                logger.log(&format!("Light {} stopped animating: {}", id, err));
+            }
+            logger.log(&format!("Animation finished after {} frames.", report.frames));
+            if !report.failed.is_empty() {
+                return Err(CLIError::LightsFailed {
+                    failed: report.failed.len(),
+                    total: lights.len(),
+                });
+            }
+        }
+        _ = tokio::signal::ctrl_c() => {
+            let report = before
+                .restore(api.as_ref(), ip, user, Some(RESTORE_TRANSITION), DEFAULT_CONCURRENCY)
+                .await?;
+            logger.log(&format!(
+                "Animation stopped, restored {} lights.",
+                report.sent.succeeded()
+            ));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,143 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use clap::ArgMatches;
+use huelight_core::config::{Config, FileHandler, TokioFileHandler};
+use huelight_core::duration::parse_duration;
+use huelight_core::error::CoreError;
+use huelight_core::hue_api::HueApi;
+use huelight_core::logger::{ILogger, Logger};
+use huelight_core::scheduler::resolve_timezone;
+use huelight_core::scripting::{ScriptDaemon, ScriptEngine, ScriptEnv};
+use tokio::runtime::Handle;
+
+use crate::error::CLIError;
+
+pub fn script_command() -> clap::Command {
+    clap::Command::new("script")
+        .about("Run Rhai automation scripts against the bridge")
+        .long_about(
+            "Run Rhai automation scripts against the bridge.\n\n\
+             Scripts can call lights(), light(id), set_light(id, #{ on: true, brightness: \"40%\", color: \"warmwhite\", transition: \"2s\" }),\n\
+             groups(), group(id), set_group(id, #{ ... }), scenes(), activate_scene(id), sensors(), sensor(id),\n\
+             sleep(\"2s\"), now() and log(message). They can't read files, import modules or use eval.\n\n\
+             For the daemon, define fn on_event(event) to react to sensor changes, and\n\
+             const SCHEDULE = \"0 7 * * 1-5\"; with fn on_schedule() to run on a cron schedule.",
+        )
+        .subcommand(
+            clap::Command::new("run")
+                .about("Run a script once")
+                .arg(
+                    clap::Arg::new("file")
+                        .required(true)
+                        .help("Path to the .rhai script"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("daemon")
+                .about("Keep running scripts' on_event and on_schedule handlers until Ctrl-C")
+                .arg(
+                    clap::Arg::new("files")
+                        .required(true)
+                        .num_args(1..)
+                        .help("Paths to the .rhai scripts"),
+                )
+                .arg(
+                    clap::Arg::new("poll")
+                        .long("poll")
+                        .default_value("2s")
+                        .help("How often to poll sensors for events, e.g. 2s"),
+                ),
+        )
+}
+
+fn script_env(
+    api: Arc<dyn HueApi + Send + Sync>,
+    config: &Config,
+    logger: &Arc<Logger>,
+) -> Result<ScriptEnv, CLIError> {
+    Ok(ScriptEnv {
+        api,
+        ip_address: config.bridge_ip.clone(),
+        username: config.username.clone(),
+        logger: logger.clone(),
+        timezone: resolve_timezone(config.timezone.as_deref()).map_err(CoreError::Parse)?,
+        colors: config.color_registry().map_err(CoreError::Parse)?,
+        runtime: Handle::current(),
+    })
+}
+
+/// Runs blocking script work until it finishes or Ctrl-C is pressed.
+async fn run_until_interrupted(
+    work: impl FnOnce() -> Result<(), CLIError> + Send + 'static,
+    logger: &Logger,
+) -> Result<(), CLIError> {
+    let task = tokio::task::spawn_blocking(work);
+    tokio::select! {
+        result = task => result.unwrap_or(Err(CLIError::InvalidCommandError)),
+        _ = tokio::signal::ctrl_c() => {
+            logger.log("Script interrupted.");
+            // The script thread can't be cancelled, and the runtime would wait for it on exit.
+            std::process::exit(130);
+        }
+    }
+}
+
+pub async fn run_script_command(
+    cmd: &ArgMatches,
+    api: Arc<dyn HueApi + Send + Sync>,
+    config: &Config,
+    logger: &Arc<Logger>,
+) -> Result<(), CLIError> {
+    let env = script_env(api, config, logger)?;
+    match cmd.subcommand() {
+        Some(("run", run_cmd)) => {
+            let path = run_cmd.get_one::<String>("file").unwrap(); // required by cli
+            let source = TokioFileHandler.read_file(path).await?;
+            let name = path.clone();
+            run_until_interrupted(
+                move || {
+                    ScriptEngine::new(env).load(&name, &source)?;
+                    Ok(())
+                },
+                logger,
+            )
+            .await
+        }
+        Some(("daemon", daemon_cmd)) => {
+            let poll = parse_duration(daemon_cmd.get_one::<String>("poll").unwrap()) // has a default value
+                .map_err(CoreError::Parse)?;
+            let mut sources = Vec::new();
+            let paths = daemon_cmd.get_many::<String>("files").unwrap(); // required by cli
+            for path in paths {
+                sources.push((path.clone(), TokioFileHandler.read_file(path).await?));
+            }
+            logger.log(&format!(
+                "Running {} script(s), polling sensors every {}. Ctrl-C to stop.",
+                sources.len(),
+                daemon_cmd.get_one::<String>("poll").unwrap() // has a default value
+            ));
+            run_until_interrupted(
+                move || {
+                    let engine = ScriptEngine::new(env.clone());
+                    let scripts = sources
+                        .iter()
+                        .map(|(name, source)| engine.load(name, source))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let logger = env.logger.clone();
+                    let mut daemon = ScriptDaemon::new(engine, env, scripts, Utc::now())?;
+                    loop {
+                        // Keep going through a bridge hiccup; the next poll will try again.
+                        if let Err(err) = daemon.tick(Utc::now()) {
+                            logger.warn(&format!("Polling the bridge failed: {err}"));
+                        }
+                        std::thread::sleep(poll);
+                    }
+                },
+                logger,
+            )
+            .await
+        }
+        _ => Err(CLIError::InvalidCommandError),
+    }
+}
@@ -0,0 +1,129 @@
+use std::sync::Arc;
+
+use clap::ArgMatches;
+use huelight_core::config::Config;
+use huelight_core::hue_api::HueApi;
+use huelight_core::logger::{ILogger, Logger};
+
+use crate::error::CLIError;
+
+pub fn bridge_command() -> clap::Command {
+    clap::Command::new("bridge")
+        .about("Inspect the Hue Bridge and manage its authorized applications")
+        .subcommand(
+            clap::Command::new("info")
+                .about("Show the bridge's name, firmware, API version, zigbee channel and whether the configured username is still authorized"),
+        )
+        .subcommand(
+            clap::Command::new("users")
+                .about("Manage applications authorized on the bridge (the whitelist)")
+                .subcommand(clap::Command::new("list").about("List authorized applications"))
+                .subcommand(
+                    clap::Command::new("revoke")
+                        .about("Remove an application from the whitelist")
+                        .arg(
+                            clap::Arg::new("username")
+                                .required(true)
+                                .help("Username of the application to revoke"),
+                        )
+                        .arg(
+                            clap::Arg::new("force")
+                                .long("force")
+                                .action(clap::ArgAction::SetTrue)
+                                .help("Allow revoking the username huelightcli itself is configured with"),
+                        ),
+                ),
+        )
+}
+
+pub async fn run_bridge(
+    cmd: &ArgMatches,
+    api: Arc<dyn HueApi + Send + Sync>,
+    config: &Config,
+    logger: &Logger,
+) -> Result<(), CLIError> {
+    let (ip, user) = (config.bridge_ip.as_str(), config.username.as_str());
+    match cmd.subcommand() {
+        Some(("info", _)) => {
+            // The public config works without a username, so failures here mean the bridge
+            // itself is unreachable rather than the username being wrong.
+            let public = api.async_get_unauthenticated_config(ip).await?;
+            let full = api.async_get_config(ip, user).await?;
+            let whitelisted = full.is_whitelisted(user);
+
+            logger.log(&format!(
+                "Bridge: {} (model {}, id {})",
+                public.name, public.modelid, public.bridgeid
+            ));
+            logger.log(&format!(
+                "Firmware: {}, API version: {}",
+                public.swversion, public.apiversion
+            ));
+            logger.log(&format!(
+                "Zigbee channel: {}",
+                full.zigbeechannel
+                    .map(|c| c.to_string())
+                    .unwrap_or_else(|| "unknown".to_string())
+            ));
+            if let Some(ipaddress) = &full.ipaddress {
+                logger.log(&format!("IP address: {}", ipaddress));
+            }
+            if let Some(mac) = &public.mac {
+                logger.log(&format!("MAC address: {}", mac));
+            }
+            if let Some(timezone) = &full.timezone {
+                logger.log(&format!(
+                    "Timezone: {} (local time {})",
+                    timezone,
+                    full.localtime.as_deref().unwrap_or("unknown")
+                ));
+            }
+            if let Some(whitelist) = &full.whitelist {
+                logger.log(&format!("Authorized applications: {}", whitelist.len()));
+            }
+            if whitelisted {
+                logger.log("Configured username is authorized.");
+            } else {
+                logger.log(
+                    "Configured username is NOT authorized on this bridge anymore. Create a new user and run `setup config` again.",
+                );
+            }
+            Ok(())
+        }
+        Some(("users", users_cmd)) => match users_cmd.subcommand() {
+            Some(("list", _)) => {
+                let full = api.async_get_config(ip, user).await?;
+                let Some(whitelist) = full.whitelist else {
+                    logger.log("The configured username is not authorized to read the whitelist.");
+                    return Ok(());
+                };
+                let mut entries: Vec<_> = whitelist.into_iter().collect();
+                entries.sort_by(|(_, a), (_, b)| b.last_use_date.cmp(&a.last_use_date));
+                for (username, entry) in entries {
+                    logger.log(&format!(
+                        "{}{}: {} (created {}, last used {})",
+                        username,
+                        if username == user { " [this tool]" } else { "" },
+                        entry.name,
+                        entry.create_date,
+                        entry.last_use_date
+                    ));
+                }
+                Ok(())
+            }
+            Some(("revoke", revoke_cmd)) => {
+                let target = revoke_cmd.get_one::<String>("username").unwrap(); // required by cli
+                if target == user && !revoke_cmd.get_flag("force") {
+                    return Err(CLIError::ConfirmationRequired(
+                        "refusing to revoke the username huelightcli is configured with; pass --force to do it anyway".to_string(),
+                    ));
+                }
+                api.async_delete_user(ip, user, target).await?;
+                logger.log(&format!("Revoked application {}.", target));
+                Ok(())
+            }
+            _ => Err(CLIError::InvalidCommandError),
+        },
+        _ => Err(CLIError::InvalidCommandError),
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use clap::ArgMatches;
+use huelight_core::batch::set_light_states;
+use huelight_core::config::Config;
+use huelight_core::domain::light::{LightId, LightState};
+use huelight_core::domain::named_colors::{CSS_COLORS, ColorChoice, HUE_PRESETS};
+use huelight_core::error::{CoreError, HueBridgeError};
+use huelight_core::fanout::{FanOutReport, Outcome};
+use huelight_core::hue_api::HueApi;
+use huelight_core::logger::{ILogger, Logger};
+
+use crate::error::CLIError;
+use crate::summary::{concurrency, concurrency_arg, finish_summary};
+
+pub fn light_color_command() -> clap::Command {
+    clap::Command::new("color")
+        .about("Sets lights to a named color, or spreads a palette across them")
+        .arg(
+            clap::Arg::new("lights")
+                .required(true)
+                .num_args(1..)
+                .help("IDs or names of the lights to color"),
+        )
+        .arg(
+            clap::Arg::new("color")
+                .required(true)
+                .help("Color or palette name (see `light colors`), #rrggbb, 2700K or xy:0.4,0.4"),
+        )
+        .arg(concurrency_arg())
+}
+
+pub fn light_colors_command() -> clap::Command {
+    clap::Command::new("colors").about("Lists the color names and palettes `light color` accepts")
+}
+
+pub async fn run_light_color(
+    cmd: &ArgMatches,
+    api: Arc<dyn HueApi + Send + Sync>,
+    config: &Config,
+    logger: &Logger,
+) -> Result<(), CLIError> {
+    let (ip, user) = (config.bridge_ip.as_str(), config.username.as_str());
+    let registry = config.color_registry().map_err(CoreError::Parse)?;
+    let color = cmd.get_one::<String>("color").unwrap(); // required by cli
+    let choice = registry.choice(color).map_err(CoreError::Parse)?;
+
+    let lights = api.async_get_all_lights(ip, user).await?;
+    let targets = cmd
+        .get_many::<String>("lights")
+        .unwrap() // required by cli
+        .map(|light| {
+            lights
+                .find(light)
+                .ok_or(CoreError::Bridge(HueBridgeError::LightNotFound))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // A light that can't show the color shouldn't stop the rest of a palette.
+    let colors = choice.spread(targets.len());
+    let mut unfit = HashMap::new();
+    let mut updates = Vec::new();
+    for ((id, light), light_color) in targets.iter().zip(&colors) {
+        let requested = LightState::default().with_on(true).with_color(*light_color);
+        match light.fit_state(&requested) {
+            Ok(state) => updates.push((*id, state)),
+            Err(err) => {
+                unfit.insert(*id, err);
+            }
+        }
+    }
+    // Lights getting the same color change together, through a group.
+    let mut sent: HashMap<_, _> =
+        set_light_states(api.as_ref(), ip, user, &updates, concurrency(cmd))
+            .await
+            .outcomes(&updates)
+            .results
+            .into_iter()
+            .collect();
+    let results = targets
+        .iter()
+        .filter_map(|(id, _)| {
+            let outcome = match unfit.remove(id) {
+                Some(err) => Outcome::from(Err(err)),
+                None => sent.remove(id)?,
+            };
+            Some((*id, outcome))
+        })
+        .collect();
+    let report = FanOutReport { results };
+
+    if let ColorChoice::Palette(palette) = &choice
+        && palette.len() > targets.len()
+    {
+        logger.info(&format!(
+            "Palette {} has {} colors; only the first {} were used.",
+            color,
+            palette.len(),
+            targets.len()
+        ));
+    }
+
+    let label = |id: &LightId| {
+        let (index, (_, light)) = targets
+            .iter()
+            .enumerate()
+            .find(|(_, (target, _))| target == id)
+            .unwrap(); // every result is one of the targets
+        format!("{} ({}) {}", id, light.name, colors[index])
+    };
+    finish_summary(report, label, logger)
+}
+
+pub fn run_light_colors(config: &Config, logger: &Logger) -> Result<(), CLIError> {
+    let registry = config.color_registry().map_err(CoreError::Parse)?;
+    if !registry.user_colors().is_empty() {
+        logger.log("Your colors:");
+        for (name, color) in registry.user_colors() {
+            logger.log(&format!("  {}: {}", name, color));
+        }
+    }
+    if !registry.palettes().is_empty() {
+        logger.log("Your palettes:");
+        for (name, palette) in registry.palettes() {
+            let colors: Vec<String> = palette.iter().map(ToString::to_string).collect();
+            logger.log(&format!("  {}: {}", name, colors.join(", ")));
+        }
+    }
+    logger.log("Hue presets:");
+    for (name, mirek) in HUE_PRESETS {
+        logger.log(&format!("  {}: {}K", name, 1_000_000 / *mirek as u32));
+    }
+    let css: Vec<&str> = CSS_COLORS.iter().map(|(name, _)| *name).collect();
+    logger.log(&format!("CSS colors: {}", css.join(", ")));
+    Ok(())
+}
@@ -1,3 +1,6 @@
+/// Exit code when a command changed some of its lights but not all of them.
+pub const PARTIAL_FAILURE_EXIT_CODE: i32 = 2;
+
 #[derive(Debug, thiserror::Error)]
 pub enum CLIError {
     #[error("invalid command error")]
@@ -14,4 +17,33 @@ pub enum CLIError {
 
     #[error("int arg unable to be parsed")]
     InvalidIntArgParse(#[from] std::num::ParseIntError),
+
+    #[error("'{0}' must be a decimal number")]
+    InvalidFloatArgParse(String),
+
+    #[error("{0}")]
+    ConfirmationRequired(String),
+
+    #[error(
+        "relative brightness {0} needs a current brightness to start from; use an absolute value such as 50%"
+    )]
+    RelativeBrightness(String),
+
+    #[error("{failed} of {total} lights could not be changed")]
+    LightsFailed { failed: usize, total: usize },
+
+    #[error("no {0} routine is pending or running")]
+    NoRoutine(String),
+
+    #[error("line {line}: {message}")]
+    Script { line: usize, message: String },
+
+    #[error("{failed} script line(s) failed")]
+    ScriptFailed { failed: usize },
+
+    #[error("could not set up logging: {0}")]
+    Logging(String),
+
+    #[error("shell error: {0}")]
+    Shell(#[from] rustyline::error::ReadlineError),
 }
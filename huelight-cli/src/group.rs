@@ -0,0 +1,127 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use clap::ArgMatches;
+use huelight_core::config::Config;
+use huelight_core::domain::brightness::{Brightness, BrightnessValue};
+use huelight_core::domain::light::LightState;
+use huelight_core::error::{CoreError, HueBridgeError};
+use huelight_core::hue_api::HueApi;
+use huelight_core::logger::{ILogger, Logger};
+use huelight_core::models::group::GroupId;
+
+use crate::BRIGHTNESS_HELP;
+use crate::error::CLIError;
+
+pub fn group_command() -> clap::Command {
+    let group_arg = clap::Arg::new("group")
+        .required(true)
+        .help("ID or name of the group, or 0 for every light");
+    clap::Command::new("group")
+        .about("Commands to control groups of lights, such as rooms and zones")
+        .subcommand(clap::Command::new("list").about("Get the list of groups on the Hue Bridge"))
+        .subcommand(
+            clap::Command::new("on")
+                .about("Turn every light in a group on")
+                .arg(group_arg.clone()),
+        )
+        .subcommand(
+            clap::Command::new("off")
+                .about("Turn every light in a group off")
+                .arg(group_arg.clone()),
+        )
+        .subcommand(
+            clap::Command::new("set")
+                .about("Sets a property of every light in a group at once, e.g. `group set Office bri 50%`")
+                .arg(group_arg)
+                .arg(
+                    clap::Arg::new("property")
+                        .required(true)
+                        .value_parser(["on", "off", "bri"])
+                        .help("What to change: on, off, or bri followed by a brightness"),
+                )
+                .arg(
+                    clap::Arg::new("brightness")
+                        .required_if_eq("property", "bri")
+                        .value_parser(BrightnessValue::from_str)
+                        .allow_hyphen_values(true)
+                        .help(BRIGHTNESS_HELP),
+                ),
+        )
+}
+
+/// The group the `group` argument names, and its current brightness when it reports one.
+async fn resolve_group(
+    api: &(dyn HueApi + Send + Sync),
+    config: &Config,
+    cmd: &ArgMatches,
+) -> Result<(GroupId, Option<Brightness>), CLIError> {
+    let group = cmd.get_one::<String>("group").unwrap(); // required by cli
+    // Group 0 holds every light but isn't listed with the others.
+    if group == "0" {
+        return Ok((0, None));
+    }
+    let groups = api
+        .async_get_all_groups(&config.bridge_ip, &config.username)
+        .await?;
+    let (id, found) = groups
+        .find(group)
+        .ok_or(CoreError::Bridge(HueBridgeError::GroupNotFound))?;
+    Ok((id, found.action.brightness))
+}
+
+pub async fn run_group(
+    cmd: &ArgMatches,
+    api: Arc<dyn HueApi + Send + Sync>,
+    config: &Config,
+    logger: &Logger,
+) -> Result<(), CLIError> {
+    let (ip, user) = (config.bridge_ip.as_str(), config.username.as_str());
+    let (property, group_cmd) = match cmd.subcommand() {
+        Some(("list", _)) => {
+            let groups = api.async_get_all_groups(ip, user).await?;
+            let mut groups: Vec<_> = groups.0.into_iter().collect();
+            groups.sort_by_key(|(id, _)| *id);
+            for (id, group) in groups {
+                logger.log(&format!(
+                    "Group ID: {}, Name: {}, Type: {}, Lights: {}, Any on: {}",
+                    id,
+                    group.name,
+                    group._type,
+                    group.lights.join(","),
+                    group.state.any_on
+                ));
+            }
+            return Ok(());
+        }
+        Some(("set", group_cmd)) => (
+            group_cmd.get_one::<String>("property").unwrap().as_str(), // required by cli
+            group_cmd,
+        ),
+        Some((power @ ("on" | "off"), group_cmd)) => (power, group_cmd),
+        _ => return Err(CLIError::InvalidCommandError),
+    };
+
+    let (group_id, current) = resolve_group(api.as_ref(), config, group_cmd).await?;
+    let brightness = group_cmd
+        .try_get_one::<BrightnessValue>("brightness")
+        .ok()
+        .flatten();
+    let state = match (property, brightness) {
+        ("on", None) => LightState::default().with_on(true),
+        ("off", None) => LightState::default().with_on(false),
+        ("bri", Some(brightness)) => {
+            if current.is_none() && brightness.absolute().is_none() {
+                return Err(CLIError::RelativeBrightness(brightness.to_string()));
+            }
+            LightState::default().with_brightness(brightness.resolve(current))
+        }
+        // A value after `on` or `off`.
+        _ => return Err(CLIError::InvalidCommandError),
+    };
+
+    logger.info(&format!("Setting {} on group {}", state, group_id));
+    api.async_set_group_state(ip, user, group_id, &state)
+        .await?;
+    Ok(())
+}
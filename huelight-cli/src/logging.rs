@@ -0,0 +1,115 @@
+use std::io::IsTerminal;
+use std::path::Path;
+
+use clap::ArgMatches;
+use huelight_core::logger::OUTPUT_TARGET;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::filter::{LevelFilter, Targets};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{Layer, fmt};
+
+use crate::error::CLIError;
+
+/// How many days of `--log-file` logs to keep.
+const LOG_FILES_KEPT: usize = 7;
+
+/// The global `-v`, `-q` and `--log-file` arguments.
+pub fn logging_args() -> [clap::Arg; 3] {
+    [
+        clap::Arg::new("verbose")
+            .long("verbose")
+            .short('v')
+            .global(true)
+            .action(clap::ArgAction::Count)
+            .help(
+                "Print more diagnostics to stderr: -v for progress, -vv for every bridge request",
+            ),
+        clap::Arg::new("quiet")
+            .long("quiet")
+            .short('q')
+            .global(true)
+            .action(clap::ArgAction::SetTrue)
+            .conflicts_with("verbose")
+            .help("Print no diagnostics to stderr but errors; command output still goes to stdout"),
+        clap::Arg::new("log_file")
+            .long("log-file")
+            .global(true)
+            .value_name("FILE")
+            .help("Also write output and diagnostics to FILE, starting a new one every day"),
+    ]
+}
+
+/// How much goes to stderr: warnings by default, errors with `-q`, more for each `-v`.
+pub fn stderr_level(cli: &ArgMatches) -> LevelFilter {
+    if cli.get_flag("quiet") {
+        return LevelFilter::ERROR;
+    }
+    match cli.get_count("verbose") {
+        0 => LevelFilter::WARN,
+        1 => LevelFilter::INFO,
+        2 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    }
+}
+
+/// Sends diagnostics to stderr and, with `--log-file`, everything including command output to
+/// a daily log file.
+pub fn init_logging(cli: &ArgMatches) -> Result<(), CLIError> {
+    let level = stderr_level(cli);
+    // Libraries (reqwest, hyper) only get to chat at -vvv.
+    let targets = Targets::new()
+        .with_default(if level == LevelFilter::TRACE {
+            level
+        } else {
+            level.min(LevelFilter::WARN)
+        })
+        .with_target("huelight_core", level)
+        .with_target("huelight_cli", level);
+    // Command output already goes to stdout.
+    let stderr = fmt::layer()
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal())
+        .without_time()
+        .with_target(false)
+        .with_filter(targets.clone().with_target(OUTPUT_TARGET, LevelFilter::OFF));
+
+    let file = match cli.get_one::<String>("log_file") {
+        Some(path) => {
+            let path = Path::new(path);
+            let mut appender = RollingFileAppender::builder()
+                .rotation(Rotation::DAILY)
+                .max_log_files(LOG_FILES_KEPT);
+            if let Some(name) = path.file_name().and_then(|name| name.to_str()) {
+                appender = appender.filename_prefix(name);
+            }
+            let directory = path
+                .parent()
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .unwrap_or(Path::new("."));
+            std::fs::create_dir_all(directory).map_err(|err| CLIError::Logging(err.to_string()))?;
+            let appender = appender
+                .build(directory)
+                .map_err(|err| CLIError::Logging(err.to_string()))?;
+            let file_level = level.max(LevelFilter::INFO);
+            Some(
+                fmt::layer()
+                    .with_writer(appender)
+                    .with_ansi(false)
+                    .with_filter(
+                        targets
+                            .with_target("huelight_core", file_level)
+                            .with_target("huelight_cli", file_level)
+                            .with_target(OUTPUT_TARGET, file_level),
+                    ),
+            )
+        }
+        None => None,
+    };
+
+    tracing_subscriber::registry()
+        .with(stderr)
+        .with(file)
+        .try_init()
+        .map_err(|err| CLIError::Logging(err.to_string()))
+}
@@ -1,34 +1,187 @@
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 
 use clap::ArgMatches;
+use hue::domain::brightness::BrightnessValue;
+use hue::domain::color::Color;
+use hue::domain::light::{Alert, LastScan, Light, LightId, LightState};
 use hue::logger::{ILogger, Logger};
-use hue::models::light::LightState;
-use huelight_core::client::ReqwestHueClient;
+use huelight_core::batch::set_light_states;
+use huelight_core::cache::{CachedHueApi, LIGHT_STATE_TTL};
+use huelight_core::cassette::{Cassette, RecordingHueClient, ReplayHueClient};
+use huelight_core::client::{
+    BRIDGE_REQUEST_INTERVAL, HueClient, RateLimitedHueClient, ReqwestHueClient,
+};
 use huelight_core::config::Config;
+use huelight_core::dry_run::DryRunHueClient;
 use huelight_core::error::{CoreError, HueBridgeError};
 use huelight_core::hue_api::{HueApi, HueApiV1};
-use huelight_core::models::hueerror::HueResponseEntry;
 use huelight_core::{self as hue};
 
+pub mod adaptive;
+pub mod animate;
+pub mod automation;
+pub mod bridge;
+pub mod color;
 pub mod error;
-use error::CLIError;
-
-/// Helper to parse the light ID, which is required for every command where it needs to be parsed.
-fn parse_light_id(light_cmd: &ArgMatches) -> u32 {
-    light_cmd
-        .get_one::<String>("light_id")
-        .unwrap() // CLI should handle this because it is marked required.
-        .parse::<u32>()
-        .expect("Light ID must be a number")
+pub mod group;
+pub mod logging;
+pub mod routine;
+pub mod rules;
+pub mod scene;
+pub mod schedule;
+pub mod script;
+pub mod sensor;
+pub mod shell;
+pub mod snapshot;
+pub mod summary;
+pub mod timer;
+use error::{CLIError, PARTIAL_FAILURE_EXIT_CODE};
+use summary::{concurrency, finish_summary};
+
+/// Help for every argument that takes a `BrightnessValue`.
+pub const BRIGHTNESS_HELP: &str = "Brightness from 0% (the minimum the light is capable of, not off) to 100%: 50%, a fraction like 0.5, the bridge's 1-254 scale as bri:127, or +10%/-10% relative to the current brightness. Values out of range are clamped.";
+
+/// How often `light search --wait` asks the bridge for newly found lights.
+const SEARCH_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Looks up the light the `light_id` argument names, by its ID or its name.
+async fn resolve_light_id(
+    api: &(dyn HueApi + Send + Sync),
+    c: &Config,
+    light_cmd: &ArgMatches,
+) -> Result<LightId, CLIError> {
+    let light = light_cmd.get_one::<String>("light_id").unwrap(); // required by cli
+    let lights = api.async_get_all_lights(&c.bridge_ip, &c.username).await?;
+    let (id, _) = lights
+        .find(light)
+        .ok_or(CoreError::Bridge(HueBridgeError::LightNotFound))?;
+    Ok(id)
 }
 
-#[tokio::main]
-async fn main() -> Result<(), CLIError> {
+/// Combines a new hue and/or saturation, given in bridge units, with the light's current color,
+/// since the bridge needs both to show a hue/saturation color.
+fn hue_saturation(light: &Light, hue: Option<u16>, saturation: Option<u8>) -> Color {
+    let (current_hue, current_saturation) = match light.state.color {
+        Some(Color::HueSaturation { hue, saturation }) => (hue, saturation),
+        _ => (0.0, 100.0),
+    };
+    Color::HueSaturation {
+        hue: hue.map_or(current_hue, |h| h as f64 / 65535.0 * 360.0),
+        saturation: saturation.map_or(current_saturation, |s| s as f64 / 254.0 * 100.0),
+    }
+}
+
+/// Looks up the lights the `lights` argument names, by their IDs or names.
+async fn resolve_lights(
+    api: &(dyn HueApi + Send + Sync),
+    c: &Config,
+    light_cmd: &ArgMatches,
+) -> Result<Vec<(LightId, Light)>, CLIError> {
+    let lights = api.async_get_all_lights(&c.bridge_ip, &c.username).await?;
+    let targets = light_cmd
+        .get_many::<String>("lights")
+        .unwrap() // required by cli
+        .map(|light| {
+            lights
+                .find(light)
+                .map(|(id, found)| (id, found.clone()))
+                .ok_or(CoreError::Bridge(HueBridgeError::LightNotFound))
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(targets)
+}
+
+/// The IDs of `targets` for progress messages, e.g. `1, 3`.
+fn light_ids(targets: &[(LightId, Light)]) -> String {
+    let ids: Vec<String> = targets.iter().map(|(id, _)| id.to_string()).collect();
+    ids.join(", ")
+}
+
+/// Fits the state built for each light to what it supports, telling the user about any
+/// clamping.
+fn fit_to_lights(
+    targets: &[(LightId, Light)],
+    build_state: impl Fn(&Light) -> LightState,
+) -> Result<Vec<(LightId, LightState)>, CLIError> {
+    let mut updates = Vec::new();
+    for (light_id, light) in targets {
+        let state = build_state(light);
+        let fitted = light.fit_state(&state)?;
+        if fitted != state {
+            tracing::warn!(
+                light_id,
+                "Adjusted the requested state to what {} supports: {}",
+                light.name,
+                fitted
+            );
+        }
+        if !light.reachable {
+            tracing::warn!(light_id, "{} is currently unreachable.", light.name);
+        }
+        updates.push((*light_id, fitted));
+    }
+    Ok(updates)
+}
+
+/// Sends each light its state, `concurrency` lights at a time; lights getting the same one
+/// change together through a group. A single light fails with its own error, several get a
+/// summary table and `LightsFailed` if any failed.
+async fn set_states(
+    api: &(dyn HueApi + Send + Sync),
+    c: &Config,
+    targets: &[(LightId, Light)],
+    updates: &[(LightId, LightState)],
+    concurrency: usize,
+    logger: &Logger,
+) -> Result<(), CLIError> {
+    let report = set_light_states(api, &c.bridge_ip, &c.username, updates, concurrency).await;
+    let name = |id: &LightId| {
+        targets
+            .iter()
+            .find(|(target, _)| target == id)
+            .map_or("?", |(_, light)| light.name.as_str())
+    };
+    finish_summary(
+        report.outcomes(updates),
+        |id| format!("{} ({})", id, name(id)),
+        logger,
+    )
+}
+
+/// Every command huelightcli understands. Script lines are parsed with the same definition.
+pub fn build_cli() -> clap::Command {
     // CLI application that will interface with the Philips Hue API to control smart lights with CMD commands.
-    let cli = clap::Command::new("huelightcli")
+    clap::Command::new("huelightcli")
         .version("1.0")
         .author("Christopher J Gambrell")
         .about("Control Philips Hue lights from the command line")
+        .arg(
+            clap::Arg::new("dry_run")
+                .long("dry-run")
+                .global(true)
+                .action(clap::ArgAction::SetTrue)
+                .help("Print the requests that would change the bridge instead of sending them, then summarize the changes"),
+        )
+        .arg(
+            clap::Arg::new("record")
+                .long("record")
+                .global(true)
+                .value_name("FILE")
+                .help("Save every bridge request and response to a cassette file"),
+        )
+        .arg(
+            clap::Arg::new("replay")
+                .long("replay")
+                .global(true)
+                .value_name("FILE")
+                .conflicts_with("record")
+                .help("Answer bridge requests from a cassette file instead of the network"),
+        )
+        .args(logging::logging_args())
         .subcommand(
             clap::Command::new("setup")
                 .about("Provides commands necessary for configuring the Hue Bridge for light control.")
@@ -47,6 +200,28 @@ async fn main() -> Result<(), CLIError> {
                         .help("Username for the Hue Bridge")
                 )
             )
+                .subcommand(clap::Command::new("location")
+                .about("Saves the location (and optionally the timezone) used for sunrise/sunset calculations.")
+                .arg(
+                    clap::Arg::new("latitude")
+                        .required(true)
+                        .long("lat")
+                        .allow_negative_numbers(true)
+                        .help("Latitude in degrees, north positive")
+                )
+                .arg(
+                    clap::Arg::new("longitude")
+                        .required(true)
+                        .long("lon")
+                        .allow_negative_numbers(true)
+                        .help("Longitude in degrees, east positive")
+                )
+                .arg(
+                    clap::Arg::new("timezone")
+                        .long("timezone")
+                        .help("IANA timezone name such as Europe/Amsterdam. Defaults to the system timezone.")
+                )
+            )
         )
         .subcommand(
         clap::Command::new("light")
@@ -58,106 +233,237 @@ async fn main() -> Result<(), CLIError> {
                 .subcommand(
                     clap::Command::new("on")
                         .about("Turn a light on")
+                        .arg(summary::concurrency_arg())
                         .arg(
-                            clap::Arg::new("light_id")
+                            clap::Arg::new("lights")
                                 .required(true)
-                                .help("ID of light to turn on")
+                                .num_args(1..)
+                                .help("IDs or names of the lights to turn on")
                         ),
                 )
                 .subcommand(
                     clap::Command::new("off")
                         .about("Turn a light off")
+                        .arg(summary::concurrency_arg())
                         .arg(
-                            clap::Arg::new("light_id")
+                            clap::Arg::new("lights")
                                 .required(true)
-                                .help("ID of light to turn off")
+                                .num_args(1..)
+                                .help("IDs or names of the lights to turn off")
                         ),
                 )
                 .subcommand(
                     clap::Command::new("toggle")
-                        .about("Toggle a light on or off")
+                        .about("Toggle lights on or off")
+                        .arg(summary::concurrency_arg())
                         .arg(
-                            clap::Arg::new("light_id")
+                            clap::Arg::new("lights")
                                 .required(true)
-                                .help("ID of light to toggle")
+                                .num_args(1..)
+                                .help("IDs or names of the lights to toggle")
                         ),
                 )
                 .subcommand(
                     clap::Command::new("brightness")
                     .about("Sets the brightness for a light")
+                    .arg(summary::concurrency_arg())
                     .arg(
-                        clap::Arg::new("light_id")
+                        clap::Arg::new("lights")
                             .required(true)
-                            .help("ID of the light to set brightness")
+                            .num_args(1..)
+                            .help("IDs or names of the lights to set brightness")
                     )
                     .arg(
                         clap::Arg::new("brightness")
                         .required(true)
-                        .help("Value between 0-255 to set light brightness to. Brightness is a scale from 1 (the minimum the light is capable of) to 254 (the maximum). A brightness of 1 is not off.")
+                        .value_parser(BrightnessValue::from_str)
+                        .allow_hyphen_values(true)
+                        .help(BRIGHTNESS_HELP)
                     )
                 )
                 .subcommand(
                     clap::Command::new("hue")
                     .about("Sets the hue for a light")
+                    .arg(summary::concurrency_arg())
                     .arg(
-                        clap::Arg::new("light_id")
+                        clap::Arg::new("lights")
                             .required(true)
-                            .help("ID of the light to set hue")
+                            .num_args(1..)
+                            .help("IDs or names of the lights to set hue")
                     )
                     .arg(
                         clap::Arg::new("hue")
                         .required(true)
+                        .value_parser(clap::value_parser!(u16))
                         .help("Value between 0-65535 to set the light hue to. This is a wrapping value. Both 0 and 65535 are red. 25500 is green and 46920 is blue.")
                     )
                 )
                 .subcommand(
                     clap::Command::new("saturation")
                     .about("Sets the saturation for a light")
+                    .arg(summary::concurrency_arg())
                     .arg(
-                        clap::Arg::new("light_id")
+                        clap::Arg::new("lights")
                             .required(true)
-                            .help("ID of the light to set saturation")
+                            .num_args(1..)
+                            .help("IDs or names of the lights to set saturation")
                     )
                     .arg(
                         clap::Arg::new("saturation")
                         .required(true)
+                        .value_parser(clap::value_parser!(u8))
                         .help("Value between 0-255 to set the light saturation to. 254 is the most saturated (colored) and 0 is the least saturated (white).")
                     )
                 )
                 .subcommand(
                     clap::Command::new("set")
                     .about("Sets various properties of the specified light")
+                    .arg(summary::concurrency_arg())
                     .arg(
-                        clap::Arg::new("light_id")
+                        clap::Arg::new("lights")
                             .required(true)
-                            .help("ID of the light to modify")
+                            .num_args(1..)
+                            .help("IDs or names of the lights to modify")
                     )
                     .arg(
                         clap::Arg::new("saturation")
                         .required(false)
                         .short('s')
+                        .value_parser(clap::value_parser!(u8))
                         .help("Value between 0-255 to set the light saturation to. 254 is the most saturated (colored) and 0 is the least saturated (white).")
                     )
                     .arg(
                         clap::Arg::new("hue")
                         .required(false)
                         .short('u')
+                        .value_parser(clap::value_parser!(u16))
                         .help("Value between 0-65535 to set the light hue to. This is a wrapping value. Both 0 and 65535 are red. 25500 is green and 46920 is blue.")
                     )
                     .arg(
                         clap::Arg::new("brightness")
                         .required(false)
                         .short('b')
-                        .help("Value between 0-255 to set light brightness to. Brightness is a scale from 1 (the minimum the light is capable of) to 254 (the maximum). A brightness of 1 is not off.")
+                        .value_parser(BrightnessValue::from_str)
+                        .allow_hyphen_values(true)
+                        .help(BRIGHTNESS_HELP)
                     )
                 )
+                .subcommand(
+                    clap::Command::new("rename")
+                    .about("Renames a light")
+                    .arg(
+                        clap::Arg::new("light_id")
+                            .required(true)
+                            .help("ID or name of the light to rename")
+                    )
+                    .arg(
+                        clap::Arg::new("name")
+                        .required(true)
+                        .help("New name for the light, up to 32 characters")
+                    )
+                )
+                .subcommand(
+                    clap::Command::new("delete")
+                    .about("Removes a light from the bridge. It has to be searched for again to use it.")
+                    .arg(
+                        clap::Arg::new("light_id")
+                            .required(true)
+                            .help("ID or name of the light to delete")
+                    )
+                    .arg(
+                        clap::Arg::new("confirm")
+                        .long("confirm")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Confirm that the light should be deleted")
+                    )
+                )
+                .subcommand(
+                    clap::Command::new("search")
+                    .about("Searches for new lights for 40 seconds")
+                    .arg(
+                        clap::Arg::new("wait")
+                        .long("wait")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Wait for the search to finish, reporting new lights as they are found")
+                    )
+                    .arg(
+                        clap::Arg::new("serial")
+                        .long("serial")
+                        .action(clap::ArgAction::Append)
+                        .help("Serial number printed on a light, to find lights paired with another bridge. Can be repeated.")
+                    )
+                )
+                .subcommand(
+                    clap::Command::new("identify")
+                    .about("Makes a light breathe so it can be found")
+                    .arg(
+                        clap::Arg::new("light_id")
+                            .required(true)
+                            .help("ID or name of the light to identify")
+                    )
+                    .arg(
+                        clap::Arg::new("long")
+                        .long("long")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Keep breathing for 15 seconds instead of once")
+                    )
+                )
+                .subcommand(color::light_color_command())
+                .subcommand(color::light_colors_command())
         )
-        .get_matches();
+        .subcommand(group::group_command())
+        .subcommand(scene::scene_command())
+        .subcommand(schedule::schedule_command())
+        .subcommand(schedule::daemon_command())
+        .subcommand(adaptive::adaptive_command())
+        .subcommand(snapshot::snapshot_command())
+        .subcommand(bridge::bridge_command())
+        .subcommand(sensor::sensor_command())
+        .subcommand(rules::rules_command())
+        .subcommand(timer::timer_command())
+        .subcommand(animate::animate_command())
+        .subcommand(routine::routine_command())
+        .subcommand(script::run_command())
+        .subcommand(automation::script_command())
+        .subcommand(shell::shell_command())
+}
 
-    let r_client = reqwest::Client::new();
-    let client = Arc::new(ReqwestHueClient::new(r_client));
+#[tokio::main]
+async fn main() -> Result<(), CLIError> {
+    let cli = build_cli().get_matches();
+    logging::init_logging(&cli)?;
+
+    let client: Arc<dyn HueClient + Send + Sync> = match cli.get_one::<String>("replay") {
+        Some(path) => Arc::new(ReplayHueClient::new(
+            Cassette::load(Path::new(path), &hue::config::TokioFileHandler).await?,
+        )),
+        None => Arc::new(RateLimitedHueClient::new(
+            Arc::new(ReqwestHueClient::new(reqwest::Client::new())),
+            BRIDGE_REQUEST_INTERVAL,
+        )),
+    };
+    let recorder = cli
+        .get_one::<String>("record")
+        .map(|path| (path, Arc::new(RecordingHueClient::new(client.clone()))));
+    let client: Arc<dyn HueClient + Send + Sync> = match &recorder {
+        Some((_, recorder)) => recorder.clone(),
+        None => client,
+    };
+    // `-q` only quiets diagnostics (see `logging::stderr_level`); command output still prints.
     let logger = Arc::new(Logger::default());
-    let api = HueApiV1::new(client, logger.clone());
+    let dry_run = cli
+        .get_flag("dry_run")
+        .then(|| Arc::new(DryRunHueClient::new(client.clone(), logger.clone())));
+    let client = match &dry_run {
+        Some(dry_run) => dry_run.clone(),
+        None => client,
+    };
+    // Bursts of commands (scripts, the shell, animations) reuse recent light states and skip
+    // writes that would change nothing.
+    let api: Arc<dyn HueApi + Send + Sync> = Arc::new(CachedHueApi::new(
+        Arc::new(HueApiV1::new(client, logger.clone())),
+        LIGHT_STATE_TTL,
+    ));
 
     let config: Result<hue::config::Config, CLIError> = match cli.subcommand_name() {
         Some(name) if name != "setup" => {
@@ -175,17 +481,67 @@ async fn main() -> Result<(), CLIError> {
     }
 
     // if we get here, we have a valid config or are running setup
-    let c = config.unwrap_or(Config {
-        bridge_ip: String::new(),
-        username: String::new(),
-    });
+    let c = config.unwrap_or_default();
 
-    return match cli.subcommand() {
+    let result = match cli.subcommand() {
+        // One config load, API and connection pool for every line of the script.
+        Some(("run", run_cmd)) => script::run_script(run_cmd, api, &c, &logger).await,
+        Some(("shell", _)) => shell::run_shell(api, &c, &logger).await,
+        _ => dispatch(&cli, api, &c, &logger).await,
+    };
+
+    // Saved even when the command failed; that's often the session worth replaying.
+    if let Some((path, recorder)) = recorder {
+        let cassette = recorder.cassette();
+        cassette
+            .save(Path::new(path), &hue::config::TokioFileHandler)
+            .await?;
+        logger.log(&format!(
+            "Recorded {} request(s) to {path}.",
+            cassette.interactions.len()
+        ));
+    }
+    if let Some(dry_run) = dry_run {
+        report_dry_run(&dry_run, logger.as_ref()).await?;
+    }
+    // Some lights changed and some didn't; scripts can tell that apart from a failed command.
+    if let Err(err @ CLIError::LightsFailed { failed, total }) = &result
+        && failed < total
+    {
+        eprintln!("Error: {err}");
+        std::process::exit(PARTIAL_FAILURE_EXIT_CODE);
+    }
+    result
+}
+
+/// Summarizes what a `--dry-run` held back.
+async fn report_dry_run(dry_run: &DryRunHueClient, logger: &Logger) -> Result<(), CLIError> {
+    let held = dry_run.held_requests().len();
+    let changes = dry_run.changes().await?;
+    logger.log(&format!(
+        "Dry run: {held} request(s) not sent, {} change(s) to the bridge.",
+        changes.len()
+    ));
+    for change in changes {
+        logger.log(&format!("  {change}"));
+    }
+    Ok(())
+}
+
+/// Runs one parsed command. `run` and `shell` are handled by their callers, so neither can
+/// start itself from inside.
+pub async fn dispatch(
+    cli: &ArgMatches,
+    api: Arc<dyn HueApi + Send + Sync>,
+    c: &Config,
+    logger: &Arc<Logger>,
+) -> Result<(), CLIError> {
+    match cli.subcommand() {
         Some(("light", sub_light_cmd)) => {
             match sub_light_cmd.subcommand() {
                 Some(("list", _)) => {
                     // Get the list of lights
-                    println!("Getting list of lights...");
+                    logger.info("Getting list of lights...");
                     let lights = api
                         .async_get_all_lights(&c.bridge_ip, &c.username)
                         .await
@@ -193,171 +549,151 @@ async fn main() -> Result<(), CLIError> {
 
                     for (id, light) in lights.0 {
                         logger.log(&format!(
-                        "Light ID: {}, On: {}, Name: {}, Type: {}, Brightness: {}, Hue: {}, Saturation: {}",
+                        "Light ID: {}, On: {}, Name: {}, Type: {}, Brightness: {}, Color: {}, Model: {}, Reachable: {}",
                         id,
                         light.state.on.unwrap_or(false),
                         light.name,
-                        light._type,
-                        light.state.brightness.unwrap_or(0),
-                        light.state.hue.unwrap_or(0),
-                        light.state.saturation.unwrap_or(0)
+                        light.kind,
+                        light.state.brightness.map_or("-".to_string(), |b| b.to_string()),
+                        light.state.color.map_or("-".to_string(), |c| c.to_string()),
+                        light.info.product_name.as_deref().or(light.info.model_id.as_deref()).unwrap_or("unknown"),
+                        light.reachable
                     ));
                     }
 
                     Ok(())
                 }
-                Some(("on", light_cmd)) => {
-                    let light_id = parse_light_id(light_cmd);
-                    println!("Turning light on for Light ID: {}", light_id);
-                    let light_state = LightState::default().with_on(true);
-                    api.async_set_light_state(&c.bridge_ip, &c.username, light_id, &light_state)
-                        .await
-                        .map_err(CLIError::HueLightCoreError)?;
-                    Ok(())
-                }
-                Some(("off", light_cmd)) => {
-                    let light_id = parse_light_id(light_cmd);
-                    println!("Turning light off for Light ID: {}", light_id);
-                    let light_state = LightState::default().with_on(false);
-                    api.async_set_light_state(&c.bridge_ip, &c.username, light_id, &light_state)
-                        .await
-                        .map_err(CLIError::HueLightCoreError)?;
-                    Ok(())
+                Some((power @ ("on" | "off"), light_cmd)) => {
+                    let targets = resolve_lights(api.as_ref(), c, light_cmd).await?;
+                    logger.info(&format!(
+                        "Turning light {} for Light ID: {}",
+                        power,
+                        light_ids(&targets)
+                    ));
+                    let light_state = LightState::default().with_on(power == "on");
+                    let updates: Vec<_> = targets
+                        .iter()
+                        .map(|(id, _)| (*id, light_state.clone()))
+                        .collect();
+                    set_states(
+                        api.as_ref(),
+                        c,
+                        &targets,
+                        &updates,
+                        concurrency(light_cmd),
+                        logger,
+                    )
+                    .await
                 }
                 Some(("toggle", light_cmd)) => {
-                    let light_id = parse_light_id(light_cmd);
-                    println!("Toggling light for Light ID: {}", light_id);
-                    let lights = api
-                        .async_get_all_lights(&c.bridge_ip, &c.username)
-                        .await
-                        .map_err(CLIError::HueLightCoreError)?;
-
-                    if let Some(light) = lights.0.get(&light_id) {
-                        let new_state = !light.state.on.unwrap_or(false);
-                        let light_state = LightState::default().with_on(new_state);
-                        let response = api
-                            .async_set_light_state(
-                                &c.bridge_ip,
-                                &c.username,
-                                light_id,
-                                &light_state,
-                            )
-                            .await
-                            .map_err(CLIError::HueLightCoreError)?;
-
-                        let success_str = format!("/lights/{}/state/on", light_id);
-                        let result_of_toggle = response.iter().find_map(|entry| match entry {
-                            HueResponseEntry::Success { success }
-                                if success.contains_key(&success_str) =>
-                            {
-                                Some(success)
-                            }
-                            _ => None,
-                        });
-
-                        let message = if result_of_toggle.is_none() {
-                            format!("Failed to toggle light {}!", light_id)
-                        } else {
-                            format!("Successfully toggled the light {}!", light_id)
-                        };
-                        logger.log(&message);
-                    } else {
-                        return Err(CLIError::HueLightCoreError(CoreError::Bridge(
-                            HueBridgeError::LightNotFound,
-                        )));
-                    }
-
-                    Ok(())
+                    let targets = resolve_lights(api.as_ref(), c, light_cmd).await?;
+                    logger.info(&format!(
+                        "Toggling light for Light ID: {}",
+                        light_ids(&targets)
+                    ));
+                    let updates: Vec<_> = targets
+                        .iter()
+                        .map(|(id, light)| {
+                            let on = light.state.on.unwrap_or(false);
+                            (*id, LightState::default().with_on(!on))
+                        })
+                        .collect();
+                    set_states(
+                        api.as_ref(),
+                        c,
+                        &targets,
+                        &updates,
+                        concurrency(light_cmd),
+                        logger,
+                    )
+                    .await
                 }
                 Some(("brightness", light_cmd)) => {
-                    let light_id = parse_light_id(light_cmd);
-                    let brightness = light_cmd
-                        .get_one::<String>("brightness")
-                        .unwrap() // required by cli
-                        .parse::<u8>()
-                        .expect("Brightness must be a number within the range: 0-255");
-
-                    println!(
-                        "Changing light brightness to {} for Light ID: {}",
-                        brightness, light_id
-                    );
-                    let l_state = LightState::default().with_brightness(brightness);
-
-                    api.async_set_light_state(&c.bridge_ip, &c.username, light_id, &l_state)
-                        .await
-                        .map_err(CLIError::HueLightCoreError)?;
+                    let targets = resolve_lights(api.as_ref(), c, light_cmd).await?;
+                    let brightness = *light_cmd.get_one::<BrightnessValue>("brightness").unwrap(); // required by cli
 
-                    Ok(())
+                    logger.info(&format!(
+                        "Changing light brightness to {} for Light ID: {}",
+                        brightness,
+                        light_ids(&targets)
+                    ));
+                    let updates = fit_to_lights(&targets, |light| {
+                        LightState::default()
+                            .with_brightness(brightness.resolve(light.state.brightness))
+                    })?;
+                    set_states(
+                        api.as_ref(),
+                        c,
+                        &targets,
+                        &updates,
+                        concurrency(light_cmd),
+                        logger,
+                    )
+                    .await
                 }
                 Some(("hue", light_cmd)) => {
-                    let light_id = parse_light_id(light_cmd);
-                    let hue = light_cmd
-                        .get_one::<String>("hue")
-                        .unwrap() // required by cli
-                        .parse::<u16>()
-                        .expect("Hue must be a number within the range: 0-65535");
-
-                    println!("Changing light hue to {} for Light ID: {}", hue, light_id);
-                    let l_state = LightState::default().with_hue(hue);
+                    let targets = resolve_lights(api.as_ref(), c, light_cmd).await?;
+                    let hue = *light_cmd.get_one::<u16>("hue").unwrap(); // required by cli
 
-                    api.async_set_light_state(&c.bridge_ip, &c.username, light_id, &l_state)
-                        .await
-                        .map_err(CLIError::HueLightCoreError)?;
-
-                    Ok(())
+                    logger.info(&format!(
+                        "Changing light hue to {} for Light ID: {}",
+                        hue,
+                        light_ids(&targets)
+                    ));
+                    let updates = fit_to_lights(&targets, |light| {
+                        LightState::default().with_color(hue_saturation(light, Some(hue), None))
+                    })?;
+                    set_states(
+                        api.as_ref(),
+                        c,
+                        &targets,
+                        &updates,
+                        concurrency(light_cmd),
+                        logger,
+                    )
+                    .await
                 }
                 Some(("saturation", light_cmd)) => {
-                    let light_id = parse_light_id(light_cmd);
-                    let saturation = light_cmd
-                        .get_one::<String>("saturation")
-                        .unwrap() // required by cli
-                        .parse::<u8>()
-                        .expect("Saturation must be a number within the range: 0-255");
-
-                    println!(
-                        "Changing light saturation to {} for Light ID: {}",
-                        saturation, light_id
-                    );
-                    let l_state = LightState::default().with_saturation(saturation);
-
-                    api.async_set_light_state(&c.bridge_ip, &c.username, light_id, &l_state)
-                        .await
-                        .map_err(CLIError::HueLightCoreError)?;
+                    let targets = resolve_lights(api.as_ref(), c, light_cmd).await?;
+                    let saturation = *light_cmd.get_one::<u8>("saturation").unwrap(); // required by cli
 
-                    Ok(())
+                    logger.info(&format!(
+                        "Changing light saturation to {} for Light ID: {}",
+                        saturation,
+                        light_ids(&targets)
+                    ));
+                    let updates = fit_to_lights(&targets, |light| {
+                        LightState::default().with_color(hue_saturation(
+                            light,
+                            None,
+                            Some(saturation),
+                        ))
+                    })?;
+                    set_states(
+                        api.as_ref(),
+                        c,
+                        &targets,
+                        &updates,
+                        concurrency(light_cmd),
+                        logger,
+                    )
+                    .await
                 }
                 Some(("set", light_cmd)) => {
-                    let light_id = parse_light_id(light_cmd);
-                    let mut l_state = LightState::default();
                     let mut action_msg: Vec<&str> = vec![];
 
-                    let saturation = light_cmd
-                        .get_one::<String>("saturation")
-                        .map(|s| s.parse::<u8>().map_err(CLIError::InvalidIntArgParse))
-                        .unwrap_or_else(|| Err(CLIError::ArgNotProvided));
-
-                    if let Ok(sat_value) = saturation {
-                        l_state = l_state.with_saturation(sat_value);
+                    let saturation = light_cmd.get_one::<u8>("saturation").copied();
+                    if saturation.is_some() {
                         action_msg.push("Saturation");
                     }
 
-                    let brightness = light_cmd
-                        .get_one::<String>("brightness")
-                        .map(|b| b.parse::<u8>().map_err(CLIError::InvalidIntArgParse))
-                        .unwrap_or_else(|| Err(CLIError::ArgNotProvided));
-
-                    if let Ok(bri_value) = brightness {
-                        l_state = l_state.with_brightness(bri_value);
+                    let brightness = light_cmd.get_one::<BrightnessValue>("brightness").copied();
+                    if brightness.is_some() {
                         action_msg.push("Brightness");
                     }
 
-                    let hue = light_cmd
-                        .get_one::<String>("hue")
-                        .map(|h| h.parse::<u16>().map_err(CLIError::InvalidIntArgParse))
-                        .unwrap_or_else(|| Err(CLIError::ArgNotProvided));
-
-                    if let Ok(hue_value) = hue {
-                        l_state = l_state.with_hue(hue_value);
+                    let hue = light_cmd.get_one::<u16>("hue").copied();
+                    if hue.is_some() {
                         action_msg.push("Hue");
                     }
 
@@ -372,15 +708,112 @@ async fn main() -> Result<(), CLIError> {
                         "No arguments provided that would change the light!".to_string()
                     };
 
-                    println!("{}", msg);
+                    if action_msg.is_empty() {
+                        logger.warn(&msg);
+                    } else {
+                        logger.info(&msg);
+                    }
 
                     // Only hit the API if the user entered at least one valid state value.
                     if !action_msg.is_empty() {
-                        api.async_set_light_state(&c.bridge_ip, &c.username, light_id, &l_state)
-                            .await
-                            .map_err(CLIError::HueLightCoreError)?;
+                        let targets = resolve_lights(api.as_ref(), c, light_cmd).await?;
+                        let updates = fit_to_lights(&targets, |light| {
+                            let mut l_state = LightState::default();
+                            if let Some(brightness) = brightness {
+                                l_state = l_state
+                                    .with_brightness(brightness.resolve(light.state.brightness));
+                            }
+                            if hue.is_some() || saturation.is_some() {
+                                l_state =
+                                    l_state.with_color(hue_saturation(light, hue, saturation));
+                            }
+                            l_state
+                        })?;
+                        set_states(
+                            api.as_ref(),
+                            c,
+                            &targets,
+                            &updates,
+                            concurrency(light_cmd),
+                            logger,
+                        )
+                        .await?;
+                    }
+
+                    Ok(())
+                }
+                Some(("rename", light_cmd)) => {
+                    let light_id = resolve_light_id(api.as_ref(), c, light_cmd).await?;
+                    let name = light_cmd.get_one::<String>("name").unwrap(); // required by cli
+                    api.async_rename_light(&c.bridge_ip, &c.username, light_id, name)
+                        .await?;
+                    logger.log(&format!("Renamed light {} to {}.", light_id, name));
+                    Ok(())
+                }
+                Some(("delete", light_cmd)) => {
+                    let light_id = resolve_light_id(api.as_ref(), c, light_cmd).await?;
+                    if !light_cmd.get_flag("confirm") {
+                        return Err(CLIError::ConfirmationRequired(format!(
+                            "deleting light {} removes it from all groups, scenes and rules; pass --confirm to do it anyway",
+                            light_id
+                        )));
+                    }
+                    api.async_delete_light(&c.bridge_ip, &c.username, light_id)
+                        .await?;
+                    logger.log(&format!("Deleted light {}.", light_id));
+                    Ok(())
+                }
+                Some(("search", search_cmd)) => {
+                    let serials: Vec<String> = search_cmd
+                        .get_many::<String>("serial")
+                        .map(|s| s.cloned().collect())
+                        .unwrap_or_default();
+                    api.async_search_lights(&c.bridge_ip, &c.username, &serials)
+                        .await?;
+                    logger.info("Searching for new lights...");
+                    if !search_cmd.get_flag("wait") {
+                        return Ok(());
                     }
 
+                    let mut reported = BTreeSet::new();
+                    loop {
+                        tokio::time::sleep(SEARCH_POLL_INTERVAL).await;
+                        let new_lights =
+                            api.async_get_new_lights(&c.bridge_ip, &c.username).await?;
+                        for (id, name) in &new_lights.lights {
+                            if reported.insert(*id) {
+                                logger.log(&format!("Found light {}: {}", id, name));
+                            }
+                        }
+                        if new_lights.lastscan != LastScan::Active {
+                            break;
+                        }
+                    }
+                    logger.log(&format!(
+                        "Search finished, {} new light(s) found.",
+                        reported.len()
+                    ));
+                    Ok(())
+                }
+                Some(("color", light_cmd)) => {
+                    color::run_light_color(light_cmd, api, c, logger).await
+                }
+                Some(("colors", _)) => color::run_light_colors(c, logger),
+                Some(("identify", light_cmd)) => {
+                    let light_id = resolve_light_id(api.as_ref(), c, light_cmd).await?;
+                    let alert = if light_cmd.get_flag("long") {
+                        Alert::LongBreathe
+                    } else {
+                        Alert::Breathe
+                    };
+                    logger.info(&format!("Identifying Light ID: {}", light_id));
+                    api.async_set_light_state(
+                        &c.bridge_ip,
+                        &c.username,
+                        light_id,
+                        &LightState::default().with_alert(alert),
+                    )
+                    .await?;
                     Ok(())
                 }
                 _ => Err(CLIError::InvalidCommandError),
@@ -404,7 +837,46 @@ async fn main() -> Result<(), CLIError> {
                         ip_address, username
                     ));
 
-                    huelight_core::config::Config::new(ip_address, username)
+                    // Keep any other settings (location, timezone) from an existing config.
+                    let mut config = Config::load(&hue::config::TokioFileHandler)
+                        .await
+                        .unwrap_or_default();
+                    config.bridge_ip = ip_address;
+                    config.username = username;
+                    config
+                        .save(logger.as_ref(), &hue::config::TokioFileHandler)
+                        .await
+                        .map_err(CLIError::HueLightCoreError)?;
+                    Ok(())
+                }
+                Some(("location", setup_location_cmd)) => {
+                    let parse_coordinate = |name: &str| {
+                        setup_location_cmd
+                            .get_one::<String>(name)
+                            .unwrap() // required by cli
+                            .parse::<f64>()
+                            .map_err(|_| CLIError::InvalidFloatArgParse(name.to_string()))
+                    };
+                    let latitude = parse_coordinate("latitude")?;
+                    let longitude = parse_coordinate("longitude")?;
+                    let timezone = setup_location_cmd.get_one::<String>("timezone").cloned();
+                    if let Some(tz) = &timezone {
+                        hue::scheduler::resolve_timezone(Some(tz)).map_err(CoreError::Parse)?;
+                    }
+
+                    let mut config = Config::load(&hue::config::TokioFileHandler).await?;
+                    config.latitude = Some(latitude);
+                    config.longitude = Some(longitude);
+                    if timezone.is_some() {
+                        config.timezone = timezone;
+                    }
+                    logger.log(&format!(
+                        "Location: {}, {}, Timezone: {}",
+                        latitude,
+                        longitude,
+                        config.timezone.as_deref().unwrap_or("system default")
+                    ));
+                    config
                         .save(logger.as_ref(), &hue::config::TokioFileHandler)
                         .await
                         .map_err(CLIError::HueLightCoreError)?;
@@ -413,6 +885,27 @@ async fn main() -> Result<(), CLIError> {
                 _ => Err(CLIError::InvalidCommandError),
             }
         }
+        Some(("group", group_cmd)) => group::run_group(group_cmd, api, c, logger).await,
+        Some(("scene", scene_cmd)) => scene::run_scene(scene_cmd, api, c, logger).await,
+        Some(("schedule", schedule_cmd)) => {
+            schedule::run_schedule(schedule_cmd, api, c, logger).await
+        }
+        Some(("daemon", daemon_cmd)) => schedule::run_daemon(daemon_cmd, api, c, logger).await,
+        Some(("adaptive", adaptive_cmd)) => {
+            adaptive::run_adaptive(adaptive_cmd, api, c, logger).await
+        }
+        Some(("snapshot", snapshot_cmd)) => {
+            snapshot::run_snapshot(snapshot_cmd, api, c, logger).await
+        }
+        Some(("bridge", bridge_cmd)) => bridge::run_bridge(bridge_cmd, api, c, logger).await,
+        Some(("sensor", sensor_cmd)) => sensor::run_sensor(sensor_cmd, api, c, logger).await,
+        Some(("rules", rules_cmd)) => rules::run_rules(rules_cmd, api, c, logger).await,
+        Some(("timer", timer_cmd)) => timer::run_timer(timer_cmd, api, c, logger).await,
+        Some(("animate", animate_cmd)) => animate::run_animate(animate_cmd, api, c, logger).await,
+        Some(("routine", routine_cmd)) => routine::run_routine(routine_cmd, api, c, logger).await,
+        Some(("script", script_cmd)) => {
+            automation::run_script_command(script_cmd, api, c, logger).await
+        }
         _ => Err(CLIError::InvalidCommandError),
-    };
+    }
 }
@@ -0,0 +1,248 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{NaiveTime, Utc};
+use clap::ArgMatches;
+use huelight_core::config::{Config, TokioFileHandler};
+use huelight_core::domain::light::{LightId, Lights};
+use huelight_core::duration::{format_duration, parse_duration};
+use huelight_core::error::{CoreError, HueBridgeError, ParseError};
+use huelight_core::hue_api::HueApi;
+use huelight_core::logger::{ILogger, Logger};
+use huelight_core::routine::{ROUTINE_STEP, Routine, RoutineKind, next_local_time};
+use huelight_core::scheduler::resolve_timezone;
+
+use crate::error::CLIError;
+
+/// While waiting for a routine to start, check this often whether it was cancelled.
+const PENDING_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+fn kind_arg() -> clap::Arg {
+    clap::Arg::new("kind")
+        .required(true)
+        .value_parser(["wake", "sleep"])
+        .help("Which routine")
+}
+
+pub fn routine_command() -> clap::Command {
+    clap::Command::new("routine")
+        .about("Wake-up and sleep routines that run on this machine; Ctrl-C cancels them")
+        .subcommand(
+            clap::Command::new("wake")
+                .about("Sunrise simulation: ramps from deep red at 1% to cool white at 100%")
+                .arg(
+                    clap::Arg::new("at")
+                        .long("at")
+                        .required(true)
+                        .help("Time to be fully awake, e.g. 06:30. The ramp starts --duration earlier."),
+                )
+                .arg(
+                    clap::Arg::new("duration")
+                        .long("duration")
+                        .short('d')
+                        .default_value("30m")
+                        .help("Length of the ramp, e.g. 30m"),
+                )
+                .arg(
+                    clap::Arg::new("lights")
+                        .long("lights")
+                        .short('l')
+                        .required(true)
+                        .num_args(1..)
+                        .help("IDs or names of lights or groups to wake up with"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("sleep")
+                .about("Fades lights from their current state to off")
+                .arg(
+                    clap::Arg::new("duration")
+                        .long("duration")
+                        .short('d')
+                        .default_value("20m")
+                        .help("Length of the fade, e.g. 20m"),
+                )
+                .arg(
+                    clap::Arg::new("lights")
+                        .long("lights")
+                        .short('l')
+                        .num_args(1..)
+                        .help("IDs or names of lights or groups to fade. Defaults to every light that is on."),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("resume")
+                .about("Continue a routine that was interrupted, e.g. by a restart")
+                .arg(kind_arg()),
+        )
+        .subcommand(
+            clap::Command::new("cancel")
+                .about("Cancel a pending or running routine")
+                .arg(kind_arg()),
+        )
+}
+
+/// Resolves light and group IDs or names to light IDs, without duplicates.
+async fn resolve_lights(
+    api: &(dyn HueApi + Send + Sync),
+    config: &Config,
+    lights: &Lights,
+    targets: &[&String],
+) -> Result<Vec<LightId>, CLIError> {
+    let (ip, user) = (config.bridge_ip.as_str(), config.username.as_str());
+    let mut ids = Vec::new();
+    let mut groups = None;
+    for target in targets {
+        let found = match lights.find(target) {
+            Some((id, _)) => vec![id],
+            None => {
+                if groups.is_none() {
+                    groups = Some(api.async_get_all_groups(ip, user).await?);
+                }
+                let (_, group) = groups
+                    .as_ref()
+                    .and_then(|groups| groups.find(target))
+                    .ok_or(CoreError::Bridge(HueBridgeError::LightNotFound))?;
+                group.light_ids()
+            }
+        };
+        for id in found {
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+    }
+    Ok(ids)
+}
+
+fn parse_kind(cmd: &ArgMatches) -> RoutineKind {
+    match cmd.get_one::<String>("kind").map(String::as_str) {
+        Some("wake") => RoutineKind::Wake,
+        _ => RoutineKind::Sleep,
+    }
+}
+
+pub async fn run_routine(
+    cmd: &ArgMatches,
+    api: Arc<dyn HueApi + Send + Sync>,
+    config: &Config,
+    logger: &Logger,
+) -> Result<(), CLIError> {
+    let (ip, user) = (config.bridge_ip.as_str(), config.username.as_str());
+    let routine = match cmd.subcommand() {
+        Some(("wake", wake_cmd)) => {
+            let at = wake_cmd.get_one::<String>("at").unwrap(); // required by cli
+            let time = NaiveTime::parse_from_str(at, "%H:%M")
+                .map_err(|_| CoreError::Parse(ParseError::InvalidTimePattern(at.clone())))?;
+            let duration = parse_duration(wake_cmd.get_one::<String>("duration").unwrap()) // has a default value
+                .map_err(CoreError::Parse)?;
+            let timezone =
+                resolve_timezone(config.timezone.as_deref()).map_err(CoreError::Parse)?;
+
+            let now = Utc::now();
+            let wake_at = next_local_time(time, now, &timezone);
+            // Too late for the full ramp: start now and still finish on time.
+            let starts = (wake_at - duration).max(now);
+            let duration = (wake_at - starts).to_std().unwrap_or(Duration::ZERO);
+
+            let lights = api.async_get_all_lights(ip, user).await?;
+            let targets: Vec<&String> = wake_cmd.get_many("lights").unwrap().collect(); // required by cli
+            let ids = resolve_lights(api.as_ref(), config, &lights, &targets).await?;
+            Routine::new(RoutineKind::Wake, starts, duration, &lights, &ids)
+        }
+        Some(("sleep", sleep_cmd)) => {
+            let duration = parse_duration(sleep_cmd.get_one::<String>("duration").unwrap()) // has a default value
+                .map_err(CoreError::Parse)?;
+            let lights = api.async_get_all_lights(ip, user).await?;
+            let ids = match sleep_cmd.get_many::<String>("lights") {
+                Some(targets) => {
+                    let targets: Vec<&String> = targets.collect();
+                    resolve_lights(api.as_ref(), config, &lights, &targets).await?
+                }
+                None => lights
+                    .0
+                    .iter()
+                    .filter(|(_, light)| light.state.on == Some(true))
+                    .map(|(id, _)| *id)
+                    .collect(),
+            };
+            Routine::new(RoutineKind::Sleep, Utc::now(), duration, &lights, &ids)
+        }
+        Some(("resume", resume_cmd)) => {
+            let kind = parse_kind(resume_cmd);
+            Routine::load(kind, &TokioFileHandler)
+                .await?
+                .ok_or_else(|| CLIError::NoRoutine(kind.name().to_string()))?
+        }
+        Some(("cancel", cancel_cmd)) => {
+            let kind = parse_kind(cancel_cmd);
+            if !Routine::cancel(kind, &TokioFileHandler).await? {
+                return Err(CLIError::NoRoutine(kind.name().to_string()));
+            }
+            logger.log("Routine cancelled.");
+            return Ok(());
+        }
+        _ => return Err(CLIError::InvalidCommandError),
+    };
+
+    if routine.lights.is_empty() {
+        logger.log("No lights to run the routine on.");
+        return Ok(());
+    }
+    routine.save(&TokioFileHandler).await?;
+    logger.log(&format!(
+        "Starting the {} routine on {} lights at {} for {}. Ctrl-C to cancel.",
+        routine.kind.name(),
+        routine.lights.len(),
+        routine.starts.with_timezone(&chrono::Local).format("%H:%M"),
+        format_duration(routine.duration)
+    ));
+
+    tokio::select! {
+        result = play(&routine, api.as_ref(), config, logger) => result,
+        _ = tokio::signal::ctrl_c() => {
+            Routine::cancel(routine.kind, &TokioFileHandler).await?;
+            logger.log("Routine cancelled.");
+            Ok(())
+        }
+    }
+}
+
+/// Waits for the routine to start, then steps the lights along it until it ends or its saved
+/// file disappears.
+async fn play(
+    routine: &Routine,
+    api: &(dyn HueApi + Send + Sync),
+    config: &Config,
+    logger: &Logger,
+) -> Result<(), CLIError> {
+    let (ip, user) = (config.bridge_ip.as_str(), config.username.as_str());
+    let colors = config.color_registry().map_err(CoreError::Parse)?;
+    let animation = routine
+        .animation(&config.wake_curve(), &colors)
+        .map_err(CoreError::Parse)?;
+
+    loop {
+        // `routine cancel` from another terminal removes the file.
+        let saved = Routine::load(routine.kind, &TokioFileHandler).await?;
+        if saved.is_none_or(|saved| saved.starts != routine.starts) {
+            logger.log("Routine cancelled.");
+            return Ok(());
+        }
+
+        let now = Utc::now();
+        let wait = match (routine.starts - now).to_std() {
+            Ok(until_start) => until_start.min(PENDING_POLL_INTERVAL),
+            Err(_) => {
+                routine.step(&animation, api, ip, user, now).await?;
+                if routine.is_finished(now) {
+                    Routine::cancel(routine.kind, &TokioFileHandler).await?;
+                    logger.log("Routine finished.");
+                    return Ok(());
+                }
+                ROUTINE_STEP
+            }
+        };
+        tokio::time::sleep(wait).await;
+    }
+}
@@ -0,0 +1,177 @@
+use std::sync::Arc;
+
+use clap::ArgMatches;
+use huelight_core::config::{Config, FileHandler, TokioFileHandler};
+use huelight_core::error::{CoreError, HueBridgeError};
+use huelight_core::hue_api::HueApi;
+use huelight_core::logger::{ILogger, Logger};
+use huelight_core::models::rule::{Rule, RuleId};
+use huelight_core::rule_file::{RuleChange, RuleFile, RuleResources, diff_rules};
+
+use crate::error::CLIError;
+
+pub fn rules_command() -> clap::Command {
+    clap::Command::new("rules")
+        .about("Manage rules that run on the bridge itself, e.g. motion-activated lights")
+        .subcommand(
+            clap::Command::new("list")
+                .about("List installed rules with their conditions and actions"),
+        )
+        .subcommand(
+            clap::Command::new("delete")
+                .about("Delete a rule from the bridge")
+                .arg(
+                    clap::Arg::new("rule")
+                        .required(true)
+                        .help("ID or name of the rule"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("apply")
+                .about("Compile a rule file and create or update its rules on the bridge")
+                .arg(
+                    clap::Arg::new("file")
+                        .required(true)
+                        .help("TOML file with [[rule]] entries of the form 'when ... then ...'"),
+                )
+                .arg(
+                    clap::Arg::new("check")
+                        .long("check")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Only show what would change on the bridge"),
+                )
+                .arg(
+                    clap::Arg::new("prune")
+                        .long("prune")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Also delete installed rules that are not in the file"),
+                ),
+        )
+}
+
+fn describe(id: RuleId, rule: &Rule) -> Vec<String> {
+    let mut lines = vec![format!(
+        "{}: {} [{}, triggered {} times]",
+        id,
+        rule.name,
+        rule.status.as_deref().unwrap_or("unknown"),
+        rule.timestriggered.unwrap_or(0)
+    )];
+    for condition in &rule.conditions {
+        // The serde names ("eq", "ddx", ...) are what the bridge API documents.
+        let operator = serde_json::to_value(condition.operator)
+            .ok()
+            .and_then(|v| v.as_str().map(str::to_string))
+            .unwrap_or_default();
+        lines.push(format!(
+            "    if   {} {} {}",
+            condition.address,
+            operator,
+            condition.value.as_deref().unwrap_or("")
+        ));
+    }
+    for action in &rule.actions {
+        lines.push(format!(
+            "    then {} {} {}",
+            action.method, action.address, action.body
+        ));
+    }
+    lines
+}
+
+async fn compile_file(
+    api: &(dyn HueApi + Send + Sync),
+    config: &Config,
+    path: &str,
+) -> Result<Vec<Rule>, CLIError> {
+    let text = TokioFileHandler.read_file(path).await?;
+    let specs = RuleFile::from_toml(&text)?.parse()?;
+
+    let (ip, user) = (config.bridge_ip.as_str(), config.username.as_str());
+    let (lights, groups, scenes, sensors) = tokio::try_join!(
+        api.async_get_all_lights(ip, user),
+        api.async_get_all_groups(ip, user),
+        api.async_get_all_scenes(ip, user),
+        api.async_get_all_sensors(ip, user),
+    )?;
+    let resources = RuleResources {
+        lights: &lights,
+        groups: &groups,
+        scenes: &scenes,
+        sensors: &sensors,
+    };
+    Ok(specs
+        .iter()
+        .map(|spec| spec.compile(&resources))
+        .collect::<Result<_, _>>()?)
+}
+
+pub async fn run_rules(
+    cmd: &ArgMatches,
+    api: Arc<dyn HueApi + Send + Sync>,
+    config: &Config,
+    logger: &Logger,
+) -> Result<(), CLIError> {
+    let (ip, user) = (config.bridge_ip.as_str(), config.username.as_str());
+
+    match cmd.subcommand() {
+        Some(("list", _)) => {
+            let rules = api.async_get_all_rules(ip, user).await?;
+            let mut sorted: Vec<_> = rules.0.iter().collect();
+            sorted.sort_by_key(|(id, _)| **id);
+            for (id, rule) in sorted {
+                for line in describe(*id, rule) {
+                    logger.log(&line);
+                }
+            }
+            Ok(())
+        }
+        Some(("delete", delete_cmd)) => {
+            let target = delete_cmd.get_one::<String>("rule").unwrap(); // required by cli
+            let rules = api.async_get_all_rules(ip, user).await?;
+            let (id, rule) =
+                rules
+                    .find(target)
+                    .ok_or(CLIError::HueLightCoreError(CoreError::Bridge(
+                        HueBridgeError::RuleNotFound,
+                    )))?;
+            api.async_delete_rule(ip, user, id).await?;
+            logger.log(&format!("Deleted rule {} ({}).", id, rule.name));
+            Ok(())
+        }
+        Some(("apply", apply_cmd)) => {
+            let path = apply_cmd.get_one::<String>("file").unwrap(); // required by cli
+            let desired = compile_file(api.as_ref(), config, path).await?;
+            let installed = api.async_get_all_rules(ip, user).await?;
+            let changes = diff_rules(desired, &installed);
+            let prune = apply_cmd.get_flag("prune");
+
+            for change in &changes {
+                logger.log(&change.to_string());
+            }
+            if apply_cmd.get_flag("check") {
+                return Ok(());
+            }
+
+            for change in changes {
+                match change {
+                    RuleChange::Create(rule) => {
+                        let id = api.async_create_rule(ip, user, &rule).await?;
+                        logger.log(&format!("Created rule {} ({}).", id, rule.name));
+                    }
+                    RuleChange::Update { id, rule } => {
+                        api.async_update_rule(ip, user, id, &rule).await?;
+                        logger.log(&format!("Updated rule {} ({}).", id, rule.name));
+                    }
+                    RuleChange::Extra { id, name } if prune => {
+                        api.async_delete_rule(ip, user, id).await?;
+                        logger.log(&format!("Deleted rule {} ({}).", id, name));
+                    }
+                    RuleChange::Unchanged { .. } | RuleChange::Extra { .. } => {}
+                }
+            }
+            Ok(())
+        }
+        _ => Err(CLIError::InvalidCommandError),
+    }
+}
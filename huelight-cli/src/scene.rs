@@ -0,0 +1,85 @@
+use std::sync::Arc;
+
+use clap::ArgMatches;
+use huelight_core::config::Config;
+use huelight_core::error::{CoreError, HueBridgeError};
+use huelight_core::hue_api::HueApi;
+use huelight_core::logger::{ILogger, Logger};
+
+use crate::error::CLIError;
+
+pub fn scene_command() -> clap::Command {
+    clap::Command::new("scene")
+        .about("Commands to list and recall scenes stored on the bridge")
+        .subcommand(clap::Command::new("list").about("Get the list of scenes on the Hue Bridge"))
+        .subcommand(
+            clap::Command::new("recall")
+                .about("Recalls a scene")
+                .arg(
+                    clap::Arg::new("scene")
+                        .required(true)
+                        .help("ID or name of the scene to recall"),
+                )
+                .arg(
+                    clap::Arg::new("group")
+                        .long("group")
+                        .short('g')
+                        .help("ID or name of the group to recall it in. Defaults to the scene's own group, or every light."),
+                ),
+        )
+}
+
+pub async fn run_scene(
+    cmd: &ArgMatches,
+    api: Arc<dyn HueApi + Send + Sync>,
+    config: &Config,
+    logger: &Logger,
+) -> Result<(), CLIError> {
+    let (ip, user) = (config.bridge_ip.as_str(), config.username.as_str());
+    match cmd.subcommand() {
+        Some(("list", _)) => {
+            let scenes = api.async_get_all_scenes(ip, user).await?;
+            let mut scenes: Vec<_> = scenes.0.into_iter().collect();
+            scenes.sort_by(|(_, a), (_, b)| a.name.cmp(&b.name));
+            for (id, scene) in scenes {
+                logger.log(&format!(
+                    "Scene ID: {}, Name: {}, Group: {}, Lights: {}",
+                    id,
+                    scene.name,
+                    scene.group.as_deref().unwrap_or("-"),
+                    scene.lights.join(",")
+                ));
+            }
+            Ok(())
+        }
+        Some(("recall", recall_cmd)) => {
+            let scene = recall_cmd.get_one::<String>("scene").unwrap(); // required by cli
+            let scenes = api.async_get_all_scenes(ip, user).await?;
+            let (scene_id, found) = scenes
+                .find(scene)
+                .ok_or(CoreError::Bridge(HueBridgeError::SceneNotFound))?;
+            let group_id = match (recall_cmd.get_one::<String>("group"), &found.group) {
+                // Group 0 holds every light but isn't listed with the others.
+                (Some(group), _) if group == "0" => 0,
+                (Some(group), _) => {
+                    let groups = api.async_get_all_groups(ip, user).await?;
+                    groups
+                        .find(group)
+                        .map(|(id, _)| id)
+                        .ok_or(CoreError::Bridge(HueBridgeError::GroupNotFound))?
+                }
+                (None, Some(own)) => own
+                    .parse()
+                    .map_err(|_| CoreError::Bridge(HueBridgeError::GroupNotFound))?,
+                (None, None) => 0,
+            };
+            logger.info(&format!(
+                "Recalling scene {} in group {}",
+                found.name, group_id
+            ));
+            api.async_recall_scene(ip, user, group_id, scene_id).await?;
+            Ok(())
+        }
+        _ => Err(CLIError::InvalidCommandError),
+    }
+}
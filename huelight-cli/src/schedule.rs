@@ -0,0 +1,229 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use chrono::{DateTime, TimeZone, Utc};
+use clap::ArgMatches;
+use huelight_core::config::FileHandler;
+use huelight_core::config::{Config, TokioFileHandler, config_dir, path_to_str};
+use huelight_core::error::{CoreError, HueBridgeError};
+use huelight_core::hue_api::HueApi;
+use huelight_core::logger::{ILogger, Logger};
+use huelight_core::scheduler::clock::SystemClock;
+use huelight_core::scheduler::executor::{ActionExecutor, HueActionExecutor};
+use huelight_core::scheduler::schedule_file::ScheduleFile;
+use huelight_core::scheduler::{Daemon, Scheduler, SchedulerState};
+
+use crate::error::CLIError;
+
+fn file_arg() -> clap::Arg {
+    clap::Arg::new("file")
+        .long("file")
+        .short('f')
+        .global(true)
+        .help(
+            "Schedule file to use. Defaults to schedule.toml in the huelightcli config directory.",
+        )
+}
+
+pub fn schedule_command() -> clap::Command {
+    clap::Command::new("schedule")
+        .about(
+            "Inspect and trigger entries of the local schedule file, and manage bridge schedules",
+        )
+        .arg(file_arg())
+        .subcommand(
+            clap::Command::new("list")
+                .about("List all local schedule entries and the schedules stored on the bridge"),
+        )
+        .subcommand(
+            clap::Command::new("delete")
+                .about("Delete a schedule or timer stored on the bridge")
+                .arg(
+                    clap::Arg::new("schedule")
+                        .required(true)
+                        .help("ID or name of the bridge schedule"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("next")
+                .about("Show the upcoming runs")
+                .arg(
+                    clap::Arg::new("count")
+                        .long("count")
+                        .short('n')
+                        .default_value("10")
+                        .help("Number of upcoming runs to show"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("run-now")
+                .about("Run a schedule entry's action immediately")
+                .arg(
+                    clap::Arg::new("name")
+                        .required(true)
+                        .help("Name of the schedule entry to run"),
+                ),
+        )
+}
+
+pub fn daemon_command() -> clap::Command {
+    clap::Command::new("daemon")
+        .about("Run the local scheduler in the foreground until interrupted with Ctrl-C")
+        .arg(file_arg())
+}
+
+fn schedule_path(cmd: &ArgMatches) -> Result<PathBuf, CLIError> {
+    Ok(match cmd.get_one::<String>("file") {
+        Some(file) => PathBuf::from(file),
+        None => config_dir()?.join("schedule.toml"),
+    })
+}
+
+/// Reads and compiles the schedule file given with `--file`, or the default one.
+async fn load_scheduler(cmd: &ArgMatches, config: &Config) -> Result<Scheduler, CLIError> {
+    let path = schedule_path(cmd)?;
+    let text = TokioFileHandler.read_file(path_to_str(&path)?).await?;
+    Ok(Scheduler::new(ScheduleFile::from_toml(&text)?, config)?)
+}
+
+fn format_time<Tz: TimeZone>(t: DateTime<Utc>, tz: &Tz) -> String
+where
+    Tz::Offset: std::fmt::Display,
+{
+    t.with_timezone(tz)
+        .format("%a %Y-%m-%d %H:%M %Z")
+        .to_string()
+}
+
+/// Lists the entries of the local schedule file with their last and next runs.
+async fn list_local(cmd: &ArgMatches, config: &Config, logger: &Logger) -> Result<(), CLIError> {
+    let scheduler = load_scheduler(cmd, config).await?;
+    let tz = scheduler.timezone();
+    let now = Utc::now();
+    let state = SchedulerState::load(&TokioFileHandler).await?;
+    logger.log(&format!("Schedule entries (timezone {}):", tz));
+    for entry in scheduler.entries() {
+        let next = if entry.enabled {
+            scheduler
+                .next_run(entry, now)
+                .map(|t| format_time(t, &tz))
+                .unwrap_or_else(|| "never".to_string())
+        } else {
+            "disabled".to_string()
+        };
+        let last = state
+            .last_runs
+            .get(&entry.name)
+            .map(|t| format_time(*t, &tz))
+            .unwrap_or_else(|| "never".to_string());
+        logger.log(&format!(
+            "{}: {} -> {} (missed runs: {:?}, last run: {}, next run: {})",
+            entry.name, entry.trigger, entry.action, entry.missed_runs, last, next
+        ));
+    }
+    Ok(())
+}
+
+pub async fn run_schedule(
+    cmd: &ArgMatches,
+    api: Arc<dyn HueApi + Send + Sync>,
+    config: &Config,
+    logger: &Logger,
+) -> Result<(), CLIError> {
+    let (ip, user) = (config.bridge_ip.as_str(), config.username.as_str());
+
+    match cmd.subcommand() {
+        Some(("list", _)) => {
+            // Only a schedule file given with --file has to exist.
+            if cmd.get_one::<String>("file").is_some() || schedule_path(cmd)?.exists() {
+                list_local(cmd, config, logger).await?;
+            }
+            let schedules = api.async_get_all_schedules(ip, user).await?;
+            let mut sorted: Vec<_> = schedules.0.iter().collect();
+            sorted.sort_by_key(|(id, _)| **id);
+            logger.log("Bridge schedules:");
+            for (id, schedule) in sorted {
+                logger.log(&format!(
+                    "{}: {} [{}] {} -> {} {} {}",
+                    id,
+                    schedule.name,
+                    schedule.status.as_deref().unwrap_or("unknown"),
+                    schedule
+                        .time_pattern()
+                        .map_or("unknown".to_string(), ToString::to_string),
+                    schedule.command.method,
+                    schedule.command.address,
+                    schedule.command.body
+                ));
+            }
+            Ok(())
+        }
+        Some(("delete", delete_cmd)) => {
+            let target = delete_cmd.get_one::<String>("schedule").unwrap(); // required by cli
+            let schedules = api.async_get_all_schedules(ip, user).await?;
+            let (id, schedule) = schedules
+                .find(target)
+                .ok_or(CoreError::Bridge(HueBridgeError::ScheduleNotFound))?;
+            api.async_delete_schedule(ip, user, id).await?;
+            logger.log(&format!("Deleted schedule {} ({}).", id, schedule.name));
+            Ok(())
+        }
+        Some(("next", next_cmd)) => {
+            let scheduler = load_scheduler(cmd, config).await?;
+            let tz = scheduler.timezone();
+            let count = next_cmd
+                .get_one::<String>("count")
+                .unwrap() // has a default value
+                .parse::<usize>()?;
+            for (entry, at) in scheduler.upcoming(Utc::now(), count) {
+                logger.log(&format!(
+                    "{}  {} -> {}",
+                    format_time(at, &tz),
+                    entry.name,
+                    entry.action
+                ));
+            }
+            Ok(())
+        }
+        Some(("run-now", run_cmd)) => {
+            let scheduler = load_scheduler(cmd, config).await?;
+            let name = run_cmd.get_one::<String>("name").unwrap(); // required by cli
+            let entry = scheduler.entry(name)?;
+            logger.log(&format!("Running '{}': {}", entry.name, entry.action));
+            HueActionExecutor::new(api, &config.bridge_ip, &config.username)
+                .execute(&entry.action)
+                .await?;
+            Ok(())
+        }
+        _ => Err(CLIError::InvalidCommandError),
+    }
+}
+
+pub async fn run_daemon(
+    cmd: &ArgMatches,
+    api: Arc<dyn HueApi + Send + Sync>,
+    config: &Config,
+    logger: &Logger,
+) -> Result<(), CLIError> {
+    let scheduler = load_scheduler(cmd, config).await?;
+    let executor = HueActionExecutor::new(api, &config.bridge_ip, &config.username);
+    let mut state = SchedulerState::load(&TokioFileHandler).await?;
+    let daemon = Daemon {
+        scheduler: &scheduler,
+        clock: &SystemClock,
+        executor: &executor,
+        logger,
+        file_handler: &TokioFileHandler,
+    };
+
+    logger.log(&format!(
+        "Scheduler daemon started with {} entries (timezone {}). Press Ctrl-C to stop.",
+        scheduler.entries().len(),
+        scheduler.timezone()
+    ));
+    tokio::select! {
+        result = daemon.run_until(&mut state, None) => result?,
+        _ = tokio::signal::ctrl_c() => logger.log("Scheduler daemon stopped."),
+    }
+    Ok(())
+}
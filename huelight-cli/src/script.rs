@@ -0,0 +1,525 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use clap::ArgMatches;
+use clap::parser::ValueSource;
+use huelight_core::config::{Config, FileHandler, TokioFileHandler};
+use huelight_core::duration::parse_duration;
+use huelight_core::hue_api::HueApi;
+use huelight_core::logger::{ILogger, Logger};
+use tokio::io::AsyncReadExt;
+use tokio::task::JoinSet;
+
+use crate::error::CLIError;
+use crate::{build_cli, dispatch};
+
+pub fn run_command() -> clap::Command {
+    clap::Command::new("run")
+        .about("Run a script of huelightcli commands with a single config load and bridge connection")
+        .long_about(
+            "Run a script of huelightcli commands with a single config load and bridge connection.\n\n\
+             Each line is a command as you would type it after `huelightcli`, e.g. `light on 3`,\n\
+             `group set Office bri 50%` or `scene recall Relax`.\n\
+             Also supported:\n  \
+             # comment\n  \
+             let room = 3          (then use $room or ${room})\n  \
+             wait 2s\n  \
+             parallel {            (commands until the closing } run at the same time)\n  \
+             }",
+        )
+        .arg(
+            clap::Arg::new("script")
+                .default_value("-")
+                .help("Path to the script, or - to read it from stdin"),
+        )
+        .arg(
+            clap::Arg::new("continue_on_error")
+                .long("continue-on-error")
+                .action(clap::ArgAction::SetTrue)
+                .help("Report failing lines and keep going instead of stopping at the first one"),
+        )
+}
+
+/// A command's line number and words.
+type NumberedCommand = (usize, Vec<String>);
+
+/// One line of a script, after tokenizing.
+#[derive(Debug, PartialEq)]
+enum Statement {
+    Command(Vec<String>),
+    Wait(Duration),
+    Let(String, String),
+    /// Commands to run at the same time, with their line numbers.
+    Parallel(Vec<NumberedCommand>),
+}
+
+/// Splits a line into words like a shell would for simple cases: whitespace separates words,
+/// and single or double quotes group them.
+pub(crate) fn tokenize(line: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+    for ch in line.chars() {
+        match quote {
+            Some(q) if ch == q => quote = None,
+            Some(_) => current.push(ch),
+            None if ch == '"' || ch == '\'' => {
+                quote = Some(ch);
+                in_word = true;
+            }
+            None if ch.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            None => {
+                current.push(ch);
+                in_word = true;
+            }
+        }
+    }
+    if quote.is_some() {
+        return Err("unterminated quote".to_string());
+    }
+    if in_word {
+        words.push(current);
+    }
+    Ok(words)
+}
+
+/// Parses a script into statements with their (1-based) line numbers.
+fn parse_script(text: &str) -> Result<Vec<(usize, Statement)>, CLIError> {
+    let syntax = |line: usize, message: &str| CLIError::Script {
+        line,
+        message: message.to_string(),
+    };
+    let mut statements = Vec::new();
+    let mut parallel: Option<(usize, Vec<NumberedCommand>)> = None;
+
+    for (index, raw) in text.lines().enumerate() {
+        let number = index + 1;
+        let line = raw.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let words = tokenize(line).map_err(|message| syntax(number, &message))?;
+
+        if let Some((_, commands)) = parallel.as_mut() {
+            match words.first().map(String::as_str) {
+                Some("}") if words.len() == 1 => {
+                    let (start, commands) = parallel.take().unwrap(); // checked above
+                    statements.push((start, Statement::Parallel(commands)));
+                }
+                Some("wait" | "let" | "parallel" | "run") => {
+                    return Err(syntax(number, "only commands can go inside parallel { }"));
+                }
+                _ => commands.push((number, words)),
+            }
+            continue;
+        }
+
+        let statement = match words.first().map(String::as_str) {
+            Some("wait") if words.len() == 2 => Statement::Wait(
+                parse_duration(&words[1]).map_err(|err| syntax(number, &err.to_string()))?,
+            ),
+            Some("wait") => return Err(syntax(number, "expected `wait <duration>`")),
+            Some("let") => match words.as_slice() {
+                [_, name, eq, value] if eq == "=" => Statement::Let(name.clone(), value.clone()),
+                _ => return Err(syntax(number, "expected `let <name> = <value>`")),
+            },
+            Some("parallel") if words.len() == 2 && words[1] == "{" => {
+                parallel = Some((number, Vec::new()));
+                continue;
+            }
+            Some("parallel") => return Err(syntax(number, "expected `parallel {`")),
+            Some("run") => return Err(syntax(number, "scripts can't run other scripts")),
+            _ => Statement::Command(words),
+        };
+        statements.push((number, statement));
+    }
+
+    match parallel {
+        Some((start, _)) => Err(syntax(start, "parallel { is never closed")),
+        None => Ok(statements),
+    }
+}
+
+/// Replaces `$name` and `${name}` in every word with the variable's value.
+fn substitute(
+    words: &[String],
+    variables: &HashMap<String, String>,
+) -> Result<Vec<String>, String> {
+    words
+        .iter()
+        .map(|word| {
+            let mut out = String::new();
+            let mut rest = word.as_str();
+            while let Some(start) = rest.find('$') {
+                out.push_str(&rest[..start]);
+                let after = &rest[start + 1..];
+                let (name, len) = match after.strip_prefix('{') {
+                    Some(braced) => {
+                        let end = braced.find('}').ok_or("unclosed ${")?;
+                        (&braced[..end], end + 2)
+                    }
+                    None => {
+                        let end = after
+                            .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                            .unwrap_or(after.len());
+                        (&after[..end], end)
+                    }
+                };
+                let value = variables
+                    .get(name)
+                    .ok_or_else(|| format!("undefined variable ${name}"))?;
+                out.push_str(value);
+                rest = &after[len..];
+            }
+            out.push_str(rest);
+            Ok(out)
+        })
+        .collect()
+}
+
+/// Parses one command line with the normal CLI.
+///
+/// Global flags such as `--dry-run` or `-v` are rejected: the client stack and logging are
+/// set up once for the whole script, so they would be silently ignored on a single line.
+fn parse_line(words: Vec<String>) -> Result<ArgMatches, String> {
+    let cli = build_cli().no_binary_name(true);
+    let global_flags: Vec<(String, String)> = cli
+        .get_arguments()
+        .filter(|arg| arg.is_global_set())
+        .map(|arg| {
+            let id = arg.get_id().to_string();
+            let flag = arg
+                .get_long()
+                .map_or_else(|| id.clone(), |long| format!("--{long}"));
+            (id, flag)
+        })
+        .collect();
+    let matches = cli
+        .try_get_matches_from(words)
+        .map_err(|err| err.to_string().trim().to_string())?;
+    match global_flags
+        .into_iter()
+        .find(|(id, _)| matches.value_source(id) == Some(ValueSource::CommandLine))
+    {
+        Some((_, flag)) => Err(format!(
+            "{flag} applies to the whole script; pass it to `huelightcli run` instead"
+        )),
+        None => Ok(matches),
+    }
+}
+
+/// Parses and runs one command line through the normal CLI.
+async fn run_line(
+    words: Vec<String>,
+    api: Arc<dyn HueApi + Send + Sync>,
+    config: &Config,
+    logger: &Arc<Logger>,
+) -> Result<(), String> {
+    let matches = parse_line(words)?;
+    dispatch(&matches, api, config, logger)
+        .await
+        .map_err(|err| err.to_string())
+}
+
+pub async fn run_script(
+    cmd: &ArgMatches,
+    api: Arc<dyn HueApi + Send + Sync>,
+    config: &Config,
+    logger: &Arc<Logger>,
+) -> Result<(), CLIError> {
+    let path = cmd.get_one::<String>("script").unwrap(); // has a default value
+    let continue_on_error = cmd.get_flag("continue_on_error");
+    let text = if path == "-" {
+        let mut text = String::new();
+        tokio::io::stdin()
+            .read_to_string(&mut text)
+            .await
+            .map_err(huelight_core::error::CoreError::FileHandlerError)?;
+        text
+    } else {
+        TokioFileHandler.read_file(path).await?
+    };
+
+    let statements = parse_script(&text)?;
+    let mut variables = HashMap::new();
+    let mut failed = 0;
+    // Reports a failed line, and says whether to stop.
+    let mut fail = |line: usize, message: String| -> Result<(), CLIError> {
+        if !continue_on_error {
+            return Err(CLIError::Script { line, message });
+        }
+        logger.error(&format!("line {line}: {message}"));
+        failed += 1;
+        Ok(())
+    };
+
+    for (line, statement) in statements {
+        match statement {
+            Statement::Let(name, value) => match substitute(&[value], &variables) {
+                Ok(mut value) => {
+                    variables.insert(name, value.remove(0));
+                }
+                Err(message) => fail(line, message)?,
+            },
+            Statement::Wait(duration) => tokio::time::sleep(duration).await,
+            Statement::Command(words) => {
+                let result = match substitute(&words, &variables) {
+                    Ok(words) => run_line(words, api.clone(), config, logger).await,
+                    Err(message) => Err(message),
+                };
+                if let Err(message) = result {
+                    fail(line, message)?;
+                }
+            }
+            Statement::Parallel(commands) => {
+                let mut tasks = JoinSet::new();
+                let mut lines = HashMap::new();
+                for (line, words) in commands {
+                    let words = match substitute(&words, &variables) {
+                        Ok(words) => words,
+                        Err(message) => {
+                            fail(line, message)?;
+                            continue;
+                        }
+                    };
+                    let (api, config, logger) = (api.clone(), config.clone(), logger.clone());
+                    let task =
+                        tasks.spawn(async move { run_line(words, api, &config, &logger).await });
+                    lines.insert(task.id(), line);
+                }
+                // Let every command in the block finish before reporting, so none is cut off.
+                let mut results = Vec::new();
+                while let Some(joined) = tasks.join_next_with_id().await {
+                    let (id, result) = match joined {
+                        Ok((id, result)) => (id, result),
+                        Err(err) => (err.id(), Err(format!("command did not finish: {err}"))),
+                    };
+                    results.push((lines[&id], result));
+                }
+                results.sort_by_key(|(line, _)| *line);
+                for (line, result) in results {
+                    if let Err(message) = result {
+                        fail(line, message)?;
+                    }
+                }
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(CLIError::ScriptFailed { failed });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use super::{Statement, parse_line, parse_script, substitute, tokenize};
+    use crate::error::CLIError;
+
+    fn words(words: &[&str]) -> Vec<String> {
+        words.iter().map(|word| word.to_string()).collect()
+    }
+
+    /// The line and message of a script syntax error.
+    fn syntax_error(text: &str) -> (usize, String) {
+        match parse_script(text) {
+            Err(CLIError::Script { line, message }) => (line, message),
+            other => panic!("expected a syntax error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    pub fn tokenize_groups_quoted_words() {
+        // Arrange
+        let line = r#"light rename 3 "Desk lamp" 'it''s'  "#;
+
+        // Act
+        let result = tokenize(line);
+
+        // Assert
+        assert_eq!(
+            result,
+            Ok(words(&["light", "rename", "3", "Desk lamp", "its"]))
+        );
+    }
+
+    #[test]
+    pub fn tokenize_keeps_empty_quoted_words() {
+        // Arrange
+        let line = r#"light rename 3 """#;
+
+        // Act
+        let result = tokenize(line);
+
+        // Assert
+        assert_eq!(result, Ok(words(&["light", "rename", "3", ""])));
+    }
+
+    #[test]
+    pub fn tokenize_unterminated_quote_expect_error() {
+        // Arrange
+        let line = r#"light rename 3 "Desk lamp"#;
+
+        // Act
+        let result = tokenize(line);
+
+        // Assert
+        assert_eq!(result, Err("unterminated quote".to_string()));
+    }
+
+    #[test]
+    pub fn parse_script_reads_every_statement_with_its_line() {
+        // Arrange
+        let text = "# morning\n\
+                    let room = Office\n\
+                    \n\
+                    wait 2s\n\
+                    parallel {\n\
+                    light on 1\n\
+                    group set $room bri 50%\n\
+                    }\n\
+                    scene recall Relax\n";
+
+        // Act
+        let statements = parse_script(text).unwrap();
+
+        // Assert
+        assert_eq!(
+            statements,
+            vec![
+                (2, Statement::Let("room".into(), "Office".into())),
+                (4, Statement::Wait(Duration::from_secs(2))),
+                (
+                    5,
+                    Statement::Parallel(vec![
+                        (6, words(&["light", "on", "1"])),
+                        (7, words(&["group", "set", "$room", "bri", "50%"])),
+                    ])
+                ),
+                (9, Statement::Command(words(&["scene", "recall", "Relax"]))),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn parse_script_unclosed_parallel_expect_error_at_its_start() {
+        // Arrange
+        let text = "light on 1\nparallel {\nlight on 2\n";
+
+        // Act
+        let (line, message) = syntax_error(text);
+
+        // Assert
+        assert_eq!(line, 2);
+        assert_eq!(message, "parallel { is never closed");
+    }
+
+    #[test]
+    pub fn parse_script_only_commands_inside_parallel_expect_error() {
+        for statement in ["wait 1s", "let x = 1", "parallel {", "run other.hue"] {
+            // Arrange
+            let text = format!("parallel {{\nlight on 1\n{statement}\n}}\n");
+
+            // Act
+            let (line, message) = syntax_error(&text);
+
+            // Assert
+            assert_eq!(line, 3, "{statement}");
+            assert_eq!(message, "only commands can go inside parallel { }");
+        }
+    }
+
+    #[test]
+    pub fn parse_script_malformed_statements_expect_error() {
+        for (text, expected) in [
+            ("wait", "expected `wait <duration>`"),
+            ("let room Office", "expected `let <name> = <value>`"),
+            ("parallel", "expected `parallel {`"),
+            ("run other.hue", "scripts can't run other scripts"),
+            ("light rename 3 \"Desk", "unterminated quote"),
+        ] {
+            // Act
+            let (line, message) = syntax_error(text);
+
+            // Assert
+            assert_eq!((line, message.as_str()), (1, expected), "{text}");
+        }
+    }
+
+    #[test]
+    pub fn substitute_replaces_plain_and_braced_variables() {
+        // Arrange
+        let variables = HashMap::from([
+            ("room".to_string(), "Office".to_string()),
+            ("bri".to_string(), "50".to_string()),
+        ]);
+
+        // Act
+        let result = substitute(
+            &words(&["group", "set", "$room", "bri", "${bri}%", "$room-2"]),
+            &variables,
+        );
+
+        // Assert
+        assert_eq!(
+            result,
+            Ok(words(&["group", "set", "Office", "bri", "50%", "Office-2"]))
+        );
+    }
+
+    #[test]
+    pub fn substitute_undefined_variable_expect_error() {
+        // Arrange
+        let variables = HashMap::new();
+
+        // Act
+        let result = substitute(&words(&["light", "on", "$desk"]), &variables);
+
+        // Assert
+        assert_eq!(result, Err("undefined variable $desk".to_string()));
+    }
+
+    #[test]
+    pub fn substitute_unclosed_brace_expect_error() {
+        // Arrange
+        let variables = HashMap::from([("desk".to_string(), "1".to_string())]);
+
+        // Act
+        let result = substitute(&words(&["light", "on", "${desk"]), &variables);
+
+        // Assert
+        assert_eq!(result, Err("unclosed ${".to_string()));
+    }
+
+    #[test]
+    pub fn parse_line_global_flag_expect_error() {
+        // Arrange
+        let lines = [
+            words(&["light", "on", "3", "--dry-run"]),
+            words(&["-v", "light", "on", "3"]),
+            words(&["light", "on", "3", "--record", "session.json"]),
+        ];
+
+        // Act
+        let results: Vec<_> = lines.into_iter().map(parse_line).collect();
+
+        // Assert
+        let errors: Vec<String> = results.into_iter().filter_map(Result::err).collect();
+        assert_eq!(errors.len(), 3);
+        assert!(errors[0].starts_with("--dry-run applies to the whole script"));
+        assert!(errors[1].starts_with("--verbose applies to the whole script"));
+        assert!(errors[2].starts_with("--record applies to the whole script"));
+        assert!(parse_line(words(&["light", "on", "3"])).is_ok());
+    }
+}
@@ -0,0 +1,239 @@
+use std::sync::Arc;
+
+use clap::ArgMatches;
+use huelight_core::config::Config;
+use huelight_core::error::{CoreError, HueBridgeError};
+use huelight_core::hue_api::HueApi;
+use huelight_core::logger::{ILogger, Logger};
+use huelight_core::models::sensor::{
+    ClipSensorType, NewSensor, Sensor, SensorConfig, SensorId, SensorStateUpdate,
+};
+
+use crate::error::CLIError;
+
+fn sensor_arg() -> clap::Arg {
+    clap::Arg::new("sensor")
+        .required(true)
+        .help("ID or name of the sensor")
+}
+
+fn bool_arg(name: &'static str, help: &'static str) -> clap::Arg {
+    clap::Arg::new(name)
+        .long(name)
+        .value_parser(clap::value_parser!(bool))
+        .help(help)
+}
+
+pub fn sensor_command() -> clap::Command {
+    clap::Command::new("sensor")
+        .about(
+            "Read motion, temperature, light level and switch sensors, and manage virtual sensors",
+        )
+        .subcommand(
+            clap::Command::new("list").about("List all sensors with their current readings"),
+        )
+        .subcommand(
+            clap::Command::new("get")
+                .about("Show a sensor's details, reading and configuration")
+                .arg(sensor_arg()),
+        )
+        .subcommand(
+            clap::Command::new("set-config")
+                .about("Change a sensor's configuration")
+                .arg(sensor_arg())
+                .arg(bool_arg(
+                    "on",
+                    "Enable (true) or disable (false) the sensor",
+                ))
+                .arg(
+                    clap::Arg::new("sensitivity")
+                        .long("sensitivity")
+                        .value_parser(clap::value_parser!(u8))
+                        .help("Motion sensitivity, from 0 up to the sensor's sensitivitymax"),
+                )
+                .arg(bool_arg(
+                    "led",
+                    "Turn the LED indication on motion on or off",
+                ))
+                .arg(
+                    clap::Arg::new("tholddark")
+                        .long("tholddark")
+                        .value_parser(clap::value_parser!(u32))
+                        .help("Light level below which the light level sensor reports dark"),
+                )
+                .arg(
+                    clap::Arg::new("tholdoffset")
+                        .long("tholdoffset")
+                        .value_parser(clap::value_parser!(u32))
+                        .help("Offset above tholddark from which the sensor reports daylight"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("set-state")
+                .about("Set the state of a virtual CLIP sensor, e.g. to trigger rules")
+                .arg(sensor_arg())
+                .arg(bool_arg(
+                    "flag",
+                    "New flag value for a CLIPGenericFlag sensor",
+                ))
+                .arg(
+                    clap::Arg::new("status")
+                        .long("status")
+                        .allow_negative_numbers(true)
+                        .value_parser(clap::value_parser!(i32))
+                        .help("New status value for a CLIPGenericStatus sensor"),
+                )
+                .arg(bool_arg(
+                    "presence",
+                    "New presence value for a CLIPPresence sensor",
+                )),
+        )
+        .subcommand(
+            clap::Command::new("create")
+                .about("Create a virtual CLIP sensor for use in rules")
+                .arg(
+                    clap::Arg::new("name")
+                        .required(true)
+                        .help("Name of the new sensor"),
+                )
+                .arg(
+                    clap::Arg::new("type")
+                        .long("type")
+                        .default_value("flag")
+                        .value_parser(["flag", "status", "presence"])
+                        .help("Kind of virtual sensor"),
+                ),
+        )
+}
+
+/// One line summary used by `sensor list`.
+fn describe(id: SensorId, sensor: &Sensor) -> String {
+    let mut line = format!(
+        "Sensor ID: {}, Name: {}, Type: {}, Reading: {}",
+        id, sensor.name, sensor._type, sensor.state
+    );
+    if let Some(battery) = sensor.config.battery {
+        line.push_str(&format!(", Battery: {}%", battery));
+    }
+    if sensor.config.reachable == Some(false) {
+        line.push_str(", UNREACHABLE");
+    }
+    if sensor.config.on == Some(false) {
+        line.push_str(", disabled");
+    }
+    line
+}
+
+async fn resolve_sensor(
+    api: &(dyn HueApi + Send + Sync),
+    config: &Config,
+    id_or_name: &str,
+) -> Result<SensorId, CLIError> {
+    let sensors = api
+        .async_get_all_sensors(&config.bridge_ip, &config.username)
+        .await?;
+    sensors
+        .find(id_or_name)
+        .map(|(id, _)| id)
+        .ok_or(CLIError::HueLightCoreError(CoreError::Bridge(
+            HueBridgeError::SensorNotFound,
+        )))
+}
+
+pub async fn run_sensor(
+    cmd: &ArgMatches,
+    api: Arc<dyn HueApi + Send + Sync>,
+    config: &Config,
+    logger: &Logger,
+) -> Result<(), CLIError> {
+    let (ip, user) = (config.bridge_ip.as_str(), config.username.as_str());
+    // Every subcommand except list and create takes a required sensor argument.
+    let sensor_of = |m: &ArgMatches| m.get_one::<String>("sensor").unwrap().clone();
+
+    match cmd.subcommand() {
+        Some(("list", _)) => {
+            let sensors = api.async_get_all_sensors(ip, user).await?;
+            let mut sorted: Vec<_> = sensors.0.iter().collect();
+            sorted.sort_by_key(|(id, _)| **id);
+            for (id, sensor) in sorted {
+                logger.log(&describe(*id, sensor));
+            }
+            Ok(())
+        }
+        Some(("get", get_cmd)) => {
+            let id = resolve_sensor(api.as_ref(), config, &sensor_of(get_cmd)).await?;
+            let sensor = api.async_get_sensor(ip, user, id).await?;
+            logger.log(&describe(id, &sensor));
+            if let Some(updated) = sensor.state.lastupdated() {
+                logger.log(&format!("Last updated: {}", updated));
+            }
+            for (label, value) in [
+                ("Model", &sensor.modelid),
+                ("Manufacturer", &sensor.manufacturername),
+                ("Unique ID", &sensor.uniqueid),
+                ("Software version", &sensor.swversion),
+            ] {
+                if let Some(value) = value {
+                    logger.log(&format!("{}: {}", label, value));
+                }
+            }
+            logger.log(&format!(
+                "Config: {}",
+                serde_json::to_string(&sensor.config).map_err(CoreError::Serialization)?
+            ));
+            Ok(())
+        }
+        Some(("set-config", set_cmd)) => {
+            let id = resolve_sensor(api.as_ref(), config, &sensor_of(set_cmd)).await?;
+            let update = SensorConfig {
+                on: set_cmd.get_one::<bool>("on").copied(),
+                sensitivity: set_cmd.get_one::<u8>("sensitivity").copied(),
+                ledindication: set_cmd.get_one::<bool>("led").copied(),
+                tholddark: set_cmd.get_one::<u32>("tholddark").copied(),
+                tholdoffset: set_cmd.get_one::<u32>("tholdoffset").copied(),
+                ..Default::default()
+            };
+            if update == SensorConfig::default() {
+                logger.warn("No arguments provided that would change the sensor!");
+                return Ok(());
+            }
+            api.async_set_sensor_config(ip, user, id, &update).await?;
+            logger.log(&format!("Updated config of sensor {}.", id));
+            Ok(())
+        }
+        Some(("set-state", set_cmd)) => {
+            let id = resolve_sensor(api.as_ref(), config, &sensor_of(set_cmd)).await?;
+            let update = SensorStateUpdate {
+                flag: set_cmd.get_one::<bool>("flag").copied(),
+                status: set_cmd.get_one::<i32>("status").copied(),
+                presence: set_cmd.get_one::<bool>("presence").copied(),
+            };
+            if update == SensorStateUpdate::default() {
+                logger.warn("No arguments provided that would change the sensor!");
+                return Ok(());
+            }
+            api.async_set_sensor_state(ip, user, id, &update).await?;
+            logger.log(&format!("Updated state of sensor {}.", id));
+            Ok(())
+        }
+        Some(("create", create_cmd)) => {
+            let name = create_cmd.get_one::<String>("name").unwrap(); // required by cli
+            let sensor_type = match create_cmd.get_one::<String>("type").map(String::as_str) {
+                Some("status") => ClipSensorType::GenericStatus,
+                Some("presence") => ClipSensorType::Presence,
+                _ => ClipSensorType::GenericFlag,
+            };
+            let id = api
+                .async_create_sensor(ip, user, &NewSensor::clip(name, sensor_type))
+                .await?;
+            logger.log(&format!(
+                "Created {} sensor '{}' with ID {}.",
+                sensor_type.type_name(),
+                name,
+                id
+            ));
+            Ok(())
+        }
+        _ => Err(CLIError::InvalidCommandError),
+    }
+}
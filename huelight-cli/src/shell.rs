@@ -0,0 +1,243 @@
+use std::sync::Arc;
+
+use huelight_core::config::{Config, config_dir};
+use huelight_core::hue_api::HueApi;
+use huelight_core::logger::{ILogger, Logger};
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::history::FileHistory;
+use rustyline::{Context, Editor, Helper, Highlighter, Hinter, Validator};
+
+use crate::error::CLIError;
+use crate::script::{run_script, tokenize};
+use crate::{build_cli, dispatch};
+
+const PROMPT: &str = "huelight> ";
+
+pub fn shell_command() -> clap::Command {
+    clap::Command::new("shell")
+        .about("Interactive shell that keeps the config and bridge connection between commands")
+        .long_about(
+            "Interactive shell that keeps the config and bridge connection between commands.\n\n\
+             Type commands as you would after `huelightcli`, e.g. `light on 3`. Tab completes\n\
+             commands, flags and the names of lights, groups, scenes and sensors.\n\
+             Also supported:\n  \
+             .json      toggle printing each command's result as a JSON object\n  \
+             .reload    fetch names from the bridge again for completion\n  \
+             exit       leave the shell (or Ctrl-D)",
+        )
+}
+
+/// Line editor support: completes command words from the CLI definition and resource names
+/// from the bridge.
+#[derive(Helper, Hinter, Highlighter, Validator)]
+struct ShellHelper {
+    cli: clap::Command,
+    names: Vec<String>,
+}
+
+impl ShellHelper {
+    /// Words that can follow `words`: subcommands and flags of the command they lead to, or
+    /// resource names once there are no more subcommands.
+    fn candidates(&self, words: &[String], partial: &str) -> Vec<String> {
+        let mut command = &self.cli;
+        for word in words {
+            if let Some(sub) = command.find_subcommand(word) {
+                command = sub;
+            }
+        }
+
+        if partial.starts_with('-') {
+            return command
+                .get_arguments()
+                .filter_map(|arg| arg.get_long())
+                .map(|long| format!("--{long}"))
+                .filter(|flag| flag.starts_with(partial))
+                .collect();
+        }
+
+        let mut candidates: Vec<String> = if command.has_subcommands() {
+            command
+                .get_subcommands()
+                .map(|sub| sub.get_name().to_string())
+                .filter(|name| name.starts_with(partial))
+                .collect()
+        } else {
+            let partial = partial.trim_start_matches(['"', '\'']).to_lowercase();
+            self.names
+                .iter()
+                .filter(|name| name.to_lowercase().starts_with(&partial))
+                .map(|name| {
+                    if name.contains(char::is_whitespace) {
+                        format!("\"{name}\"")
+                    } else {
+                        name.clone()
+                    }
+                })
+                .collect()
+        };
+        if words.is_empty() {
+            candidates.extend(
+                [".json", ".reload", "exit"]
+                    .into_iter()
+                    .filter(|meta| meta.starts_with(partial))
+                    .map(str::to_string),
+            );
+        }
+        candidates.sort();
+        candidates.dedup();
+        candidates
+    }
+}
+
+impl Completer for ShellHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let before = &line[..pos];
+        // Inside an open quote the word started at the quote, otherwise after the last space.
+        let start = match before.matches(['"', '\'']).count() % 2 {
+            1 => before.rfind(['"', '\'']).unwrap_or(0), // an odd count means there is one
+            _ => before.rfind(char::is_whitespace).map_or(0, |i| i + 1),
+        };
+        let Ok(words) = tokenize(&before[..start]) else {
+            return Ok((start, Vec::new()));
+        };
+        Ok((start, self.candidates(&words, &before[start..])))
+    }
+}
+
+/// Names of everything on the bridge a command can refer to. Missing parts only make
+/// completion less helpful, so errors are ignored.
+async fn resource_names(api: &(dyn HueApi + Send + Sync), config: &Config) -> Vec<String> {
+    let (ip, user) = (config.bridge_ip.as_str(), config.username.as_str());
+    let mut names = Vec::new();
+    if let Ok(lights) = api.async_get_all_lights(ip, user).await {
+        names.extend(lights.0.into_values().map(|light| light.name));
+    }
+    if let Ok(groups) = api.async_get_all_groups(ip, user).await {
+        names.extend(groups.0.into_values().map(|group| group.name));
+    }
+    if let Ok(scenes) = api.async_get_all_scenes(ip, user).await {
+        names.extend(scenes.0.into_values().map(|scene| scene.name));
+    }
+    if let Ok(sensors) = api.async_get_all_sensors(ip, user).await {
+        names.extend(sensors.0.into_values().map(|sensor| sensor.name));
+    }
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Runs one shell line through the normal CLI. With `json`, the command's output is collected
+/// and printed as one JSON object instead.
+async fn run_line(
+    line: &str,
+    json: bool,
+    api: Arc<dyn HueApi + Send + Sync>,
+    config: &Config,
+    logger: &Arc<Logger>,
+) {
+    let words = match tokenize(line) {
+        Ok(words) => words,
+        Err(message) => {
+            eprintln!("error: {message}");
+            return;
+        }
+    };
+    let matches = match build_cli().no_binary_name(true).try_get_matches_from(words) {
+        Ok(matches) => matches,
+        // Also how `--help` and `help` output arrives.
+        Err(err) => {
+            let _ = err.print();
+            return;
+        }
+    };
+
+    let output = if json {
+        Arc::new(Logger::quiet())
+    } else {
+        logger.clone()
+    };
+    let result = match matches.subcommand() {
+        Some(("run", run_cmd)) => run_script(run_cmd, api, config, &output).await,
+        Some(("shell", _)) => Err(CLIError::InvalidCommandError),
+        _ => dispatch(&matches, api, config, &output).await,
+    };
+
+    if json {
+        let lines: Vec<String> = output
+            .entries()
+            .iter()
+            .map(|entry| entry.trim_end().to_string())
+            .collect();
+        let report = serde_json::json!({
+            "command": line,
+            "ok": result.is_ok(),
+            "output": lines,
+            "error": result.as_ref().err().map(ToString::to_string),
+        });
+        println!("{report}");
+    } else if let Err(err) = result {
+        eprintln!("error: {err}");
+    }
+}
+
+pub async fn run_shell(
+    api: Arc<dyn HueApi + Send + Sync>,
+    config: &Config,
+    logger: &Arc<Logger>,
+) -> Result<(), CLIError> {
+    let history = config_dir()?.join("shell_history.txt");
+    let mut editor: Editor<ShellHelper, FileHistory> = Editor::new()?;
+    editor.set_helper(Some(ShellHelper {
+        cli: build_cli(),
+        names: resource_names(api.as_ref(), config).await,
+    }));
+    // There is no history file before the first session.
+    let _ = editor.load_history(&history);
+
+    logger.log("huelightcli shell. Tab completes, `.json` toggles JSON output, `exit` leaves.");
+    let mut json = false;
+    loop {
+        // Reading a line blocks; let the runtime move other tasks off this thread meanwhile.
+        let line = match tokio::task::block_in_place(|| editor.readline(PROMPT)) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(err) => return Err(err.into()),
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        editor.add_history_entry(line)?;
+
+        match line {
+            "exit" | "quit" => break,
+            ".json" => {
+                json = !json;
+                logger.log(&format!("JSON output {}.", if json { "on" } else { "off" }));
+            }
+            ".reload" => {
+                let names = resource_names(api.as_ref(), config).await;
+                logger.log(&format!("{} names loaded.", names.len()));
+                if let Some(helper) = editor.helper_mut() {
+                    helper.names = names;
+                }
+            }
+            _ => run_line(line, json, api.clone(), config, logger).await,
+        }
+    }
+
+    if let Some(dir) = history.parent() {
+        std::fs::create_dir_all(dir).map_err(huelight_core::error::CoreError::FileHandlerError)?;
+    }
+    editor.save_history(&history)?;
+    Ok(())
+}
@@ -0,0 +1,106 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use clap::ArgMatches;
+use huelight_core::config::{Config, TokioFileHandler};
+use huelight_core::domain::light::LightId;
+use huelight_core::duration::parse_duration;
+use huelight_core::error::CoreError;
+use huelight_core::hue_api::HueApi;
+use huelight_core::logger::{ILogger, Logger};
+use huelight_core::snapshot::Snapshot;
+
+use crate::error::CLIError;
+use crate::summary::{concurrency, concurrency_arg, print_summary, summary_result};
+
+pub fn snapshot_command() -> clap::Command {
+    let name_arg = clap::Arg::new("name")
+        .required(true)
+        .help("Name of the snapshot (letters, digits, '-' and '_')");
+    clap::Command::new("snapshot")
+        .about("Save the state of every light and put it back later")
+        .subcommand(
+            clap::Command::new("save")
+                .about("Save the current state of every light")
+                .arg(name_arg.clone()),
+        )
+        .subcommand(
+            clap::Command::new("restore")
+                .about("Restore every light to a saved snapshot, skipping unreachable lights")
+                .arg(name_arg)
+                .arg(
+                    clap::Arg::new("transition")
+                        .long("transition")
+                        .short('t')
+                        .help("Fade to the saved state over this long, e.g. 2s"),
+                )
+                .arg(concurrency_arg()),
+        )
+}
+
+pub async fn run_snapshot(
+    cmd: &ArgMatches,
+    api: Arc<dyn HueApi + Send + Sync>,
+    config: &Config,
+    logger: &Logger,
+) -> Result<(), CLIError> {
+    let (ip, user) = (config.bridge_ip.as_str(), config.username.as_str());
+    match cmd.subcommand() {
+        Some(("save", save_cmd)) => {
+            let name = save_cmd.get_one::<String>("name").unwrap(); // required by cli
+            let lights = api.async_get_all_lights(ip, user).await?;
+            let snapshot = Snapshot::capture(name, &lights, Utc::now());
+            snapshot.save(&TokioFileHandler).await?;
+            logger.log(&format!(
+                "Saved snapshot '{}' with {} lights.",
+                name,
+                snapshot.lights.len()
+            ));
+            Ok(())
+        }
+        Some(("restore", restore_cmd)) => {
+            let name = restore_cmd.get_one::<String>("name").unwrap(); // required by cli
+            let transition = restore_cmd
+                .get_one::<String>("transition")
+                .map(|t| parse_duration(t))
+                .transpose()
+                .map_err(CoreError::Parse)?;
+
+            let concurrency = concurrency(restore_cmd);
+
+            let snapshot = Snapshot::load(name, &TokioFileHandler).await?;
+            let report = snapshot
+                .restore(api.as_ref(), ip, user, transition, concurrency)
+                .await?;
+
+            let light_name = |id: &LightId| snapshot.lights[id].name.as_str();
+            print_summary(
+                &report.sent,
+                |id| format!("{} ({})", id, light_name(id)),
+                logger,
+            );
+            for id in &report.unreachable {
+                logger.log(&format!(
+                    "Skipped unreachable light {} ({})",
+                    id,
+                    light_name(id)
+                ));
+            }
+            for id in &report.missing {
+                logger.log(&format!(
+                    "Light {} ({}) no longer exists on the bridge",
+                    id,
+                    light_name(id)
+                ));
+            }
+            logger.log(&format!(
+                "Restored {} of {} lights from snapshot '{}'.",
+                report.sent.succeeded(),
+                snapshot.lights.len(),
+                name
+            ));
+            summary_result(&report.sent)
+        }
+        _ => Err(CLIError::InvalidCommandError),
+    }
+}
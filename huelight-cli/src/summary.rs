@@ -0,0 +1,86 @@
+use clap::ArgMatches;
+use huelight_core::fanout::{DEFAULT_CONCURRENCY, FanOutReport, Outcome};
+use huelight_core::logger::{ILogger, Logger};
+
+use crate::error::CLIError;
+
+/// The `--concurrency` argument for commands that fan out over many lights.
+pub fn concurrency_arg() -> clap::Arg {
+    clap::Arg::new("concurrency")
+        .long("concurrency")
+        .short('j')
+        .value_parser(clap::value_parser!(usize))
+        .default_value(DEFAULT_CONCURRENCY.to_string())
+        .help("How many lights to update at once")
+}
+
+/// The value of a command's `concurrency_arg`.
+pub fn concurrency(cmd: &ArgMatches) -> usize {
+    *cmd.get_one::<usize>("concurrency").unwrap() // has a default value
+}
+
+/// Prints one row per target, named by `label`, with how its call went, then the totals.
+pub fn print_summary<K, T>(
+    report: &FanOutReport<K, T>,
+    label: impl Fn(&K) -> String,
+    logger: &Logger,
+) {
+    let targets: Vec<String> = report
+        .results
+        .iter()
+        .map(|(target, _)| label(target))
+        .collect();
+    let width = targets
+        .iter()
+        .map(|target| target.chars().count())
+        .chain(["TARGET".len()])
+        .max()
+        .unwrap_or_default();
+
+    logger.log(&format!("{:<width$}  RESULT", "TARGET"));
+    for (target, (_, outcome)) in targets.iter().zip(&report.results) {
+        logger.log(&format!("{target:<width$}  {outcome}"));
+    }
+
+    let count = |matches: fn(&Outcome<T>) -> bool| {
+        report
+            .results
+            .iter()
+            .filter(|(_, outcome)| matches(outcome))
+            .count()
+    };
+    logger.log(&format!(
+        "{} succeeded, {} bridge error(s), {} network error(s).",
+        report.succeeded(),
+        count(|outcome| matches!(outcome, Outcome::BridgeError(_))),
+        count(|outcome| matches!(outcome, Outcome::NetworkError(_))),
+    ));
+}
+
+/// `LightsFailed` if any target failed, so the exit code tells a partial failure apart.
+pub fn summary_result<K, T>(report: &FanOutReport<K, T>) -> Result<(), CLIError> {
+    match report.failed() {
+        0 => Ok(()),
+        failed => Err(CLIError::LightsFailed {
+            failed,
+            total: report.results.len(),
+        }),
+    }
+}
+
+/// Finishes a command that changed `report`'s targets. A single target fails with its own
+/// error; several get a `print_summary` table and the `summary_result`.
+pub fn finish_summary<K, T>(
+    report: FanOutReport<K, T>,
+    label: impl Fn(&K) -> String,
+    logger: &Logger,
+) -> Result<(), CLIError> {
+    if report.results.len() == 1 {
+        return match report.results.into_iter().next() {
+            Some((_, Outcome::BridgeError(err) | Outcome::NetworkError(err))) => Err(err.into()),
+            _ => Ok(()),
+        };
+    }
+    print_summary(&report, label, logger);
+    summary_result(&report)
+}
@@ -0,0 +1,164 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use clap::ArgMatches;
+use huelight_core::config::Config;
+use huelight_core::domain::brightness::BrightnessValue;
+use huelight_core::domain::light::LightState;
+use huelight_core::duration::{format_duration, parse_duration};
+use huelight_core::error::{CoreError, HueBridgeError};
+use huelight_core::hue_api::HueApi;
+use huelight_core::logger::{ILogger, Logger};
+use huelight_core::models::rule::Action;
+use huelight_core::models::schedule::{Recurrence, Schedule, TimePattern};
+
+use crate::error::CLIError;
+
+pub fn timer_command() -> clap::Command {
+    clap::Command::new("timer")
+        .about("Start timers that run on the bridge, even when this machine is off")
+        .subcommand(
+            clap::Command::new("add")
+                .about("Change a light or group once the given time has passed")
+                .arg(
+                    clap::Arg::new("duration")
+                        .required(true)
+                        .help("Time until the timer fires, e.g. 10m or 1h30m"),
+                )
+                .arg(
+                    clap::Arg::new("power")
+                        .value_parser(["on", "off"])
+                        .help("Turn the light or group on or off"),
+                )
+                .arg(
+                    clap::Arg::new("light")
+                        .long("light")
+                        .help("ID or name of the light to change"),
+                )
+                .arg(
+                    clap::Arg::new("group")
+                        .long("group")
+                        .help("ID or name of the group to change"),
+                )
+                .group(
+                    clap::ArgGroup::new("target")
+                        .args(["light", "group"])
+                        .required(true),
+                )
+                .arg(
+                    clap::Arg::new("brightness")
+                        .long("brightness")
+                        .visible_alias("bri")
+                        .value_parser(BrightnessValue::from_str)
+                        .help("Brightness to set: 50%, 0.5 or bri:127. Values out of range are clamped."),
+                )
+                .arg(
+                    clap::Arg::new("repeat")
+                        .long("repeat")
+                        .value_parser(clap::value_parser!(u8))
+                        .help("Restart the timer this many times after it fires"),
+                )
+                .arg(
+                    clap::Arg::new("name")
+                        .long("name")
+                        .default_value("Timer")
+                        .help("Name of the schedule shown in the Hue app"),
+                ),
+        )
+}
+
+pub async fn run_timer(
+    cmd: &ArgMatches,
+    api: Arc<dyn HueApi + Send + Sync>,
+    config: &Config,
+    logger: &Logger,
+) -> Result<(), CLIError> {
+    let (ip, user) = (config.bridge_ip.as_str(), config.username.as_str());
+
+    match cmd.subcommand() {
+        Some(("add", add_cmd)) => {
+            let duration = parse_duration(add_cmd.get_one::<String>("duration").unwrap()) // required by cli
+                .map_err(CoreError::Parse)?;
+            let mut state = LightState::default();
+            if let Some(power) = add_cmd.get_one::<String>("power") {
+                state = state.with_on(power == "on");
+            }
+            if let Some(brightness) = add_cmd.get_one::<BrightnessValue>("brightness") {
+                // The timer fires on the bridge, so there is no current brightness to go from.
+                let brightness = brightness
+                    .absolute()
+                    .ok_or_else(|| CLIError::RelativeBrightness(brightness.to_string()))?;
+                state = state.with_brightness(brightness);
+            }
+            if state == LightState::default() {
+                logger.warn("No arguments provided that would change the light!");
+                return Ok(());
+            }
+
+            let (address, target, state) = if let Some(light) = add_cmd.get_one::<String>("light") {
+                let lights = api.async_get_all_lights(ip, user).await?;
+                let (id, found) = lights
+                    .find(light)
+                    .ok_or(CoreError::Bridge(HueBridgeError::LightNotFound))?;
+                // The bridge stores the body as given, so it has to suit the light already.
+                let fitted = found.fit_state(&state)?;
+                if fitted != state {
+                    tracing::warn!(
+                        light_id = id,
+                        "Adjusted the requested state to what {} supports: {}",
+                        found.name,
+                        fitted
+                    );
+                }
+                (
+                    format!("/api/{}/lights/{}/state", user, id),
+                    format!("light {}", found.name),
+                    fitted,
+                )
+            } else {
+                let group = add_cmd.get_one::<String>("group").unwrap(); // light or group is required
+                let id = if group == "0" {
+                    0
+                } else {
+                    let groups = api.async_get_all_groups(ip, user).await?;
+                    groups
+                        .find(group)
+                        .map(|(id, _)| id)
+                        .ok_or(CoreError::Bridge(HueBridgeError::GroupNotFound))?
+                };
+                (
+                    format!("/api/{}/groups/{}/action", user, id),
+                    format!("group {}", group),
+                    state,
+                )
+            };
+
+            let command = Action {
+                address,
+                method: "PUT".to_string(),
+                body: serde_json::to_value(&state).map_err(CoreError::Serialization)?,
+            };
+            let recurrence = match add_cmd.get_one::<u8>("repeat") {
+                Some(n) => Recurrence::Times(*n),
+                None => Recurrence::Once,
+            };
+            let localtime = TimePattern::Timer {
+                duration,
+                recurrence,
+                random: None,
+            };
+            let name = add_cmd.get_one::<String>("name").unwrap(); // has a default value
+            let id = api
+                .async_create_schedule(ip, user, &Schedule::new(name, command, localtime))
+                .await?;
+            logger.log(&format!(
+                "Started timer {}: {} in {}.",
+                id,
+                target,
+                format_duration(duration)
+            ));
+            Ok(())
+        }
+        _ => Err(CLIError::InvalidCommandError),
+    }
+}
@@ -0,0 +1,388 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+
+use crate::batch::set_light_states;
+use crate::circadian::{CircadianCurve, CircadianTarget};
+use crate::domain::brightness::Brightness;
+use crate::domain::color::Color;
+use crate::domain::light::{LightId, LightState, Lights};
+use crate::error::{CoreError, CoreResult};
+use crate::fanout::DEFAULT_CONCURRENCY;
+use crate::hue_api::HueApi;
+
+/// Brightness drift (on the 1-254 scale) still treated as "what we set".
+const BRI_TOLERANCE: i32 = 2;
+/// Color temperature drift (mirek) still treated as "what we set"; bridges round `ct`.
+const CT_TOLERANCE: i32 = 3;
+
+/// What one adaptive step did to each light.
+#[derive(Debug, Default)]
+pub struct AdaptiveReport {
+    pub adjusted: Vec<LightId>,
+    pub unchanged: Vec<LightId>,
+    pub skipped_off: Vec<LightId>,
+    pub overridden: Vec<LightId>,
+    pub missing: Vec<LightId>,
+    /// Lights that needed a new state but didn't take it; they are tried again next step.
+    pub failed: Vec<(LightId, CoreError)>,
+}
+
+/// Keeps lights on the circadian curve, backing off from any light someone changed by hand.
+///
+/// A light counts as manually overridden when its current brightness or color temperature no
+/// longer matches what this controller last set. The override is cleared when the light is
+/// turned off, so it is picked up again the next time it is switched on.
+pub struct AdaptiveController {
+    curve: CircadianCurve,
+    location: (f64, f64),
+    timezone: Tz,
+    transition: Duration,
+    last_set: HashMap<LightId, CircadianTarget>,
+    overridden: HashSet<LightId>,
+}
+
+impl AdaptiveController {
+    pub fn new(curve: CircadianCurve, location: (f64, f64), timezone: Tz) -> Self {
+        Self {
+            curve,
+            location,
+            timezone,
+            transition: Duration::from_secs(2),
+            last_set: HashMap::new(),
+            overridden: HashSet::new(),
+        }
+    }
+
+    /// Transition used for each adjustment.
+    pub fn with_transition(mut self, transition: Duration) -> Self {
+        self.transition = transition;
+        self
+    }
+
+    pub fn target_at(&self, now: DateTime<Utc>) -> CircadianTarget {
+        self.curve.target_at(now, self.location, &self.timezone)
+    }
+
+    pub fn is_overridden(&self, light_id: LightId) -> bool {
+        self.overridden.contains(&light_id)
+    }
+
+    /// Works out which lights need a new state, updating override tracking along the way.
+    pub fn plan(
+        &mut self,
+        lights: &Lights,
+        light_ids: &[LightId],
+        now: DateTime<Utc>,
+    ) -> (Vec<(LightId, LightState)>, AdaptiveReport) {
+        let target = self.target_at(now);
+        let mut report = AdaptiveReport::default();
+        let mut updates = Vec::new();
+
+        for &id in light_ids {
+            let Some(light) = lights.0.get(&id) else {
+                report.missing.push(id);
+                continue;
+            };
+            let state = &light.state;
+            let has_ct = light.features.ct_range.is_some();
+            if !state.on.unwrap_or(false) {
+                self.last_set.remove(&id);
+                self.overridden.remove(&id);
+                report.skipped_off.push(id);
+                continue;
+            }
+            if self.overridden.contains(&id) {
+                report.overridden.push(id);
+                continue;
+            }
+            if let Some(previous) = self.last_set.get(&id)
+                && !matches_target(state, previous, has_ct)
+            {
+                self.overridden.insert(id);
+                self.last_set.remove(&id);
+                report.overridden.push(id);
+                continue;
+            }
+            // Lights clamp ct to their own range, so aim for what they can actually show.
+            let light_target = match light.features.ct_range {
+                Some(range) => CircadianTarget {
+                    mirek: target.mirek.clamp(range.min, range.max),
+                    ..target
+                },
+                None => target,
+            };
+            if matches_target(state, &light_target, has_ct) {
+                self.last_set.insert(id, light_target);
+                report.unchanged.push(id);
+                continue;
+            }
+
+            let mut update = LightState::default()
+                .with_brightness(Brightness::from_percent(light_target.brightness))
+                .with_transition(self.transition);
+            if has_ct {
+                update = update.with_color(Color::ColorTemperature {
+                    mirek: light_target.mirek,
+                });
+            }
+            updates.push((id, update));
+            self.last_set.insert(id, light_target);
+            report.adjusted.push(id);
+        }
+
+        (updates, report)
+    }
+
+    /// Reads the current light states, then adjusts every light that is on and not overridden.
+    ///
+    /// Only fails if the light states can't be read; lights that reject their update end up in
+    /// the report's `failed`.
+    pub async fn step(
+        &mut self,
+        api: &(dyn HueApi + Send + Sync),
+        ip_address: &str,
+        username: &str,
+        light_ids: &[LightId],
+        now: DateTime<Utc>,
+    ) -> CoreResult<AdaptiveReport> {
+        let lights = api.async_get_all_lights(ip_address, username).await?;
+        let (updates, mut report) = self.plan(&lights, light_ids, now);
+        let sent = set_light_states(api, ip_address, username, &updates, DEFAULT_CONCURRENCY).await;
+        for (id, err) in sent.failed {
+            // It still shows its old state, which mustn't count as a manual override.
+            self.last_set.remove(&id);
+            report.adjusted.retain(|adjusted| *adjusted != id);
+            report.failed.push((id, err));
+        }
+        Ok(report)
+    }
+}
+
+/// Whether a light still shows `target`. Color only counts for lights with color temperature
+/// support, where any other color means someone picked it by hand.
+fn matches_target(state: &LightState, target: &CircadianTarget, has_ct: bool) -> bool {
+    let bri_ok = state
+        .brightness
+        .is_some_and(|b| (b.bri() as i32 - target.bri() as i32).abs() <= BRI_TOLERANCE);
+    let ct_ok = !has_ct
+        || match state.color {
+            Some(Color::ColorTemperature { mirek }) => {
+                (mirek as i32 - target.mirek as i32).abs() <= CT_TOLERANCE
+            }
+            Some(_) => false,
+            None => true,
+        };
+    bri_ok && ct_ok
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use chrono::{Duration, NaiveDate};
+    use chrono_tz::Tz;
+
+    use super::AdaptiveController;
+    use crate::circadian::CircadianCurve;
+    use crate::domain::brightness::Brightness;
+    use crate::domain::color::{Color, CtRange};
+    use crate::domain::light::{Light, LightFeatures, LightKind, LightState, Lights};
+    use crate::error::{CoreError, HueBridgeError};
+    use crate::hue_api::HueApi;
+    use crate::solar::sun_times;
+    use crate::testing::MockBridge;
+
+    fn light(state: LightState) -> Light {
+        Light {
+            name: "Desk".to_string(),
+            kind: LightKind::ColorTemperature,
+            features: LightFeatures::for_kind(&LightKind::ColorTemperature),
+            state,
+            ..Default::default()
+        }
+    }
+
+    fn controller() -> AdaptiveController {
+        AdaptiveController::new(CircadianCurve::default(), (52.37, 4.89), Tz::UTC)
+    }
+
+    #[test]
+    fn plan_adjusts_on_lights_and_skips_off_lights() {
+        // Arrange
+        let mut controller = controller();
+        let now = sun_times(NaiveDate::from_ymd_opt(2024, 6, 1).unwrap(), 52.37, 4.89).solar_noon;
+        let lights = Lights(HashMap::from([
+            (
+                1,
+                light(
+                    LightState::default()
+                        .with_on(true)
+                        .with_brightness(Brightness::from_bri(50))
+                        .with_color(Color::ColorTemperature { mirek: 400 }),
+                ),
+            ),
+            (2, light(LightState::default().with_on(false))),
+        ]));
+
+        // Act
+        let (updates, report) = controller.plan(&lights, &[1, 2, 3], now);
+
+        // Assert
+        assert_eq!(vec![1], report.adjusted);
+        assert_eq!(vec![2], report.skipped_off);
+        assert_eq!(vec![3], report.missing);
+        let target = controller.target_at(now);
+        assert_eq!(
+            Some(Color::ColorTemperature {
+                mirek: target.mirek
+            }),
+            updates[0].1.color
+        );
+        assert_eq!(
+            Some(target.bri()),
+            updates[0].1.brightness.map(Brightness::bri)
+        );
+    }
+
+    #[test]
+    fn plan_clamps_target_to_light_ct_range_without_flagging_override() {
+        // Arrange: noon wants 5500K (181 mirek), but this light only goes down to 200 mirek
+        let mut controller = controller();
+        let now = sun_times(NaiveDate::from_ymd_opt(2024, 6, 1).unwrap(), 52.37, 4.89).solar_noon;
+        let limited = |state: LightState| Light {
+            features: LightFeatures {
+                ct_range: Some(CtRange { min: 200, max: 454 }),
+                ..LightFeatures::for_kind(&LightKind::ColorTemperature)
+            },
+            ..light(state)
+        };
+        let before = Lights(HashMap::from([(
+            1,
+            limited(
+                LightState::default()
+                    .with_on(true)
+                    .with_color(Color::ColorTemperature { mirek: 400 }),
+            ),
+        )]));
+        let (updates, _) = controller.plan(&before, &[1], now);
+        let sent = updates[0].1.clone();
+
+        // Act: the light now reports exactly what was sent
+        let after = Lights(HashMap::from([(1, limited(sent.clone().with_on(true)))]));
+        let (_, report) = controller.plan(&after, &[1], now + Duration::minutes(1));
+
+        // Assert
+        assert_eq!(Some(Color::ColorTemperature { mirek: 200 }), sent.color);
+        assert_eq!(vec![1], report.unchanged);
+        assert!(!controller.is_overridden(1));
+    }
+
+    #[test]
+    fn plan_detects_manual_override_and_clears_it_when_turned_off() {
+        // Arrange
+        let mut controller = controller();
+        let now = sun_times(NaiveDate::from_ymd_opt(2024, 6, 1).unwrap(), 52.37, 4.89).solar_noon;
+        let initial = Lights(HashMap::from([(
+            1,
+            light(
+                LightState::default()
+                    .with_on(true)
+                    .with_brightness(Brightness::from_bri(10)),
+            ),
+        )]));
+        controller.plan(&initial, &[1], now);
+
+        // Act: someone dimmed the light by hand
+        let dimmed = Lights(HashMap::from([(
+            1,
+            light(
+                LightState::default()
+                    .with_on(true)
+                    .with_brightness(Brightness::from_bri(10)),
+            ),
+        )]));
+        let (updates, report) = controller.plan(&dimmed, &[1], now + Duration::minutes(1));
+
+        // Assert
+        assert!(updates.is_empty());
+        assert_eq!(vec![1], report.overridden);
+        assert!(controller.is_overridden(1));
+
+        // Act: light switched off and on again
+        let off = Lights(HashMap::from([(
+            1,
+            light(LightState::default().with_on(false)),
+        )]));
+        controller.plan(&off, &[1], now + Duration::minutes(2));
+        let (updates, _) = controller.plan(&dimmed, &[1], now + Duration::minutes(3));
+
+        // Assert
+        assert!(!controller.is_overridden(1));
+        assert_eq!(1, updates.len());
+    }
+
+    #[tokio::test]
+    pub async fn step_reports_lights_that_failed_and_retries_them() {
+        // Arrange
+        let bridge = Arc::new(
+            MockBridge::new()
+                .with_light(1, "Desk", LightKind::ColorTemperature)
+                .with_light(2, "Shelf", LightKind::Dimmable),
+        );
+        let api = bridge.api();
+        let dim = LightState::default()
+            .with_on(true)
+            .with_brightness(Brightness::from_bri(10));
+        for id in [1, 2] {
+            api.async_set_light_state("ip", "user", id, &dim)
+                .await
+                .unwrap();
+        }
+        let mut controller = controller();
+        let now = sun_times(NaiveDate::from_ymd_opt(2024, 6, 1).unwrap(), 52.37, 4.89).solar_noon;
+        bridge.pass_next(); // reading the lights
+        bridge.fail_next(CoreError::Bridge(HueBridgeError::UnexpectedJSON));
+
+        // Act
+        let first = controller
+            .step(&api, "ip", "user", &[1, 2], now)
+            .await
+            .unwrap();
+        let second = controller
+            .step(&api, "ip", "user", &[1, 2], now + Duration::minutes(1))
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(vec![2], first.adjusted);
+        assert_eq!(
+            vec![1],
+            first.failed.iter().map(|(id, _)| *id).collect::<Vec<_>>()
+        );
+        assert_eq!(vec![1], second.adjusted);
+        assert_eq!(vec![2], second.unchanged);
+        assert!(second.overridden.is_empty());
+        assert!(!controller.is_overridden(1));
+    }
+
+    #[tokio::test]
+    pub async fn step_fails_when_lights_cannot_be_read() {
+        // Arrange
+        let bridge = Arc::new(MockBridge::new().with_light(1, "Desk", LightKind::Dimmable));
+        let api = bridge.api();
+        let mut controller = controller();
+        bridge.fail_next(CoreError::Bridge(HueBridgeError::UnexpectedJSON));
+
+        // Act
+        let result = controller
+            .step(&api, "ip", "user", &[1], chrono::Utc::now())
+            .await;
+
+        // Assert
+        assert!(result.is_err());
+    }
+}
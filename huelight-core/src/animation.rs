@@ -0,0 +1,580 @@
+use std::collections::HashMap;
+use std::f64::consts::TAU;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::client::BRIDGE_REQUEST_INTERVAL;
+use crate::domain::brightness::{Brightness, BrightnessValue};
+use crate::domain::color::Color;
+use crate::domain::light::{Light, LightId, LightState};
+use crate::domain::named_colors::ColorRegistry;
+use crate::duration::parse_duration;
+use crate::error::{AnimationError, CoreError, CoreResult};
+use crate::hue_api::HueApi;
+
+/// Names accepted by `Animation::preset`.
+pub const PRESETS: &[&str] = &["breathe", "rainbow", "candle", "fade-out"];
+
+/// Shortest time between two frames, even when only one light is animated. The bridge smooths
+/// between frames with `transitiontime`, so faster updates gain little.
+const MIN_FRAME_INTERVAL: Duration = Duration::from_millis(400);
+/// How often the candle flicker picks a new random level.
+const FLICKER_STEP: Duration = Duration::from_millis(300);
+
+/// Something to play across a set of lights. Every variant works out the state of one light at
+/// a point in time, so frames can be computed for any moment without replaying earlier ones.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Animation {
+    /// Brightness rises and falls smoothly, keeping the current color.
+    Breathe { period: Duration },
+    /// Hue sweeps around the color wheel, each light a step ahead of the previous one.
+    Rainbow { period: Duration },
+    /// Warm light flickering at random; the same seed gives the same flicker.
+    Candle { seed: u64 },
+    /// Linear fade from the current brightness down to off over the whole duration.
+    FadeOut,
+    /// Keyframes loaded from a file.
+    Timeline(Timeline),
+}
+
+impl Animation {
+    pub fn preset(name: &str, seed: u64) -> Option<Animation> {
+        match name {
+            "breathe" => Some(Animation::Breathe {
+                period: Duration::from_secs(4),
+            }),
+            "rainbow" => Some(Animation::Rainbow {
+                period: Duration::from_secs(20),
+            }),
+            "candle" => Some(Animation::Candle { seed }),
+            "fade-out" => Some(Animation::FadeOut),
+            _ => None,
+        }
+    }
+
+    /// The state of light `index` (of `count`) after `elapsed` of an animation lasting
+    /// `duration`. `start` is the light's state before the animation began.
+    pub fn state_at(
+        &self,
+        elapsed: Duration,
+        duration: Duration,
+        index: usize,
+        count: usize,
+        start: &LightState,
+    ) -> LightState {
+        let on = LightState::default().with_on(true);
+        match self {
+            Animation::Breathe { period } => {
+                let phase = cycle_fraction(elapsed, *period);
+                let level = (1.0 - (phase * TAU).cos()) / 2.0;
+                let peak = start.brightness.unwrap_or(Brightness::MAX).percent();
+                on.with_brightness(Brightness::from_percent(5.0 + (peak - 5.0) * level))
+            }
+            Animation::Rainbow { period } => {
+                let offset = index as f64 / count.max(1) as f64;
+                let hue = (cycle_fraction(elapsed, *period) + offset).fract() * 360.0;
+                on.with_color(Color::HueSaturation {
+                    hue,
+                    saturation: 100.0,
+                })
+            }
+            Animation::Candle { seed } => {
+                let level = flicker(*seed, index, elapsed);
+                on.with_brightness(Brightness::from_percent(25.0 + 35.0 * level))
+                    .with_color(Color::from_kelvin(2000))
+            }
+            Animation::FadeOut => {
+                let progress = if duration.is_zero() {
+                    1.0
+                } else {
+                    (elapsed.as_secs_f64() / duration.as_secs_f64()).min(1.0)
+                };
+                if progress >= 1.0 {
+                    return LightState::default().with_on(false);
+                }
+                let from = start.brightness.unwrap_or(Brightness::MAX).percent();
+                on.with_brightness(Brightness::from_percent(from * (1.0 - progress)))
+            }
+            Animation::Timeline(timeline) => timeline.state_at(elapsed, index),
+        }
+    }
+}
+
+/// How far through the current cycle `elapsed` is, from 0 to 1.
+fn cycle_fraction(elapsed: Duration, period: Duration) -> f64 {
+    if period.is_zero() {
+        return 0.0;
+    }
+    (elapsed.as_secs_f64() / period.as_secs_f64()).fract()
+}
+
+/// Smooth value noise between 0 and 1, different for every light.
+fn flicker(seed: u64, index: usize, elapsed: Duration) -> f64 {
+    let position = elapsed.as_secs_f64() / FLICKER_STEP.as_secs_f64();
+    let step = position.floor() as u64;
+    let t = position.fract();
+    let smooth = t * t * (3.0 - 2.0 * t);
+    let (a, b) = (
+        noise(seed, index, step),
+        noise(seed, index, step.wrapping_add(1)),
+    );
+    a + (b - a) * smooth
+}
+
+/// Hashes the inputs with splitmix64 into a number between 0 and 1.
+fn noise(seed: u64, index: usize, step: u64) -> f64 {
+    let mut z = seed
+        .wrapping_add((index as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15))
+        .wrapping_add(step.wrapping_mul(0xD1B5_4A32_D192_ED03));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^= z >> 31;
+    (z >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// A point in a timeline. Fields left out keep their value from the previous keyframe.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keyframe {
+    pub at: Duration,
+    pub on: Option<bool>,
+    pub brightness: Option<Brightness>,
+    pub color: Option<Color>,
+}
+
+/// Keyframes with linear fades between them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Timeline {
+    /// Sorted by `at`.
+    keyframes: Vec<Keyframe>,
+    /// Start over after the last keyframe instead of holding it.
+    repeat: bool,
+    /// How far each light lags behind the previous one, for chases.
+    stagger: Duration,
+}
+
+/// Raw contents of an animation file, e.g.
+///
+/// ```toml
+/// repeat = true
+/// stagger = "500ms"
+///
+/// [[keyframe]]
+/// at = "0s"
+/// color = "red"
+/// brightness = "100%"
+///
+/// [[keyframe]]
+/// at = "3s"
+/// color = "blue"
+/// brightness = "20%"
+///
+/// [[keyframe]]
+/// at = "6s"
+/// color = "red"
+/// brightness = "100%"
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct TimelineFile {
+    #[serde(default)]
+    pub repeat: bool,
+    #[serde(default)]
+    pub stagger: Option<String>,
+    #[serde(default, rename = "keyframe")]
+    pub keyframes: Vec<KeyframeSpec>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct KeyframeSpec {
+    pub at: String,
+    #[serde(default)]
+    pub on: Option<bool>,
+    /// Absolute brightness such as `50%` or `bri:127`.
+    #[serde(default)]
+    pub brightness: Option<String>,
+    /// Anything `light color` accepts, except palettes.
+    #[serde(default)]
+    pub color: Option<String>,
+}
+
+impl TimelineFile {
+    pub fn from_toml(text: &str) -> CoreResult<Self> {
+        toml::from_str(text).map_err(|e| CoreError::Animation(AnimationError::File(e)))
+    }
+
+    /// Resolves durations, brightness values and color names into a playable timeline.
+    pub fn parse(&self, colors: &ColorRegistry) -> CoreResult<Timeline> {
+        let invalid = |message: String| CoreError::Animation(AnimationError::Invalid(message));
+        if self.keyframes.is_empty() {
+            return Err(invalid("the file has no keyframes".to_string()));
+        }
+
+        let mut keyframes = Vec::with_capacity(self.keyframes.len());
+        for spec in &self.keyframes {
+            let brightness = match spec.brightness.as_deref() {
+                None => None,
+                Some(text) => {
+                    let value: BrightnessValue = text.parse()?;
+                    Some(value.absolute().ok_or_else(|| {
+                        invalid(format!(
+                            "keyframe at {}: brightness must be absolute",
+                            spec.at
+                        ))
+                    })?)
+                }
+            };
+            keyframes.push(Keyframe {
+                at: parse_duration(&spec.at)?,
+                on: spec.on,
+                brightness,
+                color: spec.color.as_deref().map(|c| colors.color(c)).transpose()?,
+            });
+        }
+        keyframes.sort_by_key(|keyframe| keyframe.at);
+
+        Ok(Timeline {
+            keyframes,
+            repeat: self.repeat,
+            stagger: match &self.stagger {
+                Some(stagger) => parse_duration(stagger)?,
+                None => Duration::ZERO,
+            },
+        })
+    }
+}
+
+impl Timeline {
+    /// A timeline that plays once, with every light in step.
+    pub fn new(mut keyframes: Vec<Keyframe>) -> Self {
+        keyframes.sort_by_key(|keyframe| keyframe.at);
+        Self {
+            keyframes,
+            repeat: false,
+            stagger: Duration::ZERO,
+        }
+    }
+
+    pub fn length(&self) -> Duration {
+        self.keyframes.last().map_or(Duration::ZERO, |k| k.at)
+    }
+
+    fn state_at(&self, elapsed: Duration, index: usize) -> LightState {
+        let lag = self.stagger * index as u32;
+        let mut time = elapsed.saturating_sub(lag);
+        let length = self.length();
+        if self.repeat && !length.is_zero() {
+            time = Duration::from_secs_f64(time.as_secs_f64() % length.as_secs_f64());
+        }
+
+        // Carry fields forward so a keyframe only needs to list what changes.
+        let mut state = LightState::default();
+        let mut previous: Option<(Duration, &Keyframe)> = None;
+        for keyframe in &self.keyframes {
+            if keyframe.at > time {
+                let Some((from_at, from)) = previous else {
+                    break;
+                };
+                let span = (keyframe.at - from_at).as_secs_f64();
+                let t = (time - from_at).as_secs_f64() / span;
+                if let (Some(a), Some(b)) = (from.brightness, keyframe.brightness) {
+                    state.brightness = Some(Brightness::from_percent(
+                        a.percent() + (b.percent() - a.percent()) * t,
+                    ));
+                }
+                if let (Some(a), Some(b)) = (from.color, keyframe.color) {
+                    let ([ax, ay], [bx, by]) = (a.to_xy(), b.to_xy());
+                    state.color = Some(Color::xy(ax + (bx - ax) * t, ay + (by - ay) * t));
+                }
+                break;
+            }
+            state.on = keyframe.on.or(state.on);
+            state.brightness = keyframe.brightness.or(state.brightness);
+            state.color = keyframe.color.or(state.color);
+            previous = Some((keyframe.at, keyframe));
+        }
+        if state.on.is_none() {
+            state.on = Some(true);
+        }
+        state
+    }
+}
+
+/// Fits `state` to the light, falling back to leaving the color alone on lights that can't show
+/// it. `None` when the light can't follow even that, e.g. brightness on an on/off plug.
+pub(crate) fn fit_loosely(light: &Light, state: &LightState) -> Option<LightState> {
+    light
+        .fit_state(state)
+        .or_else(|_| {
+            light.fit_state(&LightState {
+                color: None,
+                ..state.clone()
+            })
+        })
+        .ok()
+}
+
+/// What playing an animation did.
+#[derive(Debug, Default, PartialEq)]
+pub struct AnimationReport {
+    pub frames: usize,
+    /// Lights dropped from the animation after a command to them failed.
+    pub failed: Vec<(LightId, String)>,
+}
+
+/// Plays an animation by sending a frame to every light at a fixed rate.
+///
+/// Each frame carries a transition as long as the frame interval, so the bridge fades between
+/// frames and the lights move smoothly even at a few updates per second. The interval grows
+/// with the number of lights to stay within the bridge's request rate.
+pub struct Animator {
+    animation: Animation,
+    request_interval: Duration,
+}
+
+impl Animator {
+    pub fn new(animation: Animation) -> Self {
+        Self {
+            animation,
+            request_interval: BRIDGE_REQUEST_INTERVAL,
+        }
+    }
+
+    /// Time between frames when animating `lights` lights.
+    pub fn frame_interval(&self, lights: usize) -> Duration {
+        MIN_FRAME_INTERVAL.max(self.request_interval * lights as u32)
+    }
+
+    /// Works out the state to send to each light for one frame, fitted to what each light can
+    /// show. Lights that can't show the color still follow the brightness.
+    pub fn frame(
+        &self,
+        lights: &[(LightId, &Light)],
+        start: &HashMap<LightId, LightState>,
+        elapsed: Duration,
+        duration: Duration,
+    ) -> Vec<(LightId, LightState)> {
+        let transition = self.frame_interval(lights.len());
+        let empty = LightState::default();
+        lights
+            .iter()
+            .enumerate()
+            .filter_map(|(index, (id, light))| {
+                let start = start.get(id).unwrap_or(&empty);
+                let state = self
+                    .animation
+                    .state_at(elapsed, duration, index, lights.len(), start)
+                    .with_transition(transition);
+                fit_loosely(light, &state).map(|state| (*id, state))
+            })
+            .collect()
+    }
+
+    /// Plays the animation for `duration`, ending on its final frame.
+    pub async fn play(
+        &self,
+        api: &(dyn HueApi + Send + Sync),
+        ip_address: &str,
+        username: &str,
+        lights: &[(LightId, Light)],
+        duration: Duration,
+    ) -> CoreResult<AnimationReport> {
+        let start: HashMap<LightId, LightState> = lights
+            .iter()
+            .map(|(id, light)| (*id, light.state.clone()))
+            .collect();
+        let mut active: Vec<(LightId, &Light)> = lights.iter().map(|(id, l)| (*id, l)).collect();
+        let mut last_sent: HashMap<LightId, LightState> = HashMap::new();
+        let mut report = AnimationReport::default();
+
+        let mut ticker = tokio::time::interval(self.frame_interval(active.len()));
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        let began = tokio::time::Instant::now();
+        while !active.is_empty() {
+            ticker.tick().await;
+            let elapsed = began.elapsed().min(duration);
+            for (id, state) in self.frame(&active, &start, elapsed, duration) {
+                // Holding still between keyframes needs no traffic.
+                if last_sent.get(&id) == Some(&state) {
+                    continue;
+                }
+                let result = api
+                    .async_set_light_state(ip_address, username, id, &state)
+                    .await;
+                match result {
+                    Ok(_) => {
+                        last_sent.insert(id, state);
+                    }
+                    Err(err) => {
+                        active.retain(|(active_id, _)| *active_id != id);
+                        report.failed.push((id, err.to_string()));
+                    }
+                }
+            }
+            report.frames += 1;
+            if elapsed >= duration {
+                break;
+            }
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use super::{Animation, Animator, TimelineFile, flicker};
+    use crate::domain::brightness::Brightness;
+    use crate::domain::color::Color;
+    use crate::domain::light::{Light, LightFeatures, LightKind, LightState};
+    use crate::domain::named_colors::ColorRegistry;
+    use crate::error::{AnimationError, CoreError};
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    #[test]
+    pub fn breathe_peaks_at_start_brightness_half_way() {
+        // Arrange
+        let breathe = Animation::preset("breathe", 0).unwrap();
+        let start = LightState::default().with_brightness(Brightness::from_percent(80.0));
+
+        // Act
+        let trough = breathe.state_at(Duration::ZERO, 10 * SECOND, 0, 1, &start);
+        let peak = breathe.state_at(2 * SECOND, 10 * SECOND, 0, 1, &start);
+
+        // Assert
+        assert_eq!(Some(Brightness::from_percent(5.0)), trough.brightness);
+        assert_eq!(Some(Brightness::from_percent(80.0)), peak.brightness);
+    }
+
+    #[test]
+    pub fn rainbow_spreads_hues_across_lights() {
+        // Arrange
+        let rainbow = Animation::Rainbow { period: 4 * SECOND };
+        let start = LightState::default();
+
+        // Act
+        let hues: Vec<f64> = (0..4)
+            .map(|index| {
+                match rainbow
+                    .state_at(SECOND, 10 * SECOND, index, 4, &start)
+                    .color
+                {
+                    Some(Color::HueSaturation { hue, .. }) => hue,
+                    other => panic!("expected hue/saturation, got {other:?}"),
+                }
+            })
+            .collect();
+
+        // Assert
+        assert_eq!(vec![90.0, 180.0, 270.0, 0.0], hues);
+    }
+
+    #[test]
+    pub fn candle_flicker_is_repeatable_per_seed() {
+        // Arrange
+        let at = Duration::from_millis(1_234);
+
+        // Act
+        let first = flicker(7, 2, at);
+        let again = flicker(7, 2, at);
+        let other_seed = flicker(8, 2, at);
+
+        // Assert
+        assert_eq!(first, again);
+        assert_ne!(first, other_seed);
+        assert!((0.0..1.0).contains(&first));
+    }
+
+    #[test]
+    pub fn fade_out_is_linear_and_ends_off() {
+        // Arrange
+        let start = LightState::default().with_brightness(Brightness::from_percent(60.0));
+
+        // Act
+        let half = Animation::FadeOut.state_at(5 * SECOND, 10 * SECOND, 0, 1, &start);
+        let end = Animation::FadeOut.state_at(10 * SECOND, 10 * SECOND, 0, 1, &start);
+
+        // Assert
+        assert_eq!(Some(Brightness::from_percent(30.0)), half.brightness);
+        assert_eq!(LightState::default().with_on(false), end);
+    }
+
+    #[test]
+    pub fn timeline_interpolates_and_staggers_lights() {
+        // Arrange
+        let file = TimelineFile::from_toml(
+            r#"
+            stagger = "1s"
+            [[keyframe]]
+            at = "0s"
+            brightness = "0%"
+            color = "xy:0.2,0.2"
+            [[keyframe]]
+            at = "4s"
+            brightness = "100%"
+            color = "xy:0.6,0.4"
+            "#,
+        )
+        .unwrap();
+        let timeline = Animation::Timeline(file.parse(&ColorRegistry::builtin()).unwrap());
+        let start = LightState::default();
+
+        // Act
+        let first = timeline.state_at(2 * SECOND, 4 * SECOND, 0, 2, &start);
+        let second = timeline.state_at(2 * SECOND, 4 * SECOND, 1, 2, &start);
+        let held = timeline.state_at(9 * SECOND, 4 * SECOND, 0, 2, &start);
+
+        // Assert
+        assert_eq!(Some(Brightness::from_percent(50.0)), first.brightness);
+        assert_eq!(Some(Color::xy(0.4, 0.30000000000000004)), first.color);
+        assert_eq!(Some(Brightness::from_percent(25.0)), second.brightness);
+        assert_eq!(Some(Brightness::MAX), held.brightness);
+    }
+
+    #[test]
+    pub fn timeline_relative_brightness_expect_invalid() {
+        // Arrange
+        let file =
+            TimelineFile::from_toml("[[keyframe]]\nat = \"0s\"\nbrightness = \"+10%\"").unwrap();
+
+        // Act
+        let result = file.parse(&ColorRegistry::builtin());
+
+        // Assert
+        assert!(matches!(
+            result,
+            Err(CoreError::Animation(AnimationError::Invalid(_)))
+        ));
+    }
+
+    #[test]
+    pub fn animator_frame_drops_color_for_white_lights_and_paces_transitions() {
+        // Arrange
+        let color = Light {
+            features: LightFeatures::for_kind(&LightKind::ExtendedColor),
+            ..Default::default()
+        };
+        let white = Light {
+            features: LightFeatures::for_kind(&LightKind::Dimmable),
+            ..Default::default()
+        };
+        let animator = Animator::new(Animation::Candle { seed: 1 });
+
+        // Act
+        let frame = animator.frame(
+            &[(1, &color), (2, &white)],
+            &HashMap::new(),
+            SECOND,
+            10 * SECOND,
+        );
+
+        // Assert
+        assert_eq!(2, frame.len());
+        assert!(frame[0].1.color.is_some());
+        assert_eq!(None, frame[1].1.color);
+        assert!(frame[1].1.brightness.is_some());
+        assert_eq!(Some(animator.frame_interval(2)), frame[0].1.transition);
+    }
+}
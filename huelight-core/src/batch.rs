@@ -0,0 +1,311 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::domain::light::{LightId, LightState};
+use crate::error::{CoreError, CoreResult};
+use crate::fanout::{FanOutReport, Outcome, fan_out};
+use crate::hue_api::HueApi;
+use crate::models::group::{GroupId, GroupResponse, NewGroup};
+
+/// Name of the LightGroup huelightcli creates to reach a set of lights no other group matches.
+/// The one group is reused for every such set, with its lights swapped in, so batching never
+/// fills up the bridge's group table.
+pub const BATCH_GROUP_NAME: &str = "huelightcli batch";
+
+#[derive(Debug, Default)]
+pub struct BatchReport {
+    /// Lights the bridge accepted their new state for, in the order they were asked for.
+    pub updated: Vec<LightId>,
+    /// The group each shared state was sent to.
+    pub groups: Vec<GroupId>,
+    pub failed: Vec<(LightId, CoreError)>,
+}
+
+impl BatchReport {
+    /// How each light of `updates` went, in the order they were asked for, e.g. for a summary
+    /// table.
+    pub fn outcomes(self, updates: &[(LightId, LightState)]) -> FanOutReport<LightId, ()> {
+        let mut failed: HashMap<LightId, CoreError> = self.failed.into_iter().collect();
+        let mut seen = BTreeSet::new();
+        let results = updates
+            .iter()
+            .filter(|(id, _)| seen.insert(*id))
+            .map(|(id, _)| {
+                let outcome = match failed.remove(id) {
+                    Some(err) => Outcome::from(Err(err)),
+                    None => Outcome::Success(()),
+                };
+                (*id, outcome)
+            })
+            .collect();
+        FanOutReport { results }
+    }
+}
+
+/// Lights that get the same state, in the order they were first asked for.
+fn batches(updates: &[(LightId, LightState)]) -> Vec<(&LightState, Vec<LightId>)> {
+    let mut batches: Vec<(&LightState, Vec<LightId>)> = Vec::new();
+    for (id, state) in updates {
+        match batches.iter_mut().find(|(shared, _)| *shared == state) {
+            Some((_, ids)) if !ids.contains(id) => ids.push(*id),
+            Some(_) => {}
+            None => batches.push((state, vec![*id])),
+        }
+    }
+    batches
+}
+
+/// Finds or prepares the batch group, remembering its members between batches.
+struct GroupPicker<'a> {
+    api: &'a (dyn HueApi + Send + Sync),
+    ip_address: &'a str,
+    username: &'a str,
+    groups: Option<GroupResponse>,
+    batch_group: Option<(GroupId, BTreeSet<LightId>)>,
+}
+
+impl GroupPicker<'_> {
+    /// A group whose members are exactly `lights`: an existing one if there is one, otherwise
+    /// the batch group, created or refilled as needed.
+    async fn group_for(&mut self, lights: &BTreeSet<LightId>) -> CoreResult<GroupId> {
+        let (ip, user) = (self.ip_address, self.username);
+        if self.groups.is_none() {
+            let groups = self.api.async_get_all_groups(ip, user).await?;
+            self.batch_group = groups
+                .0
+                .iter()
+                .filter(|(_, group)| group.name == BATCH_GROUP_NAME)
+                .min_by_key(|(id, _)| **id)
+                .map(|(id, group)| (*id, group.light_ids().into_iter().collect()));
+            self.groups = Some(groups);
+        }
+        let groups = self.groups.as_ref().map(|groups| &groups.0);
+
+        let existing = groups
+            .into_iter()
+            .flatten()
+            .filter(|(_, group)| group.name != BATCH_GROUP_NAME)
+            .filter(|(_, group)| group.light_ids().into_iter().collect::<BTreeSet<_>>() == *lights)
+            .map(|(id, _)| *id)
+            .min();
+        if let Some(id) = existing {
+            return Ok(id);
+        }
+
+        let ids: Vec<LightId> = lights.iter().copied().collect();
+        let id = match &self.batch_group {
+            Some((id, members)) if members == lights => *id,
+            Some((id, _)) => {
+                self.api.async_set_group_lights(ip, user, *id, &ids).await?;
+                *id
+            }
+            None => {
+                self.api
+                    .async_create_group(ip, user, &NewGroup::light_group(BATCH_GROUP_NAME, &ids))
+                    .await?
+            }
+        };
+        self.batch_group = Some((id, lights.clone()));
+        Ok(id)
+    }
+}
+
+/// Sends every light its state, using a single group action for each set of lights that share
+/// one so they change together.
+///
+/// A set is sent to an existing group with exactly those lights, or else to the batch group
+/// (see `BATCH_GROUP_NAME`). Lights on their own, and sets for which the group route fails
+/// (e.g. the bridge's group table is full), get one light state write each instead,
+/// `concurrency` at a time.
+pub async fn set_light_states(
+    api: &(dyn HueApi + Send + Sync),
+    ip_address: &str,
+    username: &str,
+    updates: &[(LightId, LightState)],
+    concurrency: usize,
+) -> BatchReport {
+    let mut picker = GroupPicker {
+        api,
+        ip_address,
+        username,
+        groups: None,
+        batch_group: None,
+    };
+    let mut report = BatchReport::default();
+
+    for (state, ids) in batches(updates) {
+        if ids.len() > 1 {
+            let lights: BTreeSet<LightId> = ids.iter().copied().collect();
+            let sent = match picker.group_for(&lights).await {
+                Ok(group_id) => api
+                    .async_set_group_state(ip_address, username, group_id, state)
+                    .await
+                    .map(|_| group_id),
+                Err(err) => Err(err),
+            };
+            match sent {
+                Ok(group_id) => {
+                    tracing::debug!(group_id, lights = ?ids, "sent a shared state as one group action");
+                    report.groups.push(group_id);
+                    report.updated.extend(ids);
+                    continue;
+                }
+                Err(err) => {
+                    tracing::info!(lights = ?ids, error = %err, "group action failed, writing each light instead");
+                }
+            }
+        }
+
+        let sent = fan_out(ids, concurrency, |id| async move {
+            api.async_set_light_state(ip_address, username, id, state)
+                .await
+                .map(|_| ())
+        })
+        .await;
+        for (id, outcome) in sent.results {
+            match outcome {
+                Outcome::Success(()) => report.updated.push(id),
+                Outcome::BridgeError(err) | Outcome::NetworkError(err) => {
+                    report.failed.push((id, err))
+                }
+            }
+        }
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use serde_json::json;
+
+    use super::{BATCH_GROUP_NAME, set_light_states};
+    use crate::domain::light::{LightKind, LightState};
+    use crate::error::{CoreError, HueBridgeError};
+    use crate::fanout::{DEFAULT_CONCURRENCY, Outcome};
+    use crate::testing::MockBridge;
+
+    fn bridge() -> MockBridge {
+        MockBridge::new()
+            .with_light(1, "Desk", LightKind::Dimmable)
+            .with_light(2, "Shelf", LightKind::Dimmable)
+            .with_light(3, "Hall", LightKind::Dimmable)
+            .with_light(4, "Porch", LightKind::Dimmable)
+    }
+
+    fn writes(bridge: &MockBridge) -> Vec<String> {
+        bridge
+            .calls()
+            .into_iter()
+            .filter(|call| call.method != "GET")
+            .map(|call| format!("{} {}", call.method, call.path))
+            .collect()
+    }
+
+    #[tokio::test]
+    pub async fn lights_matching_a_room_get_one_group_action() {
+        // Arrange
+        let bridge = Arc::new(bridge().with_group(1, "Office", &[1, 2, 3]));
+        let api = bridge.api();
+        let on = LightState::default().with_on(true);
+
+        // Act
+        let report = set_light_states(
+            &api,
+            "ip",
+            "user",
+            &[(3, on.clone()), (1, on.clone()), (2, on)],
+            DEFAULT_CONCURRENCY,
+        )
+        .await;
+
+        // Assert
+        assert_eq!(report.updated, vec![3, 1, 2]);
+        assert_eq!(report.groups, vec![1]);
+        assert_eq!(writes(&bridge), vec!["PUT groups/1/action"]);
+        assert_eq!(bridge.resource("lights/3/state/on"), Some(json!(true)));
+        assert_eq!(bridge.resource("lights/4/state/on"), Some(json!(false)));
+    }
+
+    #[tokio::test]
+    pub async fn batch_group_is_created_once_then_refilled() {
+        // Arrange
+        let bridge = Arc::new(bridge().with_group(1, "Office", &[1, 2, 3]));
+        let api = bridge.api();
+        let on = LightState::default().with_on(true);
+        let off = LightState::default().with_on(false);
+
+        // Act
+        set_light_states(
+            &api,
+            "ip",
+            "user",
+            &[(1, on.clone()), (4, on.clone())],
+            DEFAULT_CONCURRENCY,
+        )
+        .await;
+        let report = set_light_states(
+            &api,
+            "ip",
+            "user",
+            &[(2, off.clone()), (4, off), (3, on)],
+            DEFAULT_CONCURRENCY,
+        )
+        .await;
+
+        // Assert
+        assert_eq!(
+            writes(&bridge),
+            vec![
+                "POST groups",
+                "PUT groups/2/action",
+                "PUT groups/2",
+                "PUT groups/2/action",
+                "PUT lights/3/state",
+            ]
+        );
+        assert_eq!(
+            bridge.resource("groups/2/name"),
+            Some(json!(BATCH_GROUP_NAME))
+        );
+        assert_eq!(bridge.resource("groups/2/lights"), Some(json!(["2", "4"])));
+        assert_eq!(bridge.resource("lights/1/state/on"), Some(json!(true)));
+        assert_eq!(bridge.resource("lights/4/state/on"), Some(json!(false)));
+        assert_eq!(report.groups, vec![2]);
+        assert_eq!(report.updated, vec![2, 4, 3]);
+    }
+
+    #[tokio::test]
+    pub async fn falls_back_to_each_light_when_groups_cannot_be_read() {
+        // Arrange
+        let bridge = Arc::new(bridge());
+        let api = bridge.api();
+        bridge.fail_next(CoreError::Bridge(HueBridgeError::UnexpectedJSON));
+        let on = LightState::default().with_on(true);
+        let updates = [(9, on.clone()), (1, on.clone()), (2, on)];
+
+        // Act
+        let report = set_light_states(&api, "ip", "user", &updates, DEFAULT_CONCURRENCY).await;
+
+        // Assert
+        assert_eq!(
+            writes(&bridge),
+            vec![
+                "PUT lights/9/state",
+                "PUT lights/1/state",
+                "PUT lights/2/state"
+            ]
+        );
+        assert_eq!(report.updated, vec![1, 2]);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, 9);
+        assert!(report.groups.is_empty());
+        // Per light in the order asked for, ready for a summary table.
+        let outcomes = report.outcomes(&updates);
+        let ids: Vec<u32> = outcomes.results.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, vec![9, 1, 2]);
+        assert!(matches!(outcomes.results[0].1, Outcome::BridgeError(_)));
+        assert_eq!(outcomes.failed(), 1);
+    }
+}
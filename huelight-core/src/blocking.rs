@@ -0,0 +1,392 @@
+//! A synchronous facade over `HueApi`, enabled with the `blocking` feature.
+//!
+//! Like `reqwest::blocking`, `BlockingHueApi` runs each call to completion on a runtime it
+//! owns, so callers don't need one of their own. It must not be used from inside an async
+//! runtime; call the async `HueApi` there instead.
+
+use std::sync::Arc;
+
+use tokio::runtime::Runtime;
+
+use crate::client::{BRIDGE_REQUEST_INTERVAL, HueClient, RateLimitedHueClient, ReqwestHueClient};
+use crate::domain::light::{Light, LightId, LightState, Lights, NewLights};
+use crate::error::{CoreError, CoreResult};
+use crate::hue_api::{HueApi, HueApiV1};
+use crate::logger::{ILogger, Logger};
+use crate::models::bridgeconfig::BridgeConfig;
+use crate::models::group::{GroupId, GroupResponse, NewGroup};
+use crate::models::hueerror::HueResponse;
+use crate::models::rule::{Rule, RuleId, RuleResponse};
+use crate::models::scene::SceneResponse;
+use crate::models::schedule::{Schedule, ScheduleId, ScheduleResponse};
+use crate::models::sensor::{
+    NewSensor, Sensor, SensorConfig, SensorId, SensorResponse, SensorStateUpdate,
+};
+
+/// Every `HueApi` method without the `async_` prefix, blocking until the bridge answers.
+pub struct BlockingHueApi {
+    api: Arc<dyn HueApi + Send + Sync>,
+    runtime: Runtime,
+}
+
+impl BlockingHueApi {
+    /// Wraps any `HueApi`, with a new current-thread runtime to drive it.
+    pub fn new(api: Arc<dyn HueApi + Send + Sync>) -> CoreResult<Self> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(CoreError::Runtime)?;
+        Ok(Self { api, runtime })
+    }
+
+    /// A `HueApiV1` over the given client.
+    pub fn with_client(
+        client: Arc<dyn HueClient + Send + Sync>,
+        logger: Arc<dyn ILogger + Send + Sync>,
+    ) -> CoreResult<Self> {
+        Self::new(Arc::new(HueApiV1::new(client, logger)))
+    }
+
+    /// Talks to the bridge over HTTP with the usual request rate limiting.
+    pub fn connect() -> CoreResult<Self> {
+        let client = Arc::new(RateLimitedHueClient::new(
+            Arc::new(ReqwestHueClient::new(reqwest::Client::new())),
+            BRIDGE_REQUEST_INTERVAL,
+        ));
+        Self::with_client(client, Arc::new(Logger::default()))
+    }
+
+    pub fn get_all_lights(&self, ip_address: &str, username: &str) -> CoreResult<Lights> {
+        self.runtime
+            .block_on(self.api.async_get_all_lights(ip_address, username))
+    }
+
+    pub fn get_light(
+        &self,
+        ip_address: &str,
+        username: &str,
+        light_id: LightId,
+    ) -> CoreResult<Light> {
+        self.runtime
+            .block_on(self.api.async_get_light(ip_address, username, light_id))
+    }
+
+    pub fn set_light_state(
+        &self,
+        ip_address: &str,
+        username: &str,
+        light_id: u32,
+        state: &LightState,
+    ) -> CoreResult<HueResponse> {
+        self.runtime.block_on(
+            self.api
+                .async_set_light_state(ip_address, username, light_id, state),
+        )
+    }
+
+    pub fn rename_light(
+        &self,
+        ip_address: &str,
+        username: &str,
+        light_id: LightId,
+        name: &str,
+    ) -> CoreResult<HueResponse> {
+        self.runtime.block_on(
+            self.api
+                .async_rename_light(ip_address, username, light_id, name),
+        )
+    }
+
+    pub fn delete_light(
+        &self,
+        ip_address: &str,
+        username: &str,
+        light_id: LightId,
+    ) -> CoreResult<HueResponse> {
+        self.runtime
+            .block_on(self.api.async_delete_light(ip_address, username, light_id))
+    }
+
+    pub fn search_lights(
+        &self,
+        ip_address: &str,
+        username: &str,
+        serial_numbers: &[String],
+    ) -> CoreResult<HueResponse> {
+        self.runtime.block_on(
+            self.api
+                .async_search_lights(ip_address, username, serial_numbers),
+        )
+    }
+
+    pub fn get_new_lights(&self, ip_address: &str, username: &str) -> CoreResult<NewLights> {
+        self.runtime
+            .block_on(self.api.async_get_new_lights(ip_address, username))
+    }
+
+    pub fn get_all_groups(&self, ip_address: &str, username: &str) -> CoreResult<GroupResponse> {
+        self.runtime
+            .block_on(self.api.async_get_all_groups(ip_address, username))
+    }
+
+    pub fn set_group_state(
+        &self,
+        ip_address: &str,
+        username: &str,
+        group_id: GroupId,
+        state: &LightState,
+    ) -> CoreResult<HueResponse> {
+        self.runtime.block_on(
+            self.api
+                .async_set_group_state(ip_address, username, group_id, state),
+        )
+    }
+
+    pub fn create_group(
+        &self,
+        ip_address: &str,
+        username: &str,
+        group: &NewGroup,
+    ) -> CoreResult<GroupId> {
+        self.runtime
+            .block_on(self.api.async_create_group(ip_address, username, group))
+    }
+
+    pub fn set_group_lights(
+        &self,
+        ip_address: &str,
+        username: &str,
+        group_id: GroupId,
+        light_ids: &[LightId],
+    ) -> CoreResult<HueResponse> {
+        self.runtime.block_on(
+            self.api
+                .async_set_group_lights(ip_address, username, group_id, light_ids),
+        )
+    }
+
+    pub fn get_all_scenes(&self, ip_address: &str, username: &str) -> CoreResult<SceneResponse> {
+        self.runtime
+            .block_on(self.api.async_get_all_scenes(ip_address, username))
+    }
+
+    pub fn recall_scene(
+        &self,
+        ip_address: &str,
+        username: &str,
+        group_id: GroupId,
+        scene_id: &str,
+    ) -> CoreResult<HueResponse> {
+        self.runtime.block_on(
+            self.api
+                .async_recall_scene(ip_address, username, group_id, scene_id),
+        )
+    }
+
+    pub fn get_config(&self, ip_address: &str, username: &str) -> CoreResult<BridgeConfig> {
+        self.runtime
+            .block_on(self.api.async_get_config(ip_address, username))
+    }
+
+    pub fn get_unauthenticated_config(&self, ip_address: &str) -> CoreResult<BridgeConfig> {
+        self.runtime
+            .block_on(self.api.async_get_unauthenticated_config(ip_address))
+    }
+
+    pub fn is_whitelisted(&self, ip_address: &str, username: &str) -> CoreResult<bool> {
+        self.runtime
+            .block_on(self.api.async_is_whitelisted(ip_address, username))
+    }
+
+    pub fn delete_user(
+        &self,
+        ip_address: &str,
+        username: &str,
+        user_to_delete: &str,
+    ) -> CoreResult<HueResponse> {
+        self.runtime.block_on(
+            self.api
+                .async_delete_user(ip_address, username, user_to_delete),
+        )
+    }
+
+    pub fn get_all_sensors(&self, ip_address: &str, username: &str) -> CoreResult<SensorResponse> {
+        self.runtime
+            .block_on(self.api.async_get_all_sensors(ip_address, username))
+    }
+
+    pub fn get_sensor(
+        &self,
+        ip_address: &str,
+        username: &str,
+        sensor_id: SensorId,
+    ) -> CoreResult<Sensor> {
+        self.runtime
+            .block_on(self.api.async_get_sensor(ip_address, username, sensor_id))
+    }
+
+    pub fn set_sensor_config(
+        &self,
+        ip_address: &str,
+        username: &str,
+        sensor_id: SensorId,
+        config: &SensorConfig,
+    ) -> CoreResult<HueResponse> {
+        self.runtime.block_on(
+            self.api
+                .async_set_sensor_config(ip_address, username, sensor_id, config),
+        )
+    }
+
+    pub fn set_sensor_state(
+        &self,
+        ip_address: &str,
+        username: &str,
+        sensor_id: SensorId,
+        state: &SensorStateUpdate,
+    ) -> CoreResult<HueResponse> {
+        self.runtime.block_on(
+            self.api
+                .async_set_sensor_state(ip_address, username, sensor_id, state),
+        )
+    }
+
+    pub fn create_sensor(
+        &self,
+        ip_address: &str,
+        username: &str,
+        sensor: &NewSensor,
+    ) -> CoreResult<SensorId> {
+        self.runtime
+            .block_on(self.api.async_create_sensor(ip_address, username, sensor))
+    }
+
+    pub fn get_all_rules(&self, ip_address: &str, username: &str) -> CoreResult<RuleResponse> {
+        self.runtime
+            .block_on(self.api.async_get_all_rules(ip_address, username))
+    }
+
+    pub fn create_rule(&self, ip_address: &str, username: &str, rule: &Rule) -> CoreResult<RuleId> {
+        self.runtime
+            .block_on(self.api.async_create_rule(ip_address, username, rule))
+    }
+
+    pub fn update_rule(
+        &self,
+        ip_address: &str,
+        username: &str,
+        rule_id: RuleId,
+        rule: &Rule,
+    ) -> CoreResult<HueResponse> {
+        self.runtime.block_on(
+            self.api
+                .async_update_rule(ip_address, username, rule_id, rule),
+        )
+    }
+
+    pub fn delete_rule(
+        &self,
+        ip_address: &str,
+        username: &str,
+        rule_id: RuleId,
+    ) -> CoreResult<HueResponse> {
+        self.runtime
+            .block_on(self.api.async_delete_rule(ip_address, username, rule_id))
+    }
+
+    pub fn get_all_schedules(
+        &self,
+        ip_address: &str,
+        username: &str,
+    ) -> CoreResult<ScheduleResponse> {
+        self.runtime
+            .block_on(self.api.async_get_all_schedules(ip_address, username))
+    }
+
+    pub fn create_schedule(
+        &self,
+        ip_address: &str,
+        username: &str,
+        schedule: &Schedule,
+    ) -> CoreResult<ScheduleId> {
+        self.runtime.block_on(
+            self.api
+                .async_create_schedule(ip_address, username, schedule),
+        )
+    }
+
+    pub fn update_schedule(
+        &self,
+        ip_address: &str,
+        username: &str,
+        schedule_id: ScheduleId,
+        schedule: &Schedule,
+    ) -> CoreResult<HueResponse> {
+        self.runtime.block_on(self.api.async_update_schedule(
+            ip_address,
+            username,
+            schedule_id,
+            schedule,
+        ))
+    }
+
+    pub fn delete_schedule(
+        &self,
+        ip_address: &str,
+        username: &str,
+        schedule_id: ScheduleId,
+    ) -> CoreResult<HueResponse> {
+        self.runtime.block_on(
+            self.api
+                .async_delete_schedule(ip_address, username, schedule_id),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::BlockingHueApi;
+    use crate::domain::light::{LightKind, LightState};
+    use crate::error::{CoreError, HueBridgeError};
+    use crate::logger::Logger;
+    use crate::testing::MockBridge;
+
+    fn api(bridge: &Arc<MockBridge>) -> BlockingHueApi {
+        BlockingHueApi::with_client(bridge.clone(), Arc::new(Logger::default())).unwrap()
+    }
+
+    #[test]
+    pub fn blocking_calls_run_without_a_runtime() {
+        // Arrange
+        let bridge = Arc::new(MockBridge::new().with_light(1, "Desk", LightKind::Dimmable));
+        let api = api(&bridge);
+
+        // Act
+        api.set_light_state("ip", "user", 1, &LightState::default().with_on(true))
+            .unwrap();
+        let lights = api.get_all_lights("ip", "user").unwrap();
+
+        // Assert
+        assert_eq!(lights.0.get(&1).unwrap().state.on, Some(true));
+        assert_eq!(bridge.calls().len(), 2);
+    }
+
+    #[test]
+    pub fn blocking_calls_return_bridge_errors() {
+        // Arrange
+        let bridge = Arc::new(MockBridge::new());
+        bridge.fail_next(CoreError::Bridge(HueBridgeError::UnauthorizedUser));
+        let api = api(&bridge);
+
+        // Act
+        let result = api.get_all_groups("ip", "user");
+
+        // Assert
+        assert!(matches!(
+            result,
+            Err(CoreError::Bridge(HueBridgeError::UnauthorizedUser))
+        ));
+    }
+}
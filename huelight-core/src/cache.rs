@@ -0,0 +1,611 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde_json::Value;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+use crate::domain::light::{Light, LightId, LightState, Lights, NewLights};
+use crate::error::{CoreError, CoreResult};
+use crate::hue_api::HueApi;
+use crate::models::bridgeconfig::BridgeConfig;
+use crate::models::group::{GroupId, GroupResponse, NewGroup};
+use crate::models::hueerror::{HueResponse, HueResponseEntry};
+use crate::models::rule::{Rule, RuleId, RuleResponse};
+use crate::models::scene::SceneResponse;
+use crate::models::schedule::{Schedule, ScheduleId, ScheduleResponse};
+use crate::models::sensor::{
+    NewSensor, Sensor, SensorConfig, SensorId, SensorResponse, SensorStateUpdate,
+};
+
+/// How long light states read from the bridge are trusted. Long enough to cover a burst of
+/// commands, short enough that changes from the app or a switch are picked up soon.
+pub const LIGHT_STATE_TTL: Duration = Duration::from_secs(2);
+
+struct CachedLights {
+    fetched: Instant,
+    ip_address: String,
+    username: String,
+    lights: Lights,
+}
+
+/// The success entries the bridge would have sent for a write, e.g.
+/// `{"success":{"/lights/3/state/on":true}}`.
+fn success_entries(light_id: LightId, state: &LightState) -> CoreResult<Vec<HueResponseEntry>> {
+    let Value::Object(fields) = serde_json::to_value(state).map_err(CoreError::Serialization)?
+    else {
+        return Ok(Vec::new());
+    };
+    Ok(fields
+        .into_iter()
+        .map(|(field, value)| HueResponseEntry::Success {
+            success: HashMap::from([(format!("/lights/{light_id}/state/{field}"), value)]),
+        })
+        .collect())
+}
+
+/// Wraps another `HueApi` and remembers the light states it last read, for `ttl`.
+///
+/// While the states are fresh, reads are answered from them and light state writes only send
+/// the fields that would change anything. A write that would change nothing isn't sent at
+/// all, and is answered as if the bridge had accepted it. Writes that affect lights in ways
+/// the cache can't follow (groups, scenes, renames) clear it.
+pub struct CachedHueApi {
+    inner: Arc<dyn HueApi + Send + Sync>,
+    ttl: Duration,
+    lights: Mutex<Option<CachedLights>>,
+    light_turns: std::sync::Mutex<HashMap<LightId, Arc<Mutex<()>>>>,
+}
+
+impl CachedHueApi {
+    pub fn new(inner: Arc<dyn HueApi + Send + Sync>, ttl: Duration) -> Self {
+        Self {
+            inner,
+            ttl,
+            lights: Mutex::new(None),
+            light_turns: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Forgets every cached state, so the next read goes to the bridge.
+    pub async fn invalidate(&self) {
+        *self.lights.lock().await = None;
+    }
+
+    /// The lock a write to `light_id` holds, so writes to one light go out in order.
+    fn light_turn(&self, light_id: LightId) -> Arc<Mutex<()>> {
+        self.light_turns
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(light_id)
+            .or_default()
+            .clone()
+    }
+
+    /// The cached lights, if they are fresh and from the same bridge and user.
+    fn fresh<'a>(
+        &self,
+        cached: &'a mut Option<CachedLights>,
+        ip_address: &str,
+        username: &str,
+    ) -> Option<&'a mut Lights> {
+        cached
+            .as_mut()
+            .filter(|c| {
+                c.fetched.elapsed() < self.ttl
+                    && c.ip_address == ip_address
+                    && c.username == username
+            })
+            .map(|c| &mut c.lights)
+    }
+}
+
+#[async_trait]
+impl HueApi for CachedHueApi {
+    async fn async_get_all_lights(&self, ip_address: &str, username: &str) -> CoreResult<Lights> {
+        let mut cached = self.lights.lock().await;
+        if let Some(lights) = self.fresh(&mut cached, ip_address, username) {
+            return Ok(lights.clone());
+        }
+        let lights = self
+            .inner
+            .async_get_all_lights(ip_address, username)
+            .await?;
+        *cached = Some(CachedLights {
+            fetched: Instant::now(),
+            ip_address: ip_address.to_string(),
+            username: username.to_string(),
+            lights: lights.clone(),
+        });
+        Ok(lights)
+    }
+
+    async fn async_get_light(
+        &self,
+        ip_address: &str,
+        username: &str,
+        light_id: LightId,
+    ) -> CoreResult<Light> {
+        if let Some(light) = self
+            .fresh(&mut *self.lights.lock().await, ip_address, username)
+            .and_then(|lights| lights.0.get(&light_id).cloned())
+        {
+            return Ok(light);
+        }
+        self.inner
+            .async_get_light(ip_address, username, light_id)
+            .await
+    }
+
+    async fn async_set_light_state(
+        &self,
+        ip_address: &str,
+        username: &str,
+        light_id: u32,
+        state: &LightState,
+    ) -> CoreResult<HueResponse> {
+        // Writes to the same light take turns, so each one diffs against the state the one
+        // before it left. The cache itself is only locked around reading and updating it, so
+        // writes to different lights go out together.
+        let light_turn = self.light_turn(light_id);
+        let _turn = light_turn.lock().await;
+        let Some(current) = self
+            .fresh(&mut *self.lights.lock().await, ip_address, username)
+            .and_then(|lights| lights.0.get(&light_id))
+            .map(|light| light.state.clone())
+        else {
+            return self
+                .inner
+                .async_set_light_state(ip_address, username, light_id, state)
+                .await;
+        };
+
+        let delta = state.delta(&current);
+        let skipped = LightState {
+            on: state.on.filter(|_| delta.on.is_none()),
+            brightness: state.brightness.filter(|_| delta.brightness.is_none()),
+            color: state.color.filter(|_| delta.color.is_none()),
+            effect: state.effect.filter(|_| delta.effect.is_none()),
+            alert: None,
+            transition: None,
+        };
+        let mut response = if delta.is_no_op() {
+            tracing::debug!(
+                light_id,
+                "skipped a light state write that would change nothing"
+            );
+            Vec::new()
+        } else {
+            let result = self
+                .inner
+                .async_set_light_state(ip_address, username, light_id, &delta)
+                .await;
+            let mut cached = self.lights.lock().await;
+            match result {
+                Ok(response) => {
+                    if let Some(light) = self
+                        .fresh(&mut cached, ip_address, username)
+                        .and_then(|lights| lights.0.get_mut(&light_id))
+                    {
+                        let current = &mut light.state;
+                        current.on = delta.on.or(current.on);
+                        current.brightness = delta.brightness.or(current.brightness);
+                        current.color = delta.color.or(current.color);
+                        current.effect = delta.effect.or(current.effect);
+                    }
+                    response
+                }
+                // The light may have changed partly, or be gone; read it again next time.
+                other => {
+                    *cached = None;
+                    return other;
+                }
+            }
+        };
+        response.extend(success_entries(light_id, &skipped)?);
+        Ok(response)
+    }
+
+    async fn async_rename_light(
+        &self,
+        ip_address: &str,
+        username: &str,
+        light_id: LightId,
+        name: &str,
+    ) -> CoreResult<HueResponse> {
+        let response = self
+            .inner
+            .async_rename_light(ip_address, username, light_id, name)
+            .await;
+        self.invalidate().await;
+        response
+    }
+
+    async fn async_delete_light(
+        &self,
+        ip_address: &str,
+        username: &str,
+        light_id: LightId,
+    ) -> CoreResult<HueResponse> {
+        let response = self
+            .inner
+            .async_delete_light(ip_address, username, light_id)
+            .await;
+        self.invalidate().await;
+        response
+    }
+
+    async fn async_search_lights(
+        &self,
+        ip_address: &str,
+        username: &str,
+        serial_numbers: &[String],
+    ) -> CoreResult<HueResponse> {
+        let response = self
+            .inner
+            .async_search_lights(ip_address, username, serial_numbers)
+            .await;
+        self.invalidate().await;
+        response
+    }
+
+    async fn async_get_new_lights(
+        &self,
+        ip_address: &str,
+        username: &str,
+    ) -> CoreResult<NewLights> {
+        self.inner.async_get_new_lights(ip_address, username).await
+    }
+
+    async fn async_get_all_groups(
+        &self,
+        ip_address: &str,
+        username: &str,
+    ) -> CoreResult<GroupResponse> {
+        self.inner.async_get_all_groups(ip_address, username).await
+    }
+
+    async fn async_set_group_state(
+        &self,
+        ip_address: &str,
+        username: &str,
+        group_id: GroupId,
+        state: &LightState,
+    ) -> CoreResult<HueResponse> {
+        let response = self
+            .inner
+            .async_set_group_state(ip_address, username, group_id, state)
+            .await;
+        self.invalidate().await;
+        response
+    }
+
+    async fn async_create_group(
+        &self,
+        ip_address: &str,
+        username: &str,
+        group: &NewGroup,
+    ) -> CoreResult<GroupId> {
+        self.inner
+            .async_create_group(ip_address, username, group)
+            .await
+    }
+
+    async fn async_set_group_lights(
+        &self,
+        ip_address: &str,
+        username: &str,
+        group_id: GroupId,
+        light_ids: &[LightId],
+    ) -> CoreResult<HueResponse> {
+        self.inner
+            .async_set_group_lights(ip_address, username, group_id, light_ids)
+            .await
+    }
+
+    async fn async_get_all_scenes(
+        &self,
+        ip_address: &str,
+        username: &str,
+    ) -> CoreResult<SceneResponse> {
+        self.inner.async_get_all_scenes(ip_address, username).await
+    }
+
+    async fn async_recall_scene(
+        &self,
+        ip_address: &str,
+        username: &str,
+        group_id: GroupId,
+        scene_id: &str,
+    ) -> CoreResult<HueResponse> {
+        let response = self
+            .inner
+            .async_recall_scene(ip_address, username, group_id, scene_id)
+            .await;
+        self.invalidate().await;
+        response
+    }
+
+    async fn async_get_config(&self, ip_address: &str, username: &str) -> CoreResult<BridgeConfig> {
+        self.inner.async_get_config(ip_address, username).await
+    }
+
+    async fn async_get_unauthenticated_config(&self, ip_address: &str) -> CoreResult<BridgeConfig> {
+        self.inner
+            .async_get_unauthenticated_config(ip_address)
+            .await
+    }
+
+    async fn async_is_whitelisted(&self, ip_address: &str, username: &str) -> CoreResult<bool> {
+        self.inner.async_is_whitelisted(ip_address, username).await
+    }
+
+    async fn async_delete_user(
+        &self,
+        ip_address: &str,
+        username: &str,
+        user_to_delete: &str,
+    ) -> CoreResult<HueResponse> {
+        self.inner
+            .async_delete_user(ip_address, username, user_to_delete)
+            .await
+    }
+
+    async fn async_get_all_sensors(
+        &self,
+        ip_address: &str,
+        username: &str,
+    ) -> CoreResult<SensorResponse> {
+        self.inner.async_get_all_sensors(ip_address, username).await
+    }
+
+    async fn async_get_sensor(
+        &self,
+        ip_address: &str,
+        username: &str,
+        sensor_id: SensorId,
+    ) -> CoreResult<Sensor> {
+        self.inner
+            .async_get_sensor(ip_address, username, sensor_id)
+            .await
+    }
+
+    async fn async_set_sensor_config(
+        &self,
+        ip_address: &str,
+        username: &str,
+        sensor_id: SensorId,
+        config: &SensorConfig,
+    ) -> CoreResult<HueResponse> {
+        self.inner
+            .async_set_sensor_config(ip_address, username, sensor_id, config)
+            .await
+    }
+
+    async fn async_set_sensor_state(
+        &self,
+        ip_address: &str,
+        username: &str,
+        sensor_id: SensorId,
+        state: &SensorStateUpdate,
+    ) -> CoreResult<HueResponse> {
+        self.inner
+            .async_set_sensor_state(ip_address, username, sensor_id, state)
+            .await
+    }
+
+    async fn async_create_sensor(
+        &self,
+        ip_address: &str,
+        username: &str,
+        sensor: &NewSensor,
+    ) -> CoreResult<SensorId> {
+        self.inner
+            .async_create_sensor(ip_address, username, sensor)
+            .await
+    }
+
+    async fn async_get_all_rules(
+        &self,
+        ip_address: &str,
+        username: &str,
+    ) -> CoreResult<RuleResponse> {
+        self.inner.async_get_all_rules(ip_address, username).await
+    }
+
+    async fn async_create_rule(
+        &self,
+        ip_address: &str,
+        username: &str,
+        rule: &Rule,
+    ) -> CoreResult<RuleId> {
+        self.inner
+            .async_create_rule(ip_address, username, rule)
+            .await
+    }
+
+    async fn async_update_rule(
+        &self,
+        ip_address: &str,
+        username: &str,
+        rule_id: RuleId,
+        rule: &Rule,
+    ) -> CoreResult<HueResponse> {
+        self.inner
+            .async_update_rule(ip_address, username, rule_id, rule)
+            .await
+    }
+
+    async fn async_delete_rule(
+        &self,
+        ip_address: &str,
+        username: &str,
+        rule_id: RuleId,
+    ) -> CoreResult<HueResponse> {
+        self.inner
+            .async_delete_rule(ip_address, username, rule_id)
+            .await
+    }
+
+    async fn async_get_all_schedules(
+        &self,
+        ip_address: &str,
+        username: &str,
+    ) -> CoreResult<ScheduleResponse> {
+        self.inner
+            .async_get_all_schedules(ip_address, username)
+            .await
+    }
+
+    async fn async_create_schedule(
+        &self,
+        ip_address: &str,
+        username: &str,
+        schedule: &Schedule,
+    ) -> CoreResult<ScheduleId> {
+        self.inner
+            .async_create_schedule(ip_address, username, schedule)
+            .await
+    }
+
+    async fn async_update_schedule(
+        &self,
+        ip_address: &str,
+        username: &str,
+        schedule_id: ScheduleId,
+        schedule: &Schedule,
+    ) -> CoreResult<HueResponse> {
+        self.inner
+            .async_update_schedule(ip_address, username, schedule_id, schedule)
+            .await
+    }
+
+    async fn async_delete_schedule(
+        &self,
+        ip_address: &str,
+        username: &str,
+        schedule_id: ScheduleId,
+    ) -> CoreResult<HueResponse> {
+        self.inner
+            .async_delete_schedule(ip_address, username, schedule_id)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use tokio::time::Instant;
+
+    use super::{CachedHueApi, LIGHT_STATE_TTL};
+    use crate::domain::brightness::Brightness;
+    use crate::domain::light::{LightKind, LightState};
+    use crate::hue_api::HueApi;
+    use crate::models::hueerror::HueResponseEntry;
+    use crate::testing::MockBridge;
+
+    fn cached(bridge: &Arc<MockBridge>, ttl: Duration) -> CachedHueApi {
+        CachedHueApi::new(Arc::new(bridge.api()), ttl)
+    }
+
+    #[tokio::test]
+    pub async fn set_light_state_sends_only_changed_fields() {
+        // Arrange
+        let bridge = Arc::new(MockBridge::new().with_light(1, "Desk", LightKind::Dimmable));
+        let api = cached(&bridge, LIGHT_STATE_TTL);
+        api.async_get_all_lights("ip", "user").await.unwrap();
+        let state = LightState::default()
+            .with_on(true)
+            .with_brightness(Brightness::from_bri(254));
+
+        // Act
+        let first = api
+            .async_set_light_state("ip", "user", 1, &state)
+            .await
+            .unwrap();
+        let second = api
+            .async_set_light_state("ip", "user", 1, &state)
+            .await
+            .unwrap();
+        let light = api.async_get_light("ip", "user", 1).await.unwrap();
+
+        // Assert
+        let calls = bridge.calls();
+        // One read, then a single write with only `on`; the repeat and the read are cached.
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[1].body, Some(serde_json::json!({ "on": true })));
+        assert_eq!(light.state.on, Some(true));
+        // Callers still see every field confirmed, as if the bridge had been asked.
+        for response in [first, second] {
+            assert!(response.iter().any(|entry| matches!(
+                entry,
+                HueResponseEntry::Success { success } if success.contains_key("/lights/1/state/bri")
+            )));
+            assert!(response.iter().any(|entry| matches!(
+                entry,
+                HueResponseEntry::Success { success } if success.contains_key("/lights/1/state/on")
+            )));
+        }
+    }
+
+    #[tokio::test]
+    pub async fn writes_to_different_lights_overlap() {
+        // Arrange
+        let latency = Duration::from_millis(100);
+        let bridge = Arc::new(
+            MockBridge::new()
+                .with_light(1, "Desk", LightKind::Dimmable)
+                .with_light(2, "Shelf", LightKind::Dimmable)
+                .with_latency(latency),
+        );
+        let api = cached(&bridge, Duration::from_secs(60));
+        api.async_get_all_lights("ip", "user").await.unwrap();
+        let state = LightState::default().with_on(true);
+
+        // Act
+        let started = Instant::now();
+        let (first, second) = tokio::join!(
+            api.async_set_light_state("ip", "user", 1, &state),
+            api.async_set_light_state("ip", "user", 2, &state),
+        );
+        let elapsed = started.elapsed();
+
+        // Assert
+        first.unwrap();
+        second.unwrap();
+        assert!(elapsed < latency * 2, "writes took {elapsed:?}");
+        let light = api.async_get_light("ip", "user", 2).await.unwrap();
+        assert_eq!(light.state.on, Some(true));
+        assert_eq!(bridge.calls().len(), 3);
+    }
+
+    #[tokio::test]
+    pub async fn stale_or_invalidated_cache_reads_from_bridge() {
+        // Arrange
+        let bridge = Arc::new(MockBridge::new().with_light(1, "Desk", LightKind::OnOff));
+        let expired = cached(&bridge, Duration::ZERO);
+        let fresh = cached(&bridge, LIGHT_STATE_TTL);
+
+        // Act
+        expired.async_get_all_lights("ip", "user").await.unwrap();
+        expired.async_get_all_lights("ip", "user").await.unwrap();
+        fresh.async_get_all_lights("ip", "user").await.unwrap();
+        fresh
+            .async_set_group_state("ip", "user", 0, &LightState::default().with_on(true))
+            .await
+            .ok();
+        fresh.async_get_all_lights("ip", "user").await.unwrap();
+
+        // Assert
+        let gets = bridge
+            .calls()
+            .iter()
+            .filter(|call| call.method == "GET")
+            .count();
+        assert_eq!(gets, 4);
+    }
+}
@@ -0,0 +1,397 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::client::{Header, HueClient};
+use crate::client::{REDACTED_USERNAME, redact_url, redact_username, split_url};
+use crate::config::{FileHandler, path_to_str};
+use crate::error::{CoreError, CoreResult};
+
+/// One request and the bridge's response to it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Interaction {
+    pub method: String,
+    /// With the username redacted.
+    pub url: String,
+    /// With the username redacted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    /// With API keys redacted.
+    pub response: String,
+}
+
+impl Interaction {
+    /// Whether a request matches this recording. The bridge address and username are ignored,
+    /// so a cassette replays against any config, and JSON bodies are compared as values once
+    /// the request's username is redacted from them.
+    fn matches(&self, method: &str, url: &str, body: Option<&str>) -> bool {
+        let body = body.map(|body| redact_username(body, url));
+        let body = body.as_deref();
+        let same_body = match (self.body.as_deref(), body) {
+            (Some(recorded), Some(sent)) => {
+                match (
+                    serde_json::from_str::<Value>(recorded),
+                    serde_json::from_str::<Value>(sent),
+                ) {
+                    (Ok(recorded), Ok(sent)) => recorded == sent,
+                    _ => recorded == sent,
+                }
+            }
+            (recorded, sent) => recorded == sent,
+        };
+        self.method == method && request_path(&self.url) == request_path(url) && same_body
+    }
+}
+
+/// The part of a URL a replay matches on: everything after the username for bridge URLs, or
+/// the whole URL for anything else (e.g. bridge discovery).
+fn request_path(url: &str) -> &str {
+    match split_url(url) {
+        (base, _, path) if base != url => path,
+        _ => url,
+    }
+}
+
+/// The response with every API key in it replaced: the session's own `username` by
+/// `<username>`, which a replay swaps for its own, and other whitelisted keys by
+/// `<other user N>`. Keys handed out when a user is created are redacted too.
+fn redact_response(response: &str, username: &str) -> String {
+    match serde_json::from_str::<Value>(response) {
+        Ok(mut value) => {
+            redact_keys(&mut value, username, &mut 0);
+            value.to_string()
+        }
+        Err(_) if !username.is_empty() => response.replace(username, REDACTED_USERNAME),
+        Err(_) => response.to_string(),
+    }
+}
+
+fn redact_keys(value: &mut Value, username: &str, others: &mut usize) {
+    match value {
+        Value::Object(fields) => {
+            if let Some(Value::Object(whitelist)) = fields.get_mut("whitelist") {
+                *whitelist = std::mem::take(whitelist)
+                    .into_iter()
+                    .map(|(key, entry)| match key == username {
+                        true => (REDACTED_USERNAME.to_string(), entry),
+                        false => {
+                            *others += 1;
+                            (format!("<other user {others}>"), entry)
+                        }
+                    })
+                    .collect();
+            }
+            if let Some(Value::String(created)) = fields.get_mut("username") {
+                *created = REDACTED_USERNAME.to_string();
+            }
+            for field in fields.values_mut() {
+                redact_keys(field, username, others);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                redact_keys(item, username, others);
+            }
+        }
+        Value::String(text) if !username.is_empty() && text.contains(username) => {
+            *text = text.replace(username, REDACTED_USERNAME);
+        }
+        _ => {}
+    }
+}
+
+/// Recorded bridge traffic, saved as JSON.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Cassette {
+    pub interactions: Vec<Interaction>,
+}
+
+impl Cassette {
+    pub async fn save(&self, path: &Path, file_handler: &impl FileHandler) -> CoreResult<()> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            file_handler.create_dir_all(dir).await?;
+        }
+        let json = serde_json::to_string_pretty(self).map_err(CoreError::Serialization)?;
+        file_handler.write_file(path_to_str(path)?, &json).await
+    }
+
+    pub async fn load(path: &Path, file_handler: &impl FileHandler) -> CoreResult<Self> {
+        let json = file_handler.read_file(path_to_str(path)?).await?;
+        serde_json::from_str(&json).map_err(CoreError::Serialization)
+    }
+}
+
+/// Wraps another HueClient and records every request and response into a `Cassette`.
+///
+/// API keys are redacted from URLs and responses, so a cassette can be shared. Failed
+/// requests aren't recorded, since there is no response to replay.
+pub struct RecordingHueClient {
+    inner: Arc<dyn HueClient + Send + Sync>,
+    cassette: Mutex<Cassette>,
+}
+
+impl RecordingHueClient {
+    pub fn new(inner: Arc<dyn HueClient + Send + Sync>) -> Self {
+        Self {
+            inner,
+            cassette: Mutex::new(Cassette::default()),
+        }
+    }
+
+    /// Everything recorded so far.
+    pub fn cassette(&self) -> Cassette {
+        self.cassette
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    fn record(
+        &self,
+        method: &str,
+        url: &str,
+        body: Option<&str>,
+        response: CoreResult<String>,
+    ) -> CoreResult<String> {
+        let response = response?;
+        self.cassette
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .interactions
+            .push(Interaction {
+                method: method.to_string(),
+                url: redact_url(url),
+                body: body.map(|body| redact_username(body, url)),
+                response: redact_response(&response, split_url(url).1),
+            });
+        Ok(response)
+    }
+}
+
+#[async_trait]
+impl HueClient for RecordingHueClient {
+    async fn post_json(&self, url: &str, body: &str, headers: &[Header]) -> CoreResult<String> {
+        let response = self.inner.post_json(url, body, headers).await;
+        self.record("POST", url, Some(body), response)
+    }
+
+    async fn get(&self, url: &str, headers: &[Header]) -> CoreResult<String> {
+        let response = self.inner.get(url, headers).await;
+        self.record("GET", url, None, response)
+    }
+
+    async fn put_json(&self, url: &str, body: &str, headers: &[Header]) -> CoreResult<String> {
+        let response = self.inner.put_json(url, body, headers).await;
+        self.record("PUT", url, Some(body), response)
+    }
+
+    async fn delete(&self, url: &str, headers: &[Header]) -> CoreResult<String> {
+        let response = self.inner.delete(url, headers).await;
+        self.record("DELETE", url, None, response)
+    }
+}
+
+/// Answers requests from a `Cassette` without touching the network.
+///
+/// Each request is served by the first unused recording that matches it, so requests sent
+/// concurrently may arrive in any order. The session's redacted username in a response is
+/// replaced by the one the request was sent with. A request with no matching recording fails with
+/// `CoreError::UnexpectedRequest`.
+pub struct ReplayHueClient {
+    /// Recordings that haven't been served yet.
+    remaining: Mutex<Vec<Interaction>>,
+}
+
+impl ReplayHueClient {
+    pub fn new(cassette: Cassette) -> Self {
+        Self {
+            remaining: Mutex::new(cassette.interactions),
+        }
+    }
+
+    /// Recordings no request has asked for yet. A test can check this is empty to make sure
+    /// the code under test made every request it used to.
+    pub fn remaining(&self) -> Vec<Interaction> {
+        self.remaining
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    fn replay(&self, method: &str, url: &str, body: Option<&str>) -> CoreResult<String> {
+        let mut remaining = self.remaining.lock().unwrap_or_else(|e| e.into_inner());
+        match remaining
+            .iter()
+            .position(|interaction| interaction.matches(method, url, body))
+        {
+            Some(index) => {
+                let response = remaining.remove(index).response;
+                Ok(match split_url(url) {
+                    (_, "", _) => response,
+                    (_, username, _) => response.replace(REDACTED_USERNAME, username),
+                })
+            }
+            None => Err(CoreError::UnexpectedRequest(format!(
+                "{method} {}{}",
+                redact_url(url),
+                body.map(|body| format!(" {}", redact_username(body, url)))
+                    .unwrap_or_default()
+            ))),
+        }
+    }
+}
+
+#[async_trait]
+impl HueClient for ReplayHueClient {
+    async fn post_json(&self, url: &str, body: &str, _headers: &[Header]) -> CoreResult<String> {
+        self.replay("POST", url, Some(body))
+    }
+
+    async fn get(&self, url: &str, _headers: &[Header]) -> CoreResult<String> {
+        self.replay("GET", url, None)
+    }
+
+    async fn put_json(&self, url: &str, body: &str, _headers: &[Header]) -> CoreResult<String> {
+        self.replay("PUT", url, Some(body))
+    }
+
+    async fn delete(&self, url: &str, _headers: &[Header]) -> CoreResult<String> {
+        self.replay("DELETE", url, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use async_trait::async_trait;
+
+    use super::{Cassette, RecordingHueClient, ReplayHueClient};
+    use crate::client::{Header, HueClient};
+    use crate::error::{CoreError, CoreResult};
+    use crate::hue_api::{HueApi, HueApiV1};
+    use crate::logger::Logger;
+    use crate::models::rule::Action;
+    use crate::models::schedule::{Schedule, TimePattern};
+
+    /// The schedule `timer add 10m on --light 1` creates, which names `username` in its
+    /// command.
+    fn light_timer(username: &str) -> Schedule {
+        let command = Action {
+            address: format!("/api/{username}/lights/1/state"),
+            method: "PUT".to_string(),
+            body: serde_json::json!({ "on": true }),
+        };
+        Schedule::new(
+            "Timer",
+            command,
+            TimePattern::timer(std::time::Duration::from_secs(600)),
+        )
+    }
+
+    /// A bridge with a single light that accepts every write.
+    struct OneLightBridge;
+
+    #[async_trait]
+    impl HueClient for OneLightBridge {
+        async fn post_json(&self, _: &str, _: &str, _: &[Header]) -> CoreResult<String> {
+            Ok(r#"[{"success":{"id":"1"}}]"#.to_string())
+        }
+        async fn get(&self, url: &str, _: &[Header]) -> CoreResult<String> {
+            if url.ends_with("/config") {
+                return Ok(r#"{"name":"Office","swversion":"1962154010","apiversion":"1.62.0","bridgeid":"001788FFFE010203","modelid":"BSB002","whitelist":{"secret":{"last use date":"2024-05-01T10:00:00","create date":"2023-01-01T09:00:00","name":"huelightcli#laptop"},"f00dkey":{"last use date":"2024-05-01T10:00:00","create date":"2023-01-01T09:00:00","name":"app#phone"}}}"#.to_string());
+            }
+            Ok(r#"{"1":{"name":"Desk","type":"Dimmable light","state":{"on":false,"bri":1,"reachable":true}}}"#.to_string())
+        }
+        async fn put_json(&self, _: &str, _: &str, _: &[Header]) -> CoreResult<String> {
+            Ok(r#"[{"success":{"/lights/1/state/on":true}}]"#.to_string())
+        }
+        async fn delete(&self, _: &str, _: &[Header]) -> CoreResult<String> {
+            Ok(r#"[{"success":"/lights/1 deleted"}]"#.to_string())
+        }
+    }
+
+    #[tokio::test]
+    pub async fn recorded_session_replays_for_another_bridge_and_user() {
+        // Arrange
+        let recorder = Arc::new(RecordingHueClient::new(Arc::new(OneLightBridge)));
+        let api = HueApiV1::new(recorder.clone(), Arc::new(Logger::default()));
+        let lights = api
+            .async_get_all_lights("10.0.0.2", "secret")
+            .await
+            .unwrap();
+        api.async_get_config("10.0.0.2", "secret").await.unwrap();
+        let cassette = recorder.cassette();
+        let json = serde_json::to_string(&cassette).unwrap();
+
+        // Act
+        let replay = Arc::new(ReplayHueClient::new(serde_json::from_str(&json).unwrap()));
+        let api = HueApiV1::new(replay.clone(), Arc::new(Logger::default()));
+        let replayed = api
+            .async_get_all_lights("192.168.1.9", "other")
+            .await
+            .unwrap();
+        let config = api.async_get_config("192.168.1.9", "other").await.unwrap();
+
+        // Assert
+        assert!(!json.contains("secret"));
+        assert!(!json.contains("f00dkey"));
+        assert_eq!(cassette.interactions.len(), 2);
+        assert!(config.is_whitelisted("other"));
+        assert_eq!(replayed.0.len(), lights.0.len());
+        assert!(replay.remaining().is_empty());
+    }
+
+    #[tokio::test]
+    pub async fn recorded_timer_replays_for_another_user() {
+        // Arrange
+        let recorder = Arc::new(RecordingHueClient::new(Arc::new(OneLightBridge)));
+        let api = HueApiV1::new(recorder.clone(), Arc::new(Logger::default()));
+        api.async_create_schedule("10.0.0.2", "secret", &light_timer("secret"))
+            .await
+            .unwrap();
+        let json = serde_json::to_string(&recorder.cassette()).unwrap();
+
+        // Act
+        let replay = Arc::new(ReplayHueClient::new(serde_json::from_str(&json).unwrap()));
+        let api = HueApiV1::new(replay.clone(), Arc::new(Logger::default()));
+        let id = api
+            .async_create_schedule("192.168.1.9", "other", &light_timer("other"))
+            .await;
+
+        // Assert
+        assert!(!json.contains("secret"));
+        assert!(json.contains("/api/<username>/lights/1/state"));
+        assert_eq!(id.unwrap(), 1);
+        assert!(replay.remaining().is_empty());
+    }
+
+    #[tokio::test]
+    pub async fn replay_fails_on_unexpected_request() {
+        // Arrange
+        let replay = ReplayHueClient::new(Cassette::default());
+
+        // Act
+        let result = replay
+            .put_json(
+                "http://10.0.0.2/api/secret/lights/1/state",
+                r#"{"on":true}"#,
+                &[],
+            )
+            .await;
+
+        // Assert
+        match result {
+            Err(CoreError::UnexpectedRequest(request)) => {
+                assert_eq!(
+                    request,
+                    r#"PUT http://10.0.0.2/api/<username>/lights/1/state {"on":true}"#
+                );
+            }
+            other => panic!("expected an unexpected request error, got {other:?}"),
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use std::f64::consts::PI;
+
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::Tz;
+
+use crate::domain::brightness::Brightness;
+use crate::solar::{SolarEvent, sun_times};
+
+/// Converts a color temperature in kelvin to mirek, the unit the bridge uses for `ct`.
+pub fn kelvin_to_mirek(kelvin: u32) -> u16 {
+    (1_000_000 / kelvin.max(1)).min(u16::MAX as u32) as u16
+}
+
+/// Converts a brightness percentage (0-100) to the bridge's `bri` scale (1-254).
+pub fn percent_to_bri(percent: f64) -> u8 {
+    Brightness::from_percent(percent).bri()
+}
+
+/// Maps the sun's position through the day to a color temperature and brightness.
+///
+/// During the day the color temperature follows a sine from warmest at sunrise to coolest at
+/// solar noon and back, at full brightness. After sunset the light stays at the warmest
+/// temperature and brightness dips towards `min_brightness` around solar midnight.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CircadianCurve {
+    pub warmest_mirek: u16,
+    pub coolest_mirek: u16,
+    /// Brightness percentage at solar midnight.
+    pub min_brightness: f64,
+    /// Brightness percentage during the day.
+    pub max_brightness: f64,
+}
+
+impl Default for CircadianCurve {
+    fn default() -> Self {
+        Self {
+            warmest_mirek: kelvin_to_mirek(2200),
+            coolest_mirek: kelvin_to_mirek(5500),
+            min_brightness: 30.0,
+            max_brightness: 100.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CircadianTarget {
+    pub mirek: u16,
+    /// Brightness percentage (0-100).
+    pub brightness: f64,
+}
+
+impl CircadianTarget {
+    /// Brightness on the bridge's 1-254 scale.
+    pub fn bri(&self) -> u8 {
+        percent_to_bri(self.brightness)
+    }
+}
+
+impl CircadianCurve {
+    pub fn target_at(&self, now: DateTime<Utc>, location: (f64, f64), tz: &Tz) -> CircadianTarget {
+        let (event, progress) = sun_phase(now, location, tz);
+        // 0 at the phase boundaries (sunrise/sunset), 1 at solar noon/midnight.
+        let peak = (PI * progress).sin();
+        let warm = self.warmest_mirek as f64;
+        let cool = self.coolest_mirek as f64;
+        match event {
+            SolarEvent::Sunrise => CircadianTarget {
+                mirek: (warm - peak * (warm - cool)).round() as u16,
+                brightness: self.max_brightness,
+            },
+            SolarEvent::Sunset => CircadianTarget {
+                mirek: self.warmest_mirek,
+                brightness: self.max_brightness
+                    - peak * (self.max_brightness - self.min_brightness),
+            },
+        }
+    }
+}
+
+/// Returns the most recent solar event and how far (0..1) `now` is towards the next one.
+///
+/// During polar day/night the bridge still needs a curve, so the day is assumed to run
+/// from six hours before to six hours after solar noon.
+fn sun_phase(now: DateTime<Utc>, (lat, lon): (f64, f64), tz: &Tz) -> (SolarEvent, f64) {
+    let today = now.with_timezone(tz).date_naive();
+    let mut events = Vec::new();
+    for offset in -1..=1 {
+        let times = sun_times(today + Duration::days(offset), lat, lon);
+        let sunrise = times
+            .sunrise
+            .unwrap_or(times.solar_noon - Duration::hours(6));
+        let sunset = times
+            .sunset
+            .unwrap_or(times.solar_noon + Duration::hours(6));
+        events.push((sunrise, SolarEvent::Sunrise));
+        events.push((sunset, SolarEvent::Sunset));
+    }
+    events.sort_by_key(|(t, _)| *t);
+
+    let next_index = events
+        .iter()
+        .position(|(t, _)| *t > now)
+        .unwrap_or(events.len() - 1)
+        .max(1);
+    let (start, event) = events[next_index - 1];
+    let (end, _) = events[next_index];
+    let span = (end - start).num_seconds().max(1) as f64;
+    let progress = ((now - start).num_seconds() as f64 / span).clamp(0.0, 1.0);
+    (event, progress)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, NaiveDate};
+    use chrono_tz::Tz;
+
+    use super::{CircadianCurve, kelvin_to_mirek, percent_to_bri};
+    use crate::solar::sun_times;
+
+    const AMSTERDAM: (f64, f64) = (52.37, 4.89);
+
+    #[test]
+    fn target_at_solar_noon_is_coolest_and_brightest() {
+        // Arrange
+        let curve = CircadianCurve::default();
+        let times = sun_times(NaiveDate::from_ymd_opt(2024, 6, 1).unwrap(), 52.37, 4.89);
+
+        // Act
+        let target = curve.target_at(times.solar_noon, AMSTERDAM, &Tz::UTC);
+
+        // Assert
+        assert!((target.mirek as i32 - curve.coolest_mirek as i32).abs() <= 2);
+        assert_eq!(100.0, target.brightness);
+    }
+
+    #[test]
+    fn target_at_sunset_is_warmest_and_dims_towards_midnight() {
+        // Arrange
+        let curve = CircadianCurve::default();
+        let times = sun_times(NaiveDate::from_ymd_opt(2024, 6, 1).unwrap(), 52.37, 4.89);
+        let sunset = times.sunset.unwrap();
+
+        // Act
+        let at_sunset = curve.target_at(sunset + Duration::seconds(1), AMSTERDAM, &Tz::UTC);
+        let at_midnight =
+            curve.target_at(times.solar_noon + Duration::hours(12), AMSTERDAM, &Tz::UTC);
+
+        // Assert
+        assert_eq!(curve.warmest_mirek, at_sunset.mirek);
+        assert!(at_sunset.brightness > 99.0);
+        assert_eq!(curve.warmest_mirek, at_midnight.mirek);
+        assert!(at_midnight.brightness < 32.0);
+    }
+
+    #[test]
+    fn unit_conversions_clamp_to_bridge_ranges() {
+        assert_eq!(500, kelvin_to_mirek(2000));
+        assert_eq!(153, kelvin_to_mirek(6500));
+        assert_eq!(254, percent_to_bri(100.0));
+        assert_eq!(1, percent_to_bri(0.0));
+        assert_eq!(127, percent_to_bri(50.0));
+    }
+}
@@ -1,6 +1,53 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
 use crate::error::{CoreError, CoreResult};
 use async_trait::async_trait;
 use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+/// Spacing between requests that keeps the bridge happy; Philips suggests about 10 light
+/// commands per second.
+pub const BRIDGE_REQUEST_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What a redacted URL or recording shows in place of the username.
+pub(crate) const REDACTED_USERNAME: &str = "<username>";
+
+/// Splits a bridge URL into the part up to `/api/`, the username and the resource path below
+/// it, e.g. `lights/3/state`. URLs without a username (like user creation, or the public
+/// `/api/config`) have an empty one.
+pub(crate) fn split_url(url: &str) -> (&str, &str, &str) {
+    match url.find("/api/") {
+        Some(start) => {
+            let (base, rest) = url.split_at(start + "/api/".len());
+            match rest.split_once('/') {
+                _ if rest == "config" => (base, "", rest),
+                Some((username, path)) => (base, username, path),
+                None => (base, rest, ""),
+            }
+        }
+        None => (url, "", ""),
+    }
+}
+
+/// The URL with the username replaced, safe to print or share.
+pub fn redact_url(url: &str) -> String {
+    match split_url(url) {
+        (base, "", path) => format!("{base}{path}"),
+        (base, _, "") => format!("{base}{REDACTED_USERNAME}"),
+        (base, _, path) => format!("{base}{REDACTED_USERNAME}/{path}"),
+    }
+}
+
+/// `text`, e.g. a request body, with the username from `url` replaced, safe to print or share.
+pub fn redact_username(text: &str, url: &str) -> String {
+    match split_url(url) {
+        (_, "", _) => text.to_string(),
+        (_, username, _) => text.replace(username, REDACTED_USERNAME),
+    }
+}
 
 /// Used as a shared structure to provide headers to various implementations of HueClient.
 pub struct Header {
@@ -26,6 +73,7 @@ pub trait HueClient {
     async fn post_json(&self, url: &str, body: &str, headers: &[Header]) -> CoreResult<String>;
     async fn get(&self, url: &str, headers: &[Header]) -> CoreResult<String>;
     async fn put_json(&self, url: &str, body: &str, headers: &[Header]) -> CoreResult<String>;
+    async fn delete(&self, url: &str, headers: &[Header]) -> CoreResult<String>;
 }
 
 pub struct ReqwestHueClient {
@@ -50,6 +98,28 @@ impl ReqwestHueClient {
 
         Ok(map)
     }
+
+    /// Sends the request and reads the response body, tracing the method, URL (without the
+    /// username) and latency.
+    async fn send(
+        method: &'static str,
+        url: &str,
+        request: reqwest::RequestBuilder,
+    ) -> CoreResult<String> {
+        let started = Instant::now();
+        let result = async { request.send().await?.text().await }
+            .await
+            .map_err(CoreError::Network);
+        let latency_ms = started.elapsed().as_millis() as u64;
+        let url = redact_url(url);
+        match &result {
+            Ok(_) => tracing::debug!(method, %url, latency_ms, "bridge request"),
+            Err(err) => {
+                tracing::warn!(method, %url, latency_ms, error = %err, "bridge request failed")
+            }
+        }
+        result
+    }
 }
 
 #[async_trait]
@@ -58,53 +128,122 @@ impl HueClient for ReqwestHueClient {
         // Implementation for sending a POST request with JSON body
 
         let h_map = ReqwestHueClient::header_to_header_map(headers)?;
-        let res = self
-            .client
-            .post(url)
-            .headers(h_map)
-            .body(body.to_string())
-            .send()
-            .await
-            .map_err(CoreError::Network)?;
-
-        res.text().await.map_err(CoreError::Network)
+        let request = self.client.post(url).headers(h_map).body(body.to_string());
+        ReqwestHueClient::send("POST", url, request).await
     }
 
     async fn get(&self, url: &str, headers: &[Header]) -> CoreResult<String> {
         let h_map = ReqwestHueClient::header_to_header_map(headers)?;
-        let res = self
-            .client
-            .get(url)
-            .headers(h_map)
-            .send()
-            .await
-            .map_err(CoreError::Network)?;
-
-        res.text().await.map_err(CoreError::Network)
+        let request = self.client.get(url).headers(h_map);
+        ReqwestHueClient::send("GET", url, request).await
     }
 
     async fn put_json(&self, url: &str, body: &str, headers: &[Header]) -> CoreResult<String> {
         let h_map = ReqwestHueClient::header_to_header_map(headers)?;
-        let res = self
-            .client
-            .put(url)
-            .headers(h_map)
-            .body(body.to_string())
-            .send()
-            .await
-            .map_err(CoreError::Network)?;
+        let request = self.client.put(url).headers(h_map).body(body.to_string());
+        ReqwestHueClient::send("PUT", url, request).await
+    }
+
+    async fn delete(&self, url: &str, headers: &[Header]) -> CoreResult<String> {
+        let h_map = ReqwestHueClient::header_to_header_map(headers)?;
+        let request = self.client.delete(url).headers(h_map);
+        ReqwestHueClient::send("DELETE", url, request).await
+    }
+}
+
+/// Wraps another HueClient and spaces requests to each bridge at least `interval` apart.
+///
+/// Callers queue up in order, so a burst of commands is spread out instead of being dropped by
+/// the bridge. Requests sent concurrently still go out one slot after another, but their
+/// round trips overlap. Each bridge (host) has its own slots.
+pub struct RateLimitedHueClient {
+    inner: Arc<dyn HueClient + Send + Sync>,
+    interval: Duration,
+    next_slots: Mutex<HashMap<String, Instant>>,
+}
+
+impl RateLimitedHueClient {
+    pub fn new(inner: Arc<dyn HueClient + Send + Sync>, interval: Duration) -> Self {
+        Self {
+            inner,
+            interval,
+            next_slots: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Reserves the next free slot for the URL's bridge and sleeps until it comes up.
+    async fn wait_turn(&self, url: &str) {
+        let host = url
+            .split_once("://")
+            .map_or(url, |(_, rest)| rest)
+            .split('/')
+            .next()
+            .unwrap_or_default();
+        let slot = {
+            let mut slots = self.next_slots.lock().await;
+            let now = Instant::now();
+            let next = slots.entry(host.to_string()).or_insert(now);
+            let slot = (*next).max(now);
+            *next = slot + self.interval;
+            slot
+        };
+        tokio::time::sleep_until(slot).await;
+    }
+}
+
+#[async_trait]
+impl HueClient for RateLimitedHueClient {
+    async fn post_json(&self, url: &str, body: &str, headers: &[Header]) -> CoreResult<String> {
+        self.wait_turn(url).await;
+        self.inner.post_json(url, body, headers).await
+    }
+
+    async fn get(&self, url: &str, headers: &[Header]) -> CoreResult<String> {
+        self.wait_turn(url).await;
+        self.inner.get(url, headers).await
+    }
+
+    async fn put_json(&self, url: &str, body: &str, headers: &[Header]) -> CoreResult<String> {
+        self.wait_turn(url).await;
+        self.inner.put_json(url, body, headers).await
+    }
 
-        res.text().await.map_err(CoreError::Network)
+    async fn delete(&self, url: &str, headers: &[Header]) -> CoreResult<String> {
+        self.wait_turn(url).await;
+        self.inner.delete(url, headers).await
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+
     use crate::{
-        client::{self, Header},
-        error::CoreError,
+        client::{self, Header, HueClient, RateLimitedHueClient, redact_url},
+        error::{CoreError, CoreResult},
     };
 
+    struct EchoClient;
+
+    #[async_trait]
+    impl HueClient for EchoClient {
+        async fn post_json(&self, url: &str, _: &str, _: &[Header]) -> CoreResult<String> {
+            Ok(url.to_string())
+        }
+        async fn get(&self, url: &str, _: &[Header]) -> CoreResult<String> {
+            Ok(url.to_string())
+        }
+        async fn put_json(&self, url: &str, _: &str, _: &[Header]) -> CoreResult<String> {
+            Ok(url.to_string())
+        }
+        async fn delete(&self, url: &str, _: &[Header]) -> CoreResult<String> {
+            Ok(url.to_string())
+        }
+    }
+
     #[test]
     fn header_to_header_map_invalid_header_name_gives_invalid_name_error() {
         // Arrange
@@ -158,4 +297,65 @@ mod tests {
         let hv = val.unwrap().to_str().unwrap();
         assert_eq!(hv, h_value);
     }
+
+    #[tokio::test]
+    async fn rate_limited_client_spaces_requests_apart() {
+        // Arrange
+        let client = RateLimitedHueClient::new(Arc::new(EchoClient), Duration::from_millis(20));
+        let started = std::time::Instant::now();
+
+        // Act
+        for _ in 0..3 {
+            client.put_json("url", "{}", &[]).await.unwrap();
+        }
+
+        // Assert
+        // The first request goes out immediately, the next two wait a slot each.
+        assert!(started.elapsed() >= Duration::from_millis(40));
+    }
+
+    #[tokio::test]
+    async fn rate_limited_client_gives_each_bridge_its_own_slots() {
+        // Arrange
+        let client = RateLimitedHueClient::new(Arc::new(EchoClient), Duration::from_millis(200));
+        let started = std::time::Instant::now();
+
+        // Act
+        client
+            .get("http://10.0.0.2/api/user/lights", &[])
+            .await
+            .unwrap();
+        client
+            .get("http://10.0.0.3/api/user/lights", &[])
+            .await
+            .unwrap();
+
+        // Assert
+        assert!(started.elapsed() < Duration::from_millis(200));
+    }
+
+    #[test]
+    pub fn redact_url_hides_username() {
+        assert_eq!(
+            redact_url("http://10.0.0.2/api/secret/lights/3/state"),
+            "http://10.0.0.2/api/<username>/lights/3/state"
+        );
+        assert_eq!(
+            redact_url("http://10.0.0.2/api/secret"),
+            "http://10.0.0.2/api/<username>"
+        );
+        assert_eq!(redact_url("http://10.0.0.2/api/"), "http://10.0.0.2/api/");
+    }
+
+    #[test]
+    pub fn redact_url_keeps_unauthenticated_config_path() {
+        assert_eq!(
+            redact_url("http://10.0.0.2/api/config"),
+            "http://10.0.0.2/api/config"
+        );
+        assert_eq!(
+            redact_url("http://10.0.0.2/api/secret/config"),
+            "http://10.0.0.2/api/<username>/config"
+        );
+    }
 }
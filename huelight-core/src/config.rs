@@ -1,9 +1,12 @@
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use tokio::fs;
 
-use crate::error::{ConfigError, CoreError};
+use crate::domain::named_colors::ColorRegistry;
+use crate::error::{ConfigError, CoreError, ParseError};
 use crate::logger::ILogger;
+use crate::routine::{CurvePoint, default_wake_curve};
 
 pub trait FileHandler {
     fn read_file(
@@ -19,6 +22,10 @@ pub trait FileHandler {
         &self,
         path: &Path,
     ) -> impl std::future::Future<Output = Result<(), CoreError>> + Send;
+    fn remove_file(
+        &self,
+        path: &str,
+    ) -> impl std::future::Future<Output = Result<(), CoreError>> + Send;
 }
 
 #[derive(Default)]
@@ -42,12 +49,51 @@ impl FileHandler for TokioFileHandler {
             .await
             .map_err(CoreError::FileHandlerError)
     }
+
+    async fn remove_file(&self, path: &str) -> Result<(), CoreError> {
+        fs::remove_file(path)
+            .await
+            .map_err(CoreError::FileHandlerError)
+    }
+}
+
+/// Directory that holds the config file and any other state huelightcli persists.
+pub fn config_dir() -> Result<PathBuf, CoreError> {
+    Ok(dirs::config_dir()
+        .ok_or_else(|| CoreError::Config(ConfigError::ConfigDirectoryNotFoundError))?
+        .join("huelightcli"))
+}
+
+/// Helper to turn a path inside the config directory into the `&str` a FileHandler expects.
+pub fn path_to_str(path: &Path) -> Result<&str, CoreError> {
+    path.to_str()
+        .ok_or_else(|| CoreError::Config(ConfigError::ConfigPathInvalidError))
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct Config {
     pub bridge_ip: String,
     pub username: String,
+    /// Latitude in degrees (north positive), used for sunrise/sunset calculations.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latitude: Option<f64>,
+    /// Longitude in degrees (east positive), used for sunrise/sunset calculations.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub longitude: Option<f64>,
+    /// IANA timezone name (e.g. "Europe/Amsterdam"). Falls back to the system timezone.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    /// Named colors, e.g. `"sunset": "#ff7f50"`. Values may be built-in color names,
+    /// `#rrggbb`, kelvin like `2700K` or `xy:0.4,0.4`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub colors: BTreeMap<String, String>,
+    /// Named lists of colors to spread across several lights, e.g. `"beach": ["coral", "turquoise"]`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub palettes: BTreeMap<String, Vec<String>>,
+    /// Points of the `routine wake` ramp, e.g. `{ at = 0.0, brightness = "1%", color = "red" }`.
+    /// The built-in sunrise curve is used when empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub wake_curve: Vec<CurvePoint>,
 }
 
 impl Config {
@@ -55,17 +101,35 @@ impl Config {
         Config {
             bridge_ip,
             username,
+            ..Default::default()
+        }
+    }
+
+    /// The built-in color names plus the colors and palettes defined in this config.
+    pub fn color_registry(&self) -> Result<ColorRegistry, ParseError> {
+        ColorRegistry::with_user_colors(&self.colors, &self.palettes)
+    }
+
+    /// The configured wake-up curve, or the built-in one.
+    pub fn wake_curve(&self) -> Vec<CurvePoint> {
+        if self.wake_curve.is_empty() {
+            default_wake_curve()
+        } else {
+            self.wake_curve.clone()
         }
     }
 
+    /// Returns the configured location as (latitude, longitude) if both are set.
+    pub fn location(&self) -> Option<(f64, f64)> {
+        self.latitude.zip(self.longitude)
+    }
+
     pub async fn save(
         &self,
-        logger: &dyn ILogger,
+        logger: &(dyn ILogger + Sync),
         file_handler: &impl FileHandler,
     ) -> Result<(), CoreError> {
-        let config_dir = dirs::config_dir()
-            .ok_or_else(|| CoreError::Config(ConfigError::ConfigDirectoryNotFoundError))?
-            .join("huelightcli");
+        let config_dir = config_dir()?;
 
         // Create the directory
         file_handler
@@ -73,25 +137,20 @@ impl Config {
             .await
             .map_err(|err| {
                 let error_message = format!("Failed to create config directory: {:?}", err);
-                logger.log(error_message.as_str());
+                logger.error(error_message.as_str());
                 CoreError::Config(ConfigError::ConfigDirectoryCreateError)
             })?;
 
         // Make sure we can serialize the config
         let config_path = config_dir.join("config.json");
         let config_json = serde_json::to_string(self).map_err(|err| {
-            logger.log(format!("Failed to serialize config: {:?}", err).as_str());
+            logger.error(format!("Failed to serialize config: {:?}", err).as_str());
             CoreError::Serialization(err)
         })?;
 
         // Write the config file using the serialized config
         file_handler
-            .write_file(
-                config_path
-                    .to_str()
-                    .ok_or_else(|| CoreError::Config(ConfigError::ConfigPathInvalidError))?,
-                config_json.as_str(),
-            )
+            .write_file(path_to_str(&config_path)?, config_json.as_str())
             .await?;
 
         logger.log(
@@ -106,16 +165,8 @@ impl Config {
     }
 
     pub async fn load(file_handler: &impl FileHandler) -> Result<Config, CoreError> {
-        let config_dir = dirs::config_dir()
-            .ok_or_else(|| CoreError::Config(ConfigError::ConfigDirectoryNotFoundError))?
-            .join("huelightcli");
-        let path = config_dir.join("config.json");
-        let config_json = file_handler
-            .read_file(
-                path.to_str()
-                    .ok_or_else(|| CoreError::Config(ConfigError::ConfigPathInvalidError))?,
-            )
-            .await?;
+        let path = config_dir()?.join("config.json");
+        let config_json = file_handler.read_file(path_to_str(&path)?).await?;
         serde_json::from_str(config_json.as_str()).map_err(CoreError::Serialization)
     }
 }
@@ -152,6 +203,10 @@ mod tests {
             async fn create_dir_all(&self, _path: &Path) -> Result<(), CoreError> {
                 Ok(())
             }
+
+            async fn remove_file(&self, _path: &str) -> Result<(), CoreError> {
+                Ok(())
+            }
         }
 
         // Act
@@ -186,6 +241,10 @@ mod tests {
             async fn create_dir_all(&self, _path: &Path) -> Result<(), CoreError> {
                 Ok(())
             }
+
+            async fn remove_file(&self, _path: &str) -> Result<(), CoreError> {
+                Ok(())
+            }
         }
 
         // Act
@@ -220,6 +279,10 @@ mod tests {
                     "create directory error".to_string(),
                 ))
             }
+
+            async fn remove_file(&self, _path: &str) -> Result<(), CoreError> {
+                Ok(())
+            }
         }
 
         // Act
@@ -250,6 +313,10 @@ mod tests {
             async fn create_dir_all(&self, _path: &Path) -> Result<(), CoreError> {
                 Ok(())
             }
+
+            async fn remove_file(&self, _path: &str) -> Result<(), CoreError> {
+                Ok(())
+            }
         }
 
         // Act
@@ -260,6 +327,38 @@ mod tests {
         assert_eq!(_result.username, "user");
     }
 
+    #[tokio::test]
+    async fn load_config_with_location_expect_location() {
+        // Arrange
+        #[derive(Default)]
+        struct MockFileHandler;
+
+        impl FileHandler for MockFileHandler {
+            async fn read_file(&self, _path: &str) -> Result<String, CoreError> {
+                Ok("{ \"bridge_ip\": \"192.168.1.1\", \"username\": \"user\", \"latitude\": 52.37, \"longitude\": 4.89 }".to_string())
+            }
+
+            async fn write_file(&self, _path: &str, _content: &str) -> Result<(), CoreError> {
+                Ok(())
+            }
+
+            async fn create_dir_all(&self, _path: &Path) -> Result<(), CoreError> {
+                Ok(())
+            }
+
+            async fn remove_file(&self, _path: &str) -> Result<(), CoreError> {
+                Ok(())
+            }
+        }
+
+        // Act
+        let result = Config::load(&MockFileHandler).await.unwrap();
+
+        // Assert
+        assert_eq!(Some((52.37, 4.89)), result.location());
+        assert_eq!(None, result.timezone);
+    }
+
     #[tokio::test]
     async fn load_config_fail_expect_serialization_error() {
         // Arrange
@@ -281,6 +380,10 @@ mod tests {
             async fn create_dir_all(&self, _path: &Path) -> Result<(), CoreError> {
                 Ok(())
             }
+
+            async fn remove_file(&self, _path: &str) -> Result<(), CoreError> {
+                Ok(())
+            }
         }
 
         // Act
@@ -0,0 +1,212 @@
+use std::str::FromStr;
+
+use crate::error::ParseError;
+
+/// Brightness as a percentage of what a light can do, from 0 (its minimum, not off) to 100.
+///
+/// The v1 API uses a 1-254 `bri` scale and v2 uses a percentage; this type sits between them
+/// so callers never have to know which one they are talking to.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Brightness(f64);
+
+impl Brightness {
+    pub const MIN: Brightness = Brightness(0.0);
+    pub const MAX: Brightness = Brightness(100.0);
+
+    /// Creates a brightness, clamping `percent` into 0-100.
+    pub fn from_percent(percent: f64) -> Self {
+        Brightness(if percent.is_nan() {
+            0.0
+        } else {
+            percent.clamp(0.0, 100.0)
+        })
+    }
+
+    /// Converts from the bridge's native 1-254 `bri` scale.
+    pub fn from_bri(bri: u8) -> Self {
+        Self::from_percent(bri as f64 / 2.54)
+    }
+
+    pub fn percent(self) -> f64 {
+        self.0
+    }
+
+    /// The bridge's native 1-254 `bri` value.
+    pub fn bri(self) -> u8 {
+        (self.0 * 2.54).round().clamp(1.0, 254.0) as u8
+    }
+}
+
+impl std::fmt::Display for Brightness {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.0}%", self.0)
+    }
+}
+
+/// A brightness as a user types it, either absolute or relative to the light's current level.
+///
+/// Accepted forms, all clamped into range rather than rejected:
+///
+/// - `50%`: a percentage from 0 to 100.
+/// - `0.5`: a fraction from 0.0 to 1.0. Numbers without a decimal point are percentages, so
+///   `1` is 1% and `1.0` is 100%.
+/// - `bri:127`: the bridge's native 1-254 scale.
+/// - `+10%` / `-10%`: percentage points up or down from the current brightness.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BrightnessValue {
+    Absolute(Brightness),
+    /// Percentage points to add to the current brightness.
+    Relative(f64),
+}
+
+impl BrightnessValue {
+    /// The brightness to set on a light currently at `current`. Lights that don't report a
+    /// brightness count as being at their minimum.
+    pub fn resolve(self, current: Option<Brightness>) -> Brightness {
+        match self {
+            BrightnessValue::Absolute(brightness) => brightness,
+            BrightnessValue::Relative(delta) => {
+                Brightness::from_percent(current.unwrap_or(Brightness::MIN).percent() + delta)
+            }
+        }
+    }
+
+    /// The brightness if it doesn't depend on the current one.
+    pub fn absolute(self) -> Option<Brightness> {
+        match self {
+            BrightnessValue::Absolute(brightness) => Some(brightness),
+            BrightnessValue::Relative(_) => None,
+        }
+    }
+}
+
+impl std::fmt::Display for BrightnessValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BrightnessValue::Absolute(brightness) => write!(f, "{}", brightness),
+            BrightnessValue::Relative(delta) => write!(f, "{:+}%", delta),
+        }
+    }
+}
+
+impl FromStr for BrightnessValue {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ParseError::InvalidBrightness(s.to_string());
+        let text = s.trim();
+        let number = |text: &str| {
+            text.parse::<f64>()
+                .ok()
+                .filter(|n| n.is_finite())
+                .ok_or_else(invalid)
+        };
+
+        if let Some(bri) = text.strip_prefix("bri:") {
+            let bri: u32 = bri.trim().parse().map_err(|_| invalid())?;
+            return Ok(BrightnessValue::Absolute(Brightness::from_bri(
+                bri.clamp(1, 254) as u8,
+            )));
+        }
+        if let Some(sign) = text.chars().next().filter(|c| *c == '+' || *c == '-') {
+            let delta = text[1..].strip_suffix('%').ok_or_else(invalid)?;
+            let delta = number(delta)?;
+            return Ok(BrightnessValue::Relative(if sign == '-' {
+                -delta
+            } else {
+                delta
+            }));
+        }
+        let percent = match text.strip_suffix('%') {
+            Some(percent) => number(percent)?,
+            None if text.contains('.') => number(text)? * 100.0,
+            None => number(text)?,
+        };
+        Ok(BrightnessValue::Absolute(Brightness::from_percent(percent)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::brightness::{Brightness, BrightnessValue};
+
+    #[test]
+    pub fn brightness_round_trips_every_bri_value() {
+        for bri in 1..=254u8 {
+            assert_eq!(bri, Brightness::from_bri(bri).bri());
+        }
+    }
+
+    #[test]
+    pub fn brightness_from_percent_clamps() {
+        // Act / Assert
+        assert_eq!(Brightness::MAX, Brightness::from_percent(150.0));
+        assert_eq!(Brightness::MIN, Brightness::from_percent(-3.0));
+        assert_eq!(1, Brightness::MIN.bri());
+        assert_eq!("50%", Brightness::from_percent(50.0).to_string());
+    }
+
+    #[test]
+    pub fn brightness_value_parses_every_form() {
+        // Arrange
+        let cases = [
+            (
+                "50%",
+                BrightnessValue::Absolute(Brightness::from_percent(50.0)),
+            ),
+            (
+                "0.5",
+                BrightnessValue::Absolute(Brightness::from_percent(50.0)),
+            ),
+            (
+                "75",
+                BrightnessValue::Absolute(Brightness::from_percent(75.0)),
+            ),
+            (
+                "bri:127",
+                BrightnessValue::Absolute(Brightness::from_bri(127)),
+            ),
+            ("bri:0", BrightnessValue::Absolute(Brightness::from_bri(1))),
+            ("150%", BrightnessValue::Absolute(Brightness::MAX)),
+            ("+10%", BrightnessValue::Relative(10.0)),
+            ("-12.5%", BrightnessValue::Relative(-12.5)),
+        ];
+
+        for (text, expected) in cases {
+            // Act
+            let parsed: BrightnessValue = text.parse().unwrap();
+
+            // Assert
+            assert_eq!(expected, parsed, "{text}");
+        }
+    }
+
+    #[test]
+    pub fn brightness_value_parse_invalid_expect_error() {
+        for text in ["", "bright", "bri:", "bri:-1", "+10", "50%%", "NaN%"] {
+            assert!(text.parse::<BrightnessValue>().is_err(), "{text}");
+        }
+    }
+
+    #[test]
+    pub fn brightness_value_relative_resolves_against_current_and_clamps() {
+        // Arrange
+        let up = BrightnessValue::Relative(10.0);
+        let down = BrightnessValue::Relative(-30.0);
+
+        // Act / Assert
+        assert_eq!(
+            Brightness::from_percent(60.0),
+            up.resolve(Some(Brightness::from_percent(50.0)))
+        );
+        assert_eq!(
+            Brightness::MAX,
+            up.resolve(Some(Brightness::from_percent(95.0)))
+        );
+        assert_eq!(
+            Brightness::MIN,
+            down.resolve(Some(Brightness::from_percent(20.0)))
+        );
+        assert_eq!(None, down.absolute());
+    }
+}
@@ -0,0 +1,236 @@
+/// A light color in one of the three ways Hue lights can be driven.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Color {
+    /// CIE 1931 xy chromaticity.
+    Xy { x: f64, y: f64 },
+    /// White light in mirek (153 is 6500K, 500 is 2000K).
+    ColorTemperature { mirek: u16 },
+    /// Hue in degrees (0-360) and saturation as a percentage.
+    HueSaturation { hue: f64, saturation: f64 },
+}
+
+/// D65, what lights show for pure white.
+const WHITE_POINT: [f64; 2] = [0.3127, 0.3290];
+
+impl Color {
+    pub fn xy(x: f64, y: f64) -> Self {
+        Color::Xy { x, y }
+    }
+
+    pub fn from_kelvin(kelvin: u32) -> Self {
+        Color::ColorTemperature {
+            mirek: (1_000_000 / kelvin.max(1)).min(u16::MAX as u32) as u16,
+        }
+    }
+
+    /// Converts an sRGB color to xy using the wide gamut conversion Philips documents.
+    pub fn from_rgb(red: u8, green: u8, blue: u8) -> Self {
+        let linear = |c: u8| {
+            let c = c as f64 / 255.0;
+            if c > 0.04045 {
+                ((c + 0.055) / 1.055).powf(2.4)
+            } else {
+                c / 12.92
+            }
+        };
+        let (r, g, b) = (linear(red), linear(green), linear(blue));
+        let x = r * 0.664511 + g * 0.154324 + b * 0.162028;
+        let y = r * 0.283881 + g * 0.668433 + b * 0.047685;
+        let z = r * 0.000088 + g * 0.072310 + b * 0.986039;
+        let sum = x + y + z;
+        if sum == 0.0 {
+            return Color::xy(WHITE_POINT[0], WHITE_POINT[1]);
+        }
+        Color::xy(x / sum, y / sum)
+    }
+
+    /// The xy coordinates of this color, for APIs that only accept xy.
+    pub fn to_xy(&self) -> [f64; 2] {
+        match *self {
+            Color::Xy { x, y } => [x, y],
+            Color::ColorTemperature { mirek } => kelvin_to_xy(1_000_000.0 / mirek.max(1) as f64),
+            Color::HueSaturation { hue, saturation } => {
+                let [r, g, b] = hsv_to_rgb(hue, saturation / 100.0);
+                match Color::from_rgb(r, g, b) {
+                    Color::Xy { x, y } => [x, y],
+                    _ => WHITE_POINT,
+                }
+            }
+        }
+    }
+}
+
+/// Full-value HSV to 8-bit RGB.
+fn hsv_to_rgb(hue: f64, saturation: f64) -> [u8; 3] {
+    let h = hue.rem_euclid(360.0) / 60.0;
+    let s = saturation.clamp(0.0, 1.0);
+    let x = 1.0 - (h % 2.0 - 1.0).abs();
+    let (r, g, b) = match h as u32 {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    };
+    let channel = |c: f64| ((1.0 - s + s * c) * 255.0).round() as u8;
+    [channel(r), channel(g), channel(b)]
+}
+
+/// Approximates the Planckian locus (Kim et al.), valid from 1667K to 25000K.
+fn kelvin_to_xy(kelvin: f64) -> [f64; 2] {
+    let t = kelvin.clamp(1667.0, 25000.0);
+    let x = if t <= 4000.0 {
+        -0.2661239e9 / t.powi(3) - 0.2343589e6 / t.powi(2) + 0.8776956e3 / t + 0.179910
+    } else {
+        -3.0258469e9 / t.powi(3) + 2.1070379e6 / t.powi(2) + 0.2226347e3 / t + 0.240390
+    };
+    let y = if t <= 2222.0 {
+        -1.1063814 * x.powi(3) - 1.34811020 * x.powi(2) + 2.18555832 * x - 0.20219683
+    } else if t <= 4000.0 {
+        -0.9549476 * x.powi(3) - 1.37418593 * x.powi(2) + 2.09137015 * x - 0.16748867
+    } else {
+        3.0817580 * x.powi(3) - 5.87338670 * x.powi(2) + 3.75112997 * x - 0.37001483
+    };
+    [x, y]
+}
+
+impl std::fmt::Display for Color {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Color::Xy { x, y } => write!(f, "xy({:.4}, {:.4})", x, y),
+            Color::ColorTemperature { mirek } => {
+                write!(f, "{}K", 1_000_000 / (*mirek).max(1) as u32)
+            }
+            Color::HueSaturation { hue, saturation } => {
+                write!(f, "hue {:.0}°, saturation {:.0}%", hue, saturation)
+            }
+        }
+    }
+}
+
+/// Supported color temperatures in mirek.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CtRange {
+    pub min: u16,
+    pub max: u16,
+}
+
+/// A triangle in the CIE xy color space that a light can reproduce.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gamut {
+    pub red: [f64; 2],
+    pub green: [f64; 2],
+    pub blue: [f64; 2],
+}
+
+impl Gamut {
+    pub const A: Gamut = Gamut {
+        red: [0.704, 0.296],
+        green: [0.2151, 0.7106],
+        blue: [0.138, 0.08],
+    };
+    pub const B: Gamut = Gamut {
+        red: [0.675, 0.322],
+        green: [0.409, 0.518],
+        blue: [0.167, 0.04],
+    };
+    pub const C: Gamut = Gamut {
+        red: [0.6915, 0.3083],
+        green: [0.17, 0.7],
+        blue: [0.1532, 0.0475],
+    };
+
+    /// The gamut for a Hue gamut letter ("A", "B" or "C").
+    pub fn from_type(gamut_type: &str) -> Option<Gamut> {
+        match gamut_type {
+            "A" => Some(Gamut::A),
+            "B" => Some(Gamut::B),
+            "C" => Some(Gamut::C),
+            _ => None,
+        }
+    }
+
+    pub fn contains(&self, xy: [f64; 2]) -> bool {
+        // The point is inside if it lies on the same side of all three edges.
+        let side = |a: [f64; 2], b: [f64; 2]| {
+            (b[0] - a[0]) * (xy[1] - a[1]) - (b[1] - a[1]) * (xy[0] - a[0])
+        };
+        let d1 = side(self.red, self.green);
+        let d2 = side(self.green, self.blue);
+        let d3 = side(self.blue, self.red);
+        // Points on an edge (e.g. from `closest`) count as inside despite rounding.
+        const EPSILON: f64 = 1e-9;
+        let has_neg = d1 < -EPSILON || d2 < -EPSILON || d3 < -EPSILON;
+        let has_pos = d1 > EPSILON || d2 > EPSILON || d3 > EPSILON;
+        !(has_neg && has_pos)
+    }
+
+    /// The point in the gamut closest to `xy`, which is `xy` itself if it is inside.
+    pub fn closest(&self, xy: [f64; 2]) -> [f64; 2] {
+        if self.contains(xy) {
+            return xy;
+        }
+        let closest_on_edge = |a: [f64; 2], b: [f64; 2]| {
+            let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+            let t =
+                (((xy[0] - a[0]) * dx + (xy[1] - a[1]) * dy) / (dx * dx + dy * dy)).clamp(0.0, 1.0);
+            [a[0] + t * dx, a[1] + t * dy]
+        };
+        let distance = |p: [f64; 2]| (p[0] - xy[0]).powi(2) + (p[1] - xy[1]).powi(2);
+        [
+            closest_on_edge(self.red, self.green),
+            closest_on_edge(self.green, self.blue),
+            closest_on_edge(self.blue, self.red),
+        ]
+        .into_iter()
+        .min_by(|a, b| distance(*a).total_cmp(&distance(*b)))
+        .unwrap_or(xy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::color::{Color, Gamut};
+
+    fn assert_close(expected: [f64; 2], actual: [f64; 2]) {
+        assert!(
+            (expected[0] - actual[0]).abs() < 0.005 && (expected[1] - actual[1]).abs() < 0.005,
+            "expected {expected:?}, got {actual:?}"
+        );
+    }
+
+    #[test]
+    pub fn color_from_rgb_primaries_expect_known_xy() {
+        // Act / Assert
+        assert_close([0.7006, 0.2993], Color::from_rgb(255, 0, 0).to_xy());
+        assert_close([0.1724, 0.7468], Color::from_rgb(0, 255, 0).to_xy());
+        assert_close([0.3227, 0.3290], Color::from_rgb(255, 255, 255).to_xy());
+    }
+
+    #[test]
+    pub fn color_to_xy_converts_ct_and_hs() {
+        // Act
+        let warm = Color::from_kelvin(2700).to_xy();
+        let red = Color::HueSaturation {
+            hue: 0.0,
+            saturation: 100.0,
+        }
+        .to_xy();
+
+        // Assert
+        assert_close([0.4599, 0.4106], warm);
+        assert_close(Color::from_rgb(255, 0, 0).to_xy(), red);
+    }
+
+    #[test]
+    pub fn gamut_closest_keeps_points_inside() {
+        // Arrange
+        let inside = [0.4, 0.4];
+
+        // Act / Assert
+        assert_eq!(inside, Gamut::C.closest(inside));
+        assert!(!Gamut::C.contains([0.8, 0.1]));
+        assert!(Gamut::C.contains(Gamut::C.closest([0.8, 0.1])));
+    }
+}
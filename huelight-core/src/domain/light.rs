@@ -0,0 +1,595 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::domain::brightness::Brightness;
+use crate::domain::color::{Color, CtRange, Gamut};
+use crate::error::{CoreError, CoreResult};
+use crate::models::{light as v1, light_v2 as v2};
+
+pub type LightId = u32;
+
+/// All lights known to the bridge by ID.
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+pub struct Lights(pub HashMap<LightId, Light>);
+
+impl Lights {
+    /// Finds a light by its numeric ID or, failing that, by a case-insensitive name match.
+    pub fn find(&self, id_or_name: &str) -> Option<(LightId, &Light)> {
+        if let Ok(id) = id_or_name.parse::<LightId>() {
+            return self.0.get(&id).map(|l| (id, l));
+        }
+        self.0
+            .iter()
+            .find(|(_, l)| l.name.eq_ignore_ascii_case(id_or_name))
+            .map(|(id, l)| (*id, l))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum LightKind {
+    OnOff,
+    Dimmable,
+    ColorTemperature,
+    Color,
+    #[default]
+    ExtendedColor,
+    /// A type this crate doesn't know, with the name the bridge reported.
+    Other(String),
+}
+
+impl std::fmt::Display for LightKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            LightKind::OnOff => "On/Off light",
+            LightKind::Dimmable => "Dimmable light",
+            LightKind::ColorTemperature => "Color temperature light",
+            LightKind::Color => "Color light",
+            LightKind::ExtendedColor => "Extended color light",
+            LightKind::Other(name) => name,
+        })
+    }
+}
+
+/// Product details of a light. Older bridges and some third-party lights omit them.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct LightInfo {
+    pub model_id: Option<String>,
+    pub manufacturer: Option<String>,
+    pub product_name: Option<String>,
+    pub unique_id: Option<String>,
+    pub software_version: Option<String>,
+}
+
+/// What a light can do, from its reported capabilities or, when those are missing, its kind.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LightFeatures {
+    pub dimmable: bool,
+    pub color: bool,
+    /// Known for color lights that report capabilities or a gamut type.
+    pub gamut: Option<Gamut>,
+    pub ct_range: Option<CtRange>,
+    /// Whether the light can take part in entertainment streaming.
+    pub streaming: bool,
+}
+
+/// Range most Hue white ambiance lights support, used when a light doesn't report its own.
+pub(crate) const DEFAULT_CT_RANGE: CtRange = CtRange { min: 153, max: 500 };
+
+impl LightFeatures {
+    /// Features implied by a light's kind alone.
+    pub fn for_kind(kind: &LightKind) -> Self {
+        let (dimmable, color, ct) = match kind {
+            LightKind::OnOff | LightKind::Other(_) => (false, false, false),
+            LightKind::Dimmable => (true, false, false),
+            LightKind::ColorTemperature => (true, false, true),
+            LightKind::Color => (true, true, false),
+            LightKind::ExtendedColor => (true, true, true),
+        };
+        LightFeatures {
+            dimmable,
+            color,
+            gamut: None,
+            ct_range: ct.then_some(DEFAULT_CT_RANGE),
+            streaming: false,
+        }
+    }
+}
+
+/// A light as this crate sees it, whichever API version it came from.
+///
+/// Deserializes from the v1 light object returned by `GET /lights/<id>`.
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(from = "v1::Light")]
+pub struct Light {
+    pub name: String,
+    pub kind: LightKind,
+    pub state: LightState,
+    /// Whether the bridge can currently reach the light.
+    pub reachable: bool,
+    pub features: LightFeatures,
+    pub info: LightInfo,
+}
+
+impl Light {
+    /// Parses a light resource as the v2 API returns it from `GET /clip/v2/resource/light/<id>`.
+    pub fn from_v2_json(json: Value) -> CoreResult<Light> {
+        let dto: v2::Light = serde_json::from_value(json).map_err(CoreError::Serialization)?;
+        Ok(dto.into())
+    }
+
+    /// Checks a requested state against the light's features and clamps values into range.
+    ///
+    /// Color lights without color temperature support get the matching xy color instead. Asking
+    /// for something the light can't do at all, such as a color on a white bulb, is an error
+    /// rather than something to silently drop.
+    pub fn fit_state(&self, state: &LightState) -> CoreResult<LightState> {
+        let unsupported = |feature: &str| CoreError::Unsupported {
+            light: self.name.clone(),
+            feature: feature.to_string(),
+        };
+        let mut fitted = state.clone();
+
+        if fitted.brightness.is_some() && !self.features.dimmable {
+            return Err(unsupported("brightness"));
+        }
+        if fitted.effect == Some(Effect::ColorLoop) && !self.features.color {
+            return Err(unsupported("color"));
+        }
+        fitted.color = match fitted.color {
+            None => None,
+            Some(Color::ColorTemperature { mirek }) => match self.features.ct_range {
+                Some(range) => Some(Color::ColorTemperature {
+                    mirek: mirek.clamp(range.min, range.max),
+                }),
+                None if self.features.color => {
+                    let [x, y] = Color::ColorTemperature { mirek }.to_xy();
+                    let [x, y] = self.features.gamut.map_or([x, y], |g| g.closest([x, y]));
+                    Some(Color::Xy { x, y })
+                }
+                None => return Err(unsupported("color temperature")),
+            },
+            Some(_) if !self.features.color => return Err(unsupported("color")),
+            Some(Color::Xy { x, y }) => {
+                let xy = [x.clamp(0.0, 1.0), y.clamp(0.0, 1.0)];
+                let [x, y] = self.features.gamut.map_or(xy, |gamut| gamut.closest(xy));
+                Some(Color::Xy { x, y })
+            }
+            Some(Color::HueSaturation { hue, saturation }) => Some(Color::HueSaturation {
+                hue: hue.rem_euclid(360.0),
+                saturation: saturation.clamp(0.0, 100.0),
+            }),
+        };
+        Ok(fitted)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Effect {
+    None,
+    /// Cycle through all hues, keeping brightness and saturation.
+    ColorLoop,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Alert {
+    None,
+    /// Breathe once.
+    Breathe,
+    /// Breathe for 15 seconds.
+    LongBreathe,
+}
+
+/// A light's state as reported, or the changes to make to it.
+///
+/// Every field is optional; `None` leaves that part of the light alone when sending. Serializes
+/// in the v1 format, which is also what snapshots, schedule files and group actions store.
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(from = "v1::LightState", into = "v1::LightState")]
+pub struct LightState {
+    pub on: Option<bool>,
+    pub brightness: Option<Brightness>,
+    pub color: Option<Color>,
+    pub effect: Option<Effect>,
+    pub alert: Option<Alert>,
+    /// How long the light takes to get to the new state. Only meaningful when sending.
+    pub transition: Option<Duration>,
+}
+
+impl LightState {
+    pub fn with_on(mut self, on: bool) -> Self {
+        self.on = Some(on);
+        self
+    }
+
+    pub fn with_brightness(mut self, brightness: Brightness) -> Self {
+        self.brightness = Some(brightness);
+        self
+    }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = Some(color);
+        self
+    }
+
+    pub fn with_effect(mut self, effect: Effect) -> Self {
+        self.effect = Some(effect);
+        self
+    }
+
+    pub fn with_alert(mut self, alert: Alert) -> Self {
+        self.alert = Some(alert);
+        self
+    }
+
+    pub fn with_transition(mut self, transition: Duration) -> Self {
+        self.transition = Some(transition);
+        self
+    }
+
+    /// The subset of a reported state that reproduces it when sent back to the bridge.
+    ///
+    /// Lights that are off only get `on: false`, because the bridge rejects other changes to
+    /// lights that are off.
+    pub fn restorable(&self) -> LightState {
+        if self.on == Some(false) {
+            return LightState::default().with_on(false);
+        }
+        LightState {
+            on: self.on,
+            brightness: self.brightness,
+            color: self.color,
+            effect: self.effect,
+            alert: None,
+            transition: None,
+        }
+    }
+
+    /// The part of this requested state that would change a light currently in `current`.
+    ///
+    /// Brightness and color are compared at the precision the bridge reports them. Alerts are
+    /// actions rather than state, so they are always kept, and the transition is kept for
+    /// whatever is left.
+    pub fn delta(&self, current: &LightState) -> LightState {
+        LightState {
+            on: self.on.filter(|on| current.on != Some(*on)),
+            brightness: self.brightness.filter(|brightness| {
+                current
+                    .brightness
+                    .is_none_or(|current| current.bri() != brightness.bri())
+            }),
+            color: self.color.filter(|color| {
+                current
+                    .color
+                    .is_none_or(|current| !same_color(color, &current))
+            }),
+            effect: self.effect.filter(|effect| current.effect != Some(*effect)),
+            alert: self.alert,
+            transition: self.transition,
+        }
+    }
+
+    /// Whether sending this state would change nothing on the light.
+    pub fn is_no_op(&self) -> bool {
+        self.on.is_none()
+            && self.brightness.is_none()
+            && self.color.is_none()
+            && self.effect.is_none()
+            && self.alert.is_none()
+    }
+
+    /// The v2 JSON body for `PUT /clip/v2/resource/light/<id>`.
+    ///
+    /// v2 has no hue/saturation mode, so those colors are sent as xy, and no color loop.
+    pub fn to_v2_json(&self) -> CoreResult<Value> {
+        let dto = v2::LightUpdate::try_from(self)?;
+        serde_json::to_value(dto).map_err(CoreError::Serialization)
+    }
+}
+
+impl std::fmt::Display for LightState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(on) = self.on {
+            parts.push(if on { "on" } else { "off" }.to_string());
+        }
+        if let Some(brightness) = self.brightness {
+            parts.push(format!("brightness {}", brightness));
+        }
+        if let Some(color) = self.color {
+            parts.push(color.to_string());
+        }
+        if self.effect == Some(Effect::ColorLoop) {
+            parts.push("color loop".to_string());
+        }
+        match self.alert {
+            Some(Alert::Breathe) => parts.push("breathe".to_string()),
+            Some(Alert::LongBreathe) => parts.push("breathe 15s".to_string()),
+            _ => {}
+        }
+        if let Some(transition) = self.transition {
+            parts.push(format!("over {}ms", transition.as_millis()));
+        }
+        if parts.is_empty() {
+            parts.push("no change".to_string());
+        }
+        f.write_str(&parts.join(", "))
+    }
+}
+
+/// Whether two colors look the same at the precision the bridge stores them: xy to four
+/// decimals, hue in 65535ths of a turn and saturation in 254ths.
+fn same_color(a: &Color, b: &Color) -> bool {
+    match (a, b) {
+        (Color::Xy { x: ax, y: ay }, Color::Xy { x: bx, y: by }) => {
+            (ax - bx).abs() < 0.0002 && (ay - by).abs() < 0.0002
+        }
+        (Color::ColorTemperature { mirek: a }, Color::ColorTemperature { mirek: b }) => a == b,
+        (
+            Color::HueSaturation {
+                hue: ah,
+                saturation: asat,
+            },
+            Color::HueSaturation {
+                hue: bh,
+                saturation: bsat,
+            },
+        ) => (ah - bh).abs() < 0.01 && (asat - bsat).abs() < 0.4,
+        _ => false,
+    }
+}
+
+/// How far along the bridge is with searching for new lights.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LastScan {
+    /// No search has been started since the bridge booted.
+    None,
+    Active,
+    /// The time the last search finished.
+    Completed(String),
+}
+
+/// Lights found by the most recent search, from `GET /lights/new`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(from = "v1::RawNewLights")]
+pub struct NewLights {
+    pub lastscan: LastScan,
+    /// Names of the new lights by ID.
+    pub lights: BTreeMap<LightId, String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    use crate::domain::brightness::Brightness;
+    use crate::domain::color::{Color, CtRange, Gamut};
+    use crate::domain::light::{Effect, Light, LightFeatures, LightKind, LightState};
+    use crate::error::CoreError;
+
+    fn light(features: LightFeatures) -> Light {
+        Light {
+            name: "Spot".to_string(),
+            features,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    pub fn fit_state_clamps_into_features() {
+        // Arrange
+        let light = light(LightFeatures {
+            dimmable: true,
+            color: true,
+            gamut: Some(Gamut::A),
+            ct_range: Some(CtRange { min: 153, max: 454 }),
+            streaming: false,
+        });
+        let xy = LightState::default().with_color(Color::xy(0.0, 0.0));
+        let ct = LightState::default().with_color(Color::ColorTemperature { mirek: 500 });
+
+        // Act
+        let fitted_xy = light.fit_state(&xy).unwrap();
+        let fitted_ct = light.fit_state(&ct).unwrap();
+
+        // Assert
+        assert!(Gamut::A.contains(fitted_xy.color.unwrap().to_xy()));
+        assert_eq!(
+            Some(Color::ColorTemperature { mirek: 454 }),
+            fitted_ct.color
+        );
+    }
+
+    #[test]
+    pub fn fit_state_ct_on_color_only_light_expect_xy() {
+        // Arrange
+        let light = light(LightFeatures::for_kind(&LightKind::Color));
+        let warm = Color::from_kelvin(2700);
+
+        // Act
+        let fitted = light
+            .fit_state(&LightState::default().with_color(warm))
+            .unwrap();
+
+        // Assert
+        let [x, y] = warm.to_xy();
+        assert_eq!(Some(Color::xy(x, y)), fitted.color);
+    }
+
+    #[test]
+    pub fn fit_state_color_on_white_light_expect_unsupported() {
+        // Arrange
+        let light = light(LightFeatures::for_kind(&LightKind::Dimmable));
+        let state = LightState::default().with_color(Color::HueSaturation {
+            hue: 10.0,
+            saturation: 100.0,
+        });
+
+        // Act
+        let result = light.fit_state(&state);
+
+        // Assert
+        assert!(
+            matches!(result, Err(CoreError::Unsupported { feature, .. }) if feature == "color")
+        );
+    }
+
+    #[test]
+    pub fn light_state_delta_drops_unchanged_fields() {
+        // Arrange
+        let current = LightState::default()
+            .with_on(true)
+            .with_brightness(Brightness::from_bri(127))
+            .with_color(Color::Xy { x: 0.4573, y: 0.41 });
+        let requested = LightState::default()
+            .with_on(true)
+            .with_brightness(Brightness::from_percent(50.0))
+            .with_color(Color::Xy {
+                x: 0.45731,
+                y: 0.40999,
+            })
+            .with_transition(Duration::from_millis(400));
+
+        // Act
+        let unchanged = requested.delta(&current);
+        let dimmer = requested
+            .clone()
+            .with_brightness(Brightness::from_bri(20))
+            .delta(&current);
+
+        // Assert
+        assert!(unchanged.is_no_op());
+        assert_eq!(
+            dimmer,
+            LightState::default()
+                .with_brightness(Brightness::from_bri(20))
+                .with_transition(Duration::from_millis(400))
+        );
+    }
+
+    #[test]
+    pub fn light_state_restorable_off_light_only_sends_on() {
+        // Arrange
+        let reported = LightState::default()
+            .with_on(false)
+            .with_brightness(Brightness::from_bri(200))
+            .with_color(Color::xy(0.3, 0.3));
+
+        // Act
+        let restored = reported.restorable();
+
+        // Assert
+        assert_eq!(LightState::default().with_on(false), restored);
+    }
+
+    #[test]
+    pub fn light_state_v1_json_round_trips() {
+        // Arrange
+        let state = LightState::default()
+            .with_on(true)
+            .with_brightness(Brightness::from_bri(127))
+            .with_color(Color::ColorTemperature { mirek: 366 })
+            .with_effect(Effect::None)
+            .with_transition(Duration::from_millis(400));
+
+        // Act
+        let json = serde_json::to_value(&state).unwrap();
+        let parsed: LightState = serde_json::from_value(json.clone()).unwrap();
+
+        // Assert
+        assert_eq!(
+            json!({"on": true, "bri": 127, "ct": 366, "effect": "none", "transitiontime": 4}),
+            json
+        );
+        assert_eq!(state, parsed);
+    }
+
+    #[test]
+    pub fn light_state_to_v2_json_nests_fields() {
+        // Arrange
+        let state = LightState::default()
+            .with_on(true)
+            .with_brightness(Brightness::from_percent(40.0))
+            .with_color(Color::xy(0.3, 0.4))
+            .with_transition(Duration::from_millis(400));
+
+        // Act
+        let json = state.to_v2_json().unwrap();
+
+        // Assert
+        assert_eq!(
+            json!({
+                "on": {"on": true},
+                "dimming": {"brightness": 40.0},
+                "color": {"xy": {"x": 0.3, "y": 0.4}},
+                "dynamics": {"duration": 400}
+            }),
+            json
+        );
+    }
+
+    #[test]
+    pub fn light_state_to_v2_json_color_loop_expect_unsupported() {
+        // Act
+        let result = LightState::default()
+            .with_effect(Effect::ColorLoop)
+            .to_v2_json();
+
+        // Assert
+        assert!(matches!(result, Err(CoreError::UnsupportedByApi { .. })));
+    }
+
+    #[test]
+    pub fn light_from_v2_json_expect_domain_light() {
+        // Arrange
+        let json = json!({
+            "id": "3f1c...", "type": "light",
+            "metadata": {"name": "Desk", "archetype": "sultan_bulb"},
+            "on": {"on": true},
+            "dimming": {"brightness": 50.0, "min_dim_level": 0.2},
+            "color_temperature": {"mirek": 366, "mirek_valid": true,
+                "mirek_schema": {"mirek_minimum": 153, "mirek_maximum": 500}},
+            "color": {"xy": {"x": 0.45, "y": 0.41}, "gamut_type": "C"},
+            "mode": "normal"
+        });
+
+        // Act
+        let light = Light::from_v2_json(json).unwrap();
+
+        // Assert
+        assert_eq!("Desk", light.name);
+        assert_eq!(LightKind::ExtendedColor, light.kind);
+        assert_eq!(Some(Brightness::from_percent(50.0)), light.state.brightness);
+        assert_eq!(
+            Some(Color::ColorTemperature { mirek: 366 }),
+            light.state.color
+        );
+        assert_eq!(Some(Gamut::C), light.features.gamut);
+    }
+
+    #[test]
+    pub fn light_from_v1_json_picks_color_by_colormode() {
+        // Arrange
+        let json = json!({
+            "name": "Desk", "type": "Extended color light", "modelid": "LCT015",
+            "state": {"on": true, "bri": 254, "hue": 8000, "sat": 140, "xy": [0.45, 0.41],
+                "ct": 366, "colormode": "xy", "reachable": false}
+        });
+
+        // Act
+        let light: Light = serde_json::from_value(json).unwrap();
+
+        // Assert
+        assert_eq!(Some(Color::xy(0.45, 0.41)), light.state.color);
+        assert!(!light.reachable);
+        assert_eq!(Some("LCT015"), light.info.model_id.as_deref());
+        assert_eq!(
+            LightFeatures::for_kind(&LightKind::ExtendedColor),
+            light.features
+        );
+    }
+}
@@ -0,0 +1,4 @@
+pub mod brightness;
+pub mod color;
+pub mod light;
+pub mod named_colors;
@@ -0,0 +1,417 @@
+use std::collections::BTreeMap;
+
+use crate::domain::color::Color;
+use crate::error::ParseError;
+
+/// Hue's white presets, in mirek. These match the light recipes in the Hue app.
+pub const HUE_PRESETS: &[(&str, u16)] = &[
+    ("relax", 447),
+    ("read", 346),
+    ("concentrate", 233),
+    ("energize", 156),
+    ("candle", 500),
+    ("warmwhite", 370),
+    ("softwhite", 333),
+    ("neutralwhite", 250),
+    ("coolwhite", 200),
+    ("daylight", 153),
+];
+
+/// The CSS color keywords, which are the X11 colors plus a few web additions.
+pub const CSS_COLORS: &[(&str, u32)] = &[
+    ("aliceblue", 0xf0f8ff),
+    ("antiquewhite", 0xfaebd7),
+    ("aqua", 0x00ffff),
+    ("aquamarine", 0x7fffd4),
+    ("azure", 0xf0ffff),
+    ("beige", 0xf5f5dc),
+    ("bisque", 0xffe4c4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xffebcd),
+    ("blue", 0x0000ff),
+    ("blueviolet", 0x8a2be2),
+    ("brown", 0xa52a2a),
+    ("burlywood", 0xdeb887),
+    ("cadetblue", 0x5f9ea0),
+    ("chartreuse", 0x7fff00),
+    ("chocolate", 0xd2691e),
+    ("coral", 0xff7f50),
+    ("cornflowerblue", 0x6495ed),
+    ("cornsilk", 0xfff8dc),
+    ("crimson", 0xdc143c),
+    ("cyan", 0x00ffff),
+    ("darkblue", 0x00008b),
+    ("darkcyan", 0x008b8b),
+    ("darkgoldenrod", 0xb8860b),
+    ("darkgray", 0xa9a9a9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xa9a9a9),
+    ("darkkhaki", 0xbdb76b),
+    ("darkmagenta", 0x8b008b),
+    ("darkolivegreen", 0x556b2f),
+    ("darkorange", 0xff8c00),
+    ("darkorchid", 0x9932cc),
+    ("darkred", 0x8b0000),
+    ("darksalmon", 0xe9967a),
+    ("darkseagreen", 0x8fbc8f),
+    ("darkslateblue", 0x483d8b),
+    ("darkslategray", 0x2f4f4f),
+    ("darkslategrey", 0x2f4f4f),
+    ("darkturquoise", 0x00ced1),
+    ("darkviolet", 0x9400d3),
+    ("deeppink", 0xff1493),
+    ("deepskyblue", 0x00bfff),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1e90ff),
+    ("firebrick", 0xb22222),
+    ("floralwhite", 0xfffaf0),
+    ("forestgreen", 0x228b22),
+    ("fuchsia", 0xff00ff),
+    ("gainsboro", 0xdcdcdc),
+    ("ghostwhite", 0xf8f8ff),
+    ("gold", 0xffd700),
+    ("goldenrod", 0xdaa520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xadff2f),
+    ("grey", 0x808080),
+    ("honeydew", 0xf0fff0),
+    ("hotpink", 0xff69b4),
+    ("indianred", 0xcd5c5c),
+    ("indigo", 0x4b0082),
+    ("ivory", 0xfffff0),
+    ("khaki", 0xf0e68c),
+    ("lavender", 0xe6e6fa),
+    ("lavenderblush", 0xfff0f5),
+    ("lawngreen", 0x7cfc00),
+    ("lemonchiffon", 0xfffacd),
+    ("lightblue", 0xadd8e6),
+    ("lightcoral", 0xf08080),
+    ("lightcyan", 0xe0ffff),
+    ("lightgoldenrodyellow", 0xfafad2),
+    ("lightgray", 0xd3d3d3),
+    ("lightgreen", 0x90ee90),
+    ("lightgrey", 0xd3d3d3),
+    ("lightpink", 0xffb6c1),
+    ("lightsalmon", 0xffa07a),
+    ("lightseagreen", 0x20b2aa),
+    ("lightskyblue", 0x87cefa),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xb0c4de),
+    ("lightyellow", 0xffffe0),
+    ("lime", 0x00ff00),
+    ("limegreen", 0x32cd32),
+    ("linen", 0xfaf0e6),
+    ("magenta", 0xff00ff),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66cdaa),
+    ("mediumblue", 0x0000cd),
+    ("mediumorchid", 0xba55d3),
+    ("mediumpurple", 0x9370db),
+    ("mediumseagreen", 0x3cb371),
+    ("mediumslateblue", 0x7b68ee),
+    ("mediumspringgreen", 0x00fa9a),
+    ("mediumturquoise", 0x48d1cc),
+    ("mediumvioletred", 0xc71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xf5fffa),
+    ("mistyrose", 0xffe4e1),
+    ("moccasin", 0xffe4b5),
+    ("navajowhite", 0xffdead),
+    ("navy", 0x000080),
+    ("oldlace", 0xfdf5e6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6b8e23),
+    ("orange", 0xffa500),
+    ("orangered", 0xff4500),
+    ("orchid", 0xda70d6),
+    ("palegoldenrod", 0xeee8aa),
+    ("palegreen", 0x98fb98),
+    ("paleturquoise", 0xafeeee),
+    ("palevioletred", 0xdb7093),
+    ("papayawhip", 0xffefd5),
+    ("peachpuff", 0xffdab9),
+    ("peru", 0xcd853f),
+    ("pink", 0xffc0cb),
+    ("plum", 0xdda0dd),
+    ("powderblue", 0xb0e0e6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xff0000),
+    ("rosybrown", 0xbc8f8f),
+    ("royalblue", 0x4169e1),
+    ("saddlebrown", 0x8b4513),
+    ("salmon", 0xfa8072),
+    ("sandybrown", 0xf4a460),
+    ("seagreen", 0x2e8b57),
+    ("seashell", 0xfff5ee),
+    ("sienna", 0xa0522d),
+    ("silver", 0xc0c0c0),
+    ("skyblue", 0x87ceeb),
+    ("slateblue", 0x6a5acd),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xfffafa),
+    ("springgreen", 0x00ff7f),
+    ("steelblue", 0x4682b4),
+    ("tan", 0xd2b48c),
+    ("teal", 0x008080),
+    ("thistle", 0xd8bfd8),
+    ("tomato", 0xff6347),
+    ("turquoise", 0x40e0d0),
+    ("violet", 0xee82ee),
+    ("wheat", 0xf5deb3),
+    ("white", 0xffffff),
+    ("whitesmoke", 0xf5f5f5),
+    ("yellow", 0xffff00),
+    ("yellowgreen", 0x9acd32),
+];
+
+/// Lowercases a color name and drops spaces, dashes and underscores, so `Warm White`,
+/// `warm-white` and `warmwhite` are the same name.
+pub fn normalize_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| !matches!(c, ' ' | '-' | '_'))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn from_hex(rgb: u32) -> Color {
+    Color::from_rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)
+}
+
+/// Parses the color forms that need no name lookup: `#rrggbb`, `#rgb`, `2700K` and `xy:0.4,0.4`.
+fn parse_literal(text: &str) -> Option<Color> {
+    if let Some(hex) = text.strip_prefix('#') {
+        let rgb = u32::from_str_radix(hex, 16).ok()?;
+        return match hex.len() {
+            6 => Some(from_hex(rgb)),
+            // #rgb doubles each digit, so #f80 is #ff8800.
+            3 => {
+                let digit = |shift: u32| ((rgb >> shift) & 0xf) * 0x11;
+                Some(from_hex(digit(8) << 16 | digit(4) << 8 | digit(0)))
+            }
+            _ => None,
+        };
+    }
+    if let Some(kelvin) = text.strip_suffix(['k', 'K']) {
+        return kelvin.parse::<u32>().ok().map(Color::from_kelvin);
+    }
+    let (x, y) = text.strip_prefix("xy:")?.split_once(',')?;
+    let (x, y) = (x.trim().parse::<f64>().ok()?, y.trim().parse::<f64>().ok()?);
+    ((0.0..=1.0).contains(&x) && (0.0..=1.0).contains(&y)).then(|| Color::xy(x, y))
+}
+
+fn builtin(name: &str) -> Option<Color> {
+    let name = normalize_name(name);
+    HUE_PRESETS
+        .iter()
+        .find(|(preset, _)| *preset == name)
+        .map(|(_, mirek)| Color::ColorTemperature { mirek: *mirek })
+        .or_else(|| {
+            CSS_COLORS
+                .iter()
+                .find(|(css, _)| *css == name)
+                .map(|(_, rgb)| from_hex(*rgb))
+        })
+}
+
+/// What a color argument names: one color, or a palette to spread over several lights.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColorChoice {
+    Single(Color),
+    Palette(Vec<Color>),
+}
+
+/// Resolves color names to colors: the user's own colors and palettes first, then the Hue
+/// presets and CSS names, then literal values.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ColorRegistry {
+    colors: BTreeMap<String, Color>,
+    palettes: BTreeMap<String, Vec<Color>>,
+}
+
+impl ColorRegistry {
+    /// A registry with only the built-in names.
+    pub fn builtin() -> Self {
+        Self::default()
+    }
+
+    /// Adds user colors and palettes, whose values may be built-in names or literal values.
+    ///
+    /// User colors can't refer to each other, which keeps resolution free of cycles.
+    pub fn with_user_colors(
+        colors: &BTreeMap<String, String>,
+        palettes: &BTreeMap<String, Vec<String>>,
+    ) -> Result<Self, ParseError> {
+        let resolve = |name: &str, value: &str| {
+            parse_literal(value.trim())
+                .or_else(|| builtin(value))
+                .ok_or_else(|| ParseError::InvalidColor {
+                    name: name.to_string(),
+                    value: value.to_string(),
+                })
+        };
+        let colors = colors
+            .iter()
+            .map(|(name, value)| Ok((normalize_name(name), resolve(name, value)?)))
+            .collect::<Result<BTreeMap<_, _>, ParseError>>()?;
+        let palettes = palettes
+            .iter()
+            .map(|(name, values)| {
+                if values.is_empty() {
+                    return Err(ParseError::InvalidColor {
+                        name: name.clone(),
+                        value: "[]".to_string(),
+                    });
+                }
+                let palette = values
+                    .iter()
+                    .map(|value| match colors.get(&normalize_name(value)) {
+                        Some(color) => Ok(*color),
+                        None => resolve(name, value),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok((normalize_name(name), palette))
+            })
+            .collect::<Result<BTreeMap<_, _>, ParseError>>()?;
+        Ok(Self { colors, palettes })
+    }
+
+    /// Resolves a single color by name or literal value.
+    pub fn color(&self, text: &str) -> Result<Color, ParseError> {
+        let text = text.trim();
+        self.colors
+            .get(&normalize_name(text))
+            .copied()
+            .or_else(|| builtin(text))
+            .or_else(|| parse_literal(text))
+            .ok_or_else(|| ParseError::UnknownColor(text.to_string()))
+    }
+
+    /// Resolves a palette name, falling back to a single color.
+    pub fn choice(&self, text: &str) -> Result<ColorChoice, ParseError> {
+        match self.palettes.get(&normalize_name(text)) {
+            Some(palette) => Ok(ColorChoice::Palette(palette.clone())),
+            None => self.color(text).map(ColorChoice::Single),
+        }
+    }
+
+    /// The user's colors by normalized name.
+    pub fn user_colors(&self) -> &BTreeMap<String, Color> {
+        &self.colors
+    }
+
+    /// The user's palettes by normalized name.
+    pub fn palettes(&self) -> &BTreeMap<String, Vec<Color>> {
+        &self.palettes
+    }
+}
+
+impl ColorChoice {
+    /// The color for each of `count` lights, repeating a palette when there are more lights.
+    pub fn spread(&self, count: usize) -> Vec<Color> {
+        match self {
+            ColorChoice::Single(color) => vec![*color; count],
+            ColorChoice::Palette(palette) => palette.iter().copied().cycle().take(count).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::domain::color::Color;
+    use crate::domain::named_colors::{ColorChoice, ColorRegistry};
+    use crate::error::ParseError;
+
+    #[test]
+    pub fn registry_resolves_presets_css_names_and_literals() {
+        // Arrange
+        let registry = ColorRegistry::builtin();
+
+        // Act / Assert
+        assert_eq!(
+            Color::ColorTemperature { mirek: 370 },
+            registry.color("Warm White").unwrap()
+        );
+        assert_eq!(
+            Color::from_rgb(255, 127, 80),
+            registry.color("coral").unwrap()
+        );
+        assert_eq!(
+            Color::from_rgb(255, 136, 0),
+            registry.color("#f80").unwrap()
+        );
+        assert_eq!(Color::from_kelvin(2700), registry.color("2700K").unwrap());
+        assert_eq!(Color::xy(0.4, 0.5), registry.color("xy:0.4,0.5").unwrap());
+        assert!(matches!(
+            registry.color("blurple"),
+            Err(ParseError::UnknownColor(_))
+        ));
+    }
+
+    #[test]
+    pub fn registry_user_colors_take_precedence_and_feed_palettes() {
+        // Arrange
+        let colors = BTreeMap::from([
+            ("coral".to_string(), "#ff0000".to_string()),
+            ("bedtime".to_string(), "2200K".to_string()),
+        ]);
+        let palettes = BTreeMap::from([(
+            "Beach".to_string(),
+            vec!["coral".to_string(), "turquoise".to_string()],
+        )]);
+
+        // Act
+        let registry = ColorRegistry::with_user_colors(&colors, &palettes).unwrap();
+
+        // Assert
+        assert_eq!(Color::from_rgb(255, 0, 0), registry.color("coral").unwrap());
+        assert_eq!(
+            ColorChoice::Palette(vec![
+                Color::from_rgb(255, 0, 0),
+                Color::from_rgb(0x40, 0xe0, 0xd0)
+            ]),
+            registry.choice("beach").unwrap()
+        );
+        assert_eq!(
+            ColorChoice::Single(Color::from_kelvin(2200)),
+            registry.choice("bedtime").unwrap()
+        );
+    }
+
+    #[test]
+    pub fn registry_invalid_user_color_expect_error() {
+        // Arrange
+        let colors = BTreeMap::from([("mine".to_string(), "not a color".to_string())]);
+
+        // Act
+        let result = ColorRegistry::with_user_colors(&colors, &BTreeMap::new());
+
+        // Assert
+        assert!(matches!(result, Err(ParseError::InvalidColor { name, .. }) if name == "mine"));
+    }
+
+    #[test]
+    pub fn color_choice_spread_cycles_palette() {
+        // Arrange
+        let palette = ColorChoice::Palette(vec![Color::xy(0.1, 0.1), Color::xy(0.2, 0.2)]);
+
+        // Act
+        let spread = palette.spread(3);
+
+        // Assert
+        assert_eq!(
+            vec![
+                Color::xy(0.1, 0.1),
+                Color::xy(0.2, 0.2),
+                Color::xy(0.1, 0.1)
+            ],
+            spread
+        );
+    }
+}
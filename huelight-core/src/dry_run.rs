@@ -0,0 +1,592 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use serde_json::{Map, Value, json};
+
+use crate::client::{Header, HueClient, redact_url, redact_username, split_url};
+use crate::error::{CoreError, CoreResult};
+use crate::logger::ILogger;
+
+/// Write fields that describe how to change, not a state the resource ends up in.
+fn is_state_field(field: &str) -> bool {
+    field != "transitiontime" && !field.ends_with("_inc")
+}
+
+/// A mutating request a dry run held back instead of sending.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeldRequest {
+    pub method: &'static str,
+    /// With the username redacted.
+    pub url: String,
+    /// With the username redacted.
+    pub body: Option<Value>,
+}
+
+impl fmt::Display for HeldRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.method, self.url)?;
+        if let Some(body) = &self.body {
+            write!(f, " {body}")?;
+        }
+        Ok(())
+    }
+}
+
+/// One field a dry run would have changed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateChange {
+    /// Resource path below the username, e.g. `lights/3`.
+    pub resource: String,
+    /// The resource's name on the bridge, if it has one.
+    pub name: Option<String>,
+    pub field: String,
+    /// `None` when the bridge doesn't report the field.
+    pub before: Option<Value>,
+    pub after: Value,
+}
+
+impl fmt::Display for StateChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.resource)?;
+        if let Some(name) = &self.name {
+            write!(f, " ({name})")?;
+        }
+        match &self.before {
+            Some(before) => write!(f, " {}: {} -> {}", self.field, before, self.after),
+            None => write!(f, " {}: -> {}", self.field, self.after),
+        }
+    }
+}
+
+/// Writes to one resource, merged in the order they were made.
+#[derive(Clone)]
+struct PendingWrite {
+    /// Unredacted URL of the resource, for reading its current state.
+    url: String,
+    /// Resource path below the username, e.g. `lights/3`.
+    resource: String,
+    /// `state` or `action` when the write targets that part of the resource.
+    key: Option<String>,
+    fields: Map<String, Value>,
+}
+
+impl PendingWrite {
+    /// Sets the written fields on a resource as the bridge reports it.
+    fn apply(&self, resource: &mut Value) {
+        let target = match &self.key {
+            Some(key) => resource.get_mut(key),
+            None => Some(resource),
+        };
+        if let Some(Value::Object(target)) = target {
+            for (field, value) in &self.fields {
+                target.insert(field.clone(), value.clone());
+            }
+        }
+    }
+}
+
+/// Wraps another HueClient and holds back every request that would change the bridge.
+///
+/// Mutating requests are logged with the username redacted and answered with the success
+/// response the bridge would give, with an unused ID for anything created. GETs go to the
+/// bridge once per URL and are then answered from that snapshot, with the held-back writes
+/// applied so later commands see them. A group action is applied to each of the group's
+/// lights. `changes` summarizes the difference the writes would have made.
+pub struct DryRunHueClient {
+    inner: Arc<dyn HueClient + Send + Sync>,
+    logger: Arc<dyn ILogger + Send + Sync>,
+    held: Mutex<Vec<HeldRequest>>,
+    writes: Mutex<Vec<PendingWrite>>,
+    snapshot: tokio::sync::Mutex<HashMap<String, String>>,
+    /// The last ID handed out for a held-back create, by collection path, e.g. `groups`.
+    created: Mutex<HashMap<String, u32>>,
+    /// Member lights of groups the dry run created or refilled, by group ID.
+    group_lights: Mutex<HashMap<String, Vec<String>>>,
+}
+
+impl DryRunHueClient {
+    pub fn new(
+        inner: Arc<dyn HueClient + Send + Sync>,
+        logger: Arc<dyn ILogger + Send + Sync>,
+    ) -> Self {
+        Self {
+            inner,
+            logger,
+            held: Mutex::new(Vec::new()),
+            writes: Mutex::new(Vec::new()),
+            snapshot: tokio::sync::Mutex::new(HashMap::new()),
+            created: Mutex::new(HashMap::new()),
+            group_lights: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Every request held back so far, in order.
+    pub fn held_requests(&self) -> Vec<HeldRequest> {
+        self.held.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Logs and keeps a held-back request, with the username redacted from its URL and body.
+    /// Returns the body as sent.
+    fn hold(&self, method: &'static str, url: &str, body: Option<&str>) -> Option<Value> {
+        let parse = |body: &str| serde_json::from_str(body).unwrap_or(Value::String(body.into()));
+        let request = HeldRequest {
+            method,
+            url: redact_url(url),
+            body: body.map(|body| parse(&redact_username(body, url))),
+        };
+        self.logger.log(&format!("[dry-run] {request}"));
+        self.held
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(request);
+        body.map(parse)
+    }
+
+    fn record_write(&self, url: &str, fields: &Map<String, Value>) {
+        let (_, _, path) = split_url(url);
+        let (resource, key, resource_url) = match path.rsplit_once('/') {
+            Some((resource, key @ ("state" | "action"))) => (
+                resource,
+                Some(key.to_string()),
+                url.trim_end_matches(key).trim_end_matches('/'),
+            ),
+            _ => (path, None, url),
+        };
+        let mut writes = self.writes.lock().unwrap_or_else(|e| e.into_inner());
+        let index = match writes
+            .iter()
+            .position(|w| w.resource == resource && w.key == key)
+        {
+            Some(index) => index,
+            None => {
+                writes.push(PendingWrite {
+                    url: resource_url.to_string(),
+                    resource: resource.to_string(),
+                    key,
+                    fields: Map::new(),
+                });
+                writes.len() - 1
+            }
+        };
+        for (field, value) in fields {
+            if is_state_field(field) {
+                writes[index].fields.insert(field.clone(), value.clone());
+            }
+        }
+    }
+
+    /// Remembers the member lights a held-back write gave a group, e.g. `{"lights":["1","2"]}`.
+    fn set_group_lights(&self, group: &str, body: &Map<String, Value>) {
+        if let Some(Value::Array(lights)) = body.get("lights") {
+            let lights = lights
+                .iter()
+                .filter_map(|id| id.as_str().map(str::to_string))
+                .collect();
+            self.group_lights
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert(group.to_string(), lights);
+        }
+    }
+
+    /// The IDs of the lights in `group`, as the dry run left it or else as the bridge reports
+    /// it. Group 0 is every light. `url` is any URL of the same bridge and user.
+    async fn lights_of_group(
+        &self,
+        url: &str,
+        group: &str,
+        headers: &[Header],
+    ) -> Option<Vec<String>> {
+        if let Some(lights) = self
+            .group_lights
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(group)
+        {
+            return Some(lights.clone());
+        }
+        let (base, username, _) = split_url(url);
+        if group == "0" {
+            let body = self
+                .snapshot_get(&format!("{base}{username}/lights"), headers)
+                .await
+                .ok()?;
+            let lights: Map<String, Value> = serde_json::from_str(&body).ok()?;
+            return Some(lights.keys().cloned().collect());
+        }
+        let body = self
+            .snapshot_get(&format!("{base}{username}/groups/{group}"), headers)
+            .await
+            .ok()?;
+        let group: Value = serde_json::from_str(&body).ok()?;
+        group.get("lights")?.as_array().map(|lights| {
+            lights
+                .iter()
+                .filter_map(|id| id.as_str().map(str::to_string))
+                .collect()
+        })
+    }
+
+    /// The bridge's answer to a GET, fetched once and then kept, without held writes applied.
+    async fn snapshot_get(&self, url: &str, headers: &[Header]) -> CoreResult<String> {
+        let mut snapshot = self.snapshot.lock().await;
+        if let Some(body) = snapshot.get(url) {
+            return Ok(body.clone());
+        }
+        let body = self.inner.get(url, headers).await?;
+        snapshot.insert(url.to_string(), body.clone());
+        Ok(body)
+    }
+
+    /// An ID for a resource created by a POST to `url` that no resource on the bridge has: one
+    /// past the highest the bridge lists there, or the dry run handed out already. Never 0,
+    /// which for groups means every light.
+    async fn placeholder_id(&self, url: &str, headers: &[Header]) -> u32 {
+        let (_, _, path) = split_url(url);
+        let highest = match path {
+            "" => None,
+            _ => self.snapshot_get(url, headers).await.ok(),
+        }
+        .and_then(|body| serde_json::from_str::<Map<String, Value>>(&body).ok())
+        .and_then(|listed| listed.keys().filter_map(|id| id.parse().ok()).max())
+        .unwrap_or(0);
+        let mut created = self.created.lock().unwrap_or_else(|e| e.into_inner());
+        let last = created.entry(path.to_string()).or_insert(0);
+        *last = (*last).max(highest) + 1;
+        *last
+    }
+
+    /// What each held write would change, compared to the bridge's state before the dry run.
+    pub async fn changes(&self) -> CoreResult<Vec<StateChange>> {
+        // Cloned so the lock isn't held across the reads below.
+        let writes = self
+            .writes
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+
+        let mut changes = Vec::new();
+        for PendingWrite {
+            url,
+            resource,
+            key,
+            fields,
+        } in writes
+        {
+            let current: Value = match self.snapshot_get(&url, &[]).await {
+                Ok(body) => serde_json::from_str(&body).map_err(CoreError::Serialization)?,
+                // Resources that can't be read back (e.g. `config`) just show the new values.
+                Err(_) => Value::Null,
+            };
+            let name = current
+                .get("name")
+                .and_then(Value::as_str)
+                .map(str::to_string);
+            let section = match &key {
+                Some(key) => current.get(key),
+                None => Some(&current),
+            };
+            for (field, after) in fields {
+                let before = section.and_then(|s| s.get(&field)).cloned();
+                if before.as_ref() != Some(&after) {
+                    changes.push(StateChange {
+                        resource: resource.clone(),
+                        name: name.clone(),
+                        field,
+                        before,
+                        after,
+                    });
+                }
+            }
+        }
+        Ok(changes)
+    }
+}
+
+#[async_trait]
+impl HueClient for DryRunHueClient {
+    async fn post_json(&self, url: &str, body: &str, headers: &[Header]) -> CoreResult<String> {
+        let body = self.hold("POST", url, Some(body));
+        let id = self.placeholder_id(url, headers).await;
+        if let (_, _, "groups") = split_url(url)
+            && let Some(Value::Object(group)) = &body
+        {
+            self.set_group_lights(&id.to_string(), group);
+        }
+        Ok(json!([{ "success": { "id": id.to_string() } }]).to_string())
+    }
+
+    async fn get(&self, url: &str, headers: &[Header]) -> CoreResult<String> {
+        let body = self.snapshot_get(url, headers).await?;
+        let Ok(mut response) = serde_json::from_str::<Value>(&body) else {
+            return Ok(body);
+        };
+
+        let (_, _, path) = split_url(url);
+        let writes = self.writes.lock().unwrap_or_else(|e| e.into_inner());
+        for write in writes.iter() {
+            if write.resource == path {
+                write.apply(&mut response);
+            } else if let Some(id) = write
+                .resource
+                .strip_prefix(path)
+                .and_then(|rest| rest.strip_prefix('/'))
+                && let Some(resource) = response.get_mut(id)
+            {
+                write.apply(resource);
+            }
+        }
+        Ok(response.to_string())
+    }
+
+    async fn put_json(&self, url: &str, body: &str, headers: &[Header]) -> CoreResult<String> {
+        let (base, username, path) = split_url(url);
+        let success: Vec<Value> = match self.hold("PUT", url, Some(body)) {
+            Some(Value::Object(fields)) => {
+                let group = path.strip_prefix("groups/");
+                // A scene recall sets each light differently, so it stays a write to the group.
+                let members = match group.and_then(|rest| rest.strip_suffix("/action")) {
+                    Some(group) if !fields.contains_key("scene") => {
+                        self.lights_of_group(url, group, headers).await
+                    }
+                    _ => None,
+                };
+                match members {
+                    Some(lights) => {
+                        for light in lights {
+                            let light_url = format!("{base}{username}/lights/{light}/state");
+                            self.record_write(&light_url, &fields);
+                        }
+                    }
+                    None => {
+                        if let Some(group) = group.filter(|group| !group.contains('/')) {
+                            self.set_group_lights(group, &fields);
+                        }
+                        self.record_write(url, &fields);
+                    }
+                }
+                fields
+                    .into_iter()
+                    .map(|(field, value)| json!({ "success": { format!("/{path}/{field}"): value } }))
+                    .collect()
+            }
+            _ => Vec::new(),
+        };
+        Ok(Value::Array(success).to_string())
+    }
+
+    async fn delete(&self, url: &str, _headers: &[Header]) -> CoreResult<String> {
+        self.hold("DELETE", url, None);
+        let (_, _, path) = split_url(url);
+        Ok(json!([{ "success": format!("/{path} deleted") }]).to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use async_trait::async_trait;
+    use serde_json::{Value, json};
+
+    use super::DryRunHueClient;
+    use crate::batch::set_light_states;
+    use crate::client::{Header, HueClient};
+    use crate::domain::light::{LightKind, LightState};
+    use crate::error::{CoreError, CoreResult};
+    use crate::fanout::DEFAULT_CONCURRENCY;
+    use crate::hue_api::{HueApi, HueApiV1};
+    use crate::logger::Logger;
+    use crate::testing::MockBridge;
+
+    /// A bridge with one light, that fails the test if anything is written to it.
+    struct ReadOnlyBridge;
+
+    #[async_trait]
+    impl HueClient for ReadOnlyBridge {
+        async fn post_json(&self, _: &str, _: &str, _: &[Header]) -> CoreResult<String> {
+            panic!("dry run sent a POST")
+        }
+        async fn get(&self, url: &str, _: &[Header]) -> CoreResult<String> {
+            let light = json!({ "name": "Desk", "state": { "on": false, "bri": 100 } });
+            match url.rsplit_once("/api/secret/") {
+                Some((_, "lights")) => Ok(json!({ "3": light }).to_string()),
+                Some((_, "lights/3")) => Ok(light.to_string()),
+                _ => Err(CoreError::UnexpectedResponse(url.to_string())),
+            }
+        }
+        async fn put_json(&self, _: &str, _: &str, _: &[Header]) -> CoreResult<String> {
+            panic!("dry run sent a PUT")
+        }
+        async fn delete(&self, _: &str, _: &[Header]) -> CoreResult<String> {
+            panic!("dry run sent a DELETE")
+        }
+    }
+
+    #[tokio::test]
+    pub async fn dry_run_holds_writes_and_reports_changes() {
+        // Arrange
+        let client = DryRunHueClient::new(Arc::new(ReadOnlyBridge), Arc::new(Logger::default()));
+        let url = "http://10.0.0.2/api/secret/lights/3/state";
+
+        // Act
+        let response = client
+            .put_json(url, r#"{"on":true,"bri":100,"transitiontime":4}"#, &[])
+            .await
+            .unwrap();
+        let lights: Value = serde_json::from_str(
+            &client
+                .get("http://10.0.0.2/api/secret/lights", &[])
+                .await
+                .unwrap(),
+        )
+        .unwrap();
+        let changes = client.changes().await.unwrap();
+
+        // Assert
+        assert!(response.contains(r#""/lights/3/state/on":true"#));
+        assert_eq!(lights["3"]["state"]["on"], json!(true));
+        assert_eq!(client.held_requests().len(), 1);
+        assert_eq!(
+            client.held_requests()[0].url,
+            "http://10.0.0.2/api/<username>/lights/3/state"
+        );
+        // bri is unchanged and transitiontime isn't state, so only `on` differs.
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].to_string(), "lights/3 (Desk) on: false -> true");
+    }
+
+    #[tokio::test]
+    pub async fn dry_run_batch_group_gets_an_unused_id() {
+        // Arrange
+        let bridge = Arc::new(
+            MockBridge::new()
+                .with_light(1, "Desk", LightKind::Dimmable)
+                .with_light(2, "Shelf", LightKind::Dimmable)
+                .with_light(3, "Hall", LightKind::Dimmable)
+                .with_group(4, "Office", &[1, 2, 3]),
+        );
+        let client = Arc::new(DryRunHueClient::new(
+            bridge.clone(),
+            Arc::new(Logger::default()),
+        ));
+        let api = HueApiV1::new(client.clone(), Arc::new(Logger::default()));
+        let on = LightState::default().with_on(true);
+
+        // Act
+        let report = set_light_states(
+            &api,
+            "ip",
+            "user",
+            &[(1, on.clone()), (2, on)],
+            DEFAULT_CONCURRENCY,
+        )
+        .await;
+
+        // Assert
+        assert_eq!(report.groups, vec![5]);
+        let held: Vec<String> = client
+            .held_requests()
+            .iter()
+            .map(|request| format!("{} {}", request.method, request.url))
+            .collect();
+        assert_eq!(
+            held,
+            vec![
+                "POST http://ip/api/<username>/groups",
+                "PUT http://ip/api/<username>/groups/5/action",
+            ]
+        );
+        assert!(bridge.calls().iter().all(|call| call.method == "GET"));
+        let changes: Vec<String> = client
+            .changes()
+            .await
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                "lights/1 (Desk) on: false -> true",
+                "lights/2 (Shelf) on: false -> true",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    pub async fn dry_run_group_action_changes_each_member_light() {
+        // Arrange
+        let bridge = Arc::new(
+            MockBridge::new()
+                .with_light(1, "Desk", LightKind::Dimmable)
+                .with_light(2, "Shelf", LightKind::Dimmable)
+                .with_light(3, "Hall", LightKind::Dimmable)
+                .with_group(4, "Office", &[1, 2]),
+        );
+        let client = Arc::new(DryRunHueClient::new(
+            bridge.clone(),
+            Arc::new(Logger::default()),
+        ));
+        let api = HueApiV1::new(client.clone(), Arc::new(Logger::default()));
+        let on = LightState::default().with_on(true);
+
+        // Act
+        let report = set_light_states(
+            &api,
+            "ip",
+            "user",
+            &[(1, on.clone()), (2, on)],
+            DEFAULT_CONCURRENCY,
+        )
+        .await;
+        let lights = api.async_get_all_lights("ip", "user").await.unwrap();
+        let changes: Vec<String> = client
+            .changes()
+            .await
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+
+        // Assert
+        assert_eq!(report.groups, vec![4]);
+        assert_eq!(lights.0[&1].state.on, Some(true));
+        assert_eq!(lights.0[&3].state.on, Some(false));
+        assert_eq!(
+            changes,
+            vec![
+                "lights/1 (Desk) on: false -> true",
+                "lights/2 (Shelf) on: false -> true",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    pub async fn dry_run_redacts_username_in_held_bodies() {
+        // Arrange
+        let client = DryRunHueClient::new(Arc::new(ReadOnlyBridge), Arc::new(Logger::default()));
+        let body = json!({
+            "name": "Timer",
+            "command": { "address": "/api/secret/lights/3/state", "method": "PUT", "body": { "on": true } },
+            "localtime": "PT00:10:00",
+        });
+
+        // Act
+        client
+            .post_json(
+                "http://10.0.0.2/api/secret/schedules",
+                &body.to_string(),
+                &[],
+            )
+            .await
+            .unwrap();
+
+        // Assert
+        let held = client.held_requests()[0].to_string();
+        assert!(!held.contains("secret"));
+        assert!(held.contains("/api/<username>/lights/3/state"));
+    }
+}
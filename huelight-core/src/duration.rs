@@ -0,0 +1,127 @@
+use std::time::Duration;
+
+use crate::error::ParseError;
+
+/// Parses human durations such as `10m`, `1h30m`, `2s` or `500ms`. A bare number is seconds.
+pub fn parse_duration(input: &str) -> Result<Duration, ParseError> {
+    let invalid = || ParseError::InvalidDuration(input.to_string());
+    let trimmed = input.trim();
+    if trimmed.is_empty() {
+        return Err(invalid());
+    }
+    if let Ok(secs) = trimmed.parse::<u64>() {
+        return Ok(Duration::from_secs(secs));
+    }
+
+    let mut total = Duration::ZERO;
+    let mut rest = trimmed;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .ok_or_else(invalid)?;
+        let value: f64 = rest[..digits].parse().map_err(|_| invalid())?;
+        rest = &rest[digits..];
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let millis_per_unit = match &rest[..unit_len] {
+            "ms" => 1.0,
+            "s" => 1_000.0,
+            "m" => 60_000.0,
+            "h" => 3_600_000.0,
+            "d" => 86_400_000.0,
+            _ => return Err(invalid()),
+        };
+        rest = &rest[unit_len..];
+        total += Duration::from_millis((value * millis_per_unit).round() as u64);
+    }
+    Ok(total)
+}
+
+/// Parses a signed offset such as `+30m`, `-1h` or `15m` into seconds.
+pub fn parse_signed_offset(input: &str) -> Result<i64, ParseError> {
+    let trimmed = input.trim();
+    let (sign, magnitude) = match trimmed.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, trimmed.strip_prefix('+').unwrap_or(trimmed)),
+    };
+    Ok(sign * parse_duration(magnitude)?.as_secs() as i64)
+}
+
+/// Converts a duration to the bridge's `transitiontime` unit (multiples of 100ms).
+pub fn to_transition_time(duration: Duration) -> u16 {
+    (duration.as_millis() / 100).min(u16::MAX as u128) as u16
+}
+
+/// Formats a duration as `HH:MM:SS`, the form the bridge uses inside ISO 8601 durations.
+pub fn format_hms(duration: Duration) -> String {
+    let total = duration.as_secs();
+    format!(
+        "{:02}:{:02}:{:02}",
+        total / 3600,
+        (total % 3600) / 60,
+        total % 60
+    )
+}
+
+/// Formats a duration in the same compact style `parse_duration` accepts, e.g. `1h5m`.
+pub fn format_duration(duration: Duration) -> String {
+    let total = duration.as_secs();
+    if total == 0 {
+        return format!("{}ms", duration.as_millis());
+    }
+    let (h, m, s) = (total / 3600, (total % 3600) / 60, total % 60);
+    let mut out = String::new();
+    if h > 0 {
+        out.push_str(&format!("{h}h"));
+    }
+    if m > 0 {
+        out.push_str(&format!("{m}m"));
+    }
+    if s > 0 {
+        out.push_str(&format!("{s}s"));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{format_duration, parse_duration, parse_signed_offset};
+    use crate::error::ParseError;
+
+    #[test]
+    fn parse_duration_compound_units_sums_parts() {
+        assert_eq!(Duration::from_secs(5400), parse_duration("1h30m").unwrap());
+        assert_eq!(Duration::from_millis(500), parse_duration("500ms").unwrap());
+        assert_eq!(Duration::from_secs(90), parse_duration("90").unwrap());
+        assert_eq!(Duration::from_secs(90), parse_duration("1.5m").unwrap());
+    }
+
+    #[test]
+    fn parse_duration_unknown_unit_gives_invalid_duration_error() {
+        assert!(matches!(
+            parse_duration("10 minutes"),
+            Err(ParseError::InvalidDuration(_))
+        ));
+        assert!(matches!(
+            parse_duration("m"),
+            Err(ParseError::InvalidDuration(_))
+        ));
+    }
+
+    #[test]
+    fn parse_signed_offset_handles_sign() {
+        assert_eq!(-1800, parse_signed_offset("-30m").unwrap());
+        assert_eq!(900, parse_signed_offset("+15m").unwrap());
+        assert_eq!(60, parse_signed_offset("1m").unwrap());
+    }
+
+    #[test]
+    fn format_duration_round_trips_through_parse() {
+        let d = Duration::from_secs(3725);
+        assert_eq!("1h2m5s", format_duration(d));
+        assert_eq!(d, parse_duration(&format_duration(d)).unwrap());
+    }
+}
@@ -14,6 +14,9 @@ pub enum CoreError {
     #[error("file handler IO error: {0}")]
     FileHandlerError(#[from] std::io::Error),
 
+    #[error("could not start the async runtime: {0}")]
+    Runtime(std::io::Error),
+
     #[error("hue bridge returned an error: {0}")]
     Bridge(#[from] HueBridgeError),
 
@@ -28,6 +31,33 @@ pub enum CoreError {
 
     #[error("unexpected response from Hue Bridge: {0}")]
     UnexpectedResponse(String),
+
+    #[error("request not found in the cassette: {0}")]
+    UnexpectedRequest(String),
+
+    #[error("parse error: {0}")]
+    Parse(#[from] ParseError),
+
+    #[error("schedule error: {0}")]
+    Schedule(#[from] ScheduleError),
+
+    #[error("snapshot error: {0}")]
+    Snapshot(#[from] SnapshotError),
+
+    #[error("rule error: {0}")]
+    Rule(#[from] RuleError),
+
+    #[error("animation error: {0}")]
+    Animation(#[from] AnimationError),
+
+    #[error("script error: {0}")]
+    Script(#[from] ScriptError),
+
+    #[error("light '{light}' does not support {feature}")]
+    Unsupported { light: String, feature: String },
+
+    #[error("the {api} API does not support {feature}")]
+    UnsupportedByApi { api: String, feature: String },
 }
 
 #[derive(Debug, Error)]
@@ -40,6 +70,9 @@ pub enum ConfigError {
 
     #[error("config path was invalid")]
     ConfigPathInvalidError,
+
+    #[error("latitude and longitude are not set in the config")]
+    LocationNotConfigured,
 }
 
 #[derive(Debug, Error)]
@@ -53,9 +86,119 @@ pub enum HueBridgeError {
     #[error("unauthorized user")]
     UnauthorizedUser,
 
+    #[error("specified group not found")]
+    GroupNotFound,
+
+    #[error("specified scene not found")]
+    SceneNotFound,
+
+    #[error("specified sensor not found")]
+    SensorNotFound,
+
+    #[error("specified rule not found")]
+    RuleNotFound,
+
+    #[error("specified schedule not found")]
+    ScheduleNotFound,
+
     #[error("unexpected JSON")]
     UnexpectedJSON,
 
     #[error("bridge error {code}: {message}")]
     Other { code: String, message: String },
 }
+
+#[derive(Debug, Error)]
+pub enum ParseError {
+    #[error("invalid duration '{0}'")]
+    InvalidDuration(String),
+
+    #[error("invalid cron expression '{0}'")]
+    InvalidCron(String),
+
+    #[error("invalid trigger '{0}', expected sunrise/sunset with an optional offset")]
+    InvalidTrigger(String),
+
+    #[error("unknown timezone '{0}'")]
+    InvalidTimezone(String),
+
+    #[error("invalid time pattern '{0}'")]
+    InvalidTimePattern(String),
+
+    #[error("invalid brightness '{0}', expected e.g. 50%, 0.5, bri:127 or +10%")]
+    InvalidBrightness(String),
+
+    #[error("unknown color '{0}', expected a color or palette name, #rrggbb, 2700K or xy:0.4,0.4")]
+    UnknownColor(String),
+
+    #[error("color '{name}' in the config has an invalid value '{value}'")]
+    InvalidColor { name: String, value: String },
+}
+
+#[derive(Debug, Error)]
+pub enum ScheduleError {
+    #[error("schedule file could not be parsed: {0}")]
+    File(#[from] toml::de::Error),
+
+    #[error("schedule entry '{0}' not found")]
+    EntryNotFound(String),
+
+    #[error("schedule entry '{name}' is invalid: {reason}")]
+    InvalidEntry { name: String, reason: String },
+}
+
+#[derive(Debug, Error)]
+pub enum SnapshotError {
+    #[error("snapshot '{0}' not found")]
+    NotFound(String),
+
+    #[error("invalid snapshot name '{0}', use letters, digits, '-' and '_' only")]
+    InvalidName(String),
+}
+
+#[derive(Debug, Error)]
+pub enum RuleError {
+    #[error("rule file could not be parsed: {0}")]
+    File(#[from] toml::de::Error),
+
+    #[error("rule '{rule}': syntax error: {message}")]
+    Syntax { rule: String, message: String },
+
+    #[error("rule '{rule}': {kind} \"{name}\" does not exist on the bridge")]
+    UnknownResource {
+        rule: String,
+        kind: String,
+        name: String,
+    },
+
+    #[error("rule '{rule}': sensor \"{sensor}\" has no attribute '{attribute}'")]
+    InvalidAttribute {
+        rule: String,
+        sensor: String,
+        attribute: String,
+    },
+
+    #[error("rule '{rule}': {message}")]
+    Invalid { rule: String, message: String },
+}
+
+#[derive(Debug, Error)]
+pub enum AnimationError {
+    #[error("animation file could not be parsed: {0}")]
+    File(#[from] toml::de::Error),
+
+    #[error("unknown animation '{0}', expected a preset or the path to an animation file")]
+    UnknownPreset(String),
+
+    #[error("{0}")]
+    Invalid(String),
+}
+
+#[derive(Debug, Error)]
+pub enum ScriptError {
+    #[error("script '{script}' could not be compiled: {message}")]
+    Compile { script: String, message: String },
+
+    #[error("script '{script}' failed: {message}")]
+    Runtime { script: String, message: String },
+}
@@ -0,0 +1,175 @@
+use std::fmt;
+use std::future::Future;
+
+use futures::StreamExt;
+
+use crate::error::{CoreError, CoreResult};
+
+/// How many calls `fan_out` keeps in flight unless told otherwise. The rate limiter still
+/// spaces the requests to each bridge; running a few at once overlaps their round trips.
+pub const DEFAULT_CONCURRENCY: usize = 4;
+
+/// How the call for one target went.
+#[derive(Debug)]
+pub enum Outcome<T> {
+    Success(T),
+    /// The bridge answered, but with an error or something that made no sense.
+    BridgeError(CoreError),
+    /// The bridge couldn't be reached.
+    NetworkError(CoreError),
+}
+
+impl<T> Outcome<T> {
+    pub fn is_success(&self) -> bool {
+        matches!(self, Outcome::Success(_))
+    }
+}
+
+impl<T> From<CoreResult<T>> for Outcome<T> {
+    fn from(result: CoreResult<T>) -> Self {
+        match result {
+            Ok(value) => Outcome::Success(value),
+            Err(err @ CoreError::Network(_)) => Outcome::NetworkError(err),
+            Err(err) => Outcome::BridgeError(err),
+        }
+    }
+}
+
+impl<T> fmt::Display for Outcome<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Success(_) => write!(f, "ok"),
+            Outcome::BridgeError(err) => write!(f, "bridge error: {err}"),
+            Outcome::NetworkError(err) => write!(f, "network error: {err}"),
+        }
+    }
+}
+
+/// The outcome for every target of a `fan_out`, in the order the targets were given.
+#[derive(Debug)]
+pub struct FanOutReport<K, T> {
+    pub results: Vec<(K, Outcome<T>)>,
+}
+
+impl<K, T> Default for FanOutReport<K, T> {
+    fn default() -> Self {
+        Self {
+            results: Vec::new(),
+        }
+    }
+}
+
+impl<K, T> FanOutReport<K, T> {
+    pub fn succeeded(&self) -> usize {
+        self.results
+            .iter()
+            .filter(|(_, outcome)| outcome.is_success())
+            .count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.results.len() - self.succeeded()
+    }
+
+    /// Targets whose call failed, with the error.
+    pub fn failures(&self) -> impl Iterator<Item = (&K, &CoreError)> {
+        self.results
+            .iter()
+            .filter_map(|(target, outcome)| match outcome {
+                Outcome::Success(_) => None,
+                Outcome::BridgeError(err) | Outcome::NetworkError(err) => Some((target, err)),
+            })
+    }
+}
+
+/// Runs `call` for every target, with up to `concurrency` calls in flight at once.
+///
+/// A failing target doesn't stop the others; every outcome ends up in the report.
+pub async fn fan_out<K, T, F, Fut>(
+    targets: impl IntoIterator<Item = K>,
+    concurrency: usize,
+    mut call: F,
+) -> FanOutReport<K, T>
+where
+    K: Clone,
+    F: FnMut(K) -> Fut,
+    Fut: Future<Output = CoreResult<T>>,
+{
+    let results = futures::stream::iter(targets)
+        .map(|target| {
+            let result = call(target.clone());
+            async move { (target, Outcome::from(result.await)) }
+        })
+        .buffered(concurrency.max(1))
+        .collect()
+        .await;
+    FanOutReport { results }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use super::{Outcome, fan_out};
+    use crate::domain::light::{LightKind, LightState};
+    use crate::error::{CoreError, HueBridgeError};
+    use crate::hue_api::HueApi;
+    use crate::testing::MockBridge;
+
+    #[tokio::test]
+    pub async fn fan_out_keeps_target_order_and_reports_each_outcome() {
+        // Arrange
+        let bridge = Arc::new(
+            MockBridge::new()
+                .with_light(1, "Desk", LightKind::OnOff)
+                .with_light(2, "Shelf", LightKind::OnOff),
+        );
+        let api = bridge.api();
+        let on = LightState::default().with_on(true);
+
+        // Act
+        let report = fan_out([1, 9, 2], 2, |id| {
+            let (api, on) = (&api, &on);
+            async move {
+                api.async_set_light_state("ip", "user", id, on)
+                    .await
+                    .map(|_| ())
+            }
+        })
+        .await;
+
+        // Assert
+        let ids: Vec<u32> = report.results.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, vec![1, 9, 2]);
+        assert!(report.results[0].1.is_success());
+        assert!(matches!(report.results[1].1, Outcome::BridgeError(_)));
+        assert_eq!((report.succeeded(), report.failed()), (2, 1));
+        assert_eq!(
+            report.failures().map(|(id, _)| *id).collect::<Vec<_>>(),
+            vec![9]
+        );
+    }
+
+    #[tokio::test]
+    pub async fn fan_out_never_exceeds_the_concurrency_limit() {
+        // Arrange
+        let running = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+
+        // Act
+        let report = fan_out(0..10, 3, |_| async {
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            running.fetch_sub(1, Ordering::SeqCst);
+            Err::<(), _>(CoreError::Bridge(HueBridgeError::UnexpectedJSON))
+        })
+        .await;
+
+        // Assert
+        assert_eq!(peak.load(Ordering::SeqCst), 3);
+        assert_eq!(report.failed(), 10);
+    }
+}
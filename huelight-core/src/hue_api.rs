@@ -1,21 +1,36 @@
 use std::sync::Arc;
 
 use async_trait::async_trait;
+use serde_json::json;
 
 use crate::client::{Header, HueClient};
+use crate::domain::light::{Light, LightId, LightState, Lights, NewLights};
 use crate::error::{CoreError, CoreResult, HueBridgeError};
 use crate::logger::ILogger;
+use crate::models::bridgeconfig::BridgeConfig;
 use crate::models::createuser::{CreateUserEntry, CreateUserResponse, User};
-use crate::models::hueerror::HueResponse;
-use crate::models::light::{LightResponse, LightState};
+use crate::models::group::{GroupId, GroupResponse, NewGroup};
+use crate::models::hueerror::{HueResponse, check_response, created_id};
+use crate::models::rule::{Rule, RuleId, RuleResponse};
+use crate::models::scene::SceneResponse;
+use crate::models::schedule::{Schedule, ScheduleId, ScheduleResponse};
+use crate::models::sensor::{
+    NewSensor, Sensor, SensorConfig, SensorId, SensorResponse, SensorStateUpdate,
+};
 
+/// The bridge's v1 REST API.
+///
+/// Methods that change the bridge fail with `CoreError::Bridge` when the bridge answers with
+/// an error entry, so callers don't need to check the returned response list themselves.
 #[async_trait]
 pub trait HueApi {
-    async fn async_get_all_lights(
+    async fn async_get_all_lights(&self, ip_address: &str, username: &str) -> CoreResult<Lights>;
+    async fn async_get_light(
         &self,
         ip_address: &str,
         username: &str,
-    ) -> CoreResult<LightResponse>;
+        light_id: LightId,
+    ) -> CoreResult<Light>;
     async fn async_set_light_state(
         &self,
         ip_address: &str,
@@ -23,67 +38,726 @@ pub trait HueApi {
         light_id: u32,
         state: &LightState,
     ) -> CoreResult<HueResponse>;
+    async fn async_rename_light(
+        &self,
+        ip_address: &str,
+        username: &str,
+        light_id: LightId,
+        name: &str,
+    ) -> CoreResult<HueResponse>;
+    async fn async_delete_light(
+        &self,
+        ip_address: &str,
+        username: &str,
+        light_id: LightId,
+    ) -> CoreResult<HueResponse>;
+    async fn async_search_lights(
+        &self,
+        ip_address: &str,
+        username: &str,
+        serial_numbers: &[String],
+    ) -> CoreResult<HueResponse>;
+    async fn async_get_new_lights(&self, ip_address: &str, username: &str)
+    -> CoreResult<NewLights>;
+    async fn async_get_all_groups(
+        &self,
+        ip_address: &str,
+        username: &str,
+    ) -> CoreResult<GroupResponse>;
+    async fn async_set_group_state(
+        &self,
+        ip_address: &str,
+        username: &str,
+        group_id: GroupId,
+        state: &LightState,
+    ) -> CoreResult<HueResponse>;
+    async fn async_create_group(
+        &self,
+        ip_address: &str,
+        username: &str,
+        group: &NewGroup,
+    ) -> CoreResult<GroupId>;
+    async fn async_set_group_lights(
+        &self,
+        ip_address: &str,
+        username: &str,
+        group_id: GroupId,
+        light_ids: &[LightId],
+    ) -> CoreResult<HueResponse>;
+    async fn async_get_all_scenes(
+        &self,
+        ip_address: &str,
+        username: &str,
+    ) -> CoreResult<SceneResponse>;
+    async fn async_recall_scene(
+        &self,
+        ip_address: &str,
+        username: &str,
+        group_id: GroupId,
+        scene_id: &str,
+    ) -> CoreResult<HueResponse>;
+    async fn async_get_config(&self, ip_address: &str, username: &str) -> CoreResult<BridgeConfig>;
+    async fn async_get_unauthenticated_config(&self, ip_address: &str) -> CoreResult<BridgeConfig>;
+    async fn async_is_whitelisted(&self, ip_address: &str, username: &str) -> CoreResult<bool>;
+    async fn async_delete_user(
+        &self,
+        ip_address: &str,
+        username: &str,
+        user_to_delete: &str,
+    ) -> CoreResult<HueResponse>;
+    async fn async_get_all_sensors(
+        &self,
+        ip_address: &str,
+        username: &str,
+    ) -> CoreResult<SensorResponse>;
+    async fn async_get_sensor(
+        &self,
+        ip_address: &str,
+        username: &str,
+        sensor_id: SensorId,
+    ) -> CoreResult<Sensor>;
+    async fn async_set_sensor_config(
+        &self,
+        ip_address: &str,
+        username: &str,
+        sensor_id: SensorId,
+        config: &SensorConfig,
+    ) -> CoreResult<HueResponse>;
+    async fn async_set_sensor_state(
+        &self,
+        ip_address: &str,
+        username: &str,
+        sensor_id: SensorId,
+        state: &SensorStateUpdate,
+    ) -> CoreResult<HueResponse>;
+    async fn async_create_sensor(
+        &self,
+        ip_address: &str,
+        username: &str,
+        sensor: &NewSensor,
+    ) -> CoreResult<SensorId>;
+    async fn async_get_all_rules(
+        &self,
+        ip_address: &str,
+        username: &str,
+    ) -> CoreResult<RuleResponse>;
+    async fn async_create_rule(
+        &self,
+        ip_address: &str,
+        username: &str,
+        rule: &Rule,
+    ) -> CoreResult<RuleId>;
+    async fn async_update_rule(
+        &self,
+        ip_address: &str,
+        username: &str,
+        rule_id: RuleId,
+        rule: &Rule,
+    ) -> CoreResult<HueResponse>;
+    async fn async_delete_rule(
+        &self,
+        ip_address: &str,
+        username: &str,
+        rule_id: RuleId,
+    ) -> CoreResult<HueResponse>;
+    async fn async_get_all_schedules(
+        &self,
+        ip_address: &str,
+        username: &str,
+    ) -> CoreResult<ScheduleResponse>;
+    async fn async_create_schedule(
+        &self,
+        ip_address: &str,
+        username: &str,
+        schedule: &Schedule,
+    ) -> CoreResult<ScheduleId>;
+    async fn async_update_schedule(
+        &self,
+        ip_address: &str,
+        username: &str,
+        schedule_id: ScheduleId,
+        schedule: &Schedule,
+    ) -> CoreResult<HueResponse>;
+    async fn async_delete_schedule(
+        &self,
+        ip_address: &str,
+        username: &str,
+        schedule_id: ScheduleId,
+    ) -> CoreResult<HueResponse>;
+}
+
+/// HTTP methods that carry a JSON body.
+enum WriteMethod {
+    Put,
+    Post,
 }
 
-pub struct HueApiV1 {
-    client: Arc<dyn HueClient + Send + Sync>,
-    logger: Arc<dyn ILogger + Send + Sync>,
-}
+pub struct HueApiV1 {
+    client: Arc<dyn HueClient + Send + Sync>,
+    logger: Arc<dyn ILogger + Send + Sync>,
+}
+
+impl HueApiV1 {
+    pub fn new(
+        client: Arc<dyn HueClient + Send + Sync>,
+        logger: Arc<dyn ILogger + Send + Sync>,
+    ) -> Self {
+        Self { client, logger }
+    }
+
+    /// Parses a bridge response body, logging a truncated copy of the raw JSON on failure.
+    ///
+    /// GETs answer with an error list (e.g. unauthorized user) instead of the resource when
+    /// they fail, so that is checked for before reporting a serialization error.
+    fn parse_response<T: serde::de::DeserializeOwned>(
+        &self,
+        what: &str,
+        res: &str,
+    ) -> CoreResult<T> {
+        serde_json::from_str::<T>(res).map_err(|err| {
+            if let Ok(errors) = serde_json::from_str::<HueResponse>(res)
+                && let Err(bridge_err) = check_response(&errors)
+            {
+                return bridge_err;
+            }
+            self.logger.warn(&format!(
+                "Failed to parse {what} JSON: {err}. Raw (truncated): {}",
+                &res[..res.len().min(200)]
+            ));
+            CoreError::Serialization(err)
+        })
+    }
+
+    /// Sends `body` as JSON with the given method and parses the bridge's response list,
+    /// turning the first error entry in it into a `CoreError::Bridge`.
+    async fn send_json<B: serde::Serialize + Sync>(
+        &self,
+        method: WriteMethod,
+        url: &str,
+        body: &B,
+        what: &str,
+    ) -> CoreResult<HueResponse> {
+        let json = serde_json::to_string(body).map_err(CoreError::Serialization)?;
+        let headers = vec![Header::new("Content-Type", "application/json")];
+        let res = match method {
+            WriteMethod::Post => self.client.post_json(url, &json, &headers).await?,
+            WriteMethod::Put => self.client.put_json(url, &json, &headers).await?,
+        };
+        let response = self.parse_response(what, &res)?;
+        check_response(&response)?;
+        Ok(response)
+    }
+
+    /// Sends a DELETE and parses the bridge's response list like `send_json`.
+    async fn send_delete(&self, url: &str, what: &str) -> CoreResult<HueResponse> {
+        let res = self.client.delete(url, &[]).await?;
+        let response = self.parse_response(what, &res)?;
+        check_response(&response)?;
+        Ok(response)
+    }
+}
+
+#[async_trait]
+impl HueApi for HueApiV1 {
+    async fn async_get_all_lights(&self, ip_address: &str, username: &str) -> CoreResult<Lights> {
+        /*
+         * Sends a get request to the input IP Address of the Hue Bridge to retrieve all lights connected to the bridge.
+         */
+
+        let url = format!("http://{}/api/{}/lights", ip_address, username);
+        let res = self.client.get(&url, &[]).await?;
+        self.parse_response("lights", &res)
+    }
+
+    async fn async_get_light(
+        &self,
+        ip_address: &str,
+        username: &str,
+        light_id: LightId,
+    ) -> CoreResult<Light> {
+        /*
+         * Sends a get request to retrieve a single light, including its capabilities.
+         */
+
+        let url = format!("http://{}/api/{}/lights/{}", ip_address, username, light_id);
+        let res = self.client.get(&url, &[]).await?;
+        self.parse_response("light", &res)
+    }
+
+    async fn async_set_light_state(
+        &self,
+        ip_address: &str,
+        username: &str,
+        light_id: u32,
+        state: &LightState,
+    ) -> CoreResult<HueResponse> {
+        /*
+         * Sends a PUT request to change the state of a specific light.
+         */
+
+        let url = format!(
+            "http://{}/api/{}/lights/{}/state",
+            ip_address, username, light_id
+        );
+        tracing::debug!(light_id, %state, "setting light state");
+        self.send_json(WriteMethod::Put, &url, state, "light state")
+            .await
+    }
+
+    async fn async_rename_light(
+        &self,
+        ip_address: &str,
+        username: &str,
+        light_id: LightId,
+        name: &str,
+    ) -> CoreResult<HueResponse> {
+        /*
+         * Sends a PUT request to change the name of a light.
+         */
+
+        let url = format!("http://{}/api/{}/lights/{}", ip_address, username, light_id);
+        self.send_json(
+            WriteMethod::Put,
+            &url,
+            &json!({ "name": name }),
+            "rename light",
+        )
+        .await
+    }
+
+    async fn async_delete_light(
+        &self,
+        ip_address: &str,
+        username: &str,
+        light_id: LightId,
+    ) -> CoreResult<HueResponse> {
+        /*
+         * Sends a DELETE request to remove a light from the bridge.
+         */
+
+        let url = format!("http://{}/api/{}/lights/{}", ip_address, username, light_id);
+        self.send_delete(&url, "light delete").await
+    }
+
+    async fn async_search_lights(
+        &self,
+        ip_address: &str,
+        username: &str,
+        serial_numbers: &[String],
+    ) -> CoreResult<HueResponse> {
+        /*
+         * Sends a POST request to start a 40 second search for new lights. Serial numbers let
+         * the bridge find lights that are still paired with another bridge.
+         */
+
+        let url = format!("http://{}/api/{}/lights", ip_address, username);
+        let body = if serial_numbers.is_empty() {
+            json!({})
+        } else {
+            json!({ "deviceid": serial_numbers })
+        };
+        self.send_json(WriteMethod::Post, &url, &body, "light search")
+            .await
+    }
+
+    async fn async_get_new_lights(
+        &self,
+        ip_address: &str,
+        username: &str,
+    ) -> CoreResult<NewLights> {
+        /*
+         * Sends a get request for the lights found by the most recent search.
+         */
+
+        let url = format!("http://{}/api/{}/lights/new", ip_address, username);
+        let res = self.client.get(&url, &[]).await?;
+        self.parse_response("new lights", &res)
+    }
+
+    async fn async_get_all_groups(
+        &self,
+        ip_address: &str,
+        username: &str,
+    ) -> CoreResult<GroupResponse> {
+        /*
+         * Sends a get request to retrieve all groups (rooms, zones and light groups) on the bridge.
+         */
+
+        let url = format!("http://{}/api/{}/groups", ip_address, username);
+        let res = self.client.get(&url, &[]).await?;
+        self.parse_response("groups", &res)
+    }
+
+    async fn async_set_group_state(
+        &self,
+        ip_address: &str,
+        username: &str,
+        group_id: GroupId,
+        state: &LightState,
+    ) -> CoreResult<HueResponse> {
+        /*
+         * Sends a PUT request to change the state of every light in a group. Group 0 is all lights.
+         */
+
+        let url = format!(
+            "http://{}/api/{}/groups/{}/action",
+            ip_address, username, group_id
+        );
+        tracing::debug!(group_id, %state, "setting group action");
+        self.send_json(WriteMethod::Put, &url, state, "group action")
+            .await
+    }
+
+    async fn async_create_group(
+        &self,
+        ip_address: &str,
+        username: &str,
+        group: &NewGroup,
+    ) -> CoreResult<GroupId> {
+        /*
+         * Sends a POST request to create a group and returns its new ID.
+         */
+
+        let url = format!("http://{}/api/{}/groups", ip_address, username);
+        let response = self
+            .send_json(WriteMethod::Post, &url, group, "create group")
+            .await?;
+        created_id(&response)?
+            .parse()
+            .map_err(|_| CoreError::UnexpectedResponse("group ID was not a number".to_string()))
+    }
+
+    async fn async_set_group_lights(
+        &self,
+        ip_address: &str,
+        username: &str,
+        group_id: GroupId,
+        light_ids: &[LightId],
+    ) -> CoreResult<HueResponse> {
+        /*
+         * Sends a PUT request to replace the lights that belong to a group.
+         */
+
+        let url = format!("http://{}/api/{}/groups/{}", ip_address, username, group_id);
+        let lights: Vec<String> = light_ids.iter().map(ToString::to_string).collect();
+        self.send_json(
+            WriteMethod::Put,
+            &url,
+            &json!({ "lights": lights }),
+            "group lights",
+        )
+        .await
+    }
+
+    async fn async_get_all_scenes(
+        &self,
+        ip_address: &str,
+        username: &str,
+    ) -> CoreResult<SceneResponse> {
+        /*
+         * Sends a get request to retrieve all scenes stored on the bridge.
+         */
+
+        let url = format!("http://{}/api/{}/scenes", ip_address, username);
+        let res = self.client.get(&url, &[]).await?;
+        self.parse_response("scenes", &res)
+    }
+
+    async fn async_recall_scene(
+        &self,
+        ip_address: &str,
+        username: &str,
+        group_id: GroupId,
+        scene_id: &str,
+    ) -> CoreResult<HueResponse> {
+        /*
+         * Recalls a scene by sending it as the action of the given group.
+         */
+
+        let url = format!(
+            "http://{}/api/{}/groups/{}/action",
+            ip_address, username, group_id
+        );
+        self.send_json(
+            WriteMethod::Put,
+            &url,
+            &json!({ "scene": scene_id }),
+            "scene recall",
+        )
+        .await
+    }
+
+    async fn async_get_config(&self, ip_address: &str, username: &str) -> CoreResult<BridgeConfig> {
+        /*
+         * Sends a get request for the full bridge configuration, including the whitelist.
+         */
+
+        let url = format!("http://{}/api/{}/config", ip_address, username);
+        let res = self.client.get(&url, &[]).await?;
+        self.parse_response("bridge config", &res)
+    }
+
+    async fn async_get_unauthenticated_config(&self, ip_address: &str) -> CoreResult<BridgeConfig> {
+        /*
+         * Sends a get request for the public subset of the bridge configuration. Works without
+         * a username, so it tells whether the bridge is reachable at all.
+         */
+
+        let url = format!("http://{}/api/config", ip_address);
+        let res = self.client.get(&url, &[]).await?;
+        self.parse_response("public bridge config", &res)
+    }
+
+    async fn async_is_whitelisted(&self, ip_address: &str, username: &str) -> CoreResult<bool> {
+        /*
+         * Checks whether the username is still an authorized application on the bridge. The
+         * bridge answers /config for unknown usernames with the public subset, which has no
+         * whitelist, so a missing whitelist entry means the username was revoked.
+         */
+
+        match self.async_get_config(ip_address, username).await {
+            Ok(config) => Ok(config.is_whitelisted(username)),
+            Err(CoreError::Bridge(HueBridgeError::UnauthorizedUser)) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn async_delete_user(
+        &self,
+        ip_address: &str,
+        username: &str,
+        user_to_delete: &str,
+    ) -> CoreResult<HueResponse> {
+        /*
+         * Sends a DELETE request to remove an application from the bridge whitelist.
+         */
+
+        let url = format!(
+            "http://{}/api/{}/config/whitelist/{}",
+            ip_address, username, user_to_delete
+        );
+        self.send_delete(&url, "whitelist delete").await
+    }
+
+    async fn async_get_all_sensors(
+        &self,
+        ip_address: &str,
+        username: &str,
+    ) -> CoreResult<SensorResponse> {
+        /*
+         * Sends a get request to retrieve all sensors, including switches and virtual CLIP sensors.
+         */
+
+        let url = format!("http://{}/api/{}/sensors", ip_address, username);
+        let res = self.client.get(&url, &[]).await?;
+        self.parse_response("sensors", &res)
+    }
+
+    async fn async_get_sensor(
+        &self,
+        ip_address: &str,
+        username: &str,
+        sensor_id: SensorId,
+    ) -> CoreResult<Sensor> {
+        /*
+         * Sends a get request to retrieve a single sensor.
+         */
+
+        let url = format!(
+            "http://{}/api/{}/sensors/{}",
+            ip_address, username, sensor_id
+        );
+        let res = self.client.get(&url, &[]).await?;
+        self.parse_response("sensor", &res)
+    }
+
+    async fn async_set_sensor_config(
+        &self,
+        ip_address: &str,
+        username: &str,
+        sensor_id: SensorId,
+        config: &SensorConfig,
+    ) -> CoreResult<HueResponse> {
+        /*
+         * Sends a PUT request to change a sensor's configuration, e.g. motion sensitivity.
+         */
+
+        let url = format!(
+            "http://{}/api/{}/sensors/{}/config",
+            ip_address, username, sensor_id
+        );
+        self.send_json(WriteMethod::Put, &url, config, "sensor config")
+            .await
+    }
+
+    async fn async_set_sensor_state(
+        &self,
+        ip_address: &str,
+        username: &str,
+        sensor_id: SensorId,
+        state: &SensorStateUpdate,
+    ) -> CoreResult<HueResponse> {
+        /*
+         * Sends a PUT request to change the state of a virtual CLIP sensor.
+         */
+
+        let url = format!(
+            "http://{}/api/{}/sensors/{}/state",
+            ip_address, username, sensor_id
+        );
+        self.send_json(WriteMethod::Put, &url, state, "sensor state")
+            .await
+    }
+
+    async fn async_create_sensor(
+        &self,
+        ip_address: &str,
+        username: &str,
+        sensor: &NewSensor,
+    ) -> CoreResult<SensorId> {
+        /*
+         * Sends a POST request to create a virtual CLIP sensor and returns its new ID.
+         */
+
+        let url = format!("http://{}/api/{}/sensors", ip_address, username);
+        let response = self
+            .send_json(WriteMethod::Post, &url, sensor, "create sensor")
+            .await?;
+        created_id(&response)?
+            .parse()
+            .map_err(|_| CoreError::UnexpectedResponse("sensor ID was not a number".to_string()))
+    }
 
-impl HueApiV1 {
-    pub fn new(
-        client: Arc<dyn HueClient + Send + Sync>,
-        logger: Arc<dyn ILogger + Send + Sync>,
-    ) -> Self {
-        Self { client, logger }
+    async fn async_get_all_rules(
+        &self,
+        ip_address: &str,
+        username: &str,
+    ) -> CoreResult<RuleResponse> {
+        /*
+         * Sends a get request to retrieve all rules installed on the bridge.
+         */
+
+        let url = format!("http://{}/api/{}/rules", ip_address, username);
+        let res = self.client.get(&url, &[]).await?;
+        self.parse_response("rules", &res)
     }
-}
 
-#[async_trait]
-impl HueApi for HueApiV1 {
-    async fn async_get_all_lights(
+    async fn async_create_rule(
         &self,
         ip_address: &str,
         username: &str,
-    ) -> CoreResult<LightResponse> {
+        rule: &Rule,
+    ) -> CoreResult<RuleId> {
         /*
-         * Sends a get request to the input IP Address of the Hue Bridge to retrieve all lights connected to the bridge.
+         * Sends a POST request to install a rule and returns its new ID.
          */
 
-        let url = format!("http://{}/api/{}/lights", ip_address, username);
+        let url = format!("http://{}/api/{}/rules", ip_address, username);
+        let response = self
+            .send_json(WriteMethod::Post, &url, rule, "create rule")
+            .await?;
+        created_id(&response)?
+            .parse()
+            .map_err(|_| CoreError::UnexpectedResponse("rule ID was not a number".to_string()))
+    }
+
+    async fn async_update_rule(
+        &self,
+        ip_address: &str,
+        username: &str,
+        rule_id: RuleId,
+        rule: &Rule,
+    ) -> CoreResult<HueResponse> {
+        /*
+         * Sends a PUT request replacing a rule's name, conditions and actions.
+         */
+
+        let url = format!("http://{}/api/{}/rules/{}", ip_address, username, rule_id);
+        self.send_json(WriteMethod::Put, &url, rule, "update rule")
+            .await
+    }
+
+    async fn async_delete_rule(
+        &self,
+        ip_address: &str,
+        username: &str,
+        rule_id: RuleId,
+    ) -> CoreResult<HueResponse> {
+        /*
+         * Sends a DELETE request to remove a rule from the bridge.
+         */
+
+        let url = format!("http://{}/api/{}/rules/{}", ip_address, username, rule_id);
+        self.send_delete(&url, "rule delete").await
+    }
+
+    async fn async_get_all_schedules(
+        &self,
+        ip_address: &str,
+        username: &str,
+    ) -> CoreResult<ScheduleResponse> {
+        /*
+         * Sends a get request to retrieve all schedules stored on the bridge.
+         */
+
+        let url = format!("http://{}/api/{}/schedules", ip_address, username);
         let res = self.client.get(&url, &[]).await?;
-        let parsed = serde_json::from_str::<LightResponse>(&res).map_err(|err| {
-            self.logger.log(&format!(
-                "Failed to parse lights JSON: {err}. Raw (truncated): {}",
-                &res[..res.len().min(200)]
-            ));
-            CoreError::Serialization(err)
-        })?;
+        self.parse_response("schedules", &res)
+    }
+
+    async fn async_create_schedule(
+        &self,
+        ip_address: &str,
+        username: &str,
+        schedule: &Schedule,
+    ) -> CoreResult<ScheduleId> {
+        /*
+         * Sends a POST request to store a schedule or start a timer and returns its new ID.
+         */
 
-        Ok(parsed)
+        let url = format!("http://{}/api/{}/schedules", ip_address, username);
+        let response = self
+            .send_json(WriteMethod::Post, &url, schedule, "create schedule")
+            .await?;
+        created_id(&response)?
+            .parse()
+            .map_err(|_| CoreError::UnexpectedResponse("schedule ID was not a number".to_string()))
     }
 
-    async fn async_set_light_state(
+    async fn async_update_schedule(
         &self,
         ip_address: &str,
         username: &str,
-        light_id: u32,
-        state: &LightState,
+        schedule_id: ScheduleId,
+        schedule: &Schedule,
     ) -> CoreResult<HueResponse> {
         /*
-         * Sends a PUT request to change the state of a specific light.
+         * Sends a PUT request replacing a schedule's attributes.
          */
 
         let url = format!(
-            "http://{}/api/{}/lights/{}/state",
-            ip_address, username, light_id
+            "http://{}/api/{}/schedules/{}",
+            ip_address, username, schedule_id
         );
-        let json_state = serde_json::to_string(&state).map_err(CoreError::Serialization)?;
-        let headers = vec![Header::new("Content-Type", "application/json")];
-        let res = self.client.put_json(&url, &json_state, &headers).await?;
-        let hue_response_list =
-            serde_json::from_str::<HueResponse>(&res).map_err(CoreError::Serialization)?;
-        Ok(hue_response_list)
+        self.send_json(WriteMethod::Put, &url, schedule, "update schedule")
+            .await
+    }
+
+    async fn async_delete_schedule(
+        &self,
+        ip_address: &str,
+        username: &str,
+        schedule_id: ScheduleId,
+    ) -> CoreResult<HueResponse> {
+        /*
+         * Sends a DELETE request to remove a schedule or cancel a timer.
+         */
+
+        let url = format!(
+            "http://{}/api/{}/schedules/{}",
+            ip_address, username, schedule_id
+        );
+        self.send_delete(&url, "schedule delete").await
     }
 }
 
@@ -107,7 +781,7 @@ pub async fn async_create_user(
     let res = client.post_json(&url, &json_user, &headers).await?;
 
     let parsed: CreateUserResponse = serde_json::from_str(&res).map_err(|err| {
-        logger.log(&format!(
+        logger.warn(&format!(
             "Failed to parse CreateUserResponse JSON: {err}. Raw(truncated): {}",
             &res[..res.len().min(200)]
         ));
@@ -126,7 +800,7 @@ pub async fn async_create_user(
                 "Error creating user: {} - {} - {}",
                 error._type, error.address, error.description
             );
-            logger.log(&message);
+            logger.error(&message);
             match error._type {
                 101 => Err(CoreError::Bridge(HueBridgeError::LinkButtonNotPressed)),
                 _default => Err(CoreError::Bridge(HueBridgeError::Other {
@@ -138,7 +812,7 @@ pub async fn async_create_user(
         None => {
             let message =
                 "User could not be created. The Hue Bridge returned an unrecognized JSON format.";
-            logger.log(message);
+            logger.error(message);
             Err(CoreError::UnexpectedResponse(message.to_string()))
         }
     }
@@ -147,85 +821,20 @@ pub async fn async_create_user(
 #[cfg(test)]
 mod tests {
     use std::sync::Arc;
+    use std::time::Duration;
 
     use super::{HueApi, HueApiV1, async_create_user};
-    use crate::client::{Header, HueClient};
-    use crate::error::{CoreError, CoreResult, HueBridgeError};
+    use crate::domain::brightness::Brightness;
+    use crate::domain::color::Color;
+    use crate::domain::light::{Light, LightFeatures, LightKind, LightState};
+    use crate::error::{CoreError, HueBridgeError};
     use crate::logger::{ILogger, Logger};
+    use crate::models::group::NewGroup;
     use crate::models::hueerror::HueResponseEntry;
-    use crate::models::light::{Light, LightState};
-    use async_trait::async_trait;
-
-    /// Closure used to mock out behavior in the MockHueClient for HueClient.get
-    pub type GetFn = Box<dyn Fn(&str) -> CoreResult<String> + Send + Sync>;
-    /// Closure used to mock out behavior in the MockHueClient for HueClient.post_json
-    pub type PostJsonFn = Box<dyn Fn(&str, &str) -> CoreResult<String> + Send + Sync>;
-    /// Closure used to mock out behavior in the MockHueClient for HueClient.put_json
-    pub type PutJsonFn = Box<dyn Fn(&str, &str) -> CoreResult<String> + Send + Sync>;
-
-    struct MockHueClient {
-        pub post_json_fn: PostJsonFn,
-        pub get_fn: GetFn,
-        pub put_json_fn: PutJsonFn,
-    }
-
-    impl MockHueClient {
-        /// Initializes a new MockHueClient with 'Ok' returns for get, put_json, and post_json.
-        pub fn new() -> Self {
-            Self {
-                post_json_fn: Box::new(|_, _| Ok("[]".to_string())),
-                get_fn: Box::new(|_| Ok("[]".to_string())),
-                put_json_fn: Box::new(|_, _| Ok("[]".to_string())),
-            }
-        }
-
-        /// Provides a means to implement mocked behavior to MockHueClient.post_json
-        pub fn with_post_json<F>(mut self, f: F) -> Self
-        where
-            F: Fn(&str, &str) -> CoreResult<String> + Send + Sync + 'static,
-        {
-            self.post_json_fn = Box::new(f);
-            self
-        }
-
-        /// Provides a means to implement mocked behavior to MockHueClient.get
-        pub fn with_get<F>(mut self, f: F) -> Self
-        where
-            F: Fn(&str) -> CoreResult<String> + Send + Sync + 'static,
-        {
-            self.get_fn = Box::new(f);
-            self
-        }
-
-        /// Provides a means to implement mocked behavior to MockHueClient.put_json
-        pub fn with_put_json<F>(mut self, f: F) -> Self
-        where
-            F: Fn(&str, &str) -> CoreResult<String> + Send + Sync + 'static,
-        {
-            self.put_json_fn = Box::new(f);
-            self
-        }
-    }
-
-    #[async_trait]
-    impl HueClient for MockHueClient {
-        async fn post_json(
-            &self,
-            url: &str,
-            body: &str,
-            _headers: &[Header],
-        ) -> CoreResult<String> {
-            (self.post_json_fn)(url, body)
-        }
-
-        async fn get(&self, url: &str, _headers: &[Header]) -> CoreResult<String> {
-            (self.get_fn)(url)
-        }
-
-        async fn put_json(&self, url: &str, body: &str, _headers: &[Header]) -> CoreResult<String> {
-            (self.put_json_fn)(url, body)
-        }
-    }
+    use crate::models::rule::{Action, Rule};
+    use crate::models::schedule::{Schedule, TimePattern};
+    use crate::models::sensor::{ClipSensorType, NewSensor};
+    use crate::testing::MockHueClient;
 
     #[tokio::test]
     async fn async_create_user_successresponse_logs_username() {
@@ -309,22 +918,32 @@ mod tests {
         let parsed_result = result.unwrap();
         let expected_light1 = Light {
             name: "Living Room Light".to_string(),
-            _type: "Extended color light".to_string(),
+            kind: LightKind::ExtendedColor,
             state: LightState::default()
                 .with_on(true)
-                .with_brightness(200)
-                .with_hue(50000)
-                .with_saturation(150),
+                .with_brightness(Brightness::from_bri(200))
+                .with_color(Color::HueSaturation {
+                    hue: 50000.0 / 65535.0 * 360.0,
+                    saturation: 150.0 / 254.0 * 100.0,
+                }),
+            reachable: true,
+            features: LightFeatures::for_kind(&LightKind::ExtendedColor),
+            ..Default::default()
         };
 
         let expected_light2 = Light {
             name: "Bedroom Light".to_string(),
-            _type: "Dimmable light".to_string(),
+            kind: LightKind::Dimmable,
             state: LightState::default()
                 .with_on(false)
-                .with_brightness(100)
-                .with_hue(30000)
-                .with_saturation(100),
+                .with_brightness(Brightness::from_bri(100))
+                .with_color(Color::HueSaturation {
+                    hue: 30000.0 / 65535.0 * 360.0,
+                    saturation: 100.0 / 254.0 * 100.0,
+                }),
+            reachable: true,
+            features: LightFeatures::for_kind(&LightKind::Dimmable),
+            ..Default::default()
         };
 
         let light1 = parsed_result.0.get(&1).unwrap();
@@ -334,6 +953,220 @@ mod tests {
         assert_eq!(light2, &expected_light2);
     }
 
+    #[tokio::test]
+    async fn async_recall_scene_puts_scene_to_group_action() {
+        // Arrange
+        let mock_hue_client = Arc::new(MockHueClient::new().with_put_json(|url, body| {
+            assert_eq!("http://ip/api/user/groups/3/action", url);
+            assert_eq!(r#"{"scene":"AbC123"}"#, body);
+            Ok(r#"[{"success":{"/groups/3/action/scene":"AbC123"}}]"#.to_string())
+        }));
+        let logger: Arc<Logger> = Arc::new(Logger::default());
+        let api = HueApiV1::new(mock_hue_client, logger);
+
+        // Act
+        let result = api
+            .async_recall_scene("ip", "user", 3, "AbC123")
+            .await
+            .unwrap();
+
+        // Assert
+        assert!(matches!(result[0], HueResponseEntry::Success { .. }));
+    }
+
+    #[tokio::test]
+    async fn async_get_all_lights_unauthorized_returns_bridge_error() {
+        // Arrange
+        let mock_hue_client = Arc::new(MockHueClient::new().with_get(|_url| {
+            Ok(
+                r#"[{"error":{"type":1,"address":"/lights","description":"unauthorized user"}}]"#
+                    .to_string(),
+            )
+        }));
+        let logger: Arc<Logger> = Arc::new(Logger::default());
+        let api = HueApiV1::new(mock_hue_client, logger);
+
+        // Act
+        let result = api.async_get_all_lights("ip", "revoked").await;
+
+        // Assert
+        assert!(matches!(
+            result,
+            Err(CoreError::Bridge(HueBridgeError::UnauthorizedUser))
+        ));
+    }
+
+    #[tokio::test]
+    async fn async_is_whitelisted_public_config_means_revoked() {
+        // Arrange
+        let mock_hue_client = Arc::new(MockHueClient::new().with_get(|url| {
+            assert_eq!("http://ip/api/olduser/config", url);
+            Ok(r#"{"name":"Philips hue","swversion":"1962154010","apiversion":"1.62.0","bridgeid":"001788FFFE010203","modelid":"BSB002"}"#.to_string())
+        }));
+        let logger: Arc<Logger> = Arc::new(Logger::default());
+        let api = HueApiV1::new(mock_hue_client, logger);
+
+        // Act
+        let result = api.async_is_whitelisted("ip", "olduser").await.unwrap();
+
+        // Assert
+        assert!(!result);
+    }
+
+    #[tokio::test]
+    async fn async_delete_user_sends_delete_to_whitelist_entry() {
+        // Arrange
+        let mock_hue_client = Arc::new(MockHueClient::new().with_delete(|url| {
+            assert_eq!("http://ip/api/me/config/whitelist/other", url);
+            Ok(r#"[{"success":"/config/whitelist/other deleted"}]"#.to_string())
+        }));
+        let logger: Arc<Logger> = Arc::new(Logger::default());
+        let api = HueApiV1::new(mock_hue_client, logger);
+
+        // Act
+        let result = api.async_delete_user("ip", "me", "other").await;
+
+        // Assert
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn async_create_sensor_returns_new_id() {
+        // Arrange
+        let mock_hue_client = Arc::new(MockHueClient::new().with_post_json(|url, body| {
+            assert_eq!("http://ip/api/user/sensors", url);
+            assert!(body.contains(r#""type":"CLIPGenericFlag""#));
+            Ok(r#"[{"success":{"id":"12"}}]"#.to_string())
+        }));
+        let logger: Arc<Logger> = Arc::new(Logger::default());
+        let api = HueApiV1::new(mock_hue_client, logger);
+        let sensor = NewSensor::clip("Away mode", ClipSensorType::GenericFlag);
+
+        // Act
+        let result = api
+            .async_create_sensor("ip", "user", &sensor)
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(12, result);
+    }
+
+    #[tokio::test]
+    async fn async_create_group_posts_light_group_and_returns_id() {
+        // Arrange
+        let mock_hue_client = Arc::new(MockHueClient::new().with_post_json(|url, body| {
+            assert_eq!("http://ip/api/user/groups", url);
+            assert_eq!(
+                r#"{"name":"Batch","type":"LightGroup","lights":["1","2"]}"#,
+                body
+            );
+            Ok(r#"[{"success":{"id":"7"}}]"#.to_string())
+        }));
+        let logger: Arc<Logger> = Arc::new(Logger::default());
+        let api = HueApiV1::new(mock_hue_client, logger);
+
+        // Act
+        let result = api
+            .async_create_group("ip", "user", &NewGroup::light_group("Batch", &[1, 2]))
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(7, result);
+    }
+
+    #[tokio::test]
+    async fn async_create_schedule_posts_time_pattern_and_returns_id() {
+        // Arrange
+        let mock_hue_client = Arc::new(MockHueClient::new().with_post_json(|url, body| {
+            assert_eq!("http://ip/api/user/schedules", url);
+            assert!(body.contains(r#""localtime":"PT00:10:00""#));
+            Ok(r#"[{"success":{"id":"3"}}]"#.to_string())
+        }));
+        let logger: Arc<Logger> = Arc::new(Logger::default());
+        let api = HueApiV1::new(mock_hue_client, logger);
+        let command = Action {
+            address: "/api/user/lights/1/state".to_string(),
+            method: "PUT".to_string(),
+            body: serde_json::json!({"on": false}),
+        };
+        let schedule = Schedule::new(
+            "Timer",
+            command,
+            TimePattern::timer(Duration::from_secs(600)),
+        );
+
+        // Act
+        let result = api
+            .async_create_schedule("ip", "user", &schedule)
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(3, result);
+    }
+
+    #[tokio::test]
+    async fn async_search_lights_with_serials_posts_deviceid() {
+        // Arrange
+        let mock_hue_client = Arc::new(MockHueClient::new().with_post_json(|url, body| {
+            assert_eq!("http://ip/api/user/lights", url);
+            assert_eq!(r#"{"deviceid":["45AF34"]}"#, body);
+            Ok(r#"[{"success":{"/lights":"Searching for new devices"}}]"#.to_string())
+        }));
+        let logger: Arc<Logger> = Arc::new(Logger::default());
+        let api = HueApiV1::new(mock_hue_client, logger);
+
+        // Act
+        let result = api
+            .async_search_lights("ip", "user", &["45AF34".to_string()])
+            .await;
+
+        // Assert
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn async_rename_light_puts_name() {
+        // Arrange
+        let mock_hue_client = Arc::new(MockHueClient::new().with_put_json(|url, body| {
+            assert_eq!("http://ip/api/user/lights/3", url);
+            assert_eq!(r#"{"name":"Desk"}"#, body);
+            Ok(r#"[{"success":{"/lights/3/name":"Desk"}}]"#.to_string())
+        }));
+        let logger: Arc<Logger> = Arc::new(Logger::default());
+        let api = HueApiV1::new(mock_hue_client, logger);
+
+        // Act
+        let result = api.async_rename_light("ip", "user", 3, "Desk").await;
+
+        // Assert
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn async_update_rule_bridge_error_returns_error() {
+        // Arrange
+        let mock_hue_client = Arc::new(MockHueClient::new().with_put_json(|url, body| {
+            assert_eq!("http://ip/api/user/rules/4", url);
+            assert!(!body.contains("owner"));
+            Ok(r#"[{"error":{"type":7,"address":"/rules/4/actions","description":"invalid value"}}]"#.to_string())
+        }));
+        let logger: Arc<Logger> = Arc::new(Logger::default());
+        let api = HueApiV1::new(mock_hue_client, logger);
+        let rule = Rule::new("Hall", vec![], vec![]);
+
+        // Act
+        let result = api.async_update_rule("ip", "user", 4, &rule).await;
+
+        // Assert
+        assert!(matches!(
+            result,
+            Err(CoreError::Bridge(HueBridgeError::Other { .. }))
+        ));
+    }
+
     #[tokio::test]
     async fn async_set_light_state_invalid_response_returns_serialization_error() {
         // Arrange
@@ -360,7 +1193,7 @@ mod tests {
     async fn async_set_light_state_valid_response_returns_model() {
         // Arrange
         let mock_hue_client = Arc::new(MockHueClient::new().with_put_json(|_url, _body| {
-            let serialized_response = r#"[ { "error": { "type": 7, "address": "/lights/2/state/bri", "description": "invalid value, null,, for parameter, bri" } }, { "success": { "/lights/2/state/on": false } }]"#;            
+            let serialized_response = r#"[ { "success": { "/lights/2/state/bri": 100 } }, { "success": { "/lights/2/state/on": true } }]"#;
             Ok(serialized_response.to_string())
         }));
         let logger: Arc<Logger> = Arc::new(Logger::default());
@@ -376,15 +1209,34 @@ mod tests {
 
         // Assert
         assert_eq!(2, result.len());
+        assert!(
+            result
+                .iter()
+                .all(|e| matches!(e, HueResponseEntry::Success { .. }))
+        );
+    }
+
+    #[tokio::test]
+    async fn async_set_light_state_error_entry_returns_bridge_error() {
+        // Arrange
+        let mock_hue_client = Arc::new(MockHueClient::new().with_put_json(|_url, _body| {
+            let serialized_response = r#"[ { "error": { "type": 7, "address": "/lights/2/state/bri", "description": "invalid value, null,, for parameter, bri" } }, { "success": { "/lights/2/state/on": false } }]"#;
+            Ok(serialized_response.to_string())
+        }));
+        let logger: Arc<Logger> = Arc::new(Logger::default());
+        let state = LightState::default();
+
+        let api = HueApiV1::new(mock_hue_client, logger);
 
-        let has_success = result
-            .iter()
-            .any(|e| matches!(e, HueResponseEntry::Success { .. }));
-        let has_error = result
-            .iter()
-            .any(|e| matches!(e, HueResponseEntry::Error { .. }));
+        // Act
+        let result = api
+            .async_set_light_state("ipaddress", "username", 1, &state)
+            .await;
 
-        assert!(has_success);
-        assert!(has_error);
+        // Assert
+        assert!(matches!(
+            result,
+            Err(CoreError::Bridge(HueBridgeError::Other { .. }))
+        ));
     }
 }
@@ -1,6 +1,27 @@
+pub mod adaptive;
+pub mod animation;
+pub mod batch;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod cache;
+pub mod cassette;
+pub mod circadian;
 pub mod client;
 pub mod config;
+pub mod domain;
+pub mod dry_run;
+pub mod duration;
 pub mod error;
+pub mod fanout;
 pub mod hue_api;
 pub mod logger;
 pub mod models;
+pub mod routine;
+pub mod rule_file;
+pub mod scheduler;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+pub mod snapshot;
+pub mod solar;
+#[cfg(any(test, feature = "test-util"))]
+pub mod testing;
@@ -1,13 +1,66 @@
+use std::collections::VecDeque;
 use std::sync::Mutex;
 
+pub use tracing::Level;
+
+/// `tracing` target of command output, so subscribers can send it somewhere other than
+/// diagnostics (e.g. into a log file but not onto stderr).
+pub const OUTPUT_TARGET: &str = "huelight::output";
+
+/// How many messages a `Logger` keeps for `entries`; older ones are dropped so a long-running
+/// daemon doesn't grow without limit.
+pub const MAX_ENTRIES: usize = 1000;
+
 pub trait ILogger {
+    /// Command output, such as a listing or a confirmation.
     fn log(&self, message: &str);
+    /// A diagnostic about how things went, at `level`.
+    fn log_at(&self, level: Level, message: &str);
     fn entries(&self) -> Vec<String>;
+
+    fn error(&self, message: &str) {
+        self.log_at(Level::ERROR, message);
+    }
+
+    fn warn(&self, message: &str) {
+        self.log_at(Level::WARN, message);
+    }
+
+    fn info(&self, message: &str) {
+        self.log_at(Level::INFO, message);
+    }
+
+    fn debug(&self, message: &str) {
+        self.log_at(Level::DEBUG, message);
+    }
 }
 
+/// Prints command output to stdout and passes diagnostics on to `tracing`, keeping the most
+/// recent messages of both for `entries`.
+///
+/// Command output is also sent to `tracing` under `OUTPUT_TARGET`, at info level.
 #[derive(Default)]
 pub struct Logger {
-    entries: Mutex<Vec<String>>,
+    entries: Mutex<VecDeque<String>>,
+    quiet: bool,
+}
+
+impl Logger {
+    /// A logger that doesn't print command output, for callers that present it themselves.
+    pub fn quiet() -> Self {
+        Self {
+            quiet: true,
+            ..Self::default()
+        }
+    }
+
+    fn keep(&self, entry: String) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.len() == MAX_ENTRIES {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
 }
 
 impl ILogger for Logger {
@@ -16,17 +69,69 @@ impl ILogger for Logger {
          * Logs a message to the logger's internal log storage.
          * Puts a newline after each message.
          */
-        self.entries
-            .lock()
-            .unwrap_or_else(|e| e.into_inner())
-            .push(message.to_string() + "\n");
-        println!("{}", message);
+        self.keep(message.to_string() + "\n");
+        tracing::info!(target: OUTPUT_TARGET, "{message}");
+        if !self.quiet {
+            println!("{}", message);
+        }
+    }
+
+    fn log_at(&self, level: Level, message: &str) {
+        self.keep(format!("{}: {message}\n", level.as_str().to_lowercase()));
+        // The macros need the level as a constant.
+        match level {
+            Level::ERROR => tracing::error!("{message}"),
+            Level::WARN => tracing::warn!("{message}"),
+            Level::INFO => tracing::info!("{message}"),
+            Level::DEBUG => tracing::debug!("{message}"),
+            Level::TRACE => tracing::trace!("{message}"),
+        }
     }
 
     fn entries(&self) -> Vec<String> {
         self.entries
             .lock()
             .unwrap_or_else(|e| e.into_inner())
-            .clone()
+            .iter()
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ILogger, Logger, MAX_ENTRIES};
+
+    #[test]
+    pub fn entries_keep_output_and_diagnostics_up_to_the_limit() {
+        // Arrange
+        let logger = Logger::quiet();
+
+        // Act
+        logger.warn("bridge is slow");
+        for i in 0..MAX_ENTRIES {
+            logger.log(&format!("line {i}"));
+        }
+
+        // Assert
+        let entries = logger.entries();
+        assert_eq!(entries.len(), MAX_ENTRIES);
+        assert_eq!(entries[0], "line 0\n");
+        assert_eq!(
+            entries[MAX_ENTRIES - 1],
+            format!("line {}\n", MAX_ENTRIES - 1)
+        );
+    }
+
+    #[test]
+    pub fn diagnostics_are_kept_with_their_level() {
+        // Arrange
+        let logger = Logger::quiet();
+
+        // Act
+        logger.warn("bridge is slow");
+
+        // Assert
+        assert_eq!(logger.entries(), vec!["warn: bridge is slow\n"]);
     }
 }
@@ -0,0 +1,86 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+
+// Bridge configuration related models
+
+/// Bridge configuration from `/config`.
+///
+/// Without a valid username the bridge only returns a public subset (name, versions, IDs),
+/// so everything that is not part of that subset is optional.
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct BridgeConfig {
+    pub name: String,
+    pub swversion: String,
+    pub apiversion: String,
+    pub bridgeid: String,
+    pub modelid: String,
+    #[serde(default)]
+    pub mac: Option<String>,
+    #[serde(default)]
+    pub datastoreversion: Option<String>,
+    #[serde(default)]
+    pub ipaddress: Option<String>,
+    #[serde(default)]
+    pub zigbeechannel: Option<u8>,
+    #[serde(default)]
+    pub timezone: Option<String>,
+    #[serde(default)]
+    pub localtime: Option<String>,
+    /// Authorized applications keyed by username. Only present for whitelisted users.
+    #[serde(default)]
+    pub whitelist: Option<HashMap<String, WhitelistEntry>>,
+}
+
+impl BridgeConfig {
+    /// True if this config came from an authenticated request made as `username`.
+    pub fn is_whitelisted(&self, username: &str) -> bool {
+        self.whitelist
+            .as_ref()
+            .is_some_and(|whitelist| whitelist.contains_key(username))
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct WhitelistEntry {
+    pub name: String,
+    #[serde(rename = "create date")]
+    pub create_date: String,
+    #[serde(rename = "last use date")]
+    pub last_use_date: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::bridgeconfig::BridgeConfig;
+
+    #[test]
+    pub fn bridge_config_public_subset_has_no_whitelist() {
+        // Arrange
+        let json = r#"{"name":"Philips hue","datastoreversion":"131","swversion":"1962154010","apiversion":"1.62.0","mac":"00:17:88:01:02:03","bridgeid":"001788FFFE010203","factorynew":false,"replacesbridgeid":null,"modelid":"BSB002","starterkitid":""}"#;
+
+        // Act
+        let config: BridgeConfig = serde_json::from_str(json).unwrap();
+
+        // Assert
+        assert_eq!("1.62.0", config.apiversion);
+        assert_eq!(None, config.zigbeechannel);
+        assert!(!config.is_whitelisted("anyone"));
+    }
+
+    #[test]
+    pub fn bridge_config_full_parses_whitelist() {
+        // Arrange
+        let json = r#"{"name":"Office","swversion":"1962154010","apiversion":"1.62.0","bridgeid":"001788FFFE010203","modelid":"BSB002","zigbeechannel":25,"whitelist":{"abc123":{"last use date":"2024-05-01T10:00:00","create date":"2023-01-01T09:00:00","name":"huelightcli#laptop"}}}"#;
+
+        // Act
+        let config: BridgeConfig = serde_json::from_str(json).unwrap();
+
+        // Assert
+        assert_eq!(Some(25), config.zigbeechannel);
+        assert!(config.is_whitelisted("abc123"));
+        assert_eq!(
+            "huelightcli#laptop",
+            config.whitelist.unwrap()["abc123"].name
+        );
+    }
+}
@@ -0,0 +1,99 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::domain::light::{LightId, LightState};
+
+// Group related models
+pub type GroupId = u32;
+
+#[derive(Debug, Deserialize)]
+pub struct GroupResponse(pub HashMap<GroupId, Group>);
+
+impl GroupResponse {
+    /// Finds a group by its numeric ID or, failing that, by a case-insensitive name match.
+    pub fn find(&self, id_or_name: &str) -> Option<(GroupId, &Group)> {
+        if let Ok(id) = id_or_name.parse::<GroupId>() {
+            return self.0.get(&id).map(|g| (id, g));
+        }
+        self.0
+            .iter()
+            .find(|(_, g)| g.name.eq_ignore_ascii_case(id_or_name))
+            .map(|(id, g)| (*id, g))
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct Group {
+    pub name: String,
+    /// Light IDs as the bridge reports them (strings).
+    #[serde(default)]
+    pub lights: Vec<String>,
+    #[serde(rename = "type")]
+    pub _type: String,
+    #[serde(default)]
+    pub action: LightState,
+    #[serde(default)]
+    pub state: GroupStatus,
+}
+
+impl Group {
+    /// Member light IDs parsed to numbers. Entries the bridge sends that are not numeric are skipped.
+    pub fn light_ids(&self) -> Vec<LightId> {
+        self.lights
+            .iter()
+            .filter_map(|id| id.parse::<LightId>().ok())
+            .collect()
+    }
+}
+
+/// Body of `POST /groups` for creating a group.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct NewGroup {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub _type: String,
+    pub lights: Vec<String>,
+}
+
+impl NewGroup {
+    /// A plain group of lights, which unlike a room can share lights with other groups.
+    pub fn light_group(name: impl Into<String>, lights: &[LightId]) -> Self {
+        Self {
+            name: name.into(),
+            _type: "LightGroup".to_string(),
+            lights: lights.iter().map(ToString::to_string).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Default, PartialEq)]
+pub struct GroupStatus {
+    #[serde(default)]
+    pub all_on: bool,
+    #[serde(default)]
+    pub any_on: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::group::GroupResponse;
+
+    #[test]
+    pub fn group_response_find_matches_id_and_name() {
+        // Arrange
+        let json = r#"{
+            "1": { "name": "Office", "lights": ["1", "2"], "type": "Room" },
+            "2": { "name": "Hall", "lights": ["3"], "type": "Zone" }
+        }"#;
+        let groups: GroupResponse = serde_json::from_str(json).unwrap();
+
+        // Act
+        let by_id = groups.find("2").map(|(id, _)| id);
+        let by_name = groups.find("office").map(|(id, g)| (id, g.light_ids()));
+
+        // Assert
+        assert_eq!(Some(2), by_id);
+        assert_eq!(Some((1, vec![1, 2])), by_name);
+        assert!(groups.find("Garage").is_none());
+    }
+}
@@ -3,6 +3,8 @@ use std::collections::HashMap;
 use serde::Deserialize;
 use serde_json::Value;
 
+use crate::error::{CoreError, CoreResult, HueBridgeError};
+
 #[derive(Debug, Deserialize)]
 pub struct ErrorDetail {
     #[serde(rename = "type")]
@@ -16,8 +18,51 @@ pub type HueSuccessDetail = HashMap<String, Value>;
 #[derive(Debug, Deserialize)]
 #[serde(untagged)]
 pub enum HueResponseEntry {
-    Error { error: ErrorDetail },
-    Success { success: HueSuccessDetail },
+    Error {
+        error: ErrorDetail,
+    },
+    Success {
+        success: HueSuccessDetail,
+    },
+    /// Some endpoints (e.g. DELETE) report success as a plain message.
+    SuccessMessage {
+        success: String,
+    },
 }
 
 pub type HueResponse = Vec<HueResponseEntry>;
+
+/// Turns the first error entry of a bridge response into a `CoreError`, if there is one.
+pub fn check_response(response: &HueResponse) -> CoreResult<()> {
+    match response.iter().find_map(|entry| match entry {
+        HueResponseEntry::Error { error } => Some(error),
+        HueResponseEntry::Success { .. } | HueResponseEntry::SuccessMessage { .. } => None,
+    }) {
+        Some(error) if error._type == 1 => Err(CoreError::Bridge(HueBridgeError::UnauthorizedUser)),
+        Some(error) => Err(CoreError::Bridge(HueBridgeError::Other {
+            code: error._type.to_string(),
+            message: format!("{} ({})", error.description, error.address),
+        })),
+        None => Ok(()),
+    }
+}
+
+/// Returns the ID the bridge assigned to a newly created resource (`[{"success":{"id":"7"}}]`).
+pub fn created_id(response: &HueResponse) -> CoreResult<String> {
+    check_response(response)?;
+    response
+        .iter()
+        .find_map(|entry| match entry {
+            HueResponseEntry::Success { success } => success.get("id").and_then(|id| match id {
+                Value::String(s) => Some(s.clone()),
+                Value::Number(n) => Some(n.to_string()),
+                _ => None,
+            }),
+            _ => None,
+        })
+        .ok_or_else(|| {
+            CoreError::UnexpectedResponse(
+                "bridge did not return the ID of the new resource".to_string(),
+            )
+        })
+}
@@ -1,18 +1,158 @@
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::collections::HashMap;
+use std::time::Duration;
 
-// Light related models
-pub type LightId = u32;
+use crate::domain::brightness::Brightness;
+use crate::domain::color::{self, Color, Gamut};
+use crate::domain::light::{self as domain, Alert, Effect, LastScan, LightKind, NewLights};
 
-#[derive(Debug, Deserialize)]
-pub struct LightResponse(pub HashMap<LightId, Light>);
+// v1 light DTOs. Only the conversions to and from `crate::domain::light` are used outside.
 
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Deserialize, PartialEq, Default)]
 pub struct Light {
     pub state: LightState,
     pub name: String,
     #[serde(rename = "type")]
     pub _type: String,
+    #[serde(default)]
+    pub modelid: Option<String>,
+    #[serde(default)]
+    pub manufacturername: Option<String>,
+    #[serde(default)]
+    pub productname: Option<String>,
+    #[serde(default)]
+    pub uniqueid: Option<String>,
+    #[serde(default)]
+    pub swversion: Option<String>,
+    /// Reported by bridges since API 1.22; older bridges and some third-party lights omit it.
+    #[serde(default)]
+    pub capabilities: Option<LightCapabilities>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default, PartialEq)]
+pub struct LightCapabilities {
+    /// Whether the light is Friends of Hue certified.
+    #[serde(default)]
+    pub certified: bool,
+    #[serde(default)]
+    pub control: LightControl,
+    #[serde(default)]
+    pub streaming: Option<StreamingCapabilities>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default, PartialEq)]
+pub struct LightControl {
+    /// Lowest brightness in 1/100 percent. Only dimmable lights report it.
+    #[serde(default)]
+    pub mindimlevel: Option<u32>,
+    #[serde(default)]
+    pub maxlumen: Option<u32>,
+    /// "A", "B", "C" or "other".
+    #[serde(default)]
+    pub colorgamuttype: Option<String>,
+    /// Red, green and blue corners of the gamut in CIE xy.
+    #[serde(default)]
+    pub colorgamut: Option<[[f64; 2]; 3]>,
+    #[serde(default)]
+    pub ct: Option<CtRange>,
+}
+
+/// Supported color temperatures in mirek.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct CtRange {
+    pub min: u16,
+    pub max: u16,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StreamingCapabilities {
+    #[serde(default)]
+    pub renderer: bool,
+    #[serde(default)]
+    pub proxy: bool,
+}
+
+fn light_kind(type_name: &str) -> LightKind {
+    match type_name.to_ascii_lowercase().as_str() {
+        "on/off light" | "on/off plug-in unit" => LightKind::OnOff,
+        "dimmable light" | "dimmable plug-in unit" => LightKind::Dimmable,
+        "color temperature light" => LightKind::ColorTemperature,
+        "color light" => LightKind::Color,
+        "extended color light" => LightKind::ExtendedColor,
+        _ => LightKind::Other(type_name.to_string()),
+    }
+}
+
+impl From<Light> for domain::Light {
+    fn from(light: Light) -> Self {
+        let kind = light_kind(&light._type);
+        let features = match &light.capabilities {
+            Some(capabilities) => {
+                let control = &capabilities.control;
+                let gamut = control
+                    .colorgamut
+                    .map(|[red, green, blue]| Gamut { red, green, blue })
+                    .or_else(|| control.colorgamuttype.as_deref().and_then(Gamut::from_type));
+                let ct_range = control.ct.map(|ct| color::CtRange {
+                    min: ct.min,
+                    max: ct.max,
+                });
+                domain::LightFeatures {
+                    dimmable: control.mindimlevel.is_some()
+                        || gamut.is_some()
+                        || ct_range.is_some(),
+                    color: gamut.is_some(),
+                    gamut,
+                    ct_range,
+                    streaming: capabilities.streaming.is_some_and(|s| s.renderer),
+                }
+            }
+            None => domain::LightFeatures::for_kind(&kind),
+        };
+        domain::Light {
+            name: light.name,
+            kind,
+            // Every bridge reports it; a missing value means a mock or a very old firmware.
+            reachable: light.state.reachable.unwrap_or(true),
+            state: light.state.into(),
+            features,
+            info: domain::LightInfo {
+                model_id: light.modelid,
+                manufacturer: light.manufacturername,
+                product_name: light.productname,
+                unique_id: light.uniqueid,
+                software_version: light.swversion,
+            },
+        }
+    }
+}
+
+/// The bridge mixes the `lastscan` field in with the light IDs.
+#[derive(Deserialize)]
+pub struct RawNewLights {
+    lastscan: String,
+    #[serde(flatten)]
+    lights: HashMap<String, Value>,
+}
+
+impl From<RawNewLights> for NewLights {
+    fn from(raw: RawNewLights) -> Self {
+        let lastscan = match raw.lastscan.as_str() {
+            "none" => LastScan::None,
+            "active" => LastScan::Active,
+            _ => LastScan::Completed(raw.lastscan),
+        };
+        let lights = raw
+            .lights
+            .into_iter()
+            .filter_map(|(id, light)| {
+                let name = light.get("name")?.as_str()?.to_string();
+                Some((id.parse().ok()?, name))
+            })
+            .collect();
+        NewLights { lastscan, lights }
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
@@ -25,41 +165,261 @@ pub struct LightState {
     pub hue: Option<u16>,
     #[serde(rename = "sat", skip_serializing_if = "Option::is_none")]
     pub saturation: Option<u8>,
+    /// Color temperature in mirek (153 is 6500K, 500 is 2000K).
+    #[serde(rename = "ct", skip_serializing_if = "Option::is_none")]
+    pub color_temperature: Option<u16>,
+    /// CIE xy color coordinates.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub xy: Option<[f64; 2]>,
+    /// Dynamic effect, either "none" or "colorloop".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub effect: Option<String>,
+    /// Alert effect: "select" breathes once, "lselect" breathes for 15 seconds, "none" stops.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alert: Option<String>,
+    /// Transition duration in multiples of 100ms. Only meaningful when sending a state.
+    #[serde(rename = "transitiontime", skip_serializing_if = "Option::is_none")]
+    pub transition_time: Option<u16>,
+    /// Which of xy, ct or hue/sat the light is currently using. Reported by the bridge only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub colormode: Option<ColorMode>,
+    /// Whether the bridge can currently reach the light. Reported by the bridge only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reachable: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorMode {
+    Hs,
+    Xy,
+    Ct,
 }
 
+/// Full circle on the v1 16-bit hue scale.
+const HUE_SCALE: f64 = 65535.0;
+/// Full saturation on the v1 scale.
+const SAT_SCALE: f64 = 254.0;
+
 impl LightState {
-    pub fn with_on(mut self, on: bool) -> Self {
-        self.on = Some(on);
-        self
+    /// The color the light shows: the one named by `colormode` or, in states without it such
+    /// as schedule files, whichever is set, preferring xy over ct over hue/sat.
+    fn color(&self) -> Option<Color> {
+        let xy = self.xy.map(|[x, y]| Color::xy(x, y));
+        let ct = self
+            .color_temperature
+            .map(|mirek| Color::ColorTemperature { mirek });
+        // A lone hue or saturation keeps the other at full saturation or red respectively.
+        let hs = (self.hue.is_some() || self.saturation.is_some()).then(|| Color::HueSaturation {
+            hue: self.hue.unwrap_or(0) as f64 / HUE_SCALE * 360.0,
+            saturation: self
+                .saturation
+                .map_or(100.0, |sat| sat as f64 / SAT_SCALE * 100.0),
+        });
+        match self.colormode {
+            Some(ColorMode::Xy) => xy,
+            Some(ColorMode::Ct) => ct,
+            Some(ColorMode::Hs) => hs,
+            None => xy.or(ct).or(hs),
+        }
     }
+}
 
-    pub fn with_brightness(mut self, brightness: u8) -> Self {
-        self.brightness = Some(brightness);
-        self
+impl From<LightState> for domain::LightState {
+    fn from(state: LightState) -> Self {
+        domain::LightState {
+            on: state.on,
+            brightness: state.brightness.map(Brightness::from_bri),
+            color: state.color(),
+            effect: match state.effect.as_deref() {
+                Some("none") => Some(Effect::None),
+                Some("colorloop") => Some(Effect::ColorLoop),
+                _ => None,
+            },
+            alert: match state.alert.as_deref() {
+                Some("none") => Some(Alert::None),
+                Some("select") => Some(Alert::Breathe),
+                Some("lselect") => Some(Alert::LongBreathe),
+                _ => None,
+            },
+            transition: state
+                .transition_time
+                .map(|ds| Duration::from_millis(ds as u64 * 100)),
+        }
     }
+}
 
-    pub fn with_hue(mut self, hue: u16) -> Self {
-        self.hue = Some(hue);
-        self
+impl From<&domain::LightState> for LightState {
+    fn from(state: &domain::LightState) -> Self {
+        let mut dto = LightState {
+            on: state.on,
+            brightness: state.brightness.map(Brightness::bri),
+            effect: state.effect.map(|effect| {
+                match effect {
+                    Effect::None => "none",
+                    Effect::ColorLoop => "colorloop",
+                }
+                .to_string()
+            }),
+            alert: state.alert.map(|alert| {
+                match alert {
+                    Alert::None => "none",
+                    Alert::Breathe => "select",
+                    Alert::LongBreathe => "lselect",
+                }
+                .to_string()
+            }),
+            transition_time: state
+                .transition
+                .map(|t| (t.as_millis() / 100).min(u16::MAX as u128) as u16),
+            ..Default::default()
+        };
+        match state.color {
+            Some(Color::Xy { x, y }) => dto.xy = Some([x, y]),
+            Some(Color::ColorTemperature { mirek }) => dto.color_temperature = Some(mirek),
+            Some(Color::HueSaturation { hue, saturation }) => {
+                dto.hue = Some((hue.rem_euclid(360.0) / 360.0 * HUE_SCALE).round() as u16);
+                dto.saturation = Some(
+                    (saturation / 100.0 * SAT_SCALE)
+                        .round()
+                        .clamp(0.0, SAT_SCALE) as u8,
+                );
+            }
+            None => {}
+        }
+        dto
     }
+}
 
-    pub fn with_saturation(mut self, saturation: u8) -> Self {
-        self.saturation = Some(saturation);
-        self
+impl From<domain::LightState> for LightState {
+    fn from(state: domain::LightState) -> Self {
+        (&state).into()
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::models::light::LightState;
+    use crate::domain::color::{Color, CtRange, Gamut};
+    use crate::domain::light::{self as domain, LastScan, LightKind, NewLights};
+    use crate::models::light::{Light, LightState};
+
+    fn light(json: serde_json::Value) -> domain::Light {
+        serde_json::from_value::<Light>(json).unwrap().into()
+    }
+
+    #[test]
+    pub fn light_parses_capabilities_and_model_info() {
+        // Arrange
+        let json = serde_json::json!({
+            "name": "Desk", "type": "Extended color light", "modelid": "LCT015",
+            "manufacturername": "Signify Netherlands B.V.", "productname": "Hue color lamp",
+            "uniqueid": "00:17:88:01:02:03:04:05-0b", "swversion": "1.90.1",
+            "state": {"on": true, "bri": 100, "colormode": "xy", "reachable": false},
+            "capabilities": {"certified": true, "control": {"mindimlevel": 1000, "maxlumen": 806,
+                "colorgamuttype": "C", "colorgamut": [[0.6915, 0.3083], [0.17, 0.7], [0.1532, 0.0475]],
+                "ct": {"min": 153, "max": 500}}, "streaming": {"renderer": true, "proxy": true}}
+        });
+
+        // Act
+        let light = light(json);
+
+        // Assert
+        assert_eq!(Some("LCT015"), light.info.model_id.as_deref());
+        assert!(!light.reachable);
+        assert_eq!(LightKind::ExtendedColor, light.kind);
+        assert_eq!(Some(Gamut::C), light.features.gamut);
+        assert_eq!(
+            Some(CtRange { min: 153, max: 500 }),
+            light.features.ct_range
+        );
+        assert!(light.features.streaming);
+    }
+
+    #[test]
+    pub fn light_without_capabilities_expect_features_from_type() {
+        // Act
+        let light =
+            light(serde_json::json!({"name": "Hall", "type": "Dimmable light", "state": {}}));
+
+        // Assert
+        assert_eq!(LightKind::Dimmable, light.kind);
+        assert!(light.features.dimmable);
+        assert!(!light.features.color);
+        assert_eq!(None, light.features.ct_range);
+    }
+
+    #[test]
+    pub fn new_lights_parse_ids_next_to_lastscan() {
+        // Arrange
+        let json = r#"{"7": {"name": "Hue color lamp 7"}, "lastscan": "active"}"#;
+
+        // Act
+        let new_lights: NewLights = serde_json::from_str(json).unwrap();
+
+        // Assert
+        assert_eq!(LastScan::Active, new_lights.lastscan);
+        assert_eq!(
+            Some("Hue color lamp 7"),
+            new_lights.lights.get(&7).map(String::as_str)
+        );
+    }
+
+    #[test]
+    pub fn light_state_hue_saturation_round_trips_through_v1_scale() {
+        for (hue, sat) in [(0u16, 0u8), (8000, 140), (65535, 254), (32768, 1)] {
+            // Arrange
+            let dto = LightState {
+                hue: Some(hue),
+                saturation: Some(sat),
+                ..Default::default()
+            };
+
+            // Act
+            let state = domain::LightState::from(dto.clone());
+            let back = LightState::from(&state);
+
+            // Assert
+            assert_eq!(dto.saturation, back.saturation);
+            assert_eq!(dto.hue.map(|h| h % 65535), back.hue);
+        }
+    }
+
+    #[test]
+    pub fn light_state_without_colormode_prefers_xy() {
+        // Arrange
+        let dto: LightState = serde_json::from_str(r#"{"xy":[0.4,0.4],"ct":300}"#).unwrap();
+
+        // Act
+        let state = domain::LightState::from(dto);
+
+        // Assert
+        assert_eq!(Some(Color::xy(0.4, 0.4)), state.color);
+    }
+
+    #[test]
+    pub fn light_state_serialization_omits_unset_fields() {
+        // Arrange
+        let light_state = domain::LightState::default().with_color(Color::HueSaturation {
+            hue: 0.0,
+            saturation: 100.0,
+        });
+
+        // Act
+        let actual = serde_json::to_value(&light_state).unwrap();
+
+        // Assert
+        assert_eq!(serde_json::json!({"hue": 0, "sat": 254}), actual);
+    }
 
     #[test]
     pub fn light_state_serialization_omits_on_when_none() {
         // Arrange
-        let light_state = LightState::default()
-            .with_brightness(10)
-            .with_hue(11)
-            .with_saturation(12);
+        let light_state = LightState {
+            brightness: Some(10),
+            hue: Some(11),
+            saturation: Some(12),
+            ..Default::default()
+        };
 
         let expected = serde_json::json!({
             "bri": 10,
@@ -78,10 +438,12 @@ mod tests {
     #[test]
     pub fn light_state_serialization_omits_bri_when_none() {
         // Arrange
-        let light_state = LightState::default()
-            .with_on(true)
-            .with_hue(11)
-            .with_saturation(12);
+        let light_state = LightState {
+            on: Some(true),
+            hue: Some(11),
+            saturation: Some(12),
+            ..Default::default()
+        };
 
         let expected = serde_json::json!({
             "on": true,
@@ -100,10 +462,12 @@ mod tests {
     #[test]
     pub fn light_state_serialization_omits_hue_when_none() {
         // Arrange
-        let light_state = LightState::default()
-            .with_on(true)
-            .with_brightness(11)
-            .with_saturation(12);
+        let light_state = LightState {
+            on: Some(true),
+            brightness: Some(11),
+            saturation: Some(12),
+            ..Default::default()
+        };
 
         let expected = serde_json::json!({
             "on": true,
@@ -122,10 +486,12 @@ mod tests {
     #[test]
     pub fn light_state_serialization_omits_sat_when_none() {
         // Arrange
-        let light_state = LightState::default()
-            .with_on(true)
-            .with_hue(11)
-            .with_brightness(12);
+        let light_state = LightState {
+            on: Some(true),
+            hue: Some(11),
+            brightness: Some(12),
+            ..Default::default()
+        };
 
         let expected = serde_json::json!({
             "on": true,
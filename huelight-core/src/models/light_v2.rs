@@ -0,0 +1,225 @@
+use serde::{Deserialize, Serialize};
+
+use crate::domain::brightness::Brightness;
+use crate::domain::color::{Color, CtRange, Gamut};
+use crate::domain::light::{self as domain, Alert, Effect, LightKind};
+use crate::error::CoreError;
+
+// CLIP v2 light DTOs. Only the conversions to and from `crate::domain::light` are used outside.
+
+/// A light resource from `GET /clip/v2/resource/light`. Only the fields this crate uses.
+#[derive(Debug, Deserialize)]
+pub struct Light {
+    pub metadata: Metadata,
+    #[serde(default)]
+    pub on: Option<On>,
+    #[serde(default)]
+    pub dimming: Option<Dimming>,
+    #[serde(default)]
+    pub color_temperature: Option<ColorTemperature>,
+    #[serde(default)]
+    pub color: Option<ColorInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Metadata {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct On {
+    pub on: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Dimming {
+    /// Brightness in percent.
+    pub brightness: f64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ColorTemperature {
+    /// Absent while the light is showing an xy color.
+    #[serde(default)]
+    pub mirek: Option<u16>,
+    /// Whether `mirek` is the light's current color.
+    #[serde(default)]
+    pub mirek_valid: bool,
+    #[serde(default)]
+    pub mirek_schema: Option<MirekSchema>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MirekSchema {
+    pub mirek_minimum: u16,
+    pub mirek_maximum: u16,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ColorInfo {
+    pub xy: Xy,
+    #[serde(default)]
+    pub gamut: Option<GamutInfo>,
+    /// "A", "B", "C" or "other".
+    #[serde(default)]
+    pub gamut_type: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+pub struct Xy {
+    pub x: f64,
+    pub y: f64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GamutInfo {
+    pub red: Xy,
+    pub green: Xy,
+    pub blue: Xy,
+}
+
+impl From<Light> for domain::Light {
+    fn from(light: Light) -> Self {
+        let ct_range = light
+            .color_temperature
+            .as_ref()
+            .map(|ct| match &ct.mirek_schema {
+                Some(schema) => CtRange {
+                    min: schema.mirek_minimum,
+                    max: schema.mirek_maximum,
+                },
+                None => domain::DEFAULT_CT_RANGE,
+            });
+        let gamut = light.color.as_ref().and_then(|color| match &color.gamut {
+            Some(g) => Some(Gamut {
+                red: [g.red.x, g.red.y],
+                green: [g.green.x, g.green.y],
+                blue: [g.blue.x, g.blue.y],
+            }),
+            None => color.gamut_type.as_deref().and_then(Gamut::from_type),
+        });
+        let kind = match (
+            light.color.is_some(),
+            ct_range.is_some(),
+            light.dimming.is_some(),
+        ) {
+            (true, true, _) => LightKind::ExtendedColor,
+            (true, false, _) => LightKind::Color,
+            (false, true, _) => LightKind::ColorTemperature,
+            (false, false, true) => LightKind::Dimmable,
+            (false, false, false) => LightKind::OnOff,
+        };
+        let mirek = light
+            .color_temperature
+            .as_ref()
+            .filter(|ct| ct.mirek_valid)
+            .and_then(|ct| ct.mirek);
+        let color = match mirek {
+            Some(mirek) => Some(Color::ColorTemperature { mirek }),
+            None => light.color.as_ref().map(|c| Color::xy(c.xy.x, c.xy.y)),
+        };
+        domain::Light {
+            name: light.metadata.name,
+            state: domain::LightState {
+                on: light.on.map(|on| on.on),
+                brightness: light
+                    .dimming
+                    .as_ref()
+                    .map(|d| Brightness::from_percent(d.brightness)),
+                color,
+                ..Default::default()
+            },
+            // v2 reports reachability on the light's zigbee_connectivity resource instead.
+            reachable: true,
+            features: domain::LightFeatures {
+                dimmable: light.dimming.is_some(),
+                color: light.color.is_some(),
+                gamut,
+                ct_range,
+                streaming: false,
+            },
+            kind,
+            info: domain::LightInfo::default(),
+        }
+    }
+}
+
+/// The body of `PUT /clip/v2/resource/light/<id>`.
+#[derive(Debug, Serialize, Default)]
+pub struct LightUpdate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on: Option<On>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dimming: Option<Dimming>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<ColorUpdate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color_temperature: Option<MirekUpdate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dynamics: Option<Dynamics>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alert: Option<AlertUpdate>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ColorUpdate {
+    pub xy: Xy,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MirekUpdate {
+    pub mirek: u16,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Dynamics {
+    /// Transition time in milliseconds.
+    pub duration: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AlertUpdate {
+    pub action: String,
+}
+
+impl TryFrom<&domain::LightState> for LightUpdate {
+    type Error = CoreError;
+
+    fn try_from(state: &domain::LightState) -> Result<Self, Self::Error> {
+        let unsupported = |feature: &str| CoreError::UnsupportedByApi {
+            api: "v2".to_string(),
+            feature: feature.to_string(),
+        };
+        if state.effect == Some(Effect::ColorLoop) {
+            return Err(unsupported("color loop"));
+        }
+        let mut update = LightUpdate {
+            on: state.on.map(|on| On { on }),
+            dimming: state.brightness.map(|b| Dimming {
+                brightness: b.percent(),
+            }),
+            dynamics: state.transition.map(|t| Dynamics {
+                duration: t.as_millis() as u64,
+            }),
+            alert: match state.alert {
+                Some(Alert::Breathe) => Some(AlertUpdate {
+                    action: "breathe".to_string(),
+                }),
+                Some(Alert::LongBreathe) => return Err(unsupported("long breathe alerts")),
+                Some(Alert::None) | None => None,
+            },
+            ..Default::default()
+        };
+        match state.color {
+            Some(Color::ColorTemperature { mirek }) => {
+                update.color_temperature = Some(MirekUpdate { mirek })
+            }
+            Some(color) => {
+                let [x, y] = color.to_xy();
+                update.color = Some(ColorUpdate { xy: Xy { x, y } });
+            }
+            None => {}
+        }
+        Ok(update)
+    }
+}
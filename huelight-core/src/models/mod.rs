@@ -1,3 +1,10 @@
+pub mod bridgeconfig;
 pub mod createuser;
+pub mod group;
 pub mod hueerror;
-pub mod light;
+pub(crate) mod light;
+pub(crate) mod light_v2;
+pub mod rule;
+pub mod scene;
+pub mod schedule;
+pub mod sensor;
@@ -0,0 +1,124 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+// Rule related models
+pub type RuleId = u32;
+
+#[derive(Debug, Deserialize)]
+pub struct RuleResponse(pub HashMap<RuleId, Rule>);
+
+impl RuleResponse {
+    /// Finds a rule by its numeric ID or, failing that, by a case-insensitive name match.
+    pub fn find(&self, id_or_name: &str) -> Option<(RuleId, &Rule)> {
+        if let Ok(id) = id_or_name.parse::<RuleId>() {
+            return self.0.get(&id).map(|r| (id, r));
+        }
+        self.0
+            .iter()
+            .find(|(_, r)| r.name.eq_ignore_ascii_case(id_or_name))
+            .map(|(id, r)| (*id, r))
+    }
+}
+
+/// A bridge rule. Metadata fields are reported by the bridge and never sent back.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Rule {
+    pub name: String,
+    pub conditions: Vec<Condition>,
+    pub actions: Vec<Action>,
+    #[serde(default, skip_serializing)]
+    pub owner: Option<String>,
+    #[serde(default, skip_serializing)]
+    pub status: Option<String>,
+    #[serde(default, skip_serializing)]
+    pub lasttriggered: Option<String>,
+    #[serde(default, skip_serializing)]
+    pub timestriggered: Option<u32>,
+}
+
+impl Rule {
+    pub fn new(name: impl Into<String>, conditions: Vec<Condition>, actions: Vec<Action>) -> Self {
+        Self {
+            name: name.into(),
+            conditions,
+            actions,
+            owner: None,
+            status: None,
+            lasttriggered: None,
+            timestriggered: None,
+        }
+    }
+
+    /// True if both rules have the same name, conditions and actions, ignoring metadata.
+    pub fn same_definition(&self, other: &Rule) -> bool {
+        self.name == other.name
+            && self.conditions == other.conditions
+            && self.actions == other.actions
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum ConditionOperator {
+    #[serde(rename = "eq")]
+    Equals,
+    #[serde(rename = "gt")]
+    GreaterThan,
+    #[serde(rename = "lt")]
+    LessThan,
+    /// The attribute changed.
+    #[serde(rename = "dx")]
+    Changed,
+    /// The attribute changed, then stayed the same for the given duration.
+    #[serde(rename = "ddx")]
+    ChangedDelayed,
+    #[serde(rename = "stable")]
+    Stable,
+    #[serde(rename = "not stable")]
+    NotStable,
+    #[serde(rename = "in")]
+    In,
+    #[serde(rename = "not in")]
+    NotIn,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Condition {
+    /// Resource attribute, e.g. `/sensors/5/state/presence` or `/config/localtime`.
+    pub address: String,
+    pub operator: ConditionOperator,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Action {
+    /// Resource to call, e.g. `/groups/1/action`.
+    pub address: String,
+    pub method: String,
+    pub body: Value,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::rule::{ConditionOperator, RuleResponse};
+
+    #[test]
+    pub fn rules_parse_and_serialize_without_metadata() {
+        // Arrange
+        let json = r#"{"3": {"name": "Hall motion", "owner": "abc", "created": "2024-01-01T00:00:00", "lasttriggered": "none", "timestriggered": 0, "status": "enabled", "recycle": false,
+            "conditions": [{"address": "/sensors/5/state/presence", "operator": "eq", "value": "true"}, {"address": "/sensors/5/state/presence", "operator": "dx"}],
+            "actions": [{"address": "/groups/1/action", "method": "PUT", "body": {"on": true, "bri": 30}}]}}"#;
+
+        // Act
+        let rules: RuleResponse = serde_json::from_str(json).unwrap();
+        let (_, rule) = rules.find("Hall motion").unwrap();
+        let serialized = serde_json::to_value(rule).unwrap();
+
+        // Assert
+        assert_eq!(ConditionOperator::Changed, rule.conditions[1].operator);
+        assert_eq!(Some("enabled"), rule.status.as_deref());
+        assert!(serialized.get("owner").is_none());
+        assert!(serialized["conditions"][1].get("value").is_none());
+    }
+}
@@ -0,0 +1,31 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+
+// Scene related models
+pub type SceneId = String;
+
+#[derive(Debug, Deserialize)]
+pub struct SceneResponse(pub HashMap<SceneId, Scene>);
+
+impl SceneResponse {
+    /// Finds a scene by its ID or, failing that, by a case-insensitive name match.
+    pub fn find(&self, id_or_name: &str) -> Option<(&SceneId, &Scene)> {
+        self.0.get_key_value(id_or_name).or_else(|| {
+            self.0
+                .iter()
+                .find(|(_, s)| s.name.eq_ignore_ascii_case(id_or_name))
+        })
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct Scene {
+    pub name: String,
+    #[serde(rename = "type", default)]
+    pub _type: String,
+    /// Set for GroupScene scenes; the group the scene belongs to.
+    #[serde(default)]
+    pub group: Option<String>,
+    #[serde(default)]
+    pub lights: Vec<String>,
+}
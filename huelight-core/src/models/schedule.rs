@@ -0,0 +1,387 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
+
+use chrono::{NaiveDateTime, NaiveTime, Weekday};
+use serde::{Deserialize, Serialize};
+
+use crate::duration::format_hms;
+use crate::error::ParseError;
+use crate::models::rule::Action;
+
+// Bridge schedule related models
+pub type ScheduleId = u32;
+
+#[derive(Debug, Deserialize)]
+pub struct ScheduleResponse(pub HashMap<ScheduleId, Schedule>);
+
+impl ScheduleResponse {
+    /// Finds a schedule by its numeric ID or, failing that, by a case-insensitive name match.
+    pub fn find(&self, id_or_name: &str) -> Option<(ScheduleId, &Schedule)> {
+        if let Ok(id) = id_or_name.parse::<ScheduleId>() {
+            return self.0.get(&id).map(|s| (id, s));
+        }
+        self.0
+            .iter()
+            .find(|(_, s)| s.name.eq_ignore_ascii_case(id_or_name))
+            .map(|(id, s)| (*id, s))
+    }
+}
+
+/// A schedule stored on the bridge. `command.address` includes the `/api/<username>` prefix.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Schedule {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub command: Action,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub localtime: Option<TimePattern>,
+    /// The pattern in UTC, which older bridges send instead of `localtime`.
+    #[serde(default, skip_serializing)]
+    pub time: Option<TimePattern>,
+    /// `enabled` or `disabled`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    /// Whether the bridge removes the schedule once it has fired for the last time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub autodelete: Option<bool>,
+    #[serde(default, skip_serializing)]
+    pub created: Option<String>,
+    /// When a timer was started.
+    #[serde(default, skip_serializing)]
+    pub starttime: Option<String>,
+}
+
+impl Schedule {
+    pub fn new(name: impl Into<String>, command: Action, localtime: TimePattern) -> Self {
+        Self {
+            name: name.into(),
+            description: None,
+            command,
+            localtime: Some(localtime),
+            time: None,
+            status: None,
+            autodelete: None,
+            created: None,
+            starttime: None,
+        }
+    }
+
+    /// When the schedule fires: its `localtime`, or the legacy `time` if the bridge only sent
+    /// that.
+    pub fn time_pattern(&self) -> Option<&TimePattern> {
+        self.localtime.as_ref().or(self.time.as_ref())
+    }
+}
+
+/// Days of the week as the bridge encodes them: bit 6 is Monday down to bit 0 for Sunday.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Weekdays(pub u8);
+
+impl Weekdays {
+    pub const ALL: Weekdays = Weekdays(0b111_1111);
+    pub const WORKDAYS: Weekdays = Weekdays(0b111_1100);
+    pub const WEEKEND: Weekdays = Weekdays(0b000_0011);
+
+    fn bit(day: Weekday) -> u8 {
+        1 << (6 - day.num_days_from_monday())
+    }
+
+    pub fn from_days(days: &[Weekday]) -> Self {
+        Weekdays(days.iter().fold(0, |mask, day| mask | Self::bit(*day)))
+    }
+
+    pub fn contains(&self, day: Weekday) -> bool {
+        self.0 & Self::bit(day) != 0
+    }
+}
+
+/// How often a timer restarts after it fires.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recurrence {
+    Once,
+    Times(u8),
+    Forever,
+}
+
+/// The `localtime` of a bridge schedule.
+///
+/// Every form accepts an optional `A[hh]:[mm]:[ss]` suffix; the bridge then fires at a random
+/// moment up to that long after the given time.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(from = "String", into = "String")]
+pub enum TimePattern {
+    /// `2024-05-01T07:30:00`
+    Absolute {
+        at: NaiveDateTime,
+        random: Option<Duration>,
+    },
+    /// `W124/T07:00:00`
+    Recurring {
+        weekdays: Weekdays,
+        time: NaiveTime,
+        random: Option<Duration>,
+    },
+    /// `PT00:10:00`, or `R05/PT00:10:00` / `R/PT00:10:00` to repeat.
+    Timer {
+        duration: Duration,
+        recurrence: Recurrence,
+        random: Option<Duration>,
+    },
+    /// A pattern huelightcli doesn't understand, e.g. from a newer bridge, kept as it was sent.
+    Other(String),
+}
+
+impl TimePattern {
+    /// A one-shot timer that fires `duration` after it is created.
+    pub fn timer(duration: Duration) -> Self {
+        TimePattern::Timer {
+            duration,
+            recurrence: Recurrence::Once,
+            random: None,
+        }
+    }
+}
+
+/// Parses `hh:mm:ss`, where hours may exceed 23 for timers.
+fn parse_hms(text: &str) -> Option<Duration> {
+    let mut parts = text.split(':').map(|p| p.parse::<u64>().ok());
+    let (Some(Some(h)), Some(Some(m)), Some(Some(s)), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return None;
+    };
+    (m < 60 && s < 60).then(|| Duration::from_secs(h * 3600 + m * 60 + s))
+}
+
+impl FromStr for TimePattern {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ParseError::InvalidTimePattern(s.to_string());
+        let (core, random) = match s.split_once('A') {
+            Some((core, random)) => (core, Some(parse_hms(random).ok_or_else(invalid)?)),
+            None => (s, None),
+        };
+
+        if let Some(rest) = core.strip_prefix('W') {
+            let (mask, time) = rest.split_once("/T").ok_or_else(invalid)?;
+            let mask: u8 = mask.parse().map_err(|_| invalid())?;
+            if mask == 0 || mask > Weekdays::ALL.0 {
+                return Err(invalid());
+            }
+            return Ok(TimePattern::Recurring {
+                weekdays: Weekdays(mask),
+                time: NaiveTime::parse_from_str(time, "%H:%M:%S").map_err(|_| invalid())?,
+                random,
+            });
+        }
+
+        let (recurrence, timer) = match core.strip_prefix('R') {
+            Some(rest) => {
+                let (count, timer) = rest.split_once('/').ok_or_else(invalid)?;
+                let recurrence = if count.is_empty() {
+                    Recurrence::Forever
+                } else {
+                    Recurrence::Times(count.parse().map_err(|_| invalid())?)
+                };
+                (recurrence, Some(timer))
+            }
+            None => (Recurrence::Once, None),
+        };
+        if let Some(duration) = timer.unwrap_or(core).strip_prefix("PT") {
+            return Ok(TimePattern::Timer {
+                duration: parse_hms(duration).ok_or_else(invalid)?,
+                recurrence,
+                random,
+            });
+        }
+        if timer.is_some() {
+            return Err(invalid());
+        }
+
+        Ok(TimePattern::Absolute {
+            at: NaiveDateTime::parse_from_str(core, "%Y-%m-%dT%H:%M:%S").map_err(|_| invalid())?,
+            random,
+        })
+    }
+}
+
+impl From<String> for TimePattern {
+    fn from(value: String) -> Self {
+        value.parse().unwrap_or(TimePattern::Other(value))
+    }
+}
+
+impl From<TimePattern> for String {
+    fn from(value: TimePattern) -> Self {
+        value.to_string()
+    }
+}
+
+impl std::fmt::Display for TimePattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let random = match self {
+            TimePattern::Absolute { at, random } => {
+                write!(f, "{}", at.format("%Y-%m-%dT%H:%M:%S"))?;
+                random
+            }
+            TimePattern::Recurring {
+                weekdays,
+                time,
+                random,
+            } => {
+                write!(f, "W{:03}/T{}", weekdays.0, time.format("%H:%M:%S"))?;
+                random
+            }
+            TimePattern::Timer {
+                duration,
+                recurrence,
+                random,
+            } => {
+                match recurrence {
+                    Recurrence::Once => {}
+                    Recurrence::Times(n) => write!(f, "R{:02}/", n)?,
+                    Recurrence::Forever => write!(f, "R/")?,
+                }
+                write!(f, "PT{}", format_hms(*duration))?;
+                random
+            }
+            TimePattern::Other(pattern) => return write!(f, "{}", pattern),
+        };
+        match random {
+            Some(random) => write!(f, "A{}", format_hms(*random)),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::Weekday;
+
+    use crate::models::schedule::{Recurrence, ScheduleResponse, TimePattern, Weekdays};
+
+    #[test]
+    pub fn time_pattern_round_trips_every_form() {
+        // Arrange
+        let patterns = [
+            "2024-05-01T07:30:00",
+            "2024-05-01T07:30:00A00:15:00",
+            "W127/T07:00:00",
+            "W003/T09:30:00A00:30:00",
+            "PT00:10:00",
+            "PT01:00:00A00:05:00",
+            "R05/PT00:00:30",
+            "R/PT02:00:00",
+        ];
+
+        for pattern in patterns {
+            // Act
+            let parsed: TimePattern = pattern.parse().unwrap();
+
+            // Assert
+            assert_eq!(pattern, parsed.to_string());
+        }
+    }
+
+    #[test]
+    pub fn time_pattern_parse_recurring_expect_weekdays() {
+        // Act
+        let parsed: TimePattern = "W124/T07:00:00".parse().unwrap();
+
+        // Assert
+        let TimePattern::Recurring { weekdays, .. } = parsed else {
+            panic!("expected a recurring pattern");
+        };
+        assert_eq!(Weekdays::WORKDAYS, weekdays);
+        assert!(weekdays.contains(Weekday::Mon));
+        assert!(!weekdays.contains(Weekday::Sun));
+        assert_eq!(
+            Weekdays::WEEKEND,
+            Weekdays::from_days(&[Weekday::Sat, Weekday::Sun])
+        );
+    }
+
+    #[test]
+    pub fn time_pattern_parse_timer_expect_duration_and_recurrence() {
+        // Act
+        let parsed: TimePattern = "R10/PT00:10:00".parse().unwrap();
+
+        // Assert
+        assert_eq!(
+            TimePattern::Timer {
+                duration: Duration::from_secs(600),
+                recurrence: Recurrence::Times(10),
+                random: None
+            },
+            parsed
+        );
+    }
+
+    #[test]
+    pub fn time_pattern_parse_invalid_expect_error() {
+        for pattern in [
+            "",
+            "W000/T07:00:00",
+            "W200/T07:00:00",
+            "PT00:61:00",
+            "R/2024-05-01T07:30:00",
+            "tomorrow",
+        ] {
+            assert!(pattern.parse::<TimePattern>().is_err(), "{pattern}");
+        }
+    }
+
+    #[test]
+    pub fn schedules_parse_from_bridge_json() {
+        // Arrange
+        let json = r#"{"1": {"name": "Wake up", "description": "", "created": "2024-01-01T00:00:00",
+            "command": {"address": "/api/user/groups/1/action", "method": "PUT", "body": {"on": true}},
+            "localtime": "W124/T07:00:00", "time": "W124/T06:00:00", "status": "enabled", "autodelete": false}}"#;
+
+        // Act
+        let schedules: ScheduleResponse = serde_json::from_str(json).unwrap();
+        let (id, schedule) = schedules.find("wake up").unwrap();
+        let serialized = serde_json::to_value(schedule).unwrap();
+
+        // Assert
+        assert_eq!(1, id);
+        assert_eq!("W124/T07:00:00", serialized["localtime"]);
+        assert!(serialized.get("created").is_none());
+    }
+
+    #[test]
+    pub fn schedules_parse_with_an_unknown_pattern_and_a_legacy_time() {
+        // Arrange
+        let json = r#"{
+            "1": {"name": "Wake up", "command": {"address": "/api/user/groups/1/action", "method": "PUT", "body": {"on": true}},
+                "localtime": "W124/T07:00:00"},
+            "2": {"name": "Sunset", "command": {"address": "/api/user/groups/1/action", "method": "PUT", "body": {"on": true}},
+                "localtime": "sunset+00:30"},
+            "3": {"name": "Old", "command": {"address": "/api/user/lights/1/state", "method": "PUT", "body": {"on": false}},
+                "time": "PT00:10:00"}}"#;
+
+        // Act
+        let schedules: ScheduleResponse = serde_json::from_str(json).unwrap();
+
+        // Assert
+        assert_eq!(3, schedules.0.len());
+        let sunset = schedules.find("sunset").unwrap().1;
+        assert_eq!(
+            Some(&TimePattern::Other("sunset+00:30".to_string())),
+            sunset.time_pattern()
+        );
+        assert_eq!(
+            "sunset+00:30",
+            serde_json::to_value(sunset).unwrap()["localtime"]
+        );
+        assert_eq!(
+            Some(&TimePattern::timer(Duration::from_secs(600))),
+            schedules.find("old").unwrap().1.time_pattern()
+        );
+    }
+}
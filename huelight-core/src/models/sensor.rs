@@ -0,0 +1,353 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+// Sensor related models
+pub type SensorId = u32;
+
+#[derive(Debug, Deserialize)]
+pub struct SensorResponse(pub HashMap<SensorId, Sensor>);
+
+impl SensorResponse {
+    /// Finds a sensor by its numeric ID or, failing that, by a case-insensitive name match.
+    pub fn find(&self, id_or_name: &str) -> Option<(SensorId, &Sensor)> {
+        if let Ok(id) = id_or_name.parse::<SensorId>() {
+            return self.0.get(&id).map(|s| (id, s));
+        }
+        self.0
+            .iter()
+            .find(|(_, s)| s.name.eq_ignore_ascii_case(id_or_name))
+            .map(|(id, s)| (*id, s))
+    }
+}
+
+/// A sensor with its state decoded according to its `type`.
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(from = "RawSensor")]
+pub struct Sensor {
+    pub name: String,
+    pub _type: String,
+    pub modelid: Option<String>,
+    pub manufacturername: Option<String>,
+    pub uniqueid: Option<String>,
+    pub swversion: Option<String>,
+    pub config: SensorConfig,
+    pub state: SensorState,
+}
+
+#[derive(Deserialize)]
+struct RawSensor {
+    name: String,
+    #[serde(rename = "type")]
+    _type: String,
+    #[serde(default)]
+    modelid: Option<String>,
+    #[serde(default)]
+    manufacturername: Option<String>,
+    #[serde(default)]
+    uniqueid: Option<String>,
+    #[serde(default)]
+    swversion: Option<String>,
+    #[serde(default)]
+    config: SensorConfig,
+    #[serde(default)]
+    state: Value,
+}
+
+impl From<RawSensor> for Sensor {
+    fn from(raw: RawSensor) -> Self {
+        Sensor {
+            state: SensorState::decode(&raw._type, raw.state),
+            name: raw.name,
+            _type: raw._type,
+            modelid: raw.modelid,
+            manufacturername: raw.manufacturername,
+            uniqueid: raw.uniqueid,
+            swversion: raw.swversion,
+            config: raw.config,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Default, PartialEq)]
+pub struct PresenceState {
+    pub presence: Option<bool>,
+    pub lastupdated: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Default, PartialEq)]
+pub struct TemperatureState {
+    /// Hundredths of a degree Celsius.
+    pub temperature: Option<i32>,
+    pub lastupdated: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Default, PartialEq)]
+pub struct LightLevelState {
+    /// 10000 * log10(lux) + 1
+    pub lightlevel: Option<u32>,
+    pub dark: Option<bool>,
+    pub daylight: Option<bool>,
+    pub lastupdated: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Default, PartialEq)]
+pub struct SwitchState {
+    /// Button number * 1000 + event (0 initial press, 1 hold, 2 short release, 3 long release).
+    pub buttonevent: Option<u32>,
+    pub lastupdated: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Default, PartialEq)]
+pub struct GenericStatusState {
+    pub status: Option<i32>,
+    pub lastupdated: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Default, PartialEq)]
+pub struct GenericFlagState {
+    pub flag: Option<bool>,
+    pub lastupdated: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SensorState {
+    Presence(PresenceState),
+    Temperature(TemperatureState),
+    LightLevel(LightLevelState),
+    Switch(SwitchState),
+    GenericStatus(GenericStatusState),
+    GenericFlag(GenericFlagState),
+    /// Sensor types without a typed model (daylight, geofence, ...) keep their raw state.
+    Other(Value),
+}
+
+impl SensorState {
+    fn decode(sensor_type: &str, state: Value) -> Self {
+        fn typed<T: serde::de::DeserializeOwned + Default>(state: &Value) -> T {
+            serde_json::from_value(state.clone()).unwrap_or_default()
+        }
+        match sensor_type {
+            "ZLLPresence" | "CLIPPresence" => SensorState::Presence(typed(&state)),
+            "ZLLTemperature" | "CLIPTemperature" => SensorState::Temperature(typed(&state)),
+            "ZLLLightLevel" | "CLIPLightLevel" => SensorState::LightLevel(typed(&state)),
+            "ZLLSwitch" | "ZGPSwitch" | "CLIPSwitch" => SensorState::Switch(typed(&state)),
+            "CLIPGenericStatus" => SensorState::GenericStatus(typed(&state)),
+            "CLIPGenericFlag" => SensorState::GenericFlag(typed(&state)),
+            _ => SensorState::Other(state),
+        }
+    }
+
+    /// Temperature in degrees Celsius, for temperature sensors.
+    pub fn celsius(&self) -> Option<f64> {
+        match self {
+            SensorState::Temperature(t) => t.temperature.map(|t| t as f64 / 100.0),
+            _ => None,
+        }
+    }
+
+    /// Illuminance in lux, for light level sensors.
+    pub fn lux(&self) -> Option<f64> {
+        match self {
+            SensorState::LightLevel(l) => l
+                .lightlevel
+                .map(|level| 10f64.powf((level as f64 - 1.0) / 10000.0)),
+            _ => None,
+        }
+    }
+
+    pub fn lastupdated(&self) -> Option<&str> {
+        match self {
+            SensorState::Presence(s) => s.lastupdated.as_deref(),
+            SensorState::Temperature(s) => s.lastupdated.as_deref(),
+            SensorState::LightLevel(s) => s.lastupdated.as_deref(),
+            SensorState::Switch(s) => s.lastupdated.as_deref(),
+            SensorState::GenericStatus(s) => s.lastupdated.as_deref(),
+            SensorState::GenericFlag(s) => s.lastupdated.as_deref(),
+            SensorState::Other(v) => v.get("lastupdated").and_then(Value::as_str),
+        }
+    }
+}
+
+/// Human readable state, e.g. `21.50 °C` or `312.4 lux (dark)`.
+impl std::fmt::Display for SensorState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let yes_no = |b: bool| if b { "yes" } else { "no" };
+        match self {
+            SensorState::Presence(s) => match s.presence {
+                Some(p) => write!(f, "presence: {}", yes_no(p)),
+                None => write!(f, "presence: unknown"),
+            },
+            SensorState::Temperature(_) => match self.celsius() {
+                Some(c) => write!(f, "{c:.2} °C"),
+                None => write!(f, "temperature: unknown"),
+            },
+            SensorState::LightLevel(s) => {
+                match self.lux() {
+                    Some(lux) => write!(f, "{lux:.1} lux")?,
+                    None => write!(f, "light level: unknown")?,
+                }
+                match (s.dark, s.daylight) {
+                    (Some(true), _) => write!(f, " (dark)"),
+                    (_, Some(true)) => write!(f, " (daylight)"),
+                    _ => Ok(()),
+                }
+            }
+            SensorState::Switch(s) => match s.buttonevent {
+                Some(event) => {
+                    let action = match event % 1000 {
+                        0 => "initial press",
+                        1 => "hold",
+                        2 => "short release",
+                        3 => "long release",
+                        _ => "unknown event",
+                    };
+                    write!(f, "button {} {action}", event / 1000)
+                }
+                None => write!(f, "no button events yet"),
+            },
+            SensorState::GenericStatus(s) => match s.status {
+                Some(status) => write!(f, "status: {status}"),
+                None => write!(f, "status: unknown"),
+            },
+            SensorState::GenericFlag(s) => match s.flag {
+                Some(flag) => write!(f, "flag: {flag}"),
+                None => write!(f, "flag: unknown"),
+            },
+            SensorState::Other(v) => write!(f, "{v}"),
+        }
+    }
+}
+
+/// Sensor configuration. Also used to send config changes, so unset fields are omitted.
+#[derive(Debug, Deserialize, Serialize, Default, Clone, PartialEq)]
+pub struct SensorConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on: Option<bool>,
+    /// Reported by the bridge only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reachable: Option<bool>,
+    /// Battery percentage. Reported by the bridge only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub battery: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sensitivity: Option<u8>,
+    /// Reported by the bridge only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sensitivitymax: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ledindication: Option<bool>,
+    /// Light level below which the sensor reports `dark`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tholddark: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tholdoffset: Option<u32>,
+}
+
+/// State sent to a virtual CLIP sensor.
+#[derive(Debug, Serialize, Default, Clone, PartialEq)]
+pub struct SensorStateUpdate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flag: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub presence: Option<bool>,
+}
+
+/// Kinds of virtual sensor that can be created on the bridge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClipSensorType {
+    GenericFlag,
+    GenericStatus,
+    Presence,
+}
+
+impl ClipSensorType {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            ClipSensorType::GenericFlag => "CLIPGenericFlag",
+            ClipSensorType::GenericStatus => "CLIPGenericStatus",
+            ClipSensorType::Presence => "CLIPPresence",
+        }
+    }
+}
+
+/// Body of `POST /sensors` for creating a virtual CLIP sensor.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct NewSensor {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub _type: String,
+    pub modelid: String,
+    pub manufacturername: String,
+    pub swversion: String,
+    pub uniqueid: String,
+}
+
+impl NewSensor {
+    pub fn clip(name: impl Into<String>, sensor_type: ClipSensorType) -> Self {
+        let name = name.into();
+        let slug: String = name
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_lowercase()
+                } else {
+                    '-'
+                }
+            })
+            .collect();
+        Self {
+            _type: sensor_type.type_name().to_string(),
+            modelid: "huelightcli".to_string(),
+            manufacturername: "huelightcli".to_string(),
+            swversion: env!("CARGO_PKG_VERSION").to_string(),
+            uniqueid: format!("huelightcli-{slug}"),
+            name,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::sensor::{SensorResponse, SensorState};
+
+    const SENSORS: &str = r#"{
+        "5": {"state": {"presence": true, "lastupdated": "2024-05-01T10:00:00"}, "config": {"on": true, "battery": 87, "reachable": true, "sensitivity": 2, "sensitivitymax": 2}, "name": "Hall motion", "type": "ZLLPresence", "modelid": "SML001"},
+        "6": {"state": {"temperature": 2150}, "config": {"on": true, "battery": 87}, "name": "Hall temperature", "type": "ZLLTemperature"},
+        "7": {"state": {"lightlevel": 20001, "dark": false, "daylight": true}, "config": {"on": true, "tholddark": 16000}, "name": "Hall light level", "type": "ZLLLightLevel"},
+        "8": {"state": {"buttonevent": 4002}, "config": {"on": true}, "name": "Dimmer", "type": "ZLLSwitch"},
+        "9": {"state": {"flag": false}, "config": {"on": true}, "name": "Away", "type": "CLIPGenericFlag"},
+        "1": {"state": {"daylight": null}, "config": {"on": true}, "name": "Daylight", "type": "Daylight"}
+    }"#;
+
+    #[test]
+    pub fn sensors_decode_typed_state_by_type() {
+        // Act
+        let sensors: SensorResponse = serde_json::from_str(SENSORS).unwrap();
+
+        // Assert
+        let (_, motion) = sensors.find("hall motion").unwrap();
+        assert!(matches!(&motion.state, SensorState::Presence(p) if p.presence == Some(true)));
+        assert_eq!(Some(87), motion.config.battery);
+        assert!(matches!(sensors.0[&9].state, SensorState::GenericFlag(_)));
+        assert!(matches!(sensors.0[&1].state, SensorState::Other(_)));
+    }
+
+    #[test]
+    pub fn sensor_state_display_uses_human_units() {
+        // Arrange
+        let sensors: SensorResponse = serde_json::from_str(SENSORS).unwrap();
+
+        // Act
+        let temperature = sensors.0[&6].state.to_string();
+        let light = sensors.0[&7].state.to_string();
+        let switch = sensors.0[&8].state.to_string();
+
+        // Assert
+        assert_eq!("21.50 °C", temperature);
+        assert_eq!("100.0 lux (daylight)", light);
+        assert_eq!("button 4 short release", switch);
+    }
+}
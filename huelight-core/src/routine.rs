@@ -0,0 +1,377 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use chrono::{DateTime, Days, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+use crate::animation::{Animation, Keyframe, Timeline, fit_loosely};
+use crate::batch::set_light_states;
+use crate::config::{FileHandler, config_dir, path_to_str};
+use crate::domain::brightness::BrightnessValue;
+use crate::domain::light::{LightId, LightState, Lights};
+use crate::domain::named_colors::ColorRegistry;
+use crate::error::{CoreError, CoreResult, ParseError};
+use crate::fanout::DEFAULT_CONCURRENCY;
+use crate::hue_api::HueApi;
+
+/// How often a running routine updates the lights. Each update fades over the whole step, so
+/// the ramp looks continuous.
+pub const ROUTINE_STEP: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RoutineKind {
+    /// Sunrise simulation, ending fully bright at the wake-up time.
+    Wake,
+    /// Fade from the current state to off.
+    Sleep,
+}
+
+impl RoutineKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            RoutineKind::Wake => "wake",
+            RoutineKind::Sleep => "sleep",
+        }
+    }
+}
+
+/// One point on the wake-up curve, as written in the config:
+/// `{ at = 0.5, brightness = "40%", color = "2700K" }`. `at` is the fraction of the ramp, from 0
+/// to 1.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CurvePoint {
+    pub at: f64,
+    pub brightness: String,
+    pub color: String,
+}
+
+impl CurvePoint {
+    fn new(at: f64, brightness: &str, color: &str) -> Self {
+        Self {
+            at,
+            brightness: brightness.to_string(),
+            color: color.to_string(),
+        }
+    }
+}
+
+/// Deep red at 1%, through orange and warm white, to cool white at 100%.
+pub fn default_wake_curve() -> Vec<CurvePoint> {
+    vec![
+        CurvePoint::new(0.0, "1%", "xy:0.675,0.322"),
+        CurvePoint::new(0.3, "10%", "xy:0.6,0.38"),
+        CurvePoint::new(0.6, "45%", "2700K"),
+        CurvePoint::new(1.0, "100%", "6500K"),
+    ]
+}
+
+/// Turns a wake-up curve into a timeline lasting `duration`.
+pub fn wake_timeline(
+    curve: &[CurvePoint],
+    duration: Duration,
+    colors: &ColorRegistry,
+) -> Result<Timeline, ParseError> {
+    let keyframes = curve
+        .iter()
+        .map(|point| {
+            let brightness: BrightnessValue = point.brightness.parse()?;
+            Ok(Keyframe {
+                at: duration.mul_f64(point.at.clamp(0.0, 1.0)),
+                on: Some(true),
+                brightness: Some(
+                    brightness
+                        .absolute()
+                        .ok_or_else(|| ParseError::InvalidBrightness(point.brightness.clone()))?,
+                ),
+                color: Some(colors.color(&point.color)?),
+            })
+        })
+        .collect::<Result<_, ParseError>>()?;
+    Ok(Timeline::new(keyframes))
+}
+
+/// The next time the clock in `timezone` shows `time`, after `now`.
+pub fn next_local_time(time: NaiveTime, now: DateTime<Utc>, timezone: &Tz) -> DateTime<Utc> {
+    let today = now.with_timezone(timezone).date_naive();
+    (0..=2)
+        .filter_map(|days| today.checked_add_days(Days::new(days)))
+        .filter_map(|date| {
+            timezone
+                .from_local_datetime(&date.and_time(time))
+                .earliest()
+        })
+        .map(|local| local.with_timezone(&Utc))
+        .find(|at| *at > now)
+        .unwrap_or(now)
+}
+
+/// A wake or sleep routine, saved to `routines/<kind>.json` in the config directory while it is
+/// pending or running so it can pick up where it left off after a restart. Removing the file
+/// cancels it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Routine {
+    pub kind: RoutineKind,
+    pub starts: DateTime<Utc>,
+    pub duration: Duration,
+    /// The lights and their state when the routine was set up; sleep fades down from these.
+    pub lights: BTreeMap<LightId, LightState>,
+}
+
+impl Routine {
+    pub fn new(
+        kind: RoutineKind,
+        starts: DateTime<Utc>,
+        duration: Duration,
+        lights: &Lights,
+        light_ids: &[LightId],
+    ) -> Self {
+        Self {
+            kind,
+            starts,
+            duration,
+            lights: light_ids
+                .iter()
+                .filter_map(|id| Some((*id, lights.0.get(id)?.state.clone())))
+                .collect(),
+        }
+    }
+
+    pub fn ends(&self) -> DateTime<Utc> {
+        self.starts + self.duration
+    }
+
+    /// How far into the routine `now` is, or `None` before it starts.
+    pub fn elapsed(&self, now: DateTime<Utc>) -> Option<Duration> {
+        (now - self.starts)
+            .to_std()
+            .ok()
+            .map(|e| e.min(self.duration))
+    }
+
+    pub fn is_finished(&self, now: DateTime<Utc>) -> bool {
+        now >= self.ends()
+    }
+
+    /// The animation this routine plays, using `curve` for wake-ups.
+    pub fn animation(
+        &self,
+        curve: &[CurvePoint],
+        colors: &ColorRegistry,
+    ) -> Result<Animation, ParseError> {
+        Ok(match self.kind {
+            RoutineKind::Wake => Animation::Timeline(wake_timeline(curve, self.duration, colors)?),
+            RoutineKind::Sleep => Animation::FadeOut,
+        })
+    }
+
+    /// The state each light should fade to over the next step.
+    pub fn plan(
+        &self,
+        animation: &Animation,
+        lights: &Lights,
+        now: DateTime<Utc>,
+    ) -> Vec<(LightId, LightState)> {
+        let Some(elapsed) = self.elapsed(now) else {
+            return Vec::new();
+        };
+        // Aim for where the ramp will be when this step's transition ends.
+        let (target, transition) = if elapsed >= self.duration {
+            (self.duration, Duration::ZERO)
+        } else {
+            let next = (elapsed + ROUTINE_STEP).min(self.duration);
+            (next, next - elapsed)
+        };
+
+        self.lights
+            .iter()
+            .filter_map(|(id, start)| {
+                let light = lights.0.get(id).filter(|light| light.reachable)?;
+                let state = animation
+                    .state_at(target, self.duration, 0, 1, start)
+                    .with_transition(transition);
+                Some((*id, fit_loosely(light, &state)?))
+            })
+            .collect()
+    }
+
+    /// Reads the lights and sends every one its next state. Returns how many were updated.
+    pub async fn step(
+        &self,
+        animation: &Animation,
+        api: &(dyn HueApi + Send + Sync),
+        ip_address: &str,
+        username: &str,
+        now: DateTime<Utc>,
+    ) -> CoreResult<usize> {
+        let lights = api.async_get_all_lights(ip_address, username).await?;
+        let updates = self.plan(animation, &lights, now);
+        // Lights of the same kind share each wake-up step, so they can move as one group.
+        let report =
+            set_light_states(api, ip_address, username, &updates, DEFAULT_CONCURRENCY).await;
+        if let Some((_, err)) = report.failed.into_iter().next() {
+            return Err(err);
+        }
+        Ok(updates.len())
+    }
+
+    fn path(kind: RoutineKind) -> CoreResult<PathBuf> {
+        Ok(config_dir()?
+            .join("routines")
+            .join(format!("{}.json", kind.name())))
+    }
+
+    pub async fn save(&self, file_handler: &impl FileHandler) -> CoreResult<()> {
+        let path = Self::path(self.kind)?;
+        if let Some(dir) = path.parent() {
+            file_handler.create_dir_all(dir).await?;
+        }
+        let json = serde_json::to_string_pretty(self).map_err(CoreError::Serialization)?;
+        file_handler.write_file(path_to_str(&path)?, &json).await
+    }
+
+    /// The saved routine of this kind, if one is pending or running.
+    pub async fn load(
+        kind: RoutineKind,
+        file_handler: &impl FileHandler,
+    ) -> CoreResult<Option<Self>> {
+        let path = Self::path(kind)?;
+        match file_handler.read_file(path_to_str(&path)?).await {
+            Ok(json) => serde_json::from_str(&json)
+                .map(Some)
+                .map_err(CoreError::Serialization),
+            Err(CoreError::FileHandlerError(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Removes the saved routine of this kind. Returns false if there was none.
+    pub async fn cancel(kind: RoutineKind, file_handler: &impl FileHandler) -> CoreResult<bool> {
+        let path = Self::path(kind)?;
+        match file_handler.remove_file(path_to_str(&path)?).await {
+            Ok(()) => Ok(true),
+            Err(CoreError::FileHandlerError(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+                Ok(false)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use chrono::{NaiveTime, TimeZone, Utc};
+    use chrono_tz::Tz;
+
+    use super::{Routine, RoutineKind, default_wake_curve, next_local_time, wake_timeline};
+    use crate::animation::Animation;
+    use crate::domain::brightness::Brightness;
+    use crate::domain::light::{Light, LightFeatures, LightKind, LightState, Lights};
+    use crate::domain::named_colors::ColorRegistry;
+
+    fn lights(brightness: f64) -> Lights {
+        let light = Light {
+            reachable: true,
+            features: LightFeatures::for_kind(&LightKind::Dimmable),
+            state: LightState::default()
+                .with_on(true)
+                .with_brightness(Brightness::from_percent(brightness)),
+            ..Default::default()
+        };
+        Lights(HashMap::from([(1, light)]))
+    }
+
+    #[test]
+    pub fn next_local_time_rolls_over_to_tomorrow() {
+        // Arrange
+        let tz: Tz = "Europe/Amsterdam".parse().unwrap();
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 8, 0, 0).unwrap(); // 10:00 local
+
+        // Act
+        let later_today = next_local_time(NaiveTime::from_hms_opt(18, 0, 0).unwrap(), now, &tz);
+        let tomorrow = next_local_time(NaiveTime::from_hms_opt(6, 30, 0).unwrap(), now, &tz);
+
+        // Assert
+        assert_eq!(
+            Utc.with_ymd_and_hms(2024, 6, 1, 16, 0, 0).unwrap(),
+            later_today
+        );
+        assert_eq!(
+            Utc.with_ymd_and_hms(2024, 6, 2, 4, 30, 0).unwrap(),
+            tomorrow
+        );
+    }
+
+    #[test]
+    pub fn wake_curve_starts_dim_red_and_ends_full() {
+        // Arrange
+        let duration = Duration::from_secs(1800);
+        let timeline =
+            wake_timeline(&default_wake_curve(), duration, &ColorRegistry::builtin()).unwrap();
+        let wake = Animation::Timeline(timeline);
+        let start = LightState::default();
+
+        // Act
+        let first = wake.state_at(Duration::ZERO, duration, 0, 1, &start);
+        let last = wake.state_at(duration, duration, 0, 1, &start);
+
+        // Assert
+        assert_eq!(Some(Brightness::from_percent(1.0)), first.brightness);
+        assert!(first.color.unwrap().to_xy()[0] > 0.6);
+        assert_eq!(Some(Brightness::MAX), last.brightness);
+    }
+
+    #[test]
+    pub fn sleep_plan_resumes_from_saved_start_state() {
+        // Arrange
+        let starts = Utc.with_ymd_and_hms(2024, 6, 1, 22, 0, 0).unwrap();
+        // The lights were at 80% when the routine began; half way they read 40%.
+        let routine = Routine::new(
+            RoutineKind::Sleep,
+            starts,
+            Duration::from_secs(200),
+            &lights(80.0),
+            &[1],
+        );
+        let now = starts + chrono::Duration::seconds(100);
+
+        // Act
+        let plan = routine.plan(&Animation::FadeOut, &lights(40.0), now);
+
+        // Assert
+        let (id, state) = &plan[0];
+        assert_eq!(1, *id);
+        assert_eq!(Some(Brightness::from_percent(36.0)), state.brightness);
+        assert_eq!(Some(Duration::from_secs(10)), state.transition);
+    }
+
+    #[test]
+    pub fn plan_before_start_sends_nothing() {
+        // Arrange
+        let starts = Utc.with_ymd_and_hms(2024, 6, 2, 6, 0, 0).unwrap();
+        let routine = Routine::new(
+            RoutineKind::Sleep,
+            starts,
+            Duration::from_secs(600),
+            &lights(50.0),
+            &[1],
+        );
+
+        // Act
+        let plan = routine.plan(
+            &Animation::FadeOut,
+            &lights(50.0),
+            starts - chrono::Duration::seconds(1),
+        );
+
+        // Assert
+        assert!(plan.is_empty());
+    }
+}
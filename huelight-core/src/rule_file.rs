@@ -0,0 +1,957 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use chrono::NaiveTime;
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::domain::light::{LightState, Lights};
+use crate::duration::{format_hms, parse_duration, to_transition_time};
+use crate::error::{CoreError, RuleError};
+use crate::models::group::GroupResponse;
+use crate::models::light as v1;
+use crate::models::rule::{Action, Condition, ConditionOperator, Rule, RuleId, RuleResponse};
+use crate::models::scene::SceneResponse;
+use crate::models::sensor::{SensorResponse, SensorState, SensorStateUpdate};
+
+/// The bridge rejects rule names longer than this.
+const MAX_NAME_LEN: usize = 32;
+/// The bridge allows at most this many conditions and this many actions per rule.
+const MAX_CLAUSES: usize = 8;
+
+/// Raw contents of a rule file, e.g.
+///
+/// ```toml
+/// [[rule]]
+/// name = "Hall night motion"
+/// definition = '''
+/// when sensor "Hall motion" presence == true and time in 22:00..06:00
+/// then group "Hall" on bri 30
+/// '''
+///
+/// [[rule]]
+/// name = "Hall empty"
+/// definition = 'when sensor "Hall motion" presence == false for 5m then group "Hall" off'
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct RuleFile {
+    #[serde(default, rename = "rule")]
+    pub rules: Vec<RuleFileEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RuleFileEntry {
+    pub name: String,
+    /// A `when ... then ...` sentence.
+    pub definition: String,
+}
+
+impl RuleFile {
+    pub fn from_toml(text: &str) -> Result<Self, CoreError> {
+        toml::from_str(text).map_err(|e| CoreError::Rule(RuleError::File(e)))
+    }
+
+    /// Parses every entry, rejecting duplicate names.
+    pub fn parse(&self) -> Result<Vec<RuleSpec>, CoreError> {
+        let mut seen = HashSet::new();
+        self.rules
+            .iter()
+            .map(|entry| {
+                if !seen.insert(entry.name.as_str()) {
+                    return Err(CoreError::Rule(RuleError::Invalid {
+                        rule: entry.name.clone(),
+                        message: "duplicate rule name".to_string(),
+                    }));
+                }
+                RuleSpec::parse(&entry.name, &entry.definition)
+            })
+            .collect()
+    }
+}
+
+/// A literal compared against or written to a sensor attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleValue {
+    Bool(bool),
+    Int(i64),
+}
+
+impl std::fmt::Display for RuleValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuleValue::Bool(b) => write!(f, "{b}"),
+            RuleValue::Int(i) => write!(f, "{i}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SensorTest {
+    Equals(RuleValue),
+    GreaterThan(RuleValue),
+    LessThan(RuleValue),
+    Changed,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConditionSpec {
+    Sensor {
+        sensor: String,
+        attribute: String,
+        test: SensorTest,
+        /// Only fire once the attribute has kept its value for this long.
+        stable_for: Option<Duration>,
+    },
+    /// Bridge local time within `from..to`; the range may wrap past midnight.
+    Time {
+        from: NaiveTime,
+        to: NaiveTime,
+        negate: bool,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ActionSpec {
+    Light {
+        light: String,
+        state: LightState,
+    },
+    Group {
+        group: String,
+        state: LightState,
+    },
+    /// Recall a scene, on `group` if given, otherwise on the scene's own group (or all lights).
+    Scene {
+        scene: String,
+        group: Option<String>,
+    },
+    Sensor {
+        sensor: String,
+        update: SensorStateUpdate,
+    },
+}
+
+/// A parsed rule whose resource names have not been resolved yet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleSpec {
+    pub name: String,
+    pub conditions: Vec<ConditionSpec>,
+    pub actions: Vec<ActionSpec>,
+}
+
+/// Bridge resources used to resolve names in a rule to addresses.
+pub struct RuleResources<'a> {
+    pub lights: &'a Lights,
+    pub groups: &'a GroupResponse,
+    pub scenes: &'a SceneResponse,
+    pub sensors: &'a SensorResponse,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Comma,
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Word(w) => write!(f, "'{w}'"),
+            Token::Quoted(q) => write!(f, "\"{q}\""),
+            Token::Comma => write!(f, "','"),
+        }
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == ',' {
+            chars.next();
+            tokens.push(Token::Comma);
+        } else if c == '"' {
+            chars.next();
+            let mut quoted = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some(c) => quoted.push(c),
+                    None => return Err("unterminated quoted name".to_string()),
+                }
+            }
+            tokens.push(Token::Quoted(quoted));
+        } else if matches!(c, '=' | '<' | '>') {
+            let mut op = String::new();
+            while let Some(&c) = chars.peek().filter(|c| matches!(c, '=' | '<' | '>')) {
+                op.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Word(op));
+        } else {
+            let mut word = String::new();
+            while let Some(&c) = chars
+                .peek()
+                .filter(|c| !c.is_whitespace() && !matches!(c, ',' | '"' | '=' | '<' | '>'))
+            {
+                word.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Word(word));
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek_word(&self) -> Option<&str> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Word(w)) => Some(w.as_str()),
+            _ => None,
+        }
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn next_is(&mut self, keyword: &str) -> bool {
+        if self
+            .peek_word()
+            .is_some_and(|w| w.eq_ignore_ascii_case(keyword))
+        {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, keyword: &str) -> Result<(), String> {
+        if self.next_is(keyword) {
+            Ok(())
+        } else {
+            Err(format!(
+                "expected '{keyword}', found {}",
+                self.describe_next()
+            ))
+        }
+    }
+
+    fn word(&mut self, what: &str) -> Result<String, String> {
+        match self.next() {
+            Some(Token::Word(w)) => Ok(w),
+            other => Err(format!(
+                "expected {what}, found {}",
+                describe(other.as_ref())
+            )),
+        }
+    }
+
+    /// A resource name, quoted if it contains spaces.
+    fn name(&mut self, what: &str) -> Result<String, String> {
+        match self.next() {
+            Some(Token::Quoted(q)) | Some(Token::Word(q)) => Ok(q),
+            other => Err(format!(
+                "expected {what} name, found {}",
+                describe(other.as_ref())
+            )),
+        }
+    }
+
+    fn describe_next(&self) -> String {
+        describe(self.tokens.get(self.pos))
+    }
+
+    fn value(&mut self) -> Result<RuleValue, String> {
+        let word = self.word("a value")?;
+        match word.to_ascii_lowercase().as_str() {
+            "true" => Ok(RuleValue::Bool(true)),
+            "false" => Ok(RuleValue::Bool(false)),
+            _ => word
+                .parse()
+                .map(RuleValue::Int)
+                .map_err(|_| format!("'{word}' is not true, false or a whole number")),
+        }
+    }
+
+    fn number<T: std::str::FromStr>(&mut self, what: &str) -> Result<T, String> {
+        let word = self.word(what)?;
+        word.parse()
+            .map_err(|_| format!("'{word}' is not a valid {what}"))
+    }
+
+    fn duration(&mut self) -> Result<Duration, String> {
+        let word = self.word("a duration")?;
+        parse_duration(&word).map_err(|e| e.to_string())
+    }
+
+    fn condition(&mut self) -> Result<ConditionSpec, String> {
+        if self.next_is("time") {
+            let negate = self.next_is("not");
+            self.expect("in")?;
+            let range = self.word("a time range")?;
+            let (from, to) = range
+                .split_once("..")
+                .ok_or_else(|| format!("'{range}' is not a time range like 22:00..06:00"))?;
+            return Ok(ConditionSpec::Time {
+                from: parse_time(from)?,
+                to: parse_time(to)?,
+                negate,
+            });
+        }
+        self.expect("sensor")?;
+        let sensor = self.name("sensor")?;
+        let attribute = self.word("a sensor attribute")?.to_ascii_lowercase();
+        let op = self.word("a comparison")?;
+        let test = match op.to_ascii_lowercase().as_str() {
+            "==" | "is" => SensorTest::Equals(self.value()?),
+            ">" => SensorTest::GreaterThan(self.value()?),
+            "<" => SensorTest::LessThan(self.value()?),
+            "changed" => SensorTest::Changed,
+            _ => {
+                return Err(format!(
+                    "unknown comparison '{op}', use ==, >, < or changed"
+                ));
+            }
+        };
+        let stable_for = if self.next_is("for") {
+            Some(self.duration()?)
+        } else {
+            None
+        };
+        Ok(ConditionSpec::Sensor {
+            sensor,
+            attribute,
+            test,
+            stable_for,
+        })
+    }
+
+    /// Reads light state words. Values are in bridge units (bri 1-254, hue 0-65535, ...).
+    fn light_state(&mut self) -> Result<LightState, String> {
+        let mut state = v1::LightState::default();
+        let mut any = false;
+        while let Some(word) = self.peek_word().map(str::to_ascii_lowercase) {
+            match word.as_str() {
+                "on" | "off" => {
+                    self.pos += 1;
+                    state.on = Some(word == "on");
+                }
+                "bri" => {
+                    self.pos += 1;
+                    state.brightness = Some(self.number("brightness")?);
+                }
+                "ct" => {
+                    self.pos += 1;
+                    state.color_temperature = Some(self.number("color temperature")?);
+                }
+                "hue" => {
+                    self.pos += 1;
+                    state.hue = Some(self.number("hue")?);
+                }
+                "sat" => {
+                    self.pos += 1;
+                    state.saturation = Some(self.number("saturation")?);
+                }
+                "transition" => {
+                    self.pos += 1;
+                    state.transition_time = Some(to_transition_time(self.duration()?));
+                }
+                _ => break,
+            }
+            any = true;
+        }
+        if any {
+            Ok(state.into())
+        } else {
+            Err(format!(
+                "expected a light state (on, off, bri, ct, hue, sat, transition), found {}",
+                self.describe_next()
+            ))
+        }
+    }
+
+    fn action(&mut self) -> Result<ActionSpec, String> {
+        let kind = self.word("light, group, scene or sensor")?;
+        match kind.to_ascii_lowercase().as_str() {
+            "light" => Ok(ActionSpec::Light {
+                light: self.name("light")?,
+                state: self.light_state()?,
+            }),
+            "group" => Ok(ActionSpec::Group {
+                group: self.name("group")?,
+                state: self.light_state()?,
+            }),
+            "scene" => {
+                let scene = self.name("scene")?;
+                let group = if self.next_is("on") {
+                    self.expect("group")?;
+                    Some(self.name("group")?)
+                } else {
+                    None
+                };
+                Ok(ActionSpec::Scene { scene, group })
+            }
+            "sensor" => {
+                let sensor = self.name("sensor")?;
+                let attribute = self.word("flag, status or presence")?;
+                let mut update = SensorStateUpdate::default();
+                match (attribute.to_ascii_lowercase().as_str(), self.value()?) {
+                    ("flag", RuleValue::Bool(b)) => update.flag = Some(b),
+                    ("presence", RuleValue::Bool(b)) => update.presence = Some(b),
+                    ("status", RuleValue::Int(i)) => {
+                        update.status = Some(
+                            i32::try_from(i).map_err(|_| format!("status {i} is out of range"))?,
+                        )
+                    }
+                    (attr, value) => {
+                        return Err(format!("cannot set sensor {attr} to {value}"));
+                    }
+                }
+                Ok(ActionSpec::Sensor { sensor, update })
+            }
+            _ => Err(format!(
+                "expected light, group, scene or sensor, found '{kind}'"
+            )),
+        }
+    }
+}
+
+fn describe(token: Option<&Token>) -> String {
+    token.map_or("end of rule".to_string(), Token::to_string)
+}
+
+fn parse_time(text: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(text, "%H:%M:%S")
+        .or_else(|_| NaiveTime::parse_from_str(text, "%H:%M"))
+        .map_err(|_| format!("'{text}' is not a time like 22:00"))
+}
+
+impl RuleSpec {
+    /// Parses a `when <condition> [and ...] then <action> [and|, ...]` sentence.
+    pub fn parse(name: &str, definition: &str) -> Result<Self, CoreError> {
+        Self::parse_inner(name, definition).map_err(|message| {
+            CoreError::Rule(RuleError::Syntax {
+                rule: name.to_string(),
+                message,
+            })
+        })
+    }
+
+    fn parse_inner(name: &str, definition: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            tokens: tokenize(definition)?,
+            pos: 0,
+        };
+        parser.expect("when")?;
+        let mut conditions = vec![parser.condition()?];
+        while parser.next_is("and") {
+            conditions.push(parser.condition()?);
+        }
+        parser.expect("then")?;
+        let mut actions = vec![parser.action()?];
+        loop {
+            if parser.tokens.get(parser.pos) == Some(&Token::Comma) {
+                parser.pos += 1;
+            } else if !parser.next_is("and") {
+                break;
+            }
+            actions.push(parser.action()?);
+        }
+        if parser.pos < parser.tokens.len() {
+            return Err(format!("unexpected {}", parser.describe_next()));
+        }
+        Ok(Self {
+            name: name.to_string(),
+            conditions,
+            actions,
+        })
+    }
+
+    /// Resolves names against the bridge's resources and produces the rule to install.
+    pub fn compile(&self, resources: &RuleResources) -> Result<Rule, CoreError> {
+        let invalid = |message: String| {
+            CoreError::Rule(RuleError::Invalid {
+                rule: self.name.clone(),
+                message,
+            })
+        };
+        let unknown = |kind: &str, name: &str| {
+            CoreError::Rule(RuleError::UnknownResource {
+                rule: self.name.clone(),
+                kind: kind.to_string(),
+                name: name.to_string(),
+            })
+        };
+
+        if self.name.is_empty() || self.name.chars().count() > MAX_NAME_LEN {
+            return Err(invalid(format!(
+                "name must be 1 to {MAX_NAME_LEN} characters"
+            )));
+        }
+
+        let mut conditions = Vec::new();
+        for condition in &self.conditions {
+            match condition {
+                ConditionSpec::Time { from, to, negate } => conditions.push(Condition {
+                    address: "/config/localtime".to_string(),
+                    operator: if *negate {
+                        ConditionOperator::NotIn
+                    } else {
+                        ConditionOperator::In
+                    },
+                    value: Some(format!(
+                        "T{}/T{}",
+                        from.format("%H:%M:%S"),
+                        to.format("%H:%M:%S")
+                    )),
+                }),
+                ConditionSpec::Sensor {
+                    sensor,
+                    attribute,
+                    test,
+                    stable_for,
+                } => {
+                    let (id, found) = resources
+                        .sensors
+                        .find(sensor)
+                        .ok_or_else(|| unknown("sensor", sensor))?;
+                    let kind = attribute_kind(&found.state, attribute).ok_or_else(|| {
+                        CoreError::Rule(RuleError::InvalidAttribute {
+                            rule: self.name.clone(),
+                            sensor: sensor.clone(),
+                            attribute: attribute.clone(),
+                        })
+                    })?;
+                    let address = format!("/sensors/{id}/state/{attribute}");
+                    let (operator, value) = match test {
+                        SensorTest::Equals(v) => (ConditionOperator::Equals, Some(*v)),
+                        SensorTest::GreaterThan(v) => (ConditionOperator::GreaterThan, Some(*v)),
+                        SensorTest::LessThan(v) => (ConditionOperator::LessThan, Some(*v)),
+                        SensorTest::Changed => (ConditionOperator::Changed, None),
+                    };
+                    if let Some(value) = value {
+                        kind.check(operator, value).map_err(|reason| {
+                            invalid(format!("sensor \"{sensor}\" {attribute}: {reason}"))
+                        })?;
+                    }
+                    conditions.push(Condition {
+                        address: address.clone(),
+                        operator,
+                        value: value.map(|v| v.to_string()),
+                    });
+                    if let Some(stable_for) = stable_for {
+                        conditions.push(Condition {
+                            address,
+                            operator: ConditionOperator::ChangedDelayed,
+                            value: Some(format!("PT{}", format_hms(*stable_for))),
+                        });
+                    }
+                }
+            }
+        }
+
+        let mut actions = Vec::new();
+        for action in &self.actions {
+            let (address, body) = match action {
+                ActionSpec::Light { light, state } => {
+                    let (id, _) = resources
+                        .lights
+                        .find(light)
+                        .ok_or_else(|| unknown("light", light))?;
+                    (format!("/lights/{id}/state"), to_body(state)?)
+                }
+                ActionSpec::Group { group, state } => {
+                    let id = resolve_group(resources.groups, group)
+                        .ok_or_else(|| unknown("group", group))?;
+                    (format!("/groups/{id}/action"), to_body(state)?)
+                }
+                ActionSpec::Scene { scene, group } => {
+                    let (scene_id, found) = resources
+                        .scenes
+                        .find(scene)
+                        .ok_or_else(|| unknown("scene", scene))?;
+                    let group_id = match group {
+                        Some(group) => resolve_group(resources.groups, group)
+                            .ok_or_else(|| unknown("group", group))?
+                            .to_string(),
+                        None => found.group.clone().unwrap_or_else(|| "0".to_string()),
+                    };
+                    (
+                        format!("/groups/{group_id}/action"),
+                        json!({ "scene": scene_id }),
+                    )
+                }
+                ActionSpec::Sensor { sensor, update } => {
+                    let (id, _) = resources
+                        .sensors
+                        .find(sensor)
+                        .ok_or_else(|| unknown("sensor", sensor))?;
+                    (format!("/sensors/{id}/state"), to_body(update)?)
+                }
+            };
+            actions.push(Action {
+                address,
+                method: "PUT".to_string(),
+                body,
+            });
+        }
+
+        if conditions.len() > MAX_CLAUSES || actions.len() > MAX_CLAUSES {
+            return Err(invalid(format!(
+                "the bridge allows at most {MAX_CLAUSES} conditions and {MAX_CLAUSES} actions"
+            )));
+        }
+        Ok(Rule::new(self.name.clone(), conditions, actions))
+    }
+}
+
+fn to_body<T: serde::Serialize>(value: &T) -> Result<Value, CoreError> {
+    serde_json::to_value(value).map_err(CoreError::Serialization)
+}
+
+/// "0" is the bridge's implicit all-lights group and is never listed in `/groups`.
+fn resolve_group(groups: &GroupResponse, group: &str) -> Option<u32> {
+    if group == "0" {
+        return Some(0);
+    }
+    groups.find(group).map(|(id, _)| id)
+}
+
+/// The type of value a sensor attribute holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AttributeKind {
+    Bool,
+    Int,
+    /// Timestamps and untyped attributes can only be watched for changes.
+    Opaque,
+}
+
+impl AttributeKind {
+    fn check(self, operator: ConditionOperator, value: RuleValue) -> Result<(), String> {
+        match (self, value) {
+            (AttributeKind::Bool, RuleValue::Bool(_)) if operator == ConditionOperator::Equals => {
+                Ok(())
+            }
+            (AttributeKind::Bool, RuleValue::Bool(_)) => {
+                Err("true/false values can only be compared with ==".to_string())
+            }
+            (AttributeKind::Int, RuleValue::Int(_)) => Ok(()),
+            (AttributeKind::Opaque, _) => Err("can only be watched with 'changed'".to_string()),
+            (_, value) => Err(format!("{value} is the wrong type of value")),
+        }
+    }
+}
+
+/// Which attributes a sensor exposes for rule conditions, based on its decoded state.
+fn attribute_kind(state: &SensorState, attribute: &str) -> Option<AttributeKind> {
+    use AttributeKind::*;
+    let kind = match (state, attribute) {
+        (_, "lastupdated") => Opaque,
+        (SensorState::Presence(_), "presence") => Bool,
+        (SensorState::Temperature(_), "temperature") => Int,
+        (SensorState::LightLevel(_), "lightlevel") => Int,
+        (SensorState::LightLevel(_), "dark" | "daylight") => Bool,
+        (SensorState::Switch(_), "buttonevent") => Int,
+        (SensorState::GenericStatus(_), "status") => Int,
+        (SensorState::GenericFlag(_), "flag") => Bool,
+        (SensorState::Other(raw), attribute) => match raw.get(attribute)? {
+            Value::Bool(_) => Bool,
+            Value::Number(_) => Int,
+            _ => Opaque,
+        },
+        _ => return None,
+    };
+    Some(kind)
+}
+
+/// What `rules apply` will do to bring the bridge in line with a rule file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuleChange {
+    Create(Rule),
+    Update {
+        id: RuleId,
+        rule: Rule,
+    },
+    Unchanged {
+        id: RuleId,
+        name: String,
+    },
+    /// Installed on the bridge but not in the file.
+    Extra {
+        id: RuleId,
+        name: String,
+    },
+}
+
+impl std::fmt::Display for RuleChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuleChange::Create(rule) => write!(f, "+ create    \"{}\"", rule.name),
+            RuleChange::Update { id, rule } => write!(f, "~ update    \"{}\" (#{id})", rule.name),
+            RuleChange::Unchanged { id, name } => write!(f, "= unchanged \"{name}\" (#{id})"),
+            RuleChange::Extra { id, name } => write!(f, "- extra     \"{name}\" (#{id})"),
+        }
+    }
+}
+
+/// Matches desired rules to installed ones by exact name.
+///
+/// Desired rules come first, in file order, followed by extra installed rules by ID.
+pub fn diff_rules(desired: Vec<Rule>, installed: &RuleResponse) -> Vec<RuleChange> {
+    let mut installed_ids: Vec<RuleId> = installed.0.keys().copied().collect();
+    installed_ids.sort_unstable();
+    let mut matched = HashSet::new();
+
+    let mut changes: Vec<RuleChange> = desired
+        .into_iter()
+        .map(|rule| {
+            let existing = installed_ids
+                .iter()
+                .find(|id| !matched.contains(*id) && installed.0[*id].name == rule.name);
+            match existing {
+                Some(&id) => {
+                    matched.insert(id);
+                    if installed.0[&id].same_definition(&rule) {
+                        RuleChange::Unchanged {
+                            id,
+                            name: rule.name,
+                        }
+                    } else {
+                        RuleChange::Update { id, rule }
+                    }
+                }
+                None => RuleChange::Create(rule),
+            }
+        })
+        .collect();
+
+    changes.extend(
+        installed_ids
+            .into_iter()
+            .filter(|id| !matched.contains(id))
+            .map(|id| RuleChange::Extra {
+                id,
+                name: installed.0[&id].name.clone(),
+            }),
+    );
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+
+    use crate::domain::light::Lights;
+    use crate::error::{CoreError, RuleError};
+    use crate::models::group::GroupResponse;
+    use crate::models::rule::{ConditionOperator, Rule, RuleResponse};
+    use crate::models::scene::SceneResponse;
+    use crate::models::sensor::SensorResponse;
+    use crate::rule_file::{RuleChange, RuleFile, RuleResources, RuleSpec, diff_rules};
+
+    fn resources() -> (Lights, GroupResponse, SceneResponse, SensorResponse) {
+        let lights = serde_json::from_value(json!({
+            "1": {"name": "Desk", "type": "Extended color light", "state": {"on": false}}
+        }))
+        .unwrap();
+        let groups = serde_json::from_value(json!({
+            "2": {"name": "Hall", "type": "Room", "lights": ["1"], "action": {"on": false}, "state": {"all_on": false, "any_on": false}}
+        }))
+        .unwrap();
+        let scenes = serde_json::from_value(json!({
+            "abc": {"name": "Nightlight", "type": "GroupScene", "group": "2", "lights": ["1"]}
+        }))
+        .unwrap();
+        let sensors = serde_json::from_value(json!({
+            "5": {"name": "Hall motion", "type": "ZLLPresence", "state": {"presence": false, "lastupdated": "none"}, "config": {"on": true}},
+            "6": {"name": "Hall light", "type": "ZLLLightLevel", "state": {"lightlevel": 100, "dark": true, "daylight": false}, "config": {"on": true}}
+        }))
+        .unwrap();
+        (lights, groups, scenes, sensors)
+    }
+
+    fn compile(definition: &str) -> Result<Rule, CoreError> {
+        let (lights, groups, scenes, sensors) = resources();
+        let resources = RuleResources {
+            lights: &lights,
+            groups: &groups,
+            scenes: &scenes,
+            sensors: &sensors,
+        };
+        RuleSpec::parse("test", definition)?.compile(&resources)
+    }
+
+    #[test]
+    pub fn compile_motion_at_night_expect_bridge_rule() {
+        // Arrange
+        let definition = r#"when sensor "Hall motion" presence == true and time in 22:00..06:00 then group "Hall" bri 30"#;
+
+        // Act
+        let rule = compile(definition).unwrap();
+
+        // Assert
+        assert_eq!(2, rule.conditions.len());
+        assert_eq!("/sensors/5/state/presence", rule.conditions[0].address);
+        assert_eq!(Some("true"), rule.conditions[0].value.as_deref());
+        assert_eq!("/config/localtime", rule.conditions[1].address);
+        assert_eq!(ConditionOperator::In, rule.conditions[1].operator);
+        assert_eq!(
+            Some("T22:00:00/T06:00:00"),
+            rule.conditions[1].value.as_deref()
+        );
+        assert_eq!("/groups/2/action", rule.actions[0].address);
+        assert_eq!(json!({"bri": 30}), rule.actions[0].body);
+    }
+
+    #[test]
+    pub fn compile_stable_for_and_several_actions_expect_ddx_condition() {
+        // Arrange
+        let definition = r#"when sensor "Hall motion" presence == false for 5m
+            then light Desk off transition 2s, scene Nightlight and sensor 5 presence false"#;
+
+        // Act
+        let rule = compile(definition).unwrap();
+
+        // Assert
+        assert_eq!(
+            ConditionOperator::ChangedDelayed,
+            rule.conditions[1].operator
+        );
+        assert_eq!(Some("PT00:05:00"), rule.conditions[1].value.as_deref());
+        assert_eq!(
+            json!({"on": false, "transitiontime": 20}),
+            rule.actions[0].body
+        );
+        assert_eq!("/groups/2/action", rule.actions[1].address);
+        assert_eq!(json!({"scene": "abc"}), rule.actions[1].body);
+        assert_eq!("/sensors/5/state", rule.actions[2].address);
+    }
+
+    #[test]
+    pub fn compile_unknown_sensor_expect_unknown_resource() {
+        // Act
+        let result = compile(r#"when sensor "Garage" presence == true then group Hall on"#);
+
+        // Assert
+        assert!(matches!(
+            result,
+            Err(CoreError::Rule(RuleError::UnknownResource { kind, .. })) if kind == "sensor"
+        ));
+    }
+
+    #[test]
+    pub fn compile_attribute_not_on_sensor_expect_invalid_attribute() {
+        // Act
+        let result = compile(r#"when sensor "Hall motion" dark == true then group Hall on"#);
+
+        // Assert
+        assert!(matches!(
+            result,
+            Err(CoreError::Rule(RuleError::InvalidAttribute { .. }))
+        ));
+    }
+
+    #[test]
+    pub fn compile_greater_than_on_bool_expect_invalid() {
+        // Act
+        let result = compile(r#"when sensor "Hall light" dark > 1 then group Hall on"#);
+
+        // Assert
+        assert!(matches!(
+            result,
+            Err(CoreError::Rule(RuleError::Invalid { .. }))
+        ));
+    }
+
+    #[test]
+    pub fn parse_missing_then_expect_syntax_error() {
+        // Act
+        let result = RuleSpec::parse("bad", r#"when sensor "Hall motion" presence == true"#);
+
+        // Assert
+        assert!(matches!(
+            result,
+            Err(CoreError::Rule(RuleError::Syntax { message, .. })) if message.contains("'then'")
+        ));
+    }
+
+    #[test]
+    pub fn rule_file_duplicate_names_expect_error() {
+        // Arrange
+        let file = RuleFile::from_toml(
+            r#"
+            [[rule]]
+            name = "a"
+            definition = "when time in 22:00..06:00 then group 0 off"
+            [[rule]]
+            name = "a"
+            definition = "when time in 07:00..08:00 then group 0 on"
+            "#,
+        )
+        .unwrap();
+
+        // Act
+        let result = file.parse();
+
+        // Assert
+        assert!(result.is_err());
+    }
+
+    #[test]
+    pub fn diff_rules_matches_by_name() {
+        // Arrange
+        let same = compile("when time in 22:00..06:00 then group 0 off").unwrap();
+        let mut unchanged = same.clone();
+        unchanged.name = "unchanged".to_string();
+        let mut changed = same.clone();
+        changed.name = "changed".to_string();
+        let mut installed_changed = changed.clone();
+        installed_changed.conditions[0].value = Some("T23:00:00/T06:00:00".to_string());
+        let mut old = same.clone();
+        old.name = "old".to_string();
+        let mut new = same;
+        new.name = "new".to_string();
+        let installed = RuleResponse(HashMap::from([
+            (1, unchanged.clone()),
+            (2, installed_changed),
+            (3, old),
+        ]));
+
+        // Act
+        let changes = diff_rules(vec![unchanged, changed.clone(), new.clone()], &installed);
+
+        // Assert
+        assert_eq!(
+            vec![
+                RuleChange::Unchanged {
+                    id: 1,
+                    name: "unchanged".to_string()
+                },
+                RuleChange::Update {
+                    id: 2,
+                    rule: changed
+                },
+                RuleChange::Create(new),
+                RuleChange::Extra {
+                    id: 3,
+                    name: "old".to_string()
+                },
+            ],
+            changes
+        );
+    }
+}
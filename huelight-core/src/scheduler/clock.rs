@@ -0,0 +1,64 @@
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+/// Source of time for the scheduler, so the daemon loop can be driven by a virtual clock in tests.
+#[async_trait]
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+    async fn sleep_until(&self, deadline: DateTime<Utc>);
+}
+
+/// Wall clock backed by tokio timers.
+#[derive(Default)]
+pub struct SystemClock;
+
+#[async_trait]
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    async fn sleep_until(&self, deadline: DateTime<Utc>) {
+        if let Ok(wait) = (deadline - Utc::now()).to_std() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// Clock that only moves when told to. Sleeping jumps straight to the deadline.
+pub struct VirtualClock {
+    now: Mutex<DateTime<Utc>>,
+}
+
+impl VirtualClock {
+    pub fn new(start: DateTime<Utc>) -> Self {
+        Self {
+            now: Mutex::new(start),
+        }
+    }
+
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner()) = now;
+    }
+
+    pub fn advance(&self, by: chrono::Duration) {
+        let mut now = self.now.lock().unwrap_or_else(|e| e.into_inner());
+        *now += by;
+    }
+}
+
+#[async_trait]
+impl Clock for VirtualClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    async fn sleep_until(&self, deadline: DateTime<Utc>) {
+        let mut now = self.now.lock().unwrap_or_else(|e| e.into_inner());
+        if deadline > *now {
+            *now = deadline;
+        }
+    }
+}
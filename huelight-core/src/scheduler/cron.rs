@@ -0,0 +1,239 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike, Duration, LocalResult, TimeZone, Utc};
+use chrono_tz::Tz;
+
+use crate::error::ParseError;
+
+const MONTH_NAMES: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const DAY_NAMES: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// How far ahead `next_after` searches before giving up (covers `29 2 *` style expressions).
+const SEARCH_DAYS: i64 = 366 * 8;
+
+/// A standard five-field cron expression: `minute hour day-of-month month day-of-week`.
+///
+/// Supports `*`, lists, ranges, steps, month/day names and the `@hourly`, `@daily`,
+/// `@weekly`, `@monthly` and `@yearly` shortcuts. Day-of-week 0 and 7 are both Sunday.
+/// As in classic cron, when both day fields are restricted a day matches if either does.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CronExpr {
+    source: String,
+    minutes: Vec<u32>,
+    hours: Vec<u32>,
+    days_of_month: Vec<u32>,
+    months: Vec<u32>,
+    days_of_week: Vec<u32>,
+    dom_restricted: bool,
+    dow_restricted: bool,
+}
+
+impl CronExpr {
+    /// Returns the first time strictly after `after` that matches, evaluated in `tz`.
+    pub fn next_after(&self, after: DateTime<Utc>, tz: &Tz) -> Option<DateTime<Utc>> {
+        let start = after.with_timezone(tz).date_naive();
+        for offset in 0..SEARCH_DAYS {
+            let date = start + Duration::days(offset);
+            if !self.matches_date(
+                date.month(),
+                date.day(),
+                date.weekday().num_days_from_sunday(),
+            ) {
+                continue;
+            }
+            for &hour in &self.hours {
+                for &minute in &self.minutes {
+                    let naive = date.and_hms_opt(hour, minute, 0)?;
+                    let candidate = match tz.from_local_datetime(&naive) {
+                        LocalResult::Single(t) => t,
+                        LocalResult::Ambiguous(earliest, _) => earliest,
+                        // Skipped by a DST transition.
+                        LocalResult::None => continue,
+                    };
+                    let candidate = candidate.with_timezone(&Utc);
+                    if candidate > after {
+                        return Some(candidate);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn matches_date(&self, month: u32, day: u32, weekday: u32) -> bool {
+        if !self.months.contains(&month) {
+            return false;
+        }
+        let dom = self.days_of_month.contains(&day);
+        let dow = self.days_of_week.contains(&weekday);
+        match (self.dom_restricted, self.dow_restricted) {
+            (true, true) => dom || dow,
+            (true, false) => dom,
+            (false, true) => dow,
+            (false, false) => true,
+        }
+    }
+}
+
+impl std::fmt::Display for CronExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl FromStr for CronExpr {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ParseError::InvalidCron(s.to_string());
+        let expanded = match s.trim() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            other => other,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, dom, month, dow] = fields[..] else {
+            return Err(invalid());
+        };
+
+        let mut days_of_week = parse_field(dow, 0, 7, &DAY_NAMES, 0).ok_or_else(invalid)?;
+        for day in days_of_week.iter_mut() {
+            *day %= 7;
+        }
+        days_of_week.sort_unstable();
+        days_of_week.dedup();
+
+        Ok(Self {
+            source: s.trim().to_string(),
+            minutes: parse_field(minute, 0, 59, &[], 0).ok_or_else(invalid)?,
+            hours: parse_field(hour, 0, 23, &[], 0).ok_or_else(invalid)?,
+            days_of_month: parse_field(dom, 1, 31, &[], 0).ok_or_else(invalid)?,
+            months: parse_field(month, 1, 12, &MONTH_NAMES, 1).ok_or_else(invalid)?,
+            days_of_week,
+            dom_restricted: dom != "*",
+            dow_restricted: dow != "*",
+        })
+    }
+}
+
+/// Parses one cron field into a sorted list of values. `names[i]` maps to `i + name_base`.
+fn parse_field(
+    field: &str,
+    min: u32,
+    max: u32,
+    names: &[&str],
+    name_base: u32,
+) -> Option<Vec<u32>> {
+    let value = |token: &str| -> Option<u32> {
+        let lower = token.to_ascii_lowercase();
+        names
+            .iter()
+            .position(|n| *n == lower)
+            .map(|i| i as u32 + name_base)
+            .or_else(|| token.parse().ok())
+            .filter(|v| (min..=max).contains(v))
+    };
+
+    let mut values = Vec::new();
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|s| *s > 0)?),
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((a, b)) => (value(a)?, value(b)?),
+                // `5/15` means "from 5 to the end of the range every 15".
+                None if part.contains('/') => (value(range)?, max),
+                None => (value(range)?, value(range)?),
+            },
+        };
+        if start > end {
+            return None;
+        }
+        values.extend((start..=end).step_by(step as usize));
+    }
+    values.sort_unstable();
+    values.dedup();
+    Some(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use chrono_tz::Tz;
+
+    use super::CronExpr;
+    use crate::error::ParseError;
+
+    #[test]
+    fn cron_weekday_mornings_skips_weekend() {
+        // Arrange
+        let cron: CronExpr = "30 7 * * mon-fri".parse().unwrap();
+        // Friday 2024-05-10 08:00 UTC
+        let after = Utc.with_ymd_and_hms(2024, 5, 10, 8, 0, 0).unwrap();
+
+        // Act
+        let next = cron.next_after(after, &Tz::UTC).unwrap();
+
+        // Assert (Monday)
+        assert_eq!(Utc.with_ymd_and_hms(2024, 5, 13, 7, 30, 0).unwrap(), next);
+    }
+
+    #[test]
+    fn cron_is_evaluated_in_timezone() {
+        // Arrange
+        let cron: CronExpr = "0 7 * * *".parse().unwrap();
+        let tz: Tz = "Europe/Amsterdam".parse().unwrap();
+        let after = Utc.with_ymd_and_hms(2024, 7, 1, 0, 0, 0).unwrap();
+
+        // Act
+        let next = cron.next_after(after, &tz).unwrap();
+
+        // Assert (CEST is UTC+2)
+        assert_eq!(Utc.with_ymd_and_hms(2024, 7, 1, 5, 0, 0).unwrap(), next);
+    }
+
+    #[test]
+    fn cron_steps_lists_and_shortcuts_parse() {
+        // Arrange
+        let after = Utc.with_ymd_and_hms(2024, 1, 1, 10, 16, 0).unwrap();
+
+        // Act
+        let every_15: CronExpr = "*/15 * * * *".parse().unwrap();
+        let listed: CronExpr = "5,50 10 * * *".parse().unwrap();
+        let daily: CronExpr = "@daily".parse().unwrap();
+
+        // Assert
+        assert_eq!(
+            Utc.with_ymd_and_hms(2024, 1, 1, 10, 30, 0).unwrap(),
+            every_15.next_after(after, &Tz::UTC).unwrap()
+        );
+        assert_eq!(
+            Utc.with_ymd_and_hms(2024, 1, 1, 10, 50, 0).unwrap(),
+            listed.next_after(after, &Tz::UTC).unwrap()
+        );
+        assert_eq!(
+            Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap(),
+            daily.next_after(after, &Tz::UTC).unwrap()
+        );
+    }
+
+    #[test]
+    fn cron_invalid_expression_gives_invalid_cron_error() {
+        assert!(matches!(
+            "61 * * * *".parse::<CronExpr>(),
+            Err(ParseError::InvalidCron(_))
+        ));
+        assert!(matches!(
+            "* * *".parse::<CronExpr>(),
+            Err(ParseError::InvalidCron(_))
+        ));
+    }
+}
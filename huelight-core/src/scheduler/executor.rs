@@ -0,0 +1,90 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::error::{CoreError, CoreResult, HueBridgeError};
+use crate::hue_api::HueApi;
+use crate::scheduler::schedule_file::Action;
+
+/// Carries out schedule actions. Split out so the daemon loop can be tested without a bridge.
+#[async_trait]
+pub trait ActionExecutor: Send + Sync {
+    async fn execute(&self, action: &Action) -> CoreResult<()>;
+}
+
+/// Executes actions against a Hue Bridge, resolving light/group/scene names at run time.
+pub struct HueActionExecutor {
+    api: Arc<dyn HueApi + Send + Sync>,
+    bridge_ip: String,
+    username: String,
+}
+
+impl HueActionExecutor {
+    pub fn new(
+        api: Arc<dyn HueApi + Send + Sync>,
+        bridge_ip: impl Into<String>,
+        username: impl Into<String>,
+    ) -> Self {
+        Self {
+            api,
+            bridge_ip: bridge_ip.into(),
+            username: username.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl ActionExecutor for HueActionExecutor {
+    async fn execute(&self, action: &Action) -> CoreResult<()> {
+        let (ip, user) = (self.bridge_ip.as_str(), self.username.as_str());
+        match action {
+            Action::SetLight { light, state } => {
+                let lights = self.api.async_get_all_lights(ip, user).await?;
+                let (id, _) = lights
+                    .find(light)
+                    .ok_or(CoreError::Bridge(HueBridgeError::LightNotFound))?;
+                self.api.async_set_light_state(ip, user, id, state).await?;
+            }
+            Action::SetGroup { group, state } => {
+                let group_id = resolve_group(self.api.as_ref(), ip, user, group).await?;
+                self.api
+                    .async_set_group_state(ip, user, group_id, state)
+                    .await?;
+            }
+            Action::RecallScene { scene, group } => {
+                let scenes = self.api.async_get_all_scenes(ip, user).await?;
+                let (scene_id, found) = scenes
+                    .find(scene)
+                    .ok_or(CoreError::Bridge(HueBridgeError::SceneNotFound))?;
+                let group_id = match (group, &found.group) {
+                    (Some(group), _) => resolve_group(self.api.as_ref(), ip, user, group).await?,
+                    (None, Some(own)) => own
+                        .parse()
+                        .map_err(|_| CoreError::Bridge(HueBridgeError::GroupNotFound))?,
+                    (None, None) => 0,
+                };
+                self.api
+                    .async_recall_scene(ip, user, group_id, scene_id)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Resolves a group ID or name. `0` always refers to the implicit "all lights" group.
+async fn resolve_group(
+    api: &(dyn HueApi + Send + Sync),
+    ip: &str,
+    user: &str,
+    group: &str,
+) -> CoreResult<u32> {
+    if group == "0" {
+        return Ok(0);
+    }
+    let groups = api.async_get_all_groups(ip, user).await?;
+    groups
+        .find(group)
+        .map(|(id, _)| id)
+        .ok_or(CoreError::Bridge(HueBridgeError::GroupNotFound))
+}
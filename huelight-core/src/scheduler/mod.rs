@@ -0,0 +1,504 @@
+//! Local scheduler: evaluates a version-controllable schedule file and runs its actions.
+
+pub mod clock;
+pub mod cron;
+pub mod executor;
+pub mod schedule_file;
+pub mod trigger;
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{Config, FileHandler, config_dir, path_to_str};
+use crate::error::{ConfigError, CoreError, CoreResult, ParseError, ScheduleError};
+use crate::logger::ILogger;
+use crate::scheduler::clock::Clock;
+use crate::scheduler::executor::ActionExecutor;
+use crate::scheduler::schedule_file::{MissedRunPolicy, ScheduleEntry, ScheduleFile};
+use crate::scheduler::trigger::Trigger;
+
+/// Runs this close to their scheduled time count as on time rather than missed.
+const ON_TIME_GRACE: Duration = Duration::minutes(2);
+/// Missed runs older than this are never replayed, whatever the policy says.
+const MAX_CATCH_UP: Duration = Duration::hours(24);
+/// Upper bound on occurrences collected per entry in one tick (e.g. `* * * * *` after a long sleep).
+const MAX_RUNS_PER_TICK: usize = 1440;
+/// The daemon wakes at least this often so clock jumps and suspends are noticed.
+const POLL_INTERVAL: Duration = Duration::seconds(60);
+
+/// Resolves the timezone to schedule in: explicit name, then the system timezone, then UTC.
+pub fn resolve_timezone(name: Option<&str>) -> Result<Tz, ParseError> {
+    match name {
+        Some(name) => name
+            .parse::<Tz>()
+            .map_err(|_| ParseError::InvalidTimezone(name.to_string())),
+        None => Ok(iana_time_zone::get_timezone()
+            .ok()
+            .and_then(|n| n.parse::<Tz>().ok())
+            .unwrap_or(Tz::UTC)),
+    }
+}
+
+/// A run the scheduler decided should happen now.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DueRun<'a> {
+    pub entry: &'a ScheduleEntry,
+    pub scheduled_for: DateTime<Utc>,
+    /// True if the run is being caught up on rather than fired on time.
+    pub missed: bool,
+}
+
+/// A compiled schedule bound to a timezone and (optionally) a location for solar triggers.
+#[derive(Debug)]
+pub struct Scheduler {
+    entries: Vec<ScheduleEntry>,
+    timezone: Tz,
+    location: Option<(f64, f64)>,
+}
+
+impl Scheduler {
+    /// Compiles a schedule file. The file's timezone wins over the config's.
+    pub fn new(file: ScheduleFile, config: &Config) -> CoreResult<Self> {
+        let timezone = resolve_timezone(file.timezone.as_deref().or(config.timezone.as_deref()))?;
+        let policy = file.missed_runs;
+        let entries = file
+            .entries
+            .into_iter()
+            .map(|spec| spec.compile(policy))
+            .collect::<CoreResult<Vec<_>>>()?;
+
+        let location = config.location();
+        if location.is_none()
+            && entries
+                .iter()
+                .any(|e| matches!(e.trigger, Trigger::Solar { .. }))
+        {
+            return Err(CoreError::Config(ConfigError::LocationNotConfigured));
+        }
+
+        Ok(Self {
+            entries,
+            timezone,
+            location,
+        })
+    }
+
+    pub fn entries(&self) -> &[ScheduleEntry] {
+        &self.entries
+    }
+
+    pub fn timezone(&self) -> Tz {
+        self.timezone
+    }
+
+    pub fn entry(&self, name: &str) -> CoreResult<&ScheduleEntry> {
+        self.entries
+            .iter()
+            .find(|e| e.name == name)
+            .ok_or_else(|| CoreError::Schedule(ScheduleError::EntryNotFound(name.to_string())))
+    }
+
+    pub fn next_run(&self, entry: &ScheduleEntry, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        entry
+            .trigger
+            .next_after(after, &self.timezone, self.location)
+    }
+
+    /// The next `limit` runs across all enabled entries, in time order.
+    pub fn upcoming(
+        &self,
+        after: DateTime<Utc>,
+        limit: usize,
+    ) -> Vec<(&ScheduleEntry, DateTime<Utc>)> {
+        let mut runs = Vec::new();
+        for entry in self.entries.iter().filter(|e| e.enabled) {
+            let mut cursor = after;
+            for _ in 0..limit {
+                let Some(t) = self.next_run(entry, cursor) else {
+                    break;
+                };
+                runs.push((entry, t));
+                cursor = t;
+            }
+        }
+        runs.sort_by_key(|(_, t)| *t);
+        runs.truncate(limit);
+        runs
+    }
+
+    /// Runs that fall in `(since, now]`, with the missed-run policy applied to late ones.
+    pub fn due(&self, since: DateTime<Utc>, now: DateTime<Utc>) -> Vec<DueRun<'_>> {
+        let since = since.max(now - MAX_CATCH_UP);
+        let mut due = Vec::new();
+
+        for entry in self.entries.iter().filter(|e| e.enabled) {
+            let mut occurrences = Vec::new();
+            let mut cursor = since;
+            while let Some(t) = self.next_run(entry, cursor) {
+                if t > now || occurrences.len() >= MAX_RUNS_PER_TICK {
+                    break;
+                }
+                occurrences.push(t);
+                cursor = t;
+            }
+
+            let (on_time, missed): (Vec<_>, Vec<_>) = occurrences
+                .into_iter()
+                .partition(|t| now - *t <= ON_TIME_GRACE);
+            let replay: Vec<DateTime<Utc>> = match entry.missed_runs {
+                MissedRunPolicy::Skip => Vec::new(),
+                MissedRunPolicy::RunOnce => missed.last().copied().into_iter().collect(),
+                MissedRunPolicy::RunAll => missed,
+            };
+
+            due.extend(replay.into_iter().map(|t| DueRun {
+                entry,
+                scheduled_for: t,
+                missed: true,
+            }));
+            due.extend(on_time.into_iter().map(|t| DueRun {
+                entry,
+                scheduled_for: t,
+                missed: false,
+            }));
+        }
+
+        due.sort_by_key(|run| run.scheduled_for);
+        due
+    }
+
+    /// Earliest upcoming run across all enabled entries.
+    pub fn next_wake(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.entries
+            .iter()
+            .filter(|e| e.enabled)
+            .filter_map(|e| self.next_run(e, after))
+            .min()
+    }
+}
+
+/// What the daemon remembers between restarts so it can detect missed runs.
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct SchedulerState {
+    pub last_checked: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub last_runs: BTreeMap<String, DateTime<Utc>>,
+}
+
+impl SchedulerState {
+    const FILE_NAME: &'static str = "scheduler-state.json";
+
+    /// Loads the saved state, or an empty state if the daemon has never run.
+    pub async fn load(file_handler: &impl FileHandler) -> CoreResult<Self> {
+        let path = config_dir()?.join(Self::FILE_NAME);
+        match file_handler.read_file(path_to_str(&path)?).await {
+            Ok(json) => serde_json::from_str(&json).map_err(CoreError::Serialization),
+            Err(CoreError::FileHandlerError(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+                Ok(Self::default())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    pub async fn save(&self, file_handler: &impl FileHandler) -> CoreResult<()> {
+        let dir = config_dir()?;
+        file_handler.create_dir_all(&dir).await?;
+        let json = serde_json::to_string_pretty(self).map_err(CoreError::Serialization)?;
+        file_handler
+            .write_file(path_to_str(&dir.join(Self::FILE_NAME))?, &json)
+            .await
+    }
+}
+
+/// The long-running loop behind `huelightcli daemon`.
+pub struct Daemon<'a, F: FileHandler> {
+    pub scheduler: &'a Scheduler,
+    pub clock: &'a dyn Clock,
+    pub executor: &'a dyn ActionExecutor,
+    pub logger: &'a (dyn ILogger + Sync),
+    pub file_handler: &'a F,
+}
+
+impl<F: FileHandler> Daemon<'_, F> {
+    /// Runs due actions until `until` (forever if `None`), saving `state` after every tick.
+    pub async fn run_until(
+        &self,
+        state: &mut SchedulerState,
+        until: Option<DateTime<Utc>>,
+    ) -> CoreResult<()> {
+        let mut since = state.last_checked.unwrap_or_else(|| self.clock.now());
+        loop {
+            let now = self.clock.now();
+            self.tick(state, since, now).await;
+            since = now;
+            state.last_checked = Some(now);
+            if let Err(err) = state.save(self.file_handler).await {
+                self.logger
+                    .warn(&format!("Failed to save scheduler state: {err}"));
+            }
+
+            if until.is_some_and(|u| now >= u) {
+                return Ok(());
+            }
+            let mut wake = now + POLL_INTERVAL;
+            if let Some(next) = self.scheduler.next_wake(now) {
+                wake = wake.min(next);
+            }
+            if let Some(until) = until {
+                wake = wake.min(until);
+            }
+            self.clock.sleep_until(wake).await;
+        }
+    }
+
+    async fn tick(&self, state: &mut SchedulerState, since: DateTime<Utc>, now: DateTime<Utc>) {
+        for run in self.scheduler.due(since, now) {
+            let tz = self.scheduler.timezone();
+            self.logger.log(&format!(
+                "Running '{}' ({}) scheduled for {}{}",
+                run.entry.name,
+                run.entry.action,
+                run.scheduled_for
+                    .with_timezone(&tz)
+                    .format("%Y-%m-%d %H:%M"),
+                if run.missed { " [missed run]" } else { "" }
+            ));
+            match self.executor.execute(&run.entry.action).await {
+                Ok(()) => {
+                    state
+                        .last_runs
+                        .insert(run.entry.name.clone(), run.scheduled_for);
+                }
+                Err(err) => self.logger.error(&format!(
+                    "Schedule entry '{}' failed: {err}",
+                    run.entry.name
+                )),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::sync::Mutex;
+
+    use async_trait::async_trait;
+    use chrono::{Duration, TimeZone, Utc};
+
+    use super::{Daemon, Scheduler, SchedulerState};
+    use crate::config::{Config, FileHandler};
+    use crate::error::{ConfigError, CoreError, CoreResult};
+    use crate::logger::Logger;
+    use crate::scheduler::clock::VirtualClock;
+    use crate::scheduler::executor::ActionExecutor;
+    use crate::scheduler::schedule_file::{Action, ScheduleFile};
+
+    #[derive(Default)]
+    struct MockFileHandler;
+
+    impl FileHandler for MockFileHandler {
+        async fn read_file(&self, _path: &str) -> Result<String, CoreError> {
+            Ok("".to_string())
+        }
+
+        async fn write_file(&self, _path: &str, _content: &str) -> Result<(), CoreError> {
+            Ok(())
+        }
+
+        async fn create_dir_all(&self, _path: &Path) -> Result<(), CoreError> {
+            Ok(())
+        }
+
+        async fn remove_file(&self, _path: &str) -> Result<(), CoreError> {
+            Ok(())
+        }
+    }
+
+    #[derive(Default)]
+    struct RecordingExecutor {
+        executed: Mutex<Vec<Action>>,
+    }
+
+    #[async_trait]
+    impl ActionExecutor for RecordingExecutor {
+        async fn execute(&self, action: &Action) -> CoreResult<()> {
+            self.executed.lock().unwrap().push(action.clone());
+            Ok(())
+        }
+    }
+
+    fn scheduler(text: &str) -> Scheduler {
+        let config = Config {
+            timezone: Some("UTC".to_string()),
+            ..Default::default()
+        };
+        Scheduler::new(ScheduleFile::from_toml(text).unwrap(), &config).unwrap()
+    }
+
+    const HOURLY: &str = r#"
+        missed_runs = "MISSED"
+
+        [[entry]]
+        name = "hourly"
+        cron = "0 * * * *"
+        light = "Desk"
+        state = { on = true }
+    "#;
+
+    #[test]
+    fn due_on_time_run_is_returned_whatever_the_policy() {
+        // Arrange
+        let scheduler = scheduler(&HOURLY.replace("MISSED", "skip"));
+        let since = Utc.with_ymd_and_hms(2024, 1, 1, 9, 59, 0).unwrap();
+        let now = Utc.with_ymd_and_hms(2024, 1, 1, 10, 0, 30).unwrap();
+
+        // Act
+        let due = scheduler.due(since, now);
+
+        // Assert
+        assert_eq!(1, due.len());
+        assert!(!due[0].missed);
+    }
+
+    #[test]
+    fn due_missed_runs_follow_policy() {
+        // Arrange: daemon was down from 06:30 until 10:30
+        let since = Utc.with_ymd_and_hms(2024, 1, 1, 6, 30, 0).unwrap();
+        let now = Utc.with_ymd_and_hms(2024, 1, 1, 10, 30, 0).unwrap();
+
+        // Act
+        let skip = scheduler(&HOURLY.replace("MISSED", "skip"));
+        let once = scheduler(&HOURLY.replace("MISSED", "run-once"));
+        let all = scheduler(&HOURLY.replace("MISSED", "run-all"));
+
+        // Assert
+        assert!(skip.due(since, now).is_empty());
+        let once_due = once.due(since, now);
+        assert_eq!(1, once_due.len());
+        assert_eq!(
+            Utc.with_ymd_and_hms(2024, 1, 1, 10, 0, 0).unwrap(),
+            once_due[0].scheduled_for
+        );
+        assert_eq!(4, all.due(since, now).len());
+    }
+
+    #[test]
+    fn upcoming_interleaves_entries_in_time_order() {
+        // Arrange
+        let scheduler = scheduler(
+            r#"
+            [[entry]]
+            name = "a"
+            cron = "0 8 * * *"
+            light = "1"
+            state = { on = true }
+
+            [[entry]]
+            name = "b"
+            cron = "0 20 * * *"
+            light = "1"
+            state = { on = false }
+        "#,
+        );
+        let after = Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+
+        // Act
+        let names: Vec<&str> = scheduler
+            .upcoming(after, 3)
+            .iter()
+            .map(|(e, _)| e.name.as_str())
+            .collect();
+
+        // Assert
+        assert_eq!(vec!["b", "a", "b"], names);
+    }
+
+    #[test]
+    fn solar_entry_without_location_is_rejected() {
+        // Arrange
+        let file = ScheduleFile::from_toml(
+            r#"
+            [[entry]]
+            name = "dusk"
+            at = "sunset"
+            scene = "Relax"
+        "#,
+        )
+        .unwrap();
+
+        // Act
+        let result = Scheduler::new(file, &Config::default());
+
+        // Assert
+        assert!(matches!(
+            result,
+            Err(CoreError::Config(ConfigError::LocationNotConfigured))
+        ));
+    }
+
+    #[tokio::test]
+    async fn daemon_with_virtual_clock_runs_each_occurrence_once() {
+        // Arrange
+        let scheduler = scheduler(&HOURLY.replace("MISSED", "skip"));
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 9, 30, 0).unwrap();
+        let clock = VirtualClock::new(start);
+        let executor = RecordingExecutor::default();
+        let logger = Logger::default();
+        let daemon = Daemon {
+            scheduler: &scheduler,
+            clock: &clock,
+            executor: &executor,
+            logger: &logger,
+            file_handler: &MockFileHandler,
+        };
+        let mut state = SchedulerState::default();
+
+        // Act
+        daemon
+            .run_until(&mut state, Some(start + Duration::hours(3)))
+            .await
+            .unwrap();
+
+        // Assert (10:00, 11:00 and 12:00)
+        assert_eq!(3, executor.executed.lock().unwrap().len());
+        assert_eq!(
+            Some(&Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap()),
+            state.last_runs.get("hourly")
+        );
+    }
+
+    #[tokio::test]
+    async fn daemon_restart_catches_up_missed_run() {
+        // Arrange
+        let scheduler = scheduler(&HOURLY.replace("MISSED", "run-once"));
+        let now = Utc.with_ymd_and_hms(2024, 1, 1, 13, 30, 0).unwrap();
+        let clock = VirtualClock::new(now);
+        let executor = RecordingExecutor::default();
+        let logger = Logger::default();
+        let daemon = Daemon {
+            scheduler: &scheduler,
+            clock: &clock,
+            executor: &executor,
+            logger: &logger,
+            file_handler: &MockFileHandler,
+        };
+        let mut state = SchedulerState {
+            last_checked: Some(now - Duration::hours(5)),
+            ..Default::default()
+        };
+
+        // Act
+        daemon.run_until(&mut state, Some(now)).await.unwrap();
+
+        // Assert
+        assert_eq!(1, executor.executed.lock().unwrap().len());
+        assert_eq!(
+            Some(&Utc.with_ymd_and_hms(2024, 1, 1, 13, 0, 0).unwrap()),
+            state.last_runs.get("hourly")
+        );
+    }
+}
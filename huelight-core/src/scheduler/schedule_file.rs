@@ -0,0 +1,252 @@
+use serde::Deserialize;
+
+use crate::domain::light::LightState;
+use crate::error::{CoreError, ScheduleError};
+use crate::scheduler::trigger::Trigger;
+
+/// What to do about runs that were due while the daemon was not running (or the machine slept).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MissedRunPolicy {
+    /// Drop missed runs entirely.
+    #[default]
+    Skip,
+    /// Run the most recent missed occurrence once.
+    RunOnce,
+    /// Replay every missed occurrence in order.
+    RunAll,
+}
+
+/// Raw contents of a schedule file, e.g.
+///
+/// ```toml
+/// timezone = "Europe/Amsterdam"
+/// missed_runs = "run-once"
+///
+/// [[entry]]
+/// name = "weekday-wake"
+/// cron = "30 6 * * mon-fri"
+/// group = "Bedroom"
+/// state = { on = true, bri = 254 }
+///
+/// [[entry]]
+/// name = "evening"
+/// at = "sunset-30m"
+/// scene = "Relax"
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct ScheduleFile {
+    #[serde(default)]
+    pub timezone: Option<String>,
+    #[serde(default)]
+    pub missed_runs: MissedRunPolicy,
+    #[serde(default, rename = "entry")]
+    pub entries: Vec<ScheduleEntrySpec>,
+}
+
+impl ScheduleFile {
+    pub fn from_toml(text: &str) -> Result<Self, CoreError> {
+        toml::from_str(text).map_err(|e| CoreError::Schedule(ScheduleError::File(e)))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScheduleEntrySpec {
+    pub name: String,
+    /// Five-field cron expression.
+    #[serde(default)]
+    pub cron: Option<String>,
+    /// Solar trigger such as `sunrise+15m`.
+    #[serde(default)]
+    pub at: Option<String>,
+    /// Light ID or name.
+    #[serde(default)]
+    pub light: Option<String>,
+    /// Group ID or name.
+    #[serde(default)]
+    pub group: Option<String>,
+    /// Scene ID or name.
+    #[serde(default)]
+    pub scene: Option<String>,
+    #[serde(default)]
+    pub state: Option<LightState>,
+    #[serde(default)]
+    pub missed_runs: Option<MissedRunPolicy>,
+    #[serde(default = "enabled_default")]
+    pub enabled: bool,
+}
+
+fn enabled_default() -> bool {
+    true
+}
+
+/// Something a schedule entry does when it fires.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    SetLight {
+        light: String,
+        state: LightState,
+    },
+    SetGroup {
+        group: String,
+        state: LightState,
+    },
+    /// Recall a scene, on `group` if given, otherwise on the scene's own group (or all lights).
+    RecallScene {
+        scene: String,
+        group: Option<String>,
+    },
+}
+
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Action::SetLight { light, state } => {
+                write!(f, "light \"{light}\" {state}")
+            }
+            Action::SetGroup { group, state } => {
+                write!(f, "group \"{group}\" {state}")
+            }
+            Action::RecallScene { scene, group } => match group {
+                Some(group) => write!(f, "scene \"{scene}\" on group \"{group}\""),
+                None => write!(f, "scene \"{scene}\""),
+            },
+        }
+    }
+}
+
+/// A validated schedule entry.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduleEntry {
+    pub name: String,
+    pub trigger: Trigger,
+    pub action: Action,
+    pub missed_runs: MissedRunPolicy,
+    pub enabled: bool,
+}
+
+impl ScheduleEntrySpec {
+    /// Validates the raw entry, using `default_policy` when the entry has no policy of its own.
+    pub fn compile(self, default_policy: MissedRunPolicy) -> Result<ScheduleEntry, CoreError> {
+        let invalid = |reason: &str| {
+            CoreError::Schedule(ScheduleError::InvalidEntry {
+                name: self.name.clone(),
+                reason: reason.to_string(),
+            })
+        };
+
+        let trigger = match (&self.cron, &self.at) {
+            (Some(cron), None) => Trigger::Cron(cron.parse()?),
+            (None, Some(at)) => at.parse()?,
+            _ => return Err(invalid("exactly one of `cron` or `at` is required")),
+        };
+
+        let action = match (&self.light, &self.group, &self.scene, &self.state) {
+            (Some(light), None, None, Some(state)) => Action::SetLight {
+                light: light.clone(),
+                state: state.clone(),
+            },
+            (None, Some(group), None, Some(state)) => Action::SetGroup {
+                group: group.clone(),
+                state: state.clone(),
+            },
+            (None, group, Some(scene), None) => Action::RecallScene {
+                scene: scene.clone(),
+                group: group.clone(),
+            },
+            _ => {
+                return Err(invalid(
+                    "expected `light` + `state`, `group` + `state`, or `scene` (with an optional `group`)",
+                ));
+            }
+        };
+
+        Ok(ScheduleEntry {
+            name: self.name,
+            trigger,
+            action,
+            missed_runs: self.missed_runs.unwrap_or(default_policy),
+            enabled: self.enabled,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Action, MissedRunPolicy, ScheduleFile};
+    use crate::domain::brightness::Brightness;
+    use crate::domain::light::LightState;
+    use crate::error::{CoreError, ScheduleError};
+
+    #[test]
+    fn schedule_file_parses_entries_and_policy() {
+        // Arrange
+        let text = r#"
+            timezone = "Europe/Amsterdam"
+            missed_runs = "run-once"
+
+            [[entry]]
+            name = "wake"
+            cron = "30 6 * * mon-fri"
+            group = "Bedroom"
+            state = { on = true, bri = 254 }
+
+            [[entry]]
+            name = "evening"
+            at = "sunset-30m"
+            scene = "Relax"
+            missed_runs = "skip"
+        "#;
+
+        // Act
+        let file = ScheduleFile::from_toml(text).unwrap();
+        let policy = file.missed_runs;
+        let entries: Vec<_> = file
+            .entries
+            .into_iter()
+            .map(|e| e.compile(policy).unwrap())
+            .collect();
+
+        // Assert
+        assert_eq!(
+            Action::SetGroup {
+                group: "Bedroom".to_string(),
+                state: LightState::default()
+                    .with_on(true)
+                    .with_brightness(Brightness::MAX),
+            },
+            entries[0].action
+        );
+        assert_eq!(MissedRunPolicy::RunOnce, entries[0].missed_runs);
+        assert_eq!(MissedRunPolicy::Skip, entries[1].missed_runs);
+        assert!(matches!(entries[1].action, Action::RecallScene { .. }));
+    }
+
+    #[test]
+    fn schedule_entry_with_two_triggers_is_invalid() {
+        // Arrange
+        let text = r#"
+            [[entry]]
+            name = "broken"
+            cron = "0 7 * * *"
+            at = "sunrise"
+            light = "1"
+            state = { on = true }
+        "#;
+        let file = ScheduleFile::from_toml(text).unwrap();
+
+        // Act
+        let result = file
+            .entries
+            .into_iter()
+            .next()
+            .unwrap()
+            .compile(MissedRunPolicy::Skip);
+
+        // Assert
+        assert!(matches!(
+            result,
+            Err(CoreError::Schedule(ScheduleError::InvalidEntry { name, .. })) if name == "broken"
+        ));
+    }
+}
@@ -0,0 +1,147 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::Tz;
+
+use crate::duration::parse_signed_offset;
+use crate::error::ParseError;
+use crate::scheduler::cron::CronExpr;
+use crate::solar::{SolarEvent, sun_times};
+
+/// How many days ahead a solar trigger looks for the next sunrise/sunset (polar regions).
+const SOLAR_SEARCH_DAYS: i64 = 400;
+
+/// When a schedule entry fires.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Trigger {
+    Cron(CronExpr),
+    /// Sunrise or sunset shifted by `offset_secs` (negative is before the event).
+    Solar {
+        event: SolarEvent,
+        offset_secs: i64,
+    },
+}
+
+impl Trigger {
+    /// Next fire time strictly after `after`. Solar triggers need `location` as (lat, lon).
+    pub fn next_after(
+        &self,
+        after: DateTime<Utc>,
+        tz: &Tz,
+        location: Option<(f64, f64)>,
+    ) -> Option<DateTime<Utc>> {
+        match self {
+            Trigger::Cron(cron) => cron.next_after(after, tz),
+            Trigger::Solar { event, offset_secs } => {
+                let (lat, lon) = location?;
+                let start = after.with_timezone(tz).date_naive() - Duration::days(1);
+                (0..SOLAR_SEARCH_DAYS)
+                    .filter_map(|day| {
+                        sun_times(start + Duration::days(day), lat, lon)
+                            .get(*event)
+                            .map(|t| t + Duration::seconds(*offset_secs))
+                    })
+                    .find(|t| *t > after)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Trigger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Trigger::Cron(cron) => write!(f, "cron \"{cron}\""),
+            Trigger::Solar { event, offset_secs } => {
+                let name = match event {
+                    SolarEvent::Sunrise => "sunrise",
+                    SolarEvent::Sunset => "sunset",
+                };
+                match offset_secs {
+                    0 => write!(f, "{name}"),
+                    o if *o < 0 => write!(f, "{name}-{}m", -o / 60),
+                    o => write!(f, "{name}+{}m", o / 60),
+                }
+            }
+        }
+    }
+}
+
+/// Parses a solar trigger such as `sunset`, `sunrise+30m` or `sunset - 1h`.
+impl FromStr for Trigger {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let compact: String = s.chars().filter(|c| !c.is_whitespace()).collect();
+        let lower = compact.to_ascii_lowercase();
+        let (event, rest) = if let Some(rest) = lower.strip_prefix("sunrise") {
+            (SolarEvent::Sunrise, rest)
+        } else if let Some(rest) = lower.strip_prefix("sunset") {
+            (SolarEvent::Sunset, rest)
+        } else {
+            return Err(ParseError::InvalidTrigger(s.to_string()));
+        };
+        let offset_secs = if rest.is_empty() {
+            0
+        } else if rest.starts_with('+') || rest.starts_with('-') {
+            parse_signed_offset(rest).map_err(|_| ParseError::InvalidTrigger(s.to_string()))?
+        } else {
+            return Err(ParseError::InvalidTrigger(s.to_string()));
+        };
+        Ok(Trigger::Solar { event, offset_secs })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, TimeZone, Utc};
+    use chrono_tz::Tz;
+
+    use super::Trigger;
+    use crate::error::ParseError;
+    use crate::solar::{SolarEvent, sun_times};
+
+    #[test]
+    fn trigger_parses_solar_offsets() {
+        assert_eq!(
+            Trigger::Solar {
+                event: SolarEvent::Sunset,
+                offset_secs: -1800
+            },
+            "sunset - 30m".parse().unwrap()
+        );
+        assert_eq!(
+            Trigger::Solar {
+                event: SolarEvent::Sunrise,
+                offset_secs: 0
+            },
+            "Sunrise".parse().unwrap()
+        );
+        assert!(matches!(
+            "noon".parse::<Trigger>(),
+            Err(ParseError::InvalidTrigger(_))
+        ));
+        assert!(matches!(
+            "sunset30m".parse::<Trigger>(),
+            Err(ParseError::InvalidTrigger(_))
+        ));
+    }
+
+    #[test]
+    fn solar_trigger_next_after_applies_offset() {
+        // Arrange
+        let location = (52.37, 4.89);
+        let trigger: Trigger = "sunset-30m".parse().unwrap();
+        let after = Utc.with_ymd_and_hms(2024, 6, 1, 6, 0, 0).unwrap();
+        let expected = sun_times(NaiveDate::from_ymd_opt(2024, 6, 1).unwrap(), 52.37, 4.89)
+            .sunset
+            .unwrap()
+            - chrono::Duration::minutes(30);
+
+        // Act
+        let next = trigger.next_after(after, &Tz::UTC, Some(location));
+
+        // Assert
+        assert_eq!(Some(expected), next);
+        assert_eq!(None, trigger.next_after(after, &Tz::UTC, None));
+    }
+}
@@ -0,0 +1,655 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use rhai::module_resolvers::DummyModuleResolver;
+use rhai::{AST, Array, Dynamic, Engine, EvalAltResult, ImmutableString, Map, Scope};
+use tokio::runtime::Handle;
+
+use crate::domain::brightness::{Brightness, BrightnessValue};
+use crate::domain::light::{Light, LightId, LightState};
+use crate::domain::named_colors::ColorRegistry;
+use crate::duration::parse_duration;
+use crate::error::{CoreError, CoreResult, HueBridgeError, ScriptError};
+use crate::hue_api::HueApi;
+use crate::logger::ILogger;
+use crate::models::group::{Group, GroupId};
+use crate::models::sensor::{Sensor, SensorId, SensorState};
+use crate::scheduler::cron::CronExpr;
+
+/// Upper bound on the work one script run or handler call may do, so a runaway loop ends with
+/// an error instead of hanging the daemon. Time spent in `sleep` does not count.
+const MAX_OPERATIONS: u64 = 5_000_000;
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+/// Everything the script bindings need to reach the bridge.
+#[derive(Clone)]
+pub struct ScriptEnv {
+    pub api: Arc<dyn HueApi + Send + Sync>,
+    pub ip_address: String,
+    pub username: String,
+    /// Receives `print`, `log` and script errors in daemon mode.
+    pub logger: Arc<dyn ILogger + Send + Sync>,
+    /// Timezone `now()` reports in.
+    pub timezone: Tz,
+    pub colors: ColorRegistry,
+    /// Runtime the bridge calls are driven on. Scripts themselves run on a blocking thread.
+    pub runtime: Handle,
+}
+
+impl ScriptEnv {
+    fn block_on<T>(&self, call: impl Future<Output = CoreResult<T>>) -> ScriptResult<T> {
+        self.runtime
+            .block_on(call)
+            .map_err(|err| err.to_string().into())
+    }
+
+    fn find_light(&self, id_or_name: &str) -> ScriptResult<(LightId, Light)> {
+        let lights = self.block_on(
+            self.api
+                .async_get_all_lights(&self.ip_address, &self.username),
+        )?;
+        lights
+            .find(id_or_name)
+            .map(|(id, light)| (id, light.clone()))
+            .ok_or_else(|| format!("light '{id_or_name}' not found").into())
+    }
+
+    fn find_group(&self, id_or_name: &str) -> ScriptResult<(GroupId, Group)> {
+        let mut groups = self.block_on(
+            self.api
+                .async_get_all_groups(&self.ip_address, &self.username),
+        )?;
+        let id = groups
+            .find(id_or_name)
+            .map(|(id, _)| id)
+            .ok_or_else(|| format!("group '{id_or_name}' not found"))?;
+        Ok((id, groups.0.remove(&id).unwrap())) // found above
+    }
+
+    /// Builds a light state from a script map such as
+    /// `#{ on: true, brightness: "20%", color: "warmwhite", transition: "2s" }`. Relative
+    /// brightness is resolved against `current`.
+    fn state_from_map(&self, map: &Map, current: Option<Brightness>) -> ScriptResult<LightState> {
+        let mut state = LightState::default();
+        for (key, value) in map {
+            state = match key.as_str() {
+                "on" => state.with_on(value.as_bool().map_err(|_| "on must be true or false")?),
+                "brightness" => {
+                    let brightness = match value.as_float() {
+                        Ok(percent) => Brightness::from_percent(percent),
+                        Err(_) => match value.as_int() {
+                            Ok(percent) => Brightness::from_percent(percent as f64),
+                            Err(_) => value
+                                .to_string()
+                                .parse::<BrightnessValue>()
+                                .map_err(|err| err.to_string())?
+                                .resolve(current),
+                        },
+                    };
+                    state.with_brightness(brightness)
+                }
+                "color" => state.with_color(
+                    self.colors
+                        .color(&value.to_string())
+                        .map_err(|err| err.to_string())?,
+                ),
+                "transition" => state.with_transition(duration_arg(value)?),
+                other => return Err(format!("unknown light state field '{other}'").into()),
+            };
+        }
+        Ok(state)
+    }
+}
+
+/// A duration given to a binding, either as text like `"2s"` or as milliseconds.
+fn duration_arg(value: &Dynamic) -> ScriptResult<Duration> {
+    match value.as_int() {
+        Ok(millis) => Ok(Duration::from_millis(millis.max(0) as u64)),
+        Err(_) => parse_duration(&value.to_string()).map_err(|err| err.to_string().into()),
+    }
+}
+
+fn light_map(id: LightId, light: &Light) -> Map {
+    let mut map = Map::new();
+    map.insert("id".into(), (id as i64).into());
+    map.insert("name".into(), light.name.clone().into());
+    map.insert("kind".into(), light.kind.to_string().into());
+    map.insert("on".into(), light.state.on.unwrap_or(false).into());
+    map.insert(
+        "brightness".into(),
+        light
+            .state
+            .brightness
+            .map_or(Dynamic::UNIT, |b| b.percent().into()),
+    );
+    map.insert(
+        "color".into(),
+        light
+            .state
+            .color
+            .map_or(Dynamic::UNIT, |c| c.to_string().into()),
+    );
+    map.insert("reachable".into(), light.reachable.into());
+    map
+}
+
+fn group_map(id: GroupId, group: &Group) -> Map {
+    let mut map = Map::new();
+    map.insert("id".into(), (id as i64).into());
+    map.insert("name".into(), group.name.clone().into());
+    map.insert("type".into(), group._type.clone().into());
+    let lights: Array = group
+        .light_ids()
+        .into_iter()
+        .map(|id| (id as i64).into())
+        .collect();
+    map.insert("lights".into(), lights.into());
+    map.insert("any_on".into(), group.state.any_on.into());
+    map.insert("all_on".into(), group.state.all_on.into());
+    map
+}
+
+/// A sensor as a script sees it: the common fields plus whatever its type reports.
+fn sensor_map(id: SensorId, sensor: &Sensor) -> Map {
+    let mut map = Map::new();
+    let mut set = |key: &str, value: Dynamic| {
+        map.insert(key.into(), value);
+    };
+    set("id", (id as i64).into());
+    set("name", sensor.name.clone().into());
+    set("type", sensor._type.clone().into());
+    set("text", sensor.state.to_string().into());
+    set(
+        "lastupdated",
+        sensor
+            .state
+            .lastupdated()
+            .map_or(Dynamic::UNIT, |t| t.to_string().into()),
+    );
+    let optional = |value: Option<Dynamic>| value.unwrap_or(Dynamic::UNIT);
+    match &sensor.state {
+        SensorState::Presence(s) => set("presence", optional(s.presence.map(Into::into))),
+        SensorState::Temperature(_) => {
+            set("celsius", optional(sensor.state.celsius().map(Into::into)))
+        }
+        SensorState::LightLevel(s) => {
+            set("lux", optional(sensor.state.lux().map(Into::into)));
+            set("dark", optional(s.dark.map(Into::into)));
+            set("daylight", optional(s.daylight.map(Into::into)));
+        }
+        SensorState::Switch(s) => set(
+            "buttonevent",
+            optional(s.buttonevent.map(|e| (e as i64).into())),
+        ),
+        SensorState::GenericStatus(s) => {
+            set("status", optional(s.status.map(|v| (v as i64).into())))
+        }
+        SensorState::GenericFlag(s) => set("flag", optional(s.flag.map(Into::into))),
+        SensorState::Other(_) => {}
+    }
+    map
+}
+
+/// A compiled script and the variables its top level left behind.
+pub struct Script {
+    pub name: String,
+    ast: AST,
+    scope: Scope<'static>,
+}
+
+impl Script {
+    /// Whether the script defines a handler function with this name.
+    pub fn has_handler(&self, name: &str) -> bool {
+        self.ast.iter_functions().any(|f| f.name == name)
+    }
+
+    /// The cron expression in the script's `SCHEDULE` constant, if it has one.
+    pub fn schedule(&self) -> CoreResult<Option<CronExpr>> {
+        let Some(schedule) = self.scope.get_value::<ImmutableString>("SCHEDULE") else {
+            return Ok(None);
+        };
+        schedule
+            .parse()
+            .map(Some)
+            .map_err(|err: crate::error::ParseError| self.error(err.to_string()))
+    }
+
+    fn error(&self, message: String) -> CoreError {
+        CoreError::Script(ScriptError::Runtime {
+            script: self.name.clone(),
+            message,
+        })
+    }
+}
+
+/// A sandboxed Rhai engine with bindings to the bridge.
+///
+/// Scripts can't touch files, import modules or `eval` code, and every run is capped at
+/// `MAX_OPERATIONS`. Bindings:
+///
+/// - `lights()`, `light(id_or_name)`, `set_light(id_or_name, #{ on, brightness, color, transition })`
+/// - `groups()`, `group(id_or_name)`, `set_group(id_or_name, #{ ... })`
+/// - `scenes()`, `activate_scene(id_or_name)`
+/// - `sensors()`, `sensor(id_or_name)`
+/// - `sleep("2s")` or `sleep(2000)`, `now()`, `log(message)`
+///
+/// Bridge calls block, so scripts must run on a thread where blocking is allowed, e.g. with
+/// `tokio::task::spawn_blocking`.
+pub struct ScriptEngine {
+    engine: Engine,
+}
+
+impl ScriptEngine {
+    pub fn new(env: ScriptEnv) -> Self {
+        let mut engine = Engine::new();
+        engine
+            .set_max_operations(MAX_OPERATIONS)
+            .set_max_call_levels(64)
+            .set_max_expr_depths(64, 64)
+            .set_max_string_size(1 << 20)
+            .set_max_array_size(10_000)
+            .set_max_map_size(10_000)
+            .set_module_resolver(DummyModuleResolver::new());
+        engine.disable_symbol("eval");
+
+        let env = Arc::new(env);
+        Self::register_output(&mut engine, &env);
+        Self::register_lights(&mut engine, &env);
+        Self::register_groups(&mut engine, &env);
+        Self::register_sensors(&mut engine, &env);
+        Self { engine }
+    }
+
+    fn register_output(engine: &mut Engine, env: &Arc<ScriptEnv>) {
+        let e = env.clone();
+        engine.on_print(move |text| e.logger.log(text));
+        let e = env.clone();
+        engine.on_debug(move |text, _, _| e.logger.log(text));
+        let e = env.clone();
+        engine.register_fn("log", move |text: &str| e.logger.log(text));
+
+        engine.register_fn("sleep", |duration: Dynamic| -> ScriptResult<()> {
+            std::thread::sleep(duration_arg(&duration)?);
+            Ok(())
+        });
+        let e = env.clone();
+        engine.register_fn("now", move || {
+            let now = Utc::now().with_timezone(&e.timezone);
+            let mut map = Map::new();
+            map.insert("time".into(), now.format("%H:%M").to_string().into());
+            map.insert("date".into(), now.format("%Y-%m-%d").to_string().into());
+            map.insert(
+                "hour".into(),
+                (now.format("%H").to_string().parse::<i64>().unwrap_or(0)).into(),
+            );
+            map.insert(
+                "minute".into(),
+                (now.format("%M").to_string().parse::<i64>().unwrap_or(0)).into(),
+            );
+            map.insert(
+                "weekday".into(),
+                now.format("%a").to_string().to_lowercase().into(),
+            );
+            map
+        });
+    }
+
+    fn register_lights(engine: &mut Engine, env: &Arc<ScriptEnv>) {
+        let e = env.clone();
+        engine.register_fn("lights", move || -> ScriptResult<Array> {
+            let lights = e.block_on(e.api.async_get_all_lights(&e.ip_address, &e.username))?;
+            let mut sorted: Vec<_> = lights.0.iter().collect();
+            sorted.sort_by_key(|(id, _)| **id);
+            Ok(sorted
+                .into_iter()
+                .map(|(id, light)| light_map(*id, light).into())
+                .collect())
+        });
+        let e = env.clone();
+        engine.register_fn("light", move |id: Dynamic| -> ScriptResult<Map> {
+            let (id, light) = e.find_light(&id.to_string())?;
+            Ok(light_map(id, &light))
+        });
+        let e = env.clone();
+        engine.register_fn(
+            "set_light",
+            move |id: Dynamic, state: Map| -> ScriptResult<()> {
+                let (id, light) = e.find_light(&id.to_string())?;
+                let requested = e.state_from_map(&state, light.state.brightness)?;
+                let fitted = light.fit_state(&requested).map_err(|err| err.to_string())?;
+                e.block_on(
+                    e.api
+                        .async_set_light_state(&e.ip_address, &e.username, id, &fitted),
+                )?;
+                Ok(())
+            },
+        );
+    }
+
+    fn register_groups(engine: &mut Engine, env: &Arc<ScriptEnv>) {
+        let e = env.clone();
+        engine.register_fn("groups", move || -> ScriptResult<Array> {
+            let groups = e.block_on(e.api.async_get_all_groups(&e.ip_address, &e.username))?;
+            let mut sorted: Vec<_> = groups.0.iter().collect();
+            sorted.sort_by_key(|(id, _)| **id);
+            Ok(sorted
+                .into_iter()
+                .map(|(id, group)| group_map(*id, group).into())
+                .collect())
+        });
+        let e = env.clone();
+        engine.register_fn("group", move |id: Dynamic| -> ScriptResult<Map> {
+            let (id, group) = e.find_group(&id.to_string())?;
+            Ok(group_map(id, &group))
+        });
+        let e = env.clone();
+        engine.register_fn(
+            "set_group",
+            move |id: Dynamic, state: Map| -> ScriptResult<()> {
+                let (id, group) = e.find_group(&id.to_string())?;
+                let state = e.state_from_map(&state, group.action.brightness)?;
+                e.block_on(
+                    e.api
+                        .async_set_group_state(&e.ip_address, &e.username, id, &state),
+                )?;
+                Ok(())
+            },
+        );
+
+        let e = env.clone();
+        engine.register_fn("scenes", move || -> ScriptResult<Array> {
+            let scenes = e.block_on(e.api.async_get_all_scenes(&e.ip_address, &e.username))?;
+            Ok(scenes
+                .0
+                .iter()
+                .map(|(id, scene)| {
+                    let mut map = Map::new();
+                    map.insert("id".into(), id.clone().into());
+                    map.insert("name".into(), scene.name.clone().into());
+                    map.insert(
+                        "group".into(),
+                        scene.group.clone().map_or(Dynamic::UNIT, Into::into),
+                    );
+                    map.into()
+                })
+                .collect())
+        });
+        let e = env.clone();
+        engine.register_fn("activate_scene", move |id: &str| -> ScriptResult<()> {
+            let scenes = e.block_on(e.api.async_get_all_scenes(&e.ip_address, &e.username))?;
+            let (scene_id, scene) = scenes
+                .find(id)
+                .ok_or_else(|| format!("scene '{id}' not found"))?;
+            let group_id = match &scene.group {
+                Some(group) => group
+                    .parse()
+                    .map_err(|_| CoreError::Bridge(HueBridgeError::GroupNotFound).to_string())?,
+                None => 0,
+            };
+            e.block_on(
+                e.api
+                    .async_recall_scene(&e.ip_address, &e.username, group_id, scene_id),
+            )?;
+            Ok(())
+        });
+    }
+
+    fn register_sensors(engine: &mut Engine, env: &Arc<ScriptEnv>) {
+        let e = env.clone();
+        engine.register_fn("sensors", move || -> ScriptResult<Array> {
+            let sensors = e.block_on(e.api.async_get_all_sensors(&e.ip_address, &e.username))?;
+            let mut sorted: Vec<_> = sensors.0.iter().collect();
+            sorted.sort_by_key(|(id, _)| **id);
+            Ok(sorted
+                .into_iter()
+                .map(|(id, sensor)| sensor_map(*id, sensor).into())
+                .collect())
+        });
+        let e = env.clone();
+        engine.register_fn("sensor", move |id: Dynamic| -> ScriptResult<Map> {
+            let sensors = e.block_on(e.api.async_get_all_sensors(&e.ip_address, &e.username))?;
+            let (id, sensor) = sensors
+                .find(&id.to_string())
+                .ok_or_else(|| format!("sensor '{id}' not found"))?;
+            Ok(sensor_map(id, sensor))
+        });
+    }
+
+    /// Compiles a script and runs its top level, which is all `script run` does.
+    pub fn load(&self, name: &str, source: &str) -> CoreResult<Script> {
+        let ast = self.engine.compile(source).map_err(|err| {
+            CoreError::Script(ScriptError::Compile {
+                script: name.to_string(),
+                message: err.to_string(),
+            })
+        })?;
+        let mut script = Script {
+            name: name.to_string(),
+            ast,
+            scope: Scope::new(),
+        };
+        self.engine
+            .run_ast_with_scope(&mut script.scope, &script.ast)
+            .map_err(|err| script.error(err.to_string()))?;
+        Ok(script)
+    }
+
+    /// Calls `on_schedule()` in the script.
+    pub fn fire_schedule(&self, script: &mut Script) -> CoreResult<()> {
+        self.engine
+            .call_fn::<Dynamic>(&mut script.scope, &script.ast, "on_schedule", ())
+            .map(|_| ())
+            .map_err(|err| script.error(err.to_string()))
+    }
+
+    /// Calls `on_event(event)` in the script.
+    pub fn fire_event(&self, script: &mut Script, event: Map) -> CoreResult<()> {
+        self.engine
+            .call_fn::<Dynamic>(&mut script.scope, &script.ast, "on_event", (event,))
+            .map(|_| ())
+            .map_err(|err| script.error(err.to_string()))
+    }
+}
+
+struct DaemonScript {
+    script: Script,
+    schedule: Option<CronExpr>,
+    next_run: Option<DateTime<Utc>>,
+}
+
+/// Runs script handlers as things happen: `on_event(event)` whenever a sensor reports a new
+/// state, and `on_schedule()` whenever the script's `SCHEDULE` cron expression comes due.
+///
+/// Script errors are logged and the daemon carries on; only bridge errors while polling are
+/// returned.
+pub struct ScriptDaemon {
+    engine: ScriptEngine,
+    env: ScriptEnv,
+    scripts: Vec<DaemonScript>,
+    /// Last `lastupdated` seen per sensor; `None` until the first poll.
+    seen: Option<HashMap<SensorId, String>>,
+}
+
+impl ScriptDaemon {
+    pub fn new(
+        engine: ScriptEngine,
+        env: ScriptEnv,
+        scripts: Vec<Script>,
+        now: DateTime<Utc>,
+    ) -> CoreResult<Self> {
+        let scripts = scripts
+            .into_iter()
+            .map(|script| {
+                let schedule = script.schedule()?;
+                let next_run = schedule
+                    .as_ref()
+                    .and_then(|cron| cron.next_after(now, &env.timezone));
+                Ok(DaemonScript {
+                    script,
+                    schedule,
+                    next_run,
+                })
+            })
+            .collect::<CoreResult<_>>()?;
+        Ok(Self {
+            engine,
+            env,
+            scripts,
+            seen: None,
+        })
+    }
+
+    fn report(&self, result: CoreResult<()>) {
+        if let Err(err) = result {
+            self.env.logger.error(&err.to_string());
+        }
+    }
+
+    /// Polls the sensors and runs every handler that is due.
+    pub fn tick(&mut self, now: DateTime<Utc>) -> CoreResult<()> {
+        let env = &self.env;
+        let sensors = env.runtime.block_on(
+            env.api
+                .async_get_all_sensors(&env.ip_address, &env.username),
+        )?;
+        let mut events = Vec::new();
+        let mut seen = HashMap::new();
+        for (id, sensor) in &sensors.0 {
+            let Some(updated) = sensor.state.lastupdated() else {
+                continue;
+            };
+            // The first poll only records where things stand.
+            if let Some(previous) = &self.seen
+                && previous.get(id).is_some_and(|last| last != updated)
+            {
+                let mut event = sensor_map(*id, sensor);
+                event.insert("kind".into(), "sensor".into());
+                events.push(event);
+            }
+            seen.insert(*id, updated.to_string());
+        }
+        self.seen = Some(seen);
+
+        for index in 0..self.scripts.len() {
+            if self.scripts[index].script.has_handler("on_event") {
+                for event in &events {
+                    let result = self
+                        .engine
+                        .fire_event(&mut self.scripts[index].script, event.clone());
+                    self.report(result);
+                }
+            }
+            let entry = &mut self.scripts[index];
+            if entry.next_run.is_some_and(|due| due <= now) {
+                entry.next_run = entry
+                    .schedule
+                    .as_ref()
+                    .and_then(|cron| cron.next_after(now, &self.env.timezone));
+                if entry.script.has_handler("on_schedule") {
+                    let result = self.engine.fire_schedule(&mut entry.script);
+                    self.report(result);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use chrono_tz::Tz;
+
+    use super::{ScriptEngine, ScriptEnv};
+    use crate::client::ReqwestHueClient;
+    use crate::domain::named_colors::ColorRegistry;
+    use crate::error::{CoreError, ScriptError};
+    use crate::hue_api::HueApiV1;
+    use crate::logger::{ILogger, Logger};
+
+    fn engine(logger: Arc<Logger>) -> (ScriptEngine, tokio::runtime::Runtime) {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let client = Arc::new(ReqwestHueClient::new(reqwest::Client::new()));
+        let env = ScriptEnv {
+            api: Arc::new(HueApiV1::new(client, logger.clone())),
+            ip_address: "127.0.0.1".to_string(),
+            username: "user".to_string(),
+            logger,
+            timezone: Tz::UTC,
+            colors: ColorRegistry::builtin(),
+            runtime: runtime.handle().clone(),
+        };
+        (ScriptEngine::new(env), runtime)
+    }
+
+    #[test]
+    pub fn load_runs_top_level_and_reads_schedule() {
+        // Arrange
+        let logger = Arc::new(Logger::default());
+        let (engine, _runtime) = engine(logger.clone());
+
+        // Act
+        let script = engine
+            .load(
+                "evening",
+                r#"
+                const SCHEDULE = "0 19 * * *";
+                print("loaded at " + now().time);
+                fn on_schedule() { log("due"); }
+                "#,
+            )
+            .unwrap();
+
+        // Assert
+        assert!(script.has_handler("on_schedule"));
+        assert!(!script.has_handler("on_event"));
+        assert!(script.schedule().unwrap().is_some());
+        assert!(logger.entries().iter().any(|e| e.contains("loaded at")));
+    }
+
+    #[test]
+    pub fn fire_schedule_runs_handler() {
+        // Arrange
+        let logger = Arc::new(Logger::default());
+        let (engine, _runtime) = engine(logger.clone());
+        let mut script = engine
+            .load("tick", r#"fn on_schedule() { log("tick " + (1 + 1)); }"#)
+            .unwrap();
+
+        // Act
+        engine.fire_schedule(&mut script).unwrap();
+
+        // Assert
+        assert!(logger.entries().iter().any(|e| e.contains("tick 2")));
+    }
+
+    #[test]
+    pub fn sandbox_rejects_eval_imports_and_endless_loops() {
+        // Arrange
+        let (engine, _runtime) = engine(Arc::new(Logger::default()));
+
+        // Act
+        let eval = engine.load("eval", r#"eval("1 + 1")"#);
+        let import = engine.load("import", r#"import "secrets" as s;"#);
+        let endless = engine.load("endless", "loop { }");
+
+        // Assert
+        assert!(matches!(
+            eval,
+            Err(CoreError::Script(ScriptError::Compile { .. }))
+        ));
+        assert!(matches!(
+            import,
+            Err(CoreError::Script(ScriptError::Runtime { .. }))
+        ));
+        assert!(matches!(
+            endless,
+            Err(CoreError::Script(ScriptError::Runtime { .. }))
+        ));
+    }
+}
@@ -0,0 +1,342 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{FileHandler, config_dir, path_to_str};
+use crate::domain::light::{LightId, LightState, Lights};
+use crate::error::{CoreError, CoreResult, SnapshotError};
+use crate::fanout::{FanOutReport, fan_out};
+use crate::hue_api::HueApi;
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct LightSnapshot {
+    pub name: String,
+    /// The state to send back on restore, already reduced with `LightState::restorable`.
+    pub state: LightState,
+}
+
+/// The saved state of every light, stored as `snapshots/<name>.json` in the config directory.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Snapshot {
+    pub name: String,
+    pub created: DateTime<Utc>,
+    pub lights: BTreeMap<LightId, LightSnapshot>,
+}
+
+/// Outcome of restoring a snapshot, per light.
+#[derive(Debug, Default)]
+pub struct RestoreReport {
+    /// How sending the saved state went for every light that was still there and reachable.
+    pub sent: FanOutReport<LightId, ()>,
+    pub unreachable: Vec<LightId>,
+    /// Lights in the snapshot that no longer exist on the bridge.
+    pub missing: Vec<LightId>,
+}
+
+impl Snapshot {
+    pub fn capture(name: impl Into<String>, lights: &Lights, now: DateTime<Utc>) -> Self {
+        Self {
+            name: name.into(),
+            created: now,
+            lights: lights
+                .0
+                .iter()
+                .map(|(id, light)| {
+                    (
+                        *id,
+                        LightSnapshot {
+                            name: light.name.clone(),
+                            state: light.state.restorable(),
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    fn path(name: &str) -> CoreResult<PathBuf> {
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(CoreError::Snapshot(SnapshotError::InvalidName(
+                name.to_string(),
+            )));
+        }
+        Ok(config_dir()?.join("snapshots").join(format!("{name}.json")))
+    }
+
+    pub async fn save(&self, file_handler: &impl FileHandler) -> CoreResult<()> {
+        let path = Self::path(&self.name)?;
+        if let Some(dir) = path.parent() {
+            file_handler.create_dir_all(dir).await?;
+        }
+        let json = serde_json::to_string_pretty(self).map_err(CoreError::Serialization)?;
+        file_handler.write_file(path_to_str(&path)?, &json).await
+    }
+
+    pub async fn load(name: &str, file_handler: &impl FileHandler) -> CoreResult<Self> {
+        let path = Self::path(name)?;
+        let json = match file_handler.read_file(path_to_str(&path)?).await {
+            Err(CoreError::FileHandlerError(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(CoreError::Snapshot(SnapshotError::NotFound(
+                    name.to_string(),
+                )));
+            }
+            other => other?,
+        };
+        serde_json::from_str(&json).map_err(CoreError::Serialization)
+    }
+
+    /// Sends every saved state back to the bridge, `concurrency` lights at a time, skipping
+    /// lights that are unreachable now.
+    pub async fn restore(
+        &self,
+        api: &(dyn HueApi + Send + Sync),
+        ip_address: &str,
+        username: &str,
+        transition: Option<Duration>,
+        concurrency: usize,
+    ) -> CoreResult<RestoreReport> {
+        let current = api.async_get_all_lights(ip_address, username).await?;
+        let mut report = RestoreReport::default();
+
+        let mut targets = Vec::new();
+        for id in self.lights.keys() {
+            match current.0.get(id) {
+                None => report.missing.push(*id),
+                Some(light) if !light.reachable => report.unreachable.push(*id),
+                Some(_) => targets.push(*id),
+            }
+        }
+
+        report.sent = fan_out(targets, concurrency, |id| {
+            let mut state = self.lights[&id].state.clone();
+            state.transition = transition;
+            async move {
+                api.async_set_light_state(ip_address, username, id, &state)
+                    .await
+                    .map(|_| ())
+            }
+        })
+        .await;
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use chrono::Utc;
+    use serde_json::json;
+    use tokio::time::Instant;
+
+    use super::{LightSnapshot, Snapshot};
+    use crate::cache::{CachedHueApi, LIGHT_STATE_TTL};
+    use crate::domain::brightness::Brightness;
+    use crate::domain::color::Color;
+    use crate::domain::light::{Light, LightKind, LightState, Lights};
+    use crate::error::{CoreError, HueBridgeError, SnapshotError};
+    use crate::fanout::Outcome;
+    use crate::testing::MockBridge;
+
+    /// A snapshot that turns every one of `ids` on at 50%.
+    fn snapshot(ids: &[u32]) -> Snapshot {
+        let state = LightState::default()
+            .with_on(true)
+            .with_brightness(Brightness::from_bri(127));
+        Snapshot {
+            name: "evening".to_string(),
+            created: Utc::now(),
+            lights: ids
+                .iter()
+                .map(|id| {
+                    (
+                        *id,
+                        LightSnapshot {
+                            name: format!("Light {id}"),
+                            state: state.clone(),
+                        },
+                    )
+                })
+                .collect::<BTreeMap<_, _>>(),
+        }
+    }
+
+    #[test]
+    fn capture_stores_restorable_state_per_light() {
+        // Arrange
+        let light: Light = serde_json::from_value(serde_json::json!({
+            "name": "Desk", "type": "Extended color light",
+            "state": {"on": true, "bri": 80, "xy": [0.5, 0.4], "ct": 300, "alert": "select",
+                "colormode": "xy", "reachable": true}
+        }))
+        .unwrap();
+        let lights = Lights(HashMap::from([(4, light)]));
+
+        // Act
+        let snapshot = Snapshot::capture("demo", &lights, Utc::now());
+
+        // Assert
+        let saved = &snapshot.lights[&4];
+        assert_eq!("Desk", saved.name);
+        assert_eq!(
+            LightState::default()
+                .with_on(true)
+                .with_brightness(Brightness::from_bri(80))
+                .with_color(Color::xy(0.5, 0.4)),
+            saved.state
+        );
+    }
+
+    #[test]
+    fn snapshot_path_rejects_path_traversal() {
+        assert!(matches!(
+            Snapshot::path("../config"),
+            Err(CoreError::Snapshot(SnapshotError::InvalidName(_)))
+        ));
+        assert!(Snapshot::path("party-2024").is_ok());
+    }
+
+    #[tokio::test]
+    pub async fn restore_sends_saved_states_and_skips_unreachable_and_missing_lights() {
+        // Arrange
+        let bridge = Arc::new(
+            MockBridge::new()
+                .with_light(1, "Desk", LightKind::Dimmable)
+                .with_light(2, "Shelf", LightKind::Dimmable)
+                .with_resource(
+                    "lights",
+                    "3",
+                    json!({ "name": "Porch", "type": "Dimmable light",
+                        "state": { "on": false, "bri": 1, "reachable": false } }),
+                ),
+        );
+        let api = bridge.api();
+
+        // Act
+        let report = snapshot(&[1, 2, 3, 9])
+            .restore(&api, "ip", "user", Some(Duration::from_secs(2)), 2)
+            .await
+            .unwrap();
+
+        // Assert
+        let sent: Vec<u32> = report.sent.results.iter().map(|(id, _)| *id).collect();
+        assert_eq!(sent, vec![1, 2]);
+        assert_eq!(report.sent.succeeded(), 2);
+        assert_eq!(report.unreachable, vec![3]);
+        assert_eq!(report.missing, vec![9]);
+        assert_eq!(bridge.resource("lights/2/state/on"), Some(json!(true)));
+        assert_eq!(bridge.resource("lights/2/state/bri"), Some(json!(127)));
+        assert_eq!(bridge.resource("lights/3/state/on"), Some(json!(false)));
+        let writes: Vec<_> = bridge
+            .calls()
+            .into_iter()
+            .filter(|call| call.method == "PUT")
+            .collect();
+        assert_eq!(writes.len(), 2);
+        assert_eq!(
+            writes[0]
+                .body
+                .as_ref()
+                .and_then(|body| body.get("transitiontime").cloned()),
+            Some(json!(20))
+        );
+    }
+
+    #[tokio::test]
+    pub async fn restore_reports_each_failed_light_and_restores_the_rest() {
+        // Arrange
+        let bridge = Arc::new(
+            MockBridge::new()
+                .with_light(1, "Desk", LightKind::Dimmable)
+                .with_light(2, "Shelf", LightKind::Dimmable)
+                .with_light(3, "Hall", LightKind::Dimmable),
+        );
+        let api = bridge.api();
+        bridge.pass_next(); // reading the lights
+        bridge.reject_next(
+            201,
+            "parameter, bri, is not modifiable. Device is set to off.",
+        );
+        bridge.fail_next(CoreError::Bridge(HueBridgeError::UnexpectedJSON));
+
+        // Act
+        let report = snapshot(&[1, 2, 3])
+            .restore(&api, "ip", "user", None, 1)
+            .await
+            .unwrap();
+
+        // Assert
+        assert!(matches!(
+            report.sent.results[0],
+            (1, Outcome::BridgeError(_))
+        ));
+        assert!(matches!(
+            report.sent.results[1],
+            (2, Outcome::BridgeError(_))
+        ));
+        assert!(matches!(report.sent.results[2], (3, Outcome::Success(()))));
+        assert_eq!(
+            report
+                .sent
+                .failures()
+                .map(|(id, _)| *id)
+                .collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert_eq!(bridge.resource("lights/1/state/on"), Some(json!(false)));
+        assert_eq!(bridge.resource("lights/3/state/on"), Some(json!(true)));
+    }
+
+    #[tokio::test]
+    pub async fn restore_through_the_cache_updates_lights_concurrently() {
+        // Arrange
+        let latency = Duration::from_millis(100);
+        let bridge = Arc::new(
+            MockBridge::new()
+                .with_light(1, "Desk", LightKind::Dimmable)
+                .with_light(2, "Shelf", LightKind::Dimmable)
+                .with_light(3, "Hall", LightKind::Dimmable)
+                .with_latency(latency),
+        );
+        let api = CachedHueApi::new(Arc::new(bridge.api()), LIGHT_STATE_TTL);
+
+        // Act
+        let started = Instant::now();
+        let report = snapshot(&[1, 2, 3])
+            .restore(&api, "ip", "user", None, 3)
+            .await
+            .unwrap();
+        let elapsed = started.elapsed();
+
+        // Assert
+        assert_eq!(report.sent.succeeded(), 3);
+        // One read, then the three writes together.
+        assert!(elapsed < latency * 3, "restore took {elapsed:?}");
+        assert_eq!(bridge.resource("lights/3/state/bri"), Some(json!(127)));
+    }
+
+    #[tokio::test]
+    pub async fn restore_fails_when_lights_cannot_be_read() {
+        // Arrange
+        let bridge = Arc::new(MockBridge::new().with_light(1, "Desk", LightKind::Dimmable));
+        let api = bridge.api();
+        bridge.fail_next(CoreError::Bridge(HueBridgeError::UnexpectedJSON));
+
+        // Act
+        let result = snapshot(&[1]).restore(&api, "ip", "user", None, 1).await;
+
+        // Assert
+        assert!(result.is_err());
+        assert_eq!(bridge.calls().len(), 1);
+    }
+}
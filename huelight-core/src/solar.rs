@@ -0,0 +1,142 @@
+use chrono::{DateTime, NaiveDate, Utc};
+
+/// Sun altitude (degrees) at which the upper limb touches the horizon, accounting for refraction.
+const SUNRISE_ALTITUDE: f64 = -0.833;
+/// Julian date of the J2000 epoch (2000-01-01 12:00 UTC).
+const J2000: f64 = 2451545.0;
+/// Julian date of the Unix epoch.
+const UNIX_EPOCH_JULIAN: f64 = 2440587.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SolarEvent {
+    Sunrise,
+    Sunset,
+}
+
+/// Sun times for a single calendar day. Sunrise/sunset are `None` during polar day or night.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SunTimes {
+    pub sunrise: Option<DateTime<Utc>>,
+    pub sunset: Option<DateTime<Utc>>,
+    pub solar_noon: DateTime<Utc>,
+}
+
+impl SunTimes {
+    pub fn get(&self, event: SolarEvent) -> Option<DateTime<Utc>> {
+        match event {
+            SolarEvent::Sunrise => self.sunrise,
+            SolarEvent::Sunset => self.sunset,
+        }
+    }
+}
+
+/// Computes sunrise, sunset and solar noon for the given local calendar date and location.
+///
+/// Uses the NOAA sunrise equation, which is accurate to within a couple of minutes for
+/// non-polar latitudes. Longitude is east positive.
+pub fn sun_times(date: NaiveDate, latitude: f64, longitude: f64) -> SunTimes {
+    let epoch = NaiveDate::from_ymd_opt(2000, 1, 1).expect("valid date");
+    let n = (date - epoch).num_days() as f64;
+
+    // Mean solar time, solar mean anomaly and equation of the center.
+    let mean_solar_time = n - longitude / 360.0;
+    let m = (357.5291 + 0.98560028 * mean_solar_time).rem_euclid(360.0);
+    let m_rad = m.to_radians();
+    let center = 1.9148 * m_rad.sin() + 0.0200 * (2.0 * m_rad).sin() + 0.0003 * (3.0 * m_rad).sin();
+
+    // Ecliptic longitude, solar transit and declination of the sun.
+    let ecliptic = (m + center + 180.0 + 102.9372)
+        .rem_euclid(360.0)
+        .to_radians();
+    let transit = J2000 + mean_solar_time + 0.0053 * m_rad.sin() - 0.0069 * (2.0 * ecliptic).sin();
+    let sin_declination = ecliptic.sin() * 23.4397_f64.to_radians().sin();
+    let cos_declination = (1.0 - sin_declination * sin_declination).sqrt();
+
+    let lat = latitude.to_radians();
+    let cos_hour_angle = (SUNRISE_ALTITUDE.to_radians().sin() - lat.sin() * sin_declination)
+        / (lat.cos() * cos_declination);
+
+    let (sunrise, sunset) = if (-1.0..=1.0).contains(&cos_hour_angle) {
+        let hour_angle = cos_hour_angle.acos().to_degrees();
+        (
+            Some(julian_to_utc(transit - hour_angle / 360.0)),
+            Some(julian_to_utc(transit + hour_angle / 360.0)),
+        )
+    } else {
+        (None, None)
+    };
+
+    SunTimes {
+        sunrise,
+        sunset,
+        solar_noon: julian_to_utc(transit),
+    }
+}
+
+fn julian_to_utc(julian: f64) -> DateTime<Utc> {
+    let millis = ((julian - UNIX_EPOCH_JULIAN) * 86_400_000.0).round() as i64;
+    DateTime::from_timestamp_millis(millis).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, TimeZone, Utc};
+
+    use super::sun_times;
+
+    fn assert_close(expected: chrono::DateTime<Utc>, actual: chrono::DateTime<Utc>) {
+        let diff = (expected - actual).num_seconds().abs();
+        assert!(diff <= 180, "expected {expected}, got {actual}");
+    }
+
+    #[test]
+    fn sun_times_london_midsummer_matches_almanac() {
+        // Arrange
+        let date = NaiveDate::from_ymd_opt(2024, 6, 21).unwrap();
+
+        // Act
+        let times = sun_times(date, 51.5074, -0.1278);
+
+        // Assert
+        assert_close(
+            Utc.with_ymd_and_hms(2024, 6, 21, 3, 43, 0).unwrap(),
+            times.sunrise.unwrap(),
+        );
+        assert_close(
+            Utc.with_ymd_and_hms(2024, 6, 21, 20, 21, 0).unwrap(),
+            times.sunset.unwrap(),
+        );
+    }
+
+    #[test]
+    fn sun_times_east_longitude_shifts_noon_earlier() {
+        // Arrange
+        let date = NaiveDate::from_ymd_opt(2024, 3, 20).unwrap();
+
+        // Act (Tokyo)
+        let times = sun_times(date, 35.6762, 139.6503);
+
+        // Assert
+        assert_close(
+            Utc.with_ymd_and_hms(2024, 3, 20, 2, 49, 0).unwrap(),
+            times.solar_noon,
+        );
+        assert_close(
+            Utc.with_ymd_and_hms(2024, 3, 19, 20, 45, 0).unwrap(),
+            times.sunrise.unwrap(),
+        );
+    }
+
+    #[test]
+    fn sun_times_polar_night_has_no_sunrise() {
+        // Arrange
+        let date = NaiveDate::from_ymd_opt(2024, 12, 21).unwrap();
+
+        // Act (Longyearbyen, Svalbard)
+        let times = sun_times(date, 78.2232, 15.6267);
+
+        // Assert
+        assert!(times.sunrise.is_none());
+        assert!(times.sunset.is_none());
+    }
+}
@@ -0,0 +1,600 @@
+//! Test doubles for code built on this crate, enabled with the `test-util` feature.
+//!
+//! `MockBridge` behaves like a small bridge: it keeps lights, groups and other resources in
+//! memory, applies writes to them, records every call and can be told to fail. `MockHueClient`
+//! is the lower-level option that answers each request with a closure.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde_json::{Map, Value, json};
+
+use crate::client::split_url;
+use crate::client::{Header, HueClient};
+use crate::domain::light::{LightId, LightKind};
+use crate::error::{CoreError, CoreResult};
+use crate::hue_api::HueApiV1;
+use crate::logger::Logger;
+
+/// Closure used to mock out behavior in the MockHueClient for HueClient.get
+pub type GetFn = Box<dyn Fn(&str) -> CoreResult<String> + Send + Sync>;
+/// Closure used to mock out behavior in the MockHueClient for HueClient.post_json
+pub type PostJsonFn = Box<dyn Fn(&str, &str) -> CoreResult<String> + Send + Sync>;
+/// Closure used to mock out behavior in the MockHueClient for HueClient.put_json
+pub type PutJsonFn = Box<dyn Fn(&str, &str) -> CoreResult<String> + Send + Sync>;
+/// Closure used to mock out behavior in the MockHueClient for HueClient.delete
+pub type DeleteFn = Box<dyn Fn(&str) -> CoreResult<String> + Send + Sync>;
+
+/// Answers each request with a closure, `[]` by default.
+pub struct MockHueClient {
+    pub post_json_fn: PostJsonFn,
+    pub get_fn: GetFn,
+    pub put_json_fn: PutJsonFn,
+    pub delete_fn: DeleteFn,
+}
+
+impl Default for MockHueClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockHueClient {
+    /// Initializes a new MockHueClient with 'Ok' returns for get, put_json, and post_json.
+    pub fn new() -> Self {
+        Self {
+            post_json_fn: Box::new(|_, _| Ok("[]".to_string())),
+            get_fn: Box::new(|_| Ok("[]".to_string())),
+            put_json_fn: Box::new(|_, _| Ok("[]".to_string())),
+            delete_fn: Box::new(|_| Ok("[]".to_string())),
+        }
+    }
+
+    /// Provides a means to implement mocked behavior to MockHueClient.post_json
+    pub fn with_post_json<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&str, &str) -> CoreResult<String> + Send + Sync + 'static,
+    {
+        self.post_json_fn = Box::new(f);
+        self
+    }
+
+    /// Provides a means to implement mocked behavior to MockHueClient.get
+    pub fn with_get<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&str) -> CoreResult<String> + Send + Sync + 'static,
+    {
+        self.get_fn = Box::new(f);
+        self
+    }
+
+    /// Provides a means to implement mocked behavior to MockHueClient.put_json
+    pub fn with_put_json<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&str, &str) -> CoreResult<String> + Send + Sync + 'static,
+    {
+        self.put_json_fn = Box::new(f);
+        self
+    }
+
+    /// Provides a means to implement mocked behavior to MockHueClient.delete
+    pub fn with_delete<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&str) -> CoreResult<String> + Send + Sync + 'static,
+    {
+        self.delete_fn = Box::new(f);
+        self
+    }
+}
+
+#[async_trait]
+impl HueClient for MockHueClient {
+    async fn post_json(&self, url: &str, body: &str, _headers: &[Header]) -> CoreResult<String> {
+        (self.post_json_fn)(url, body)
+    }
+
+    async fn get(&self, url: &str, _headers: &[Header]) -> CoreResult<String> {
+        (self.get_fn)(url)
+    }
+
+    async fn put_json(&self, url: &str, body: &str, _headers: &[Header]) -> CoreResult<String> {
+        (self.put_json_fn)(url, body)
+    }
+
+    async fn delete(&self, url: &str, _headers: &[Header]) -> CoreResult<String> {
+        (self.delete_fn)(url)
+    }
+}
+
+/// A request the mock bridge received.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockCall {
+    pub method: &'static str,
+    /// Path below the username, e.g. `lights/3/state`.
+    pub path: String,
+    pub body: Option<Value>,
+}
+
+/// A failure queued with `MockBridge::fail_next` or `MockBridge::reject_next`.
+enum Failure {
+    /// Returned instead of a response, like a network error.
+    Error(CoreError),
+    /// Answered with a bridge error response, which the API parses like a real one.
+    Rejected { code: u32, description: String },
+    /// Let through, so the failures queued after it hit later requests.
+    Pass,
+}
+
+/// The bridge's error response for a resource that doesn't exist.
+fn not_available(path: &str) -> String {
+    json!([{
+        "error": {
+            "type": 3,
+            "address": format!("/{path}"),
+            "description": format!("resource, /{path}, not available"),
+        }
+    }])
+    .to_string()
+}
+
+/// Applies a state write to a light or group action: `*_inc` fields step the value they name,
+/// `transitiontime` and `scene` only say how to get there, and the rest are set as given.
+fn apply_state(state: &mut Map<String, Value>, write: &Map<String, Value>) {
+    for (field, value) in write {
+        match field.as_str() {
+            "transitiontime" | "scene" => {}
+            step if step.ends_with("_inc") => {
+                let field = step.trim_end_matches("_inc");
+                let (min, max) = match field {
+                    "bri" => (1, 254),
+                    "sat" => (0, 254),
+                    "hue" => (0, 65535),
+                    _ => (153, 500),
+                };
+                let current = state.get(field).and_then(Value::as_i64).unwrap_or(min);
+                let stepped = current + value.as_i64().unwrap_or(0);
+                state.insert(field.to_string(), stepped.clamp(min, max).into());
+            }
+            _ => {
+                state.insert(field.clone(), value.clone());
+            }
+        }
+        let mode = match field.as_str() {
+            "xy" => Some("xy"),
+            "ct" | "ct_inc" => Some("ct"),
+            "hue" | "sat" | "hue_inc" | "sat_inc" => Some("hs"),
+            _ => None,
+        };
+        if let Some(mode) = mode {
+            state.insert("colormode".to_string(), mode.into());
+        }
+    }
+}
+
+/// An in-memory bridge that implements `HueClient`.
+///
+/// Writes change what later reads return: light states, group actions (which also change the
+/// member lights), scene recalls, renames, and created or deleted resources. Every call is
+/// recorded for assertions, and failures or latency can be injected.
+pub struct MockBridge {
+    resources: Mutex<Value>,
+    calls: Mutex<Vec<MockCall>>,
+    failures: Mutex<VecDeque<Failure>>,
+    latency: Duration,
+}
+
+impl Default for MockBridge {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockBridge {
+    /// A bridge with no resources.
+    pub fn new() -> Self {
+        Self {
+            resources: Mutex::new(json!({
+                "lights": {},
+                "groups": {},
+                "scenes": {},
+                "sensors": {},
+                "rules": {},
+                "schedules": {},
+                "config": { "name": "Mock bridge", "whitelist": {} },
+            })),
+            calls: Mutex::new(Vec::new()),
+            failures: Mutex::new(VecDeque::new()),
+            latency: Duration::ZERO,
+        }
+    }
+
+    /// Adds a reachable light that is off, with the state fields its kind reports.
+    pub fn with_light(self, id: LightId, name: &str, kind: LightKind) -> Self {
+        let mut state = json!({ "on": false, "reachable": true, "alert": "none" });
+        if kind != LightKind::OnOff {
+            state["bri"] = 254.into();
+        }
+        if matches!(kind, LightKind::ColorTemperature | LightKind::ExtendedColor) {
+            state["ct"] = 366.into();
+            state["colormode"] = "ct".into();
+        }
+        if matches!(kind, LightKind::Color | LightKind::ExtendedColor) {
+            state["xy"] = json!([0.4573, 0.41]);
+            state["hue"] = 8418.into();
+            state["sat"] = 140.into();
+            state["effect"] = "none".into();
+        }
+        self.with_resource(
+            "lights",
+            &id.to_string(),
+            json!({ "name": name, "type": kind.to_string(), "state": state }),
+        )
+    }
+
+    /// Adds a room with the given member lights.
+    pub fn with_group(self, id: u32, name: &str, lights: &[LightId]) -> Self {
+        let lights: Vec<String> = lights.iter().map(ToString::to_string).collect();
+        self.with_resource(
+            "groups",
+            &id.to_string(),
+            json!({
+                "name": name,
+                "type": "Room",
+                "lights": lights,
+                "action": { "on": false },
+                "state": { "any_on": false, "all_on": false },
+            }),
+        )
+    }
+
+    /// Adds any resource as the bridge would report it, e.g. a scene under `scenes`. A scene's
+    /// `lightstates` are applied when it is recalled.
+    pub fn with_resource(self, kind: &str, id: &str, resource: Value) -> Self {
+        self.lock_resources()[kind][id] = resource;
+        self
+    }
+
+    /// Replaces what `GET config` returns.
+    pub fn with_config(self, config: Value) -> Self {
+        self.lock_resources()["config"] = config;
+        self
+    }
+
+    /// Delays every response, e.g. to test timeouts or concurrency.
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Makes the next request fail with `error` without touching any state.
+    pub fn fail_next(&self, error: CoreError) {
+        self.lock_failures().push_back(Failure::Error(error));
+    }
+
+    /// Makes the next request get a bridge error response with this error type and description.
+    pub fn reject_next(&self, code: u32, description: &str) {
+        self.lock_failures().push_back(Failure::Rejected {
+            code,
+            description: description.to_string(),
+        });
+    }
+
+    /// Lets the next request through as usual, so a failure queued after it hits a later one.
+    pub fn pass_next(&self) {
+        self.lock_failures().push_back(Failure::Pass);
+    }
+
+    /// Every request so far, in order, including failed ones.
+    pub fn calls(&self) -> Vec<MockCall> {
+        self.calls.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// The current value at a path below the username, e.g. `lights/1/state`.
+    pub fn resource(&self, path: &str) -> Option<Value> {
+        let resources = self.lock_resources();
+        path.split('/')
+            .filter(|segment| !segment.is_empty())
+            .try_fold(&*resources, |value, segment| value.get(segment))
+            .cloned()
+    }
+
+    /// An API that sends its requests to this bridge.
+    pub fn api(self: &Arc<Self>) -> HueApiV1 {
+        HueApiV1::new(self.clone(), Arc::new(Logger::default()))
+    }
+
+    fn lock_resources(&self) -> std::sync::MutexGuard<'_, Value> {
+        self.resources.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn lock_failures(&self) -> std::sync::MutexGuard<'_, VecDeque<Failure>> {
+        self.failures.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Records the call, waits out the latency and applies any queued failure.
+    async fn receive(
+        &self,
+        method: &'static str,
+        url: &str,
+        body: Option<&str>,
+    ) -> Result<(String, Option<Value>), CoreResult<String>> {
+        let (_, _, path) = split_url(url);
+        let body = body.map(|body| serde_json::from_str(body).unwrap_or(Value::Null));
+        self.calls
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(MockCall {
+                method,
+                path: path.to_string(),
+                body: body.clone(),
+            });
+        if !self.latency.is_zero() {
+            tokio::time::sleep(self.latency).await;
+        }
+        match self.lock_failures().pop_front() {
+            Some(Failure::Error(error)) => Err(Err(error)),
+            Some(Failure::Rejected { code, description }) => Err(Ok(json!([{
+                "error": { "type": code, "address": format!("/{path}"), "description": description }
+            }])
+            .to_string())),
+            Some(Failure::Pass) | None => Ok((path.to_string(), body)),
+        }
+    }
+
+    fn put(&self, path: &str, write: &Map<String, Value>) -> String {
+        let mut resources = self.lock_resources();
+        let segments: Vec<&str> = path.split('/').collect();
+        match segments.as_slice() {
+            ["lights", id, "state"] => {
+                let Some(Value::Object(state)) = resources["lights"]
+                    .get_mut(*id)
+                    .and_then(|light| light.get_mut("state"))
+                else {
+                    return not_available(path);
+                };
+                apply_state(state, write);
+            }
+            ["groups", id, "action"] => {
+                let Some(group) = resources["groups"].get(*id).cloned() else {
+                    return not_available(path);
+                };
+                if let Some(Value::Object(action)) = resources["groups"][*id].get_mut("action") {
+                    apply_state(action, write);
+                }
+                let members: Vec<String> = group["lights"]
+                    .as_array()
+                    .map(|ids| {
+                        ids.iter()
+                            .filter_map(|id| id.as_str().map(str::to_string))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                // A scene sets each of its lights its own way, after the rest of the write.
+                let scene_states = write
+                    .get("scene")
+                    .and_then(Value::as_str)
+                    .and_then(|scene| resources["scenes"][scene].get("lightstates").cloned());
+                for member in &members {
+                    if let Some(Value::Object(state)) = resources["lights"]
+                        .get_mut(member)
+                        .and_then(|light| light.get_mut("state"))
+                    {
+                        apply_state(state, write);
+                        if let Some(Value::Object(scene_state)) =
+                            scene_states.as_ref().and_then(|states| states.get(member))
+                        {
+                            apply_state(state, scene_state);
+                        }
+                    }
+                }
+                let on: Vec<bool> = members
+                    .iter()
+                    .map(|member| resources["lights"][member]["state"]["on"] == json!(true))
+                    .collect();
+                resources["groups"][*id]["state"] = json!({
+                    "any_on": on.iter().any(|on| *on),
+                    "all_on": !on.is_empty() && on.iter().all(|on| *on),
+                });
+            }
+            _ => {
+                let Some(target) = segments
+                    .iter()
+                    .try_fold(&mut *resources, |value, segment| value.get_mut(*segment))
+                else {
+                    return not_available(path);
+                };
+                let Value::Object(target) = target else {
+                    return not_available(path);
+                };
+                for (field, value) in write {
+                    target.insert(field.clone(), value.clone());
+                }
+            }
+        }
+        let success: Vec<Value> = write
+            .iter()
+            .map(|(field, value)| json!({ "success": { format!("/{path}/{field}"): value } }))
+            .collect();
+        Value::Array(success).to_string()
+    }
+
+    fn post(&self, path: &str, body: Value) -> String {
+        match path {
+            // Creating a user.
+            "" => json!([{ "success": { "username": "mockuser" } }]).to_string(),
+            // Starting a light search.
+            "lights" => {
+                json!([{ "success": { "/lights": "Searching for new devices" } }]).to_string()
+            }
+            kind => {
+                let mut resources = self.lock_resources();
+                let Some(Value::Object(existing)) = resources.get_mut(kind) else {
+                    return not_available(path);
+                };
+                let id = existing
+                    .keys()
+                    .filter_map(|id| id.parse::<u32>().ok())
+                    .max()
+                    .unwrap_or(0)
+                    + 1;
+                existing.insert(id.to_string(), body);
+                json!([{ "success": { "id": id.to_string() } }]).to_string()
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl HueClient for MockBridge {
+    async fn post_json(&self, url: &str, body: &str, _headers: &[Header]) -> CoreResult<String> {
+        match self.receive("POST", url, Some(body)).await {
+            Ok((path, body)) => Ok(self.post(&path, body.unwrap_or(Value::Null))),
+            Err(failed) => failed,
+        }
+    }
+
+    async fn get(&self, url: &str, _headers: &[Header]) -> CoreResult<String> {
+        let path = match self.receive("GET", url, None).await {
+            Ok((path, _)) => path,
+            Err(failed) => return failed,
+        };
+        Ok(match path.as_str() {
+            "lights/new" => json!({ "lastscan": "none" }).to_string(),
+            _ => match self.resource(&path) {
+                Some(resource) => resource.to_string(),
+                None => not_available(&path),
+            },
+        })
+    }
+
+    async fn put_json(&self, url: &str, body: &str, _headers: &[Header]) -> CoreResult<String> {
+        match self.receive("PUT", url, Some(body)).await {
+            Ok((path, Some(Value::Object(write)))) => Ok(self.put(&path, &write)),
+            Ok((path, _)) => Ok(json!([{
+                "error": { "type": 2, "address": format!("/{path}"), "description": "body contains invalid JSON" }
+            }])
+            .to_string()),
+            Err(failed) => failed,
+        }
+    }
+
+    async fn delete(&self, url: &str, _headers: &[Header]) -> CoreResult<String> {
+        let path = match self.receive("DELETE", url, None).await {
+            Ok((path, _)) => path,
+            Err(failed) => return failed,
+        };
+        let mut resources = self.lock_resources();
+        let removed = match path.split_once('/') {
+            Some((kind, id)) => resources
+                .get_mut(kind)
+                .and_then(Value::as_object_mut)
+                .and_then(|existing| existing.remove(id)),
+            None => None,
+        };
+        Ok(match removed {
+            Some(_) => json!([{ "success": format!("/{path} deleted") }]).to_string(),
+            None => not_available(&path),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use serde_json::json;
+
+    use super::MockBridge;
+    use crate::domain::brightness::Brightness;
+    use crate::domain::light::{LightKind, LightState};
+    use crate::error::{CoreError, HueBridgeError};
+    use crate::hue_api::HueApi;
+
+    #[tokio::test]
+    pub async fn light_state_persists_across_writes() {
+        // Arrange
+        let bridge = Arc::new(MockBridge::new().with_light(1, "Desk", LightKind::Dimmable));
+        let api = bridge.api();
+
+        // Act
+        api.async_set_light_state(
+            "ip",
+            "user",
+            1,
+            &LightState::default()
+                .with_on(true)
+                .with_brightness(Brightness::from_bri(100)),
+        )
+        .await
+        .unwrap();
+        let light = api.async_get_light("ip", "user", 1).await.unwrap();
+
+        // Assert
+        assert_eq!(light.state.on, Some(true));
+        assert_eq!(light.state.brightness, Some(Brightness::from_bri(100)));
+        let calls = bridge.calls();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].method, "PUT");
+        assert_eq!(calls[0].path, "lights/1/state");
+    }
+
+    #[tokio::test]
+    pub async fn group_action_and_scene_recall_change_member_lights() {
+        // Arrange
+        let bridge = Arc::new(
+            MockBridge::new()
+                .with_light(1, "Desk", LightKind::ExtendedColor)
+                .with_light(2, "Shelf", LightKind::ExtendedColor)
+                .with_group(1, "Office", &[1, 2])
+                .with_resource(
+                    "scenes",
+                    "AbC",
+                    json!({ "name": "Focus", "group": "1", "lights": ["1", "2"],
+                            "lightstates": { "2": { "on": true, "bri": 10 } } }),
+                ),
+        );
+        let api = bridge.api();
+
+        // Act
+        api.async_set_group_state("ip", "user", 1, &LightState::default().with_on(true))
+            .await
+            .unwrap();
+        api.async_recall_scene("ip", "user", 1, "AbC")
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(bridge.resource("lights/1/state/on"), Some(json!(true)));
+        assert_eq!(bridge.resource("lights/2/state/bri"), Some(json!(10)));
+        assert_eq!(bridge.resource("groups/1/state/all_on"), Some(json!(true)));
+    }
+
+    #[tokio::test]
+    pub async fn injected_failures_apply_to_the_next_request_only() {
+        // Arrange
+        let bridge = Arc::new(MockBridge::new().with_light(1, "Desk", LightKind::OnOff));
+        let api = bridge.api();
+        bridge.fail_next(CoreError::Bridge(HueBridgeError::UnexpectedJSON));
+        bridge.reject_next(1, "unauthorized user");
+
+        // Act
+        let failed = api.async_get_all_lights("ip", "user").await;
+        let rejected = api.async_get_all_lights("ip", "user").await;
+        let missing = api.async_get_light("ip", "user", 9).await;
+        let lights = api.async_get_all_lights("ip", "user").await;
+
+        // Assert
+        assert!(matches!(
+            failed,
+            Err(CoreError::Bridge(HueBridgeError::UnexpectedJSON))
+        ));
+        assert!(matches!(
+            rejected,
+            Err(CoreError::Bridge(HueBridgeError::UnauthorizedUser))
+        ));
+        assert!(missing.is_err());
+        assert_eq!(lights.unwrap().0.len(), 1);
+    }
+}